@@ -4,6 +4,7 @@ use axum::{
     Json,
 };
 use serde_json::json;
+use service_auth::AuthError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,20 +15,20 @@ pub enum ApiError {
     #[error("Permission denied")]
     Forbidden,
 
+    #[error("Email address is not verified")]
+    EmailNotVerified,
+
+    #[error("Access token is missing the required scope")]
+    InsufficientScope,
+
     #[error("Resource not found")]
     NotFound,
 
-    #[error("Invalid input: {0}")]
-    BadRequest(String),
-
     #[error("Internal server error")]
     Internal(#[from] anyhow::Error),
 
     #[error("Service error: {0}")]
     ServiceError(String),
-
-    #[error("Rate limit exceeded")]
-    RateLimitExceeded,
 }
 
 impl IntoResponse for ApiError {
@@ -35,8 +36,9 @@ impl IntoResponse for ApiError {
         let (status, error_message) = match self {
             ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
             ApiError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
+            ApiError::EmailNotVerified => (StatusCode::FORBIDDEN, self.to_string()),
+            ApiError::InsufficientScope => (StatusCode::FORBIDDEN, self.to_string()),
             ApiError::NotFound => (StatusCode::NOT_FOUND, self.to_string()),
-            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             ApiError::Internal(err) => {
                 tracing::error!("Internal error: {:?}", err);
                 (
@@ -48,9 +50,6 @@ impl IntoResponse for ApiError {
                 tracing::error!("Service error: {}", msg);
                 (StatusCode::BAD_GATEWAY, msg)
             }
-            ApiError::RateLimitExceeded => {
-                (StatusCode::TOO_MANY_REQUESTS, self.to_string())
-            }
         };
 
         let body = Json(json!({
@@ -64,4 +63,14 @@ impl IntoResponse for ApiError {
     }
 }
 
+impl From<AuthError> for ApiError {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::Unauthorized => ApiError::Unauthorized,
+            AuthError::Forbidden => ApiError::Forbidden,
+            AuthError::Internal(err) => ApiError::Internal(err),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, ApiError>;
\ No newline at end of file
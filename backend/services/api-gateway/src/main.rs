@@ -1,16 +1,13 @@
 mod error;
-mod handlers;
 mod middleware;
 mod routes;
 mod services;
 mod types;
 
-use axum::{
-    routing::{get, post},
-    Router,
-};
+use axum::{middleware::from_fn, Extension, Router};
 use dotenv::dotenv;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -32,14 +29,35 @@ async fn main() {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        .expose_headers([middleware::impersonation::IMPERSONATED_BY]);
+
+    // Keep local copies of the auth service's signing keys and revoked tokens
+    // so requests don't hit the auth service
+    let jwks = Arc::new(middleware::JwksCache::from_env());
+    jwks.clone().spawn_refresh();
+
+    let revocations = Arc::new(middleware::RevocationCache::new(
+        services::client::ServiceClient::auth(),
+    ));
+    revocations.clone().spawn_refresh();
+
+    let access_tokens = Arc::new(middleware::PersonalAccessTokens::new(
+        services::client::ServiceClient::auth(),
+    ));
 
     // Build the router
     let app = Router::new()
         .merge(routes::auth::router())
+        .merge(routes::oauth::router())
+        .merge(routes::me::router())
         .merge(routes::posts::router())
         .merge(routes::users::router())
         .merge(routes::comments::router())
+        .layer(from_fn(middleware::mark_impersonated_writes))
+        .layer(Extension(jwks))
+        .layer(Extension(revocations))
+        .layer(Extension(access_tokens))
         .layer(TraceLayer::new_for_http())
         .layer(cors);
 
@@ -52,8 +70,11 @@ async fn main() {
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
     tracing::info!("listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::rbac::{Role, Scope};
+use crate::{error::Result, services::client::ServiceClient};
+
+/// What personal access tokens start with, so they can be told apart from
+/// JWTs before anything is verified.
+pub const PREFIX: &str = "blog_pat_";
+
+#[derive(Debug, Deserialize)]
+pub struct AccessTokenIdentity {
+    pub user_id: Uuid,
+    pub roles: Vec<Role>,
+    pub email_verified: bool,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Serialize)]
+struct VerifyRequest<'a> {
+    token: &'a str,
+}
+
+/// Checks personal access tokens with the auth service. Unlike JWTs they
+/// cannot be verified locally, and the auth service records each use.
+pub struct PersonalAccessTokens {
+    client: ServiceClient,
+}
+
+impl PersonalAccessTokens {
+    pub fn new(client: ServiceClient) -> Self {
+        Self { client }
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<AccessTokenIdentity> {
+        self.client
+            .post_json("/auth/tokens/verify", &VerifyRequest { token })
+            .await
+    }
+}
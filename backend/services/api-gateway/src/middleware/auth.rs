@@ -1,27 +1,90 @@
+use std::sync::Arc;
+
 use axum::{
     async_trait,
-    extract::{FromRequestParts, TypedHeader},
-    headers::{authorization::Bearer, Authorization},
-    http::request::Parts,
-    RequestPartsExt,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, Extensions, HeaderMap},
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
+use service_auth::JwksCache;
 use uuid::Uuid;
 
+use super::{
+    access_tokens::{self, PersonalAccessTokens},
+    rbac::{has_permission, Permission, Role, Scope},
+    revocation::RevocationCache,
+};
 use crate::error::{ApiError, Result};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
     pub email: String,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    #[serde(default)]
+    pub email_verified: bool,
+    /// Seconds since the epoch, to the millisecond.
+    pub iat: f64,
     pub exp: usize,
+    pub jti: Uuid,
+    /// Space-separated scopes, when the token was issued to an OAuth client.
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// The admin behind an impersonation token.
+    #[serde(default)]
+    pub act: Option<Actor>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: Uuid,
 }
 
 #[derive(Debug, Clone)]
 pub struct AuthUser {
     pub id: Uuid,
-    pub email: String,
+    pub roles: Vec<Role>,
+    pub email_verified: bool,
+    /// Set when the request carries a personal access token or a token
+    /// issued to an OAuth client, limiting what it may do. The user's own
+    /// sessions are not limited.
+    pub scopes: Option<Vec<Scope>>,
+    /// The admin acting as this user, when the request carries an
+    /// impersonation token. `id` is still the impersonated user.
+    pub actor: Option<Uuid>,
+}
+
+impl AuthUser {
+    pub fn can(&self, permission: Permission) -> bool {
+        has_permission(&self.roles, permission)
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope))
+    }
+
+    /// Account-wide actions such as deleting the account need the user's own
+    /// session, not a token or an admin acting for them.
+    pub fn ensure_session(&self) -> Result<()> {
+        if self.scopes.is_none() && self.actor.is_none() {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden)
+        }
+    }
+
+    /// Publishing posts and comments is held back until the user has
+    /// confirmed their email address.
+    pub fn ensure_verified(&self) -> Result<()> {
+        if self.email_verified {
+            Ok(())
+        } else {
+            Err(ApiError::EmailNotVerified)
+        }
+    }
 }
 
 #[async_trait]
@@ -32,60 +95,74 @@ where
     type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        // Several extractors on one route each need the user; verify once
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
+        }
+
         // Extract the token from the authorization header
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| ApiError::Unauthorized)?;
-
-        // Decode the user data
-        let token_data = decode::<Claims>(
-            bearer.token(),
-            &DecodingKey::from_secret(
-                std::env::var("JWT_SECRET")
-                    .unwrap_or_else(|_| "your-secret-key".to_string())
-                    .as_bytes(),
-            ),
-            &Validation::default(),
-        )
-        .map_err(|_| ApiError::Unauthorized)?;
-
-        Ok(AuthUser {
-            id: token_data.claims.sub,
-            email: token_data.claims.email,
-        })
+        let token = bearer_token(&parts.headers)?;
+
+        let user = if token.starts_with(access_tokens::PREFIX) {
+            let identity = extension::<PersonalAccessTokens>(&parts.extensions)?
+                .verify(token)
+                .await?;
+            AuthUser {
+                id: identity.user_id,
+                roles: identity.roles,
+                email_verified: identity.email_verified,
+                scopes: Some(identity.scopes),
+                actor: None,
+            }
+        } else {
+            // Verify and decode the user data
+            let claims = authenticate(&parts.extensions, token).await?;
+            AuthUser {
+                id: claims.sub,
+                roles: claims.roles,
+                email_verified: claims.email_verified,
+                scopes: claims
+                    .scope
+                    .map(|scope| scope.split(' ').filter_map(Scope::parse).collect()),
+                actor: claims.act.map(|actor| actor.sub),
+            }
+        };
+
+        parts.extensions.insert(user.clone());
+        Ok(user)
     }
 }
 
-pub async fn require_auth<B>(
-    req: axum::http::Request<B>,
-    next: axum::middleware::Next<B>,
-) -> Result<axum::response::Response> {
-    let auth_header = req
-        .headers()
-        .get(axum::http::header::AUTHORIZATION)
-        .ok_or(ApiError::Unauthorized)?;
-
-    let auth_header_str = auth_header
+fn bearer_token(headers: &HeaderMap) -> Result<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .ok_or(ApiError::Unauthorized)?
         .to_str()
-        .map_err(|_| ApiError::Unauthorized)?;
+        .map_err(|_| ApiError::Unauthorized)?
+        .strip_prefix("Bearer ")
+        .ok_or(ApiError::Unauthorized)
+}
+
+/// Checks the token's signature against the auth service's published keys,
+/// then makes sure it has not been revoked since it was issued.
+async fn authenticate(extensions: &Extensions, token: &str) -> Result<Claims> {
+    let claims: Claims = extension::<JwksCache>(extensions)?.verify(token).await?;
 
-    if !auth_header_str.starts_with("Bearer ") {
+    if extension::<RevocationCache>(extensions)?
+        .is_revoked(&claims)
+        .await?
+    {
         return Err(ApiError::Unauthorized);
     }
 
-    let token = &auth_header_str["Bearer ".len()..];
-
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(
-            std::env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "your-secret-key".to_string())
-                .as_bytes(),
-        ),
-        &Validation::default(),
-    )
-    .map_err(|_| ApiError::Unauthorized)?;
-
-    Ok(next.run(req).await)
-}
\ No newline at end of file
+    Ok(claims)
+}
+
+fn extension<T: Send + Sync + 'static>(extensions: &Extensions) -> Result<Arc<T>> {
+    extensions.get::<Arc<T>>().cloned().ok_or_else(|| {
+        ApiError::Internal(anyhow::anyhow!(
+            "{} is not installed",
+            std::any::type_name::<T>()
+        ))
+    })
+}
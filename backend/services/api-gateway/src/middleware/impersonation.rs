@@ -0,0 +1,44 @@
+use axum::{
+    extract::{FromRequestParts, Request},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use super::auth::AuthUser;
+
+/// Names the admin behind a write made with an impersonation token.
+pub const IMPERSONATED_BY: HeaderName = HeaderName::from_static("x-impersonated-by");
+
+/// Marks writes made while an admin impersonates a user: they are logged
+/// with both identities, and the response carries `X-Impersonated-By` with
+/// the admin's id. Reads pass through unmarked.
+///
+/// Must run inside the layers that install the JWKS and revocation caches.
+/// Requests whose token doesn't check out are left for the route to reject.
+pub async fn mark_impersonated_writes(req: Request, next: Next) -> Response {
+    if req.method().is_safe() {
+        return next.run(req).await;
+    }
+
+    let (mut parts, body) = req.into_parts();
+    let user = AuthUser::from_request_parts(&mut parts, &()).await.ok();
+    let req = Request::from_parts(parts, body);
+
+    let Some((user_id, actor)) = user.and_then(|user| Some((user.id, user.actor?))) else {
+        return next.run(req).await;
+    };
+
+    tracing::warn!(
+        %user_id,
+        actor_id = %actor,
+        method = %req.method(),
+        path = req.uri().path(),
+        "Write made while impersonating"
+    );
+    let mut response = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&actor.to_string()) {
+        response.headers_mut().insert(IMPERSONATED_BY, value);
+    }
+    response
+}
@@ -1,3 +1,12 @@
+pub mod access_tokens;
 pub mod auth;
+pub mod impersonation;
+pub mod rbac;
+pub mod revocation;
 
-pub use auth::{AuthUser, Claims, require_auth};
\ No newline at end of file
+pub use access_tokens::PersonalAccessTokens;
+pub use auth::AuthUser;
+pub use impersonation::mark_impersonated_writes;
+pub use service_auth::JwksCache;
+pub use rbac::{perm, scope, Permission, Require, Scoped};
+pub use revocation::RevocationCache;
@@ -0,0 +1,170 @@
+use std::marker::PhantomData;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+
+use super::auth::AuthUser;
+use crate::error::ApiError;
+
+pub use service_auth::rbac::{
+    has_permission, perm, scope, Permission, RequiredPermission, RequiredScope, Role, Scope,
+};
+
+/// An [`AuthUser`] whose roles grant `P`. Anyone else is rejected with
+/// `Forbidden`.
+pub struct Require<P> {
+    pub user: AuthUser,
+    _permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for Require<P>
+where
+    S: Send + Sync,
+    P: RequiredPermission,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        if !user.can(P::PERMISSION) {
+            return Err(ApiError::Forbidden);
+        }
+
+        Ok(Require {
+            user,
+            _permission: PhantomData,
+        })
+    }
+}
+
+/// Passes when the caller may use scope `S`: any session, or a personal
+/// access token or OAuth client token that was granted it. Other tokens are
+/// rejected with `InsufficientScope`. Scopes only limit tokens, so a request
+/// without one is left to the route's other extractors. Pair it with
+/// [`Require`] or [`AuthUser`] for the user itself; they share one
+/// verification.
+pub struct Scoped<S>(PhantomData<S>);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for Scoped<T>
+where
+    S: Send + Sync,
+    T: RequiredScope,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if parts.headers.contains_key(AUTHORIZATION) {
+            let user = AuthUser::from_request_parts(parts, state).await?;
+            if !T::is_met(|scope| user.has_scope(scope)) {
+                return Err(ApiError::InsufficientScope);
+            }
+        }
+
+        Ok(Scoped(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use axum::{
+        http::StatusCode,
+        response::{IntoResponse, Response},
+        routing::post,
+        Extension, Json, Router,
+    };
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{middleware::PersonalAccessTokens, services::client::ServiceClient};
+
+    /// Stands in for the auth service's `/auth/tokens/verify`: one token is
+    /// limited to writing comments, any other is unknown, revoked or expired.
+    async fn verify(Json(req): Json<Value>) -> Response {
+        if req["token"] != "blog_pat_comments" {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        Json(json!({
+            "token_id": Uuid::new_v4(),
+            "user_id": Uuid::new_v4(),
+            "email": "alice@example.com",
+            "roles": ["author"],
+            "email_verified": true,
+            "scopes": ["comments:write"],
+        }))
+        .into_response()
+    }
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap()
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn access_tokens_only_reach_routes_within_their_scopes() {
+        let auth = serve(Router::new().route("/auth/tokens/verify", post(verify))).await;
+        let tokens = Arc::new(PersonalAccessTokens::new(ServiceClient::new(auth)));
+        let gateway = serve(
+            Router::new()
+                .route(
+                    "/comments",
+                    post(
+                        |_: Require<perm::CreateComments>, _: Scoped<scope::CommentsWrite>| async {
+                        },
+                    ),
+                )
+                .route(
+                    "/posts",
+                    post(|_: Require<perm::CreatePosts>, _: Scoped<scope::PostsWrite>| async {}),
+                )
+                .layer(Extension(tokens)),
+        )
+        .await;
+
+        let http = reqwest::Client::new();
+        let call = |path: &str, token: &str| {
+            http.post(format!("{}{}", gateway, path))
+                .bearer_auth(token)
+                .send()
+        };
+        assert_eq!(
+            call("/comments", "blog_pat_comments")
+                .await
+                .unwrap()
+                .status(),
+            reqwest::StatusCode::OK
+        );
+        // Authors may write posts, but this token wasn't granted it.
+        let response = call("/posts", "blog_pat_comments").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            ApiError::InsufficientScope.to_string()
+        );
+        assert_eq!(
+            call("/comments", "blog_pat_revoked")
+                .await
+                .unwrap()
+                .status(),
+            reqwest::StatusCode::UNAUTHORIZED
+        );
+    }
+}
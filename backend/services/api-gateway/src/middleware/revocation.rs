@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::auth::Claims;
+use crate::{
+    error::{ApiError, Result},
+    services::client::ServiceClient,
+};
+
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 30;
+const DEFAULT_MAX_AGE_SECS: u64 = 120;
+
+#[derive(Debug, Deserialize)]
+struct RevocationList {
+    tokens: Vec<Uuid>,
+    users: Vec<UserRevocation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserRevocation {
+    user_id: Uuid,
+    revoked_before: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Snapshot {
+    tokens: HashSet<Uuid>,
+    /// When each user's tokens stop being valid, in milliseconds since the
+    /// epoch.
+    users: HashMap<Uuid, i64>,
+    /// When the list was fetched; `None` until the first fetch succeeds.
+    fetched_at: Option<Instant>,
+}
+
+/// Local copy of the auth service's revocation list, refreshed in the
+/// background so that token checks don't wait on the network.
+///
+/// A copy older than `REVOCATION_MAX_AGE_SECS` (or one never fetched) could
+/// be missing revocations, so it is fetched again before a token is
+/// checked. If that fails the token is refused rather than let through.
+pub struct RevocationCache {
+    client: ServiceClient,
+    snapshot: RwLock<Snapshot>,
+    max_age: Duration,
+}
+
+impl RevocationCache {
+    pub fn new(client: ServiceClient) -> Self {
+        let max_age = std::env::var("REVOCATION_MAX_AGE_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(DEFAULT_MAX_AGE_SECS);
+
+        Self {
+            client,
+            snapshot: RwLock::new(Snapshot::default()),
+            max_age: Duration::from_secs(max_age),
+        }
+    }
+
+    pub async fn is_revoked(&self, claims: &Claims) -> Result<bool> {
+        if self.is_stale() {
+            self.refresh().await.map_err(|err| {
+                tracing::warn!(
+                    "Revocation list is out of date and can't be fetched: {}",
+                    err
+                );
+                ApiError::ServiceError("Unable to check token revocation".to_string())
+            })?;
+        }
+
+        let snapshot = self.snapshot.read().unwrap();
+        Ok(snapshot.tokens.contains(&claims.jti)
+            || snapshot
+                .users
+                .get(&claims.sub)
+                .is_some_and(|revoked_before| {
+                    (claims.iat * 1000.0).round() as i64 <= *revoked_before
+                }))
+    }
+
+    fn is_stale(&self) -> bool {
+        self.snapshot
+            .read()
+            .unwrap()
+            .fetched_at
+            .is_none_or(|fetched_at| fetched_at.elapsed() > self.max_age)
+    }
+
+    pub async fn refresh(&self) -> Result<()> {
+        let list: RevocationList = self.client.get_json("/auth/revocations").await?;
+
+        let snapshot = Snapshot {
+            tokens: list.tokens.into_iter().collect(),
+            users: list
+                .users
+                .into_iter()
+                .map(|revocation| {
+                    (
+                        revocation.user_id,
+                        revocation.revoked_before.timestamp_millis(),
+                    )
+                })
+                .collect(),
+            fetched_at: Some(Instant::now()),
+        };
+        *self.snapshot.write().unwrap() = snapshot;
+
+        Ok(())
+    }
+
+    /// Polls the auth service every `REVOCATION_REFRESH_SECS` seconds. On
+    /// failure the previous snapshot is kept until it grows too old.
+    pub fn spawn_refresh(self: Arc<Self>) {
+        let interval = std::env::var("REVOCATION_REFRESH_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL_SECS);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.refresh().await {
+                    tracing::warn!("Failed to refresh revocation list: {}", err);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{routing::get, Json, Router};
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+
+    fn claims(sub: Uuid, iat: f64) -> Claims {
+        Claims {
+            sub,
+            email: "reader@example.com".to_string(),
+            roles: Vec::new(),
+            email_verified: true,
+            iat,
+            exp: usize::MAX,
+            jti: Uuid::new_v4(),
+            scope: None,
+            act: None,
+        }
+    }
+
+    /// Serves `tokens` as the revoked token ids, and counts the fetches.
+    async fn auth_service(tokens: Arc<RwLock<Vec<Uuid>>>) -> (ServiceClient, Arc<AtomicUsize>) {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = fetches.clone();
+        let app = Router::new().route(
+            "/auth/revocations",
+            get(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let tokens = tokens.read().unwrap().clone();
+                Json(json!({ "tokens": tokens, "users": [], "generated_at": Utc::now() }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (ServiceClient::new(url), fetches)
+    }
+
+    /// Nothing listens on port 1, so every fetch fails.
+    fn unreachable() -> RevocationCache {
+        RevocationCache::new(ServiceClient::new("http://127.0.0.1:1"))
+    }
+
+    #[tokio::test]
+    async fn user_revocations_compare_to_the_millisecond() {
+        let sub = Uuid::new_v4();
+        let revoked_before = Utc.timestamp_millis_opt(1_700_000_000_500).unwrap();
+        let cache = unreachable();
+        {
+            let mut snapshot = cache.snapshot.write().unwrap();
+            snapshot
+                .users
+                .insert(sub, revoked_before.timestamp_millis());
+            snapshot.fetched_at = Some(Instant::now());
+        }
+
+        assert!(cache
+            .is_revoked(&claims(sub, 1_700_000_000.0))
+            .await
+            .unwrap());
+        assert!(cache
+            .is_revoked(&claims(sub, 1_700_000_000.5))
+            .await
+            .unwrap());
+        assert!(!cache
+            .is_revoked(&claims(sub, 1_700_000_000.501))
+            .await
+            .unwrap());
+        assert!(!cache
+            .is_revoked(&claims(Uuid::new_v4(), 1_700_000_000.0))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn tokens_are_refused_until_the_list_is_fetched() {
+        let cache = unreachable();
+        assert!(matches!(
+            cache.is_revoked(&claims(Uuid::new_v4(), 0.0)).await,
+            Err(ApiError::ServiceError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_stale_list_is_fetched_again_before_use() {
+        let revoked = Arc::new(RwLock::new(Vec::new()));
+        let (client, fetches) = auth_service(revoked.clone()).await;
+        let cache = RevocationCache::new(client);
+        let token = claims(Uuid::new_v4(), 0.0);
+
+        assert!(!cache.is_revoked(&token).await.unwrap());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        // Revoked after the fetch: a fresh copy is trusted as it is.
+        revoked.write().unwrap().push(token.jti);
+        assert!(!cache.is_revoked(&token).await.unwrap());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        let fetched_at = Instant::now() - cache.max_age - Duration::from_secs(1);
+        cache.snapshot.write().unwrap().fetched_at = Some(fetched_at);
+        assert!(cache.is_revoked(&token).await.unwrap());
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn a_stale_list_that_cant_be_fetched_refuses_tokens() {
+        let cache = unreachable();
+        let fetched_at = Instant::now() - cache.max_age - Duration::from_secs(1);
+        cache.snapshot.write().unwrap().fetched_at = Some(fetched_at);
+
+        assert!(matches!(
+            cache.is_revoked(&claims(Uuid::new_v4(), 0.0)).await,
+            Err(ApiError::ServiceError(_))
+        ));
+    }
+}
@@ -1,91 +1,552 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
 use axum::{
-    routing::post,
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
+    response::Response,
+    routing::{delete, get, post, put},
     Json, Router,
 };
-use serde::Deserialize;
+use reqwest::Method;
 use uuid::Uuid;
 
 use crate::{
     error::{ApiError, Result},
-    middleware::Claims,
+    middleware::{perm, Require},
+    services::client::{with_forwarded_for, ServiceClient},
 };
 
-#[derive(Debug, Deserialize)]
-pub struct LoginRequest {
-    email: String,
-    password: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct RegisterRequest {
-    email: String,
-    username: String,
-    password: String,
-}
-
 pub fn router() -> Router {
     Router::new()
         .route("/auth/login", post(login))
         .route("/auth/register", post(register))
+        .route("/auth/2fa/verify", post(verify_two_factor))
+        .route("/auth/2fa/setup", post(setup_two_factor))
+        .route("/auth/2fa/confirm", post(confirm_two_factor))
+        .route("/auth/2fa/disable", post(disable_two_factor))
+        .route("/auth/2fa/recovery-codes", post(regenerate_recovery_codes))
+        .route("/auth/oidc/providers", get(oidc_providers))
+        .route("/auth/oidc/:provider/authorize", get(start_oidc_login))
+        .route("/auth/oidc/:provider/callback", post(finish_oidc_login))
+        .route("/auth/passkeys", get(list_passkeys))
+        .route("/auth/passkeys/:id", delete(delete_passkey))
+        .route(
+            "/auth/passkeys/register/start",
+            post(start_passkey_registration),
+        )
+        .route(
+            "/auth/passkeys/register/finish",
+            post(finish_passkey_registration),
+        )
+        .route("/auth/passkeys/login/start", post(start_passkey_login))
+        .route("/auth/passkeys/login/finish", post(finish_passkey_login))
+        .route("/auth/refresh", post(refresh))
+        .route("/auth/logout", post(logout))
+        .route("/auth/logout/all", post(logout_all))
+        .route("/auth/sessions", get(list_sessions))
+        .route("/auth/sessions/:id", delete(revoke_session))
+        .route(
+            "/auth/tokens",
+            get(list_access_tokens).post(create_access_token),
+        )
+        .route("/auth/tokens/:id", delete(delete_access_token))
+        .route("/auth/password/forgot", post(forgot_password))
+        .route("/auth/password/reset", post(reset_password))
+        .route("/auth/password/change", post(change_password))
+        .route("/auth/magic-link", post(send_magic_link))
+        .route("/auth/magic-link/consume", post(consume_magic_link))
+        .route("/auth/verify", get(verify_email))
+        .route("/auth/verify/resend", post(resend_verification))
+        .route("/auth/unlock", post(unlock_account))
+        .route("/auth/users/:id/roles", put(update_roles))
+        .route("/auth/users/:id/unlock", post(unlock_user))
+        .route("/auth/users/:id/impersonate", post(impersonate))
+        .route("/auth/audit", get(audit_log))
+        .with_state(ServiceClient::auth())
+}
+
+async fn login(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized("/auth/login", &with_forwarded_for(&headers, peer), &req)
+        .await
+}
+
+async fn register(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized("/auth/register", &with_forwarded_for(&headers, peer), &req)
+        .await
+}
+
+async fn verify_two_factor(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/2fa/verify",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn setup_two_factor(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/2fa/setup",
+            &with_forwarded_for(&headers, peer),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+async fn confirm_two_factor(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/2fa/confirm",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn disable_two_factor(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/2fa/disable",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn regenerate_recovery_codes(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/2fa/recovery-codes",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn oidc_providers(State(client): State<ServiceClient>) -> Result<Response> {
+    client
+        .forward_authorized_empty(Method::GET, "/auth/oidc/providers", &HeaderMap::new())
+        .await
+}
+
+async fn start_oidc_login(
+    State(client): State<ServiceClient>,
+    Path(provider): Path<String>,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::GET,
+            &oidc_path(&provider, "authorize")?,
+            &HeaderMap::new(),
+        )
+        .await
+}
+
+async fn finish_oidc_login(
+    State(client): State<ServiceClient>,
+    Path(provider): Path<String>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            &oidc_path(&provider, "callback")?,
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+/// Provider ids are plain slugs; anything else could rewrite the forwarded path.
+fn oidc_path(provider: &str, action: &str) -> Result<String> {
+    if provider.is_empty()
+        || !provider
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+    {
+        return Err(ApiError::NotFound);
+    }
+    Ok(format!("/auth/oidc/{}/{}", provider, action))
+}
+
+async fn start_passkey_registration(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/passkeys/register/start",
+            &with_forwarded_for(&headers, peer),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+async fn finish_passkey_registration(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/passkeys/register/finish",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn start_passkey_login(
+    State(client): State<ServiceClient>,
+    req: Option<Json<serde_json::Value>>,
+) -> Result<Response> {
+    let body = req
+        .map(|Json(body)| body)
+        .unwrap_or_else(|| serde_json::json!({}));
+    client
+        .forward_post("/auth/passkeys/login/start", &body)
+        .await
+}
+
+async fn finish_passkey_login(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/passkeys/login/finish",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn list_passkeys(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::GET,
+            "/auth/passkeys",
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn delete_passkey(
+    State(client): State<ServiceClient>,
+    Path(id): Path<Uuid>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::DELETE,
+            &format!("/auth/passkeys/{}", id),
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
 }
 
-async fn login(Json(req): Json<LoginRequest>) -> Result<Json<serde_json::Value>> {
-    // TODO: Implement actual authentication logic with auth service
-    // This is a mock implementation
-    let user_id = Uuid::new_v4();
-    let claims = Claims {
-        sub: user_id,
-        email: req.email,
-        exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
-    };
-
-    let token = jsonwebtoken::encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &jsonwebtoken::EncodingKey::from_secret(
-            std::env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "your-secret-key".to_string())
-                .as_bytes(),
-        ),
-    )
-    .map_err(|_| ApiError::Internal(anyhow::anyhow!("Failed to create token")))?;
-
-    Ok(Json(serde_json::json!({
-        "token": token,
-        "user": {
-            "id": user_id,
-            "email": req.email
-        }
-    })))
-}
-
-async fn register(Json(req): Json<RegisterRequest>) -> Result<Json<serde_json::Value>> {
-    // TODO: Implement actual registration logic with auth service
-    // This is a mock implementation
-    let user_id = Uuid::new_v4();
-    let claims = Claims {
-        sub: user_id,
-        email: req.email.clone(),
-        exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
-    };
-
-    let token = jsonwebtoken::encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &jsonwebtoken::EncodingKey::from_secret(
-            std::env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "your-secret-key".to_string())
-                .as_bytes(),
-        ),
-    )
-    .map_err(|_| ApiError::Internal(anyhow::anyhow!("Failed to create token")))?;
-
-    Ok(Json(serde_json::json!({
-        "token": token,
-        "user": {
-            "id": user_id,
-            "email": req.email,
-            "username": req.username
-        }
-    })))
-}
\ No newline at end of file
+async fn refresh(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized("/auth/refresh", &with_forwarded_for(&headers, peer), &req)
+        .await
+}
+
+async fn logout(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    req: Option<Json<serde_json::Value>>,
+) -> Result<Response> {
+    let body = req
+        .map(|Json(req)| req)
+        .unwrap_or_else(|| serde_json::json!({}));
+    client
+        .forward_post_authorized("/auth/logout", &with_forwarded_for(&headers, peer), &body)
+        .await
+}
+
+async fn logout_all(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/logout/all",
+            &with_forwarded_for(&headers, peer),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+async fn list_sessions(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::GET,
+            "/auth/sessions",
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn revoke_session(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::DELETE,
+            &format!("/auth/sessions/{}", id),
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn list_access_tokens(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::GET,
+            "/auth/tokens",
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn create_access_token(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized("/auth/tokens", &with_forwarded_for(&headers, peer), &req)
+        .await
+}
+
+async fn delete_access_token(
+    State(client): State<ServiceClient>,
+    Path(id): Path<Uuid>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::DELETE,
+            &format!("/auth/tokens/{}", id),
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn forgot_password(
+    State(client): State<ServiceClient>,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client.forward_post("/auth/password/forgot", &req).await
+}
+
+async fn reset_password(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/password/reset",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn change_password(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/password/change",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn send_magic_link(
+    State(client): State<ServiceClient>,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client.forward_post("/auth/magic-link", &req).await
+}
+
+async fn consume_magic_link(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/magic-link/consume",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn verify_email(
+    State(client): State<ServiceClient>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Response> {
+    client.forward_get("/auth/verify", &query).await
+}
+
+async fn resend_verification(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/auth/verify/resend",
+            &with_forwarded_for(&headers, peer),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+async fn unlock_account(
+    State(client): State<ServiceClient>,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client.forward_post("/auth/unlock", &req).await
+}
+
+async fn unlock_user(
+    _admin: Require<perm::ManageUsers>,
+    State(client): State<ServiceClient>,
+    Path(id): Path<Uuid>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            &format!("/auth/users/{}/unlock", id),
+            &with_forwarded_for(&headers, peer),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+async fn update_roles(
+    _admin: Require<perm::ManageUsers>,
+    State(client): State<ServiceClient>,
+    Path(id): Path<Uuid>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_authorized(
+            Method::PUT,
+            &format!("/auth/users/{}/roles", id),
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn impersonate(
+    _admin: Require<perm::ManageUsers>,
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            &format!("/auth/users/{}/impersonate", id),
+            &with_forwarded_for(&headers, peer),
+            &serde_json::json!({}),
+        )
+        .await
+}
+
+async fn audit_log(
+    _admin: Require<perm::ManageUsers>,
+    State(client): State<ServiceClient>,
+    Query(query): Query<HashMap<String, String>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_get_authorized("/auth/audit", &query, &with_forwarded_for(&headers, peer))
+        .await
+}
@@ -1,13 +1,13 @@
 use axum::{
     extract::{Path, Query, State},
-    routing::{get, post, put, delete},
+    routing::{get, put},
     Json, Router,
 };
 use uuid::Uuid;
 
 use crate::{
     error::Result,
-    middleware::AuthUser,
+    middleware::{scope, AuthUser, Scoped},
     services::{mock::MockCommentService, CommentService},
     types::{CreateCommentRequest, PaginationParams, UpdateCommentRequest},
 };
@@ -16,11 +16,11 @@ pub fn router() -> Router {
     let comment_service = MockCommentService;
 
     Router::new()
-        .route("/posts/:post_id/comments", get(get_comments).post(create_comment))
         .route(
-            "/comments/:id",
-            put(update_comment).delete(delete_comment),
+            "/posts/:post_id/comments",
+            get(get_comments).post(create_comment),
         )
+        .route("/comments/:id", put(update_comment).delete(delete_comment))
         .with_state(comment_service)
 }
 
@@ -32,9 +32,7 @@ async fn get_comments(
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20);
 
-    let (comments, total) = service
-        .get_comments(post_id, page, per_page, None)
-        .await?;
+    let (comments, total) = service.get_comments(post_id, page, per_page, None).await?;
 
     Ok(Json(serde_json::json!({
         "comments": comments,
@@ -49,17 +47,24 @@ async fn get_comments(
 
 async fn create_comment(
     auth: Option<AuthUser>,
+    _: Scoped<scope::CommentsWrite>,
     Path(post_id): Path<Uuid>,
     State(service): State<MockCommentService>,
     Json(req): Json<CreateCommentRequest>,
 ) -> Result<Json<serde_json::Value>> {
+    if let Some(user) = &auth {
+        user.ensure_verified()?;
+    }
     let author_id = auth.map(|user| user.id);
     let comment = service.create_comment(post_id, author_id, req).await?;
     Ok(Json(serde_json::json!({ "comment": comment })))
 }
 
+/// Changing an existing comment takes either the author's scope or the
+/// moderator's.
 async fn update_comment(
     auth: Option<AuthUser>,
+    _: Scoped<(scope::CommentsWrite, scope::CommentsModerate)>,
     Path(id): Path<Uuid>,
     State(service): State<MockCommentService>,
     Json(req): Json<UpdateCommentRequest>,
@@ -71,10 +76,11 @@ async fn update_comment(
 
 async fn delete_comment(
     auth: Option<AuthUser>,
+    _: Scoped<(scope::CommentsWrite, scope::CommentsModerate)>,
     Path(id): Path<Uuid>,
     State(service): State<MockCommentService>,
 ) -> Result<Json<serde_json::Value>> {
     let author_id = auth.map(|user| user.id);
     service.delete_comment(id, author_id).await?;
     Ok(Json(serde_json::json!({ "success": true })))
-}
\ No newline at end of file
+}
@@ -0,0 +1,365 @@
+use std::io::{Cursor, Write};
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    error::{ApiError, Result},
+    middleware::AuthUser,
+    services::client::{with_forwarded_for, ServiceClient},
+};
+
+/// The services holding a user's data. Each exposes `GET .../me/export` and
+/// `DELETE .../me` for the signed-in user.
+///
+/// The profile is part of the auth service's account. The user and comment
+/// services don't store anything yet, so follows and comments are neither
+/// exported nor deleted; they join here once those services keep them.
+#[derive(Clone)]
+struct AccountServices {
+    auth: ServiceClient,
+    blog: ServiceClient,
+}
+
+pub fn router() -> Router {
+    routes(AccountServices {
+        auth: ServiceClient::auth(),
+        blog: ServiceClient::blog(),
+    })
+}
+
+fn routes(services: AccountServices) -> Router {
+    Router::new()
+        .route("/me", delete(delete_account))
+        .route("/me/export", get(export_data))
+        .with_state(services)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    #[default]
+    Json,
+    Zip,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// Collects everything the services hold about the caller into one
+/// download: a JSON document, or a ZIP with one file per section.
+async fn export_data(
+    State(services): State<AccountServices>,
+    auth: AuthUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response> {
+    auth.ensure_session()?;
+    let headers = with_forwarded_for(&headers, peer);
+
+    let (account, mut posts) = futures::try_join!(
+        services
+            .auth
+            .get_json_authorized::<Value>("/auth/me/export", &headers),
+        services
+            .blog
+            .get_json_authorized::<Value>("/me/export", &headers),
+    )?;
+    let generated_at = Utc::now();
+    let sections = [("account", account), ("posts", posts["posts"].take())];
+    let filename = format!("blog-export-{}", generated_at.format("%Y%m%d"));
+
+    match query.format {
+        ExportFormat::Json => {
+            let mut archive = json!({
+                "user_id": auth.id,
+                "generated_at": generated_at,
+            });
+            for (name, section) in sections {
+                archive[name] = section;
+            }
+            Ok((
+                [(CONTENT_DISPOSITION, attachment(&filename, "json"))],
+                Json(archive),
+            )
+                .into_response())
+        }
+        ExportFormat::Zip => {
+            let manifest = json!({
+                "user_id": auth.id,
+                "generated_at": generated_at,
+            });
+            let mut files = vec![("manifest.json".to_string(), manifest)];
+            files.extend(
+                sections
+                    .into_iter()
+                    .map(|(name, section)| (format!("{}.json", name), section)),
+            );
+            let bytes = zip_archive(&files).map_err(ApiError::Internal)?;
+            Ok((
+                [
+                    (CONTENT_TYPE, "application/zip".to_string()),
+                    (CONTENT_DISPOSITION, attachment(&filename, "zip")),
+                ],
+                bytes,
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Erases the caller's data everywhere. Posts are deleted first, and the
+/// account, with the profile, goes last so the caller's token keeps working
+/// until the blog service is done. Any service that fails stops the deletion
+/// with an error rather than reporting success, and every step can be
+/// repeated, so it can simply be retried.
+async fn delete_account(
+    State(services): State<AccountServices>,
+    auth: AuthUser,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<StatusCode> {
+    auth.ensure_session()?;
+    let headers = with_forwarded_for(&headers, peer);
+
+    services.blog.delete_authorized("/me", &headers).await?;
+    services
+        .auth
+        .delete_authorized("/auth/me", &headers)
+        .await?;
+
+    tracing::info!(user_id = %auth.id, "Deleted account");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn attachment(filename: &str, extension: &str) -> String {
+    format!("attachment; filename=\"{}.{}\"", filename, extension)
+}
+
+fn zip_archive(files: &[(String, Value)]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, contents) in files {
+        writer.start_file(name.as_str(), options)?;
+        writer.write_all(&serde_json::to_vec_pretty(contents)?)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    use axum::http::header::AUTHORIZATION;
+    use uuid::Uuid;
+    use zip::ZipArchive;
+
+    use super::*;
+
+    const TOKEN: &str = "Bearer session-token";
+
+    /// What the stand-in auth and blog services hold, and the order their
+    /// data was deleted in.
+    #[derive(Default)]
+    struct Store {
+        account: Option<Value>,
+        posts: Vec<Value>,
+        deleted: Vec<&'static str>,
+    }
+
+    type SharedStore = Arc<Mutex<Store>>;
+
+    fn is_caller(headers: &HeaderMap) -> bool {
+        headers
+            .get(AUTHORIZATION)
+            .is_some_and(|value| value == TOKEN)
+    }
+
+    async fn auth_export(State(store): State<SharedStore>, headers: HeaderMap) -> Response {
+        if !is_caller(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        match &store.lock().unwrap().account {
+            Some(account) => Json(account.clone()).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    async fn auth_delete(State(store): State<SharedStore>, headers: HeaderMap) -> StatusCode {
+        if !is_caller(&headers) {
+            return StatusCode::UNAUTHORIZED;
+        }
+        let mut store = store.lock().unwrap();
+        store.account = None;
+        store.deleted.push("account");
+        StatusCode::NO_CONTENT
+    }
+
+    async fn blog_export(State(store): State<SharedStore>, headers: HeaderMap) -> Response {
+        if !is_caller(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        Json(json!({ "posts": store.lock().unwrap().posts })).into_response()
+    }
+
+    async fn blog_delete(State(store): State<SharedStore>, headers: HeaderMap) -> StatusCode {
+        if !is_caller(&headers) {
+            return StatusCode::UNAUTHORIZED;
+        }
+        let mut store = store.lock().unwrap();
+        store.posts.clear();
+        store.deleted.push("posts");
+        StatusCode::NO_CONTENT
+    }
+
+    async fn serve(router: Router) -> ServiceClient {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        ServiceClient::new(url)
+    }
+
+    fn session(id: Uuid) -> AuthUser {
+        AuthUser {
+            id,
+            roles: Vec::new(),
+            email_verified: true,
+            scopes: None,
+            actor: None,
+        }
+    }
+
+    /// Serves the gateway's `/me` routes in front of stand-in auth and blog
+    /// services sharing `store`, with the auth middleware having let `user`
+    /// through, and returns a client for them.
+    async fn gateway(store: &SharedStore, user: &AuthUser) -> Gateway {
+        let auth = Router::new()
+            .route("/auth/me", delete(auth_delete))
+            .route("/auth/me/export", get(auth_export))
+            .with_state(store.clone());
+        let blog = Router::new()
+            .route("/me", delete(blog_delete))
+            .route("/me/export", get(blog_export))
+            .with_state(store.clone());
+        let app = routes(AccountServices {
+            auth: serve(auth).await,
+            blog: serve(blog).await,
+        })
+        .layer(axum::Extension(user.clone()));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap()
+        });
+        Gateway {
+            url,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    struct Gateway {
+        url: String,
+        http: reqwest::Client,
+    }
+
+    impl Gateway {
+        async fn send(&self, method: reqwest::Method, path: &str) -> reqwest::Response {
+            self.http
+                .request(method, format!("{}{}", self.url, path))
+                .header(reqwest::header::AUTHORIZATION, TOKEN)
+                .send()
+                .await
+                .unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn accounts_are_exported_then_deleted() {
+        let store = SharedStore::default();
+        {
+            let mut store = store.lock().unwrap();
+            store.account = Some(json!({ "user": { "username": "alice" } }));
+            store.posts = vec![json!({ "title": "Hello" }), json!({ "title": "Draft" })];
+        }
+        let user = session(Uuid::new_v4());
+        let gateway = gateway(&store, &user).await;
+
+        let response = gateway.send(reqwest::Method::GET, "/me/export").await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let archive: Value = response.json().await.unwrap();
+        assert_eq!(archive["user_id"], json!(user.id));
+        assert_eq!(archive["account"]["user"]["username"], "alice");
+        assert_eq!(archive["posts"][1]["title"], "Draft");
+
+        let response = gateway
+            .send(reqwest::Method::GET, "/me/export?format=zip")
+            .await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let bytes = response.bytes().await.unwrap().to_vec();
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut names: Vec<_> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(names, ["account.json", "manifest.json", "posts.json"]);
+        let mut posts = String::new();
+        zip.by_name("posts.json")
+            .unwrap()
+            .read_to_string(&mut posts)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&posts).unwrap()[0]["title"],
+            "Hello"
+        );
+
+        let response = gateway.send(reqwest::Method::DELETE, "/me").await;
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+        {
+            let store = store.lock().unwrap();
+            // The account goes last, while the caller's token still works.
+            assert_eq!(store.deleted, ["posts", "account"]);
+            assert!(store.account.is_none());
+            assert!(store.posts.is_empty());
+        }
+
+        let response = gateway.send(reqwest::Method::GET, "/me/export").await;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn only_the_users_own_session_may_delete_the_account() {
+        let store = SharedStore::default();
+        store.lock().unwrap().account = Some(json!({}));
+        let impersonated = AuthUser {
+            actor: Some(Uuid::new_v4()),
+            ..session(Uuid::new_v4())
+        };
+        let gateway = gateway(&store, &impersonated).await;
+
+        let response = gateway.send(reqwest::Method::DELETE, "/me").await;
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+        assert!(store.lock().unwrap().deleted.is_empty());
+    }
+}
@@ -1,9 +1,6 @@
 pub mod auth;
 pub mod comments;
+pub mod me;
+pub mod oauth;
 pub mod posts;
 pub mod users;
-
-pub use auth::router as auth_router;
-pub use comments::router as comments_router;
-pub use posts::router as posts_router;
-pub use users::router as users_router;
\ No newline at end of file
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Form, Path, Query, State},
+    http::HeaderMap,
+    response::Response,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use reqwest::Method;
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    services::client::{with_forwarded_for, ServiceClient},
+};
+
+/// The OAuth authorization server lives in the auth service. Introspection
+/// is left out: it is for backend services, which call the auth service
+/// directly.
+pub fn router() -> Router {
+    Router::new()
+        .route("/oauth/clients", get(list_clients).post(register_client))
+        .route("/oauth/clients/:id", delete(delete_client))
+        .route(
+            "/oauth/authorize",
+            get(preview_authorization).post(decide_authorization),
+        )
+        .route("/oauth/token", post(token))
+        .route("/oauth/revoke", post(revoke))
+        .route("/oauth/consents", get(list_consents))
+        .route("/oauth/consents/:client_id", delete(revoke_consent))
+        .with_state(ServiceClient::auth())
+}
+
+async fn list_clients(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::GET,
+            "/oauth/clients",
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn register_client(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized("/oauth/clients", &with_forwarded_for(&headers, peer), &req)
+        .await
+}
+
+async fn delete_client(
+    State(client): State<ServiceClient>,
+    Path(id): Path<Uuid>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::DELETE,
+            &format!("/oauth/clients/{}", id),
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn preview_authorization(
+    State(client): State<ServiceClient>,
+    Query(query): Query<HashMap<String, String>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_get_authorized(
+            "/oauth/authorize",
+            &query,
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn decide_authorization(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<serde_json::Value>,
+) -> Result<Response> {
+    client
+        .forward_post_authorized(
+            "/oauth/authorize",
+            &with_forwarded_for(&headers, peer),
+            &req,
+        )
+        .await
+}
+
+async fn token(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Response> {
+    client
+        .forward_form_authorized("/oauth/token", &with_forwarded_for(&headers, peer), &form)
+        .await
+}
+
+async fn revoke(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Response> {
+    client
+        .forward_form_authorized("/oauth/revoke", &with_forwarded_for(&headers, peer), &form)
+        .await
+}
+
+async fn list_consents(
+    State(client): State<ServiceClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::GET,
+            "/oauth/consents",
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
+
+async fn revoke_consent(
+    State(client): State<ServiceClient>,
+    Path(client_id): Path<Uuid>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    client
+        .forward_authorized_empty(
+            Method::DELETE,
+            &format!("/oauth/consents/{}", client_id),
+            &with_forwarded_for(&headers, peer),
+        )
+        .await
+}
@@ -1,13 +1,13 @@
 use axum::{
     extract::{Path, Query, State},
-    routing::{get, post, put, delete},
+    routing::get,
     Json, Router,
 };
 use uuid::Uuid;
 
 use crate::{
     error::Result,
-    middleware::AuthUser,
+    middleware::{perm, scope, Require, Scoped},
     services::{mock::MockPostService, PostService},
     types::{CreatePostRequest, PaginationParams, UpdatePostRequest},
 };
@@ -55,29 +55,33 @@ async fn get_post_by_slug(
 }
 
 async fn create_post(
-    auth: AuthUser,
+    Require { user, .. }: Require<perm::CreatePosts>,
+    _: Scoped<scope::PostsWrite>,
     State(service): State<MockPostService>,
     Json(req): Json<CreatePostRequest>,
 ) -> Result<Json<serde_json::Value>> {
-    let post = service.create_post(auth.id, req).await?;
+    user.ensure_verified()?;
+    let post = service.create_post(user.id, req).await?;
     Ok(Json(serde_json::json!({ "post": post })))
 }
 
 async fn update_post(
-    auth: AuthUser,
+    Require { user, .. }: Require<perm::CreatePosts>,
+    _: Scoped<scope::PostsWrite>,
     Path(id): Path<Uuid>,
     State(service): State<MockPostService>,
     Json(req): Json<UpdatePostRequest>,
 ) -> Result<Json<serde_json::Value>> {
-    let post = service.update_post(id, auth.id, req).await?;
+    let post = service.update_post(id, user.id, req).await?;
     Ok(Json(serde_json::json!({ "post": post })))
 }
 
 async fn delete_post(
-    auth: AuthUser,
+    Require { user, .. }: Require<perm::CreatePosts>,
+    _: Scoped<scope::PostsWrite>,
     Path(id): Path<Uuid>,
     State(service): State<MockPostService>,
 ) -> Result<Json<serde_json::Value>> {
-    service.delete_post(id, auth.id).await?;
+    service.delete_post(id, user.id).await?;
     Ok(Json(serde_json::json!({ "success": true })))
 }
\ No newline at end of file
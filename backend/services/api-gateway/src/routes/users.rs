@@ -1,13 +1,13 @@
 use axum::{
     extract::{Path, State},
-    routing::{get, put},
+    routing::get,
     Json, Router,
 };
 use uuid::Uuid;
 
 use crate::{
     error::Result,
-    middleware::AuthUser,
+    middleware::{scope, AuthUser, Permission, Scoped},
     services::{mock::MockUserService, UserService},
 };
 
@@ -15,8 +15,10 @@ pub fn router() -> Router {
     let user_service = MockUserService;
 
     Router::new()
-        .route("/users/:username", get(get_user_by_username))
-        .route("/users/:id", put(update_user))
+        .route(
+            "/users/:username",
+            get(get_user_by_username).put(update_user),
+        )
         .with_state(user_service)
 }
 
@@ -30,15 +32,16 @@ async fn get_user_by_username(
 
 async fn update_user(
     auth: AuthUser,
+    _: Scoped<scope::ProfileWrite>,
     Path(id): Path<Uuid>,
     State(service): State<MockUserService>,
     Json(user): Json<crate::types::User>,
 ) -> Result<Json<serde_json::Value>> {
-    // Verify that the authenticated user is updating their own profile
-    if auth.id != id {
+    // Users may only update their own profile, unless they manage accounts
+    if auth.id != id && !auth.can(Permission::ManageUsers) {
         return Err(crate::error::ApiError::Forbidden);
     }
 
     let updated_user = service.update_user(id, user).await?;
     Ok(Json(serde_json::json!({ "user": updated_user })))
-}
\ No newline at end of file
+}
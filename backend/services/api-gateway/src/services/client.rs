@@ -0,0 +1,299 @@
+use std::net::SocketAddr;
+
+use axum::{
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, RETRY_AFTER, USER_AGENT},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{ApiError, Result};
+
+/// Thin HTTP client for forwarding requests to a backend service.
+#[derive(Clone)]
+pub struct ServiceClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl ServiceClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn auth() -> Self {
+        Self::new(
+            std::env::var("AUTH_SERVICE_URL")
+                .unwrap_or_else(|_| "http://localhost:3001".to_string()),
+        )
+    }
+
+    pub fn blog() -> Self {
+        Self::new(
+            std::env::var("BLOG_SERVICE_URL")
+                .unwrap_or_else(|_| "http://localhost:3002".to_string()),
+        )
+    }
+
+    /// POSTs `body` to `path` and relays the backend's status and JSON body.
+    pub async fn forward_post<T: Serialize>(&self, path: &str, body: &T) -> Result<Response> {
+        self.forward_post_authorized(path, &HeaderMap::new(), body)
+            .await
+    }
+
+    /// Like `forward_post`, but passes the caller's `Authorization`,
+    /// `X-Forwarded-For` and `User-Agent` headers on.
+    pub async fn forward_post_authorized<T: Serialize>(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        body: &T,
+    ) -> Result<Response> {
+        self.forward_authorized(Method::POST, path, headers, body)
+            .await
+    }
+
+    /// Sends `body` to `path` with the given method and the caller's
+    /// `Authorization` header, and relays the backend's response.
+    pub async fn forward_authorized<T: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &HeaderMap,
+        body: &T,
+    ) -> Result<Response> {
+        let request = self.http.request(method, self.url(path)).json(body);
+        send_authorized(request, headers).await
+    }
+
+    /// Like `forward_authorized`, but sends `form` urlencoded, as OAuth
+    /// token endpoints expect.
+    pub async fn forward_form_authorized<T: Serialize>(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        form: &T,
+    ) -> Result<Response> {
+        let request = self.http.post(self.url(path)).form(form);
+        send_authorized(request, headers).await
+    }
+
+    /// Like `forward_get`, passing the caller's `Authorization` header on.
+    pub async fn forward_get_authorized<Q: Serialize>(
+        &self,
+        path: &str,
+        query: &Q,
+        headers: &HeaderMap,
+    ) -> Result<Response> {
+        let request = self.http.get(self.url(path)).query(query);
+        send_authorized(request, headers).await
+    }
+
+    /// Like `forward_authorized`, for requests without a body such as GET
+    /// and DELETE.
+    pub async fn forward_authorized_empty(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<Response> {
+        let request = self.http.request(method, self.url(path));
+        send_authorized(request, headers).await
+    }
+
+    /// GETs `path` with `query` and relays the backend's response.
+    pub async fn forward_get<Q: Serialize>(&self, path: &str, query: &Q) -> Result<Response> {
+        let response = self
+            .http
+            .get(self.url(path))
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| ApiError::ServiceError(e.to_string()))?;
+
+        relay(response).await
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.http
+            .get(self.url(path))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| ApiError::ServiceError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| ApiError::ServiceError(format!("Invalid response body: {}", e)))
+    }
+
+    /// POSTs `body` to `path` and decodes the JSON response. A 401 from the
+    /// backend becomes `Unauthorized`.
+    pub async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T> {
+        let response = self
+            .http
+            .post(self.url(path))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| ApiError::ServiceError(e.to_string()))?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(ApiError::Unauthorized);
+        }
+
+        response
+            .error_for_status()
+            .map_err(|e| ApiError::ServiceError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| ApiError::ServiceError(format!("Invalid response body: {}", e)))
+    }
+
+    /// GETs `path` with the caller's `Authorization` header and decodes the
+    /// JSON response.
+    pub async fn get_json_authorized<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<T> {
+        let request = with_caller_headers(self.http.get(self.url(path)), headers);
+        check_status(request.send().await)?
+            .json()
+            .await
+            .map_err(|e| ApiError::ServiceError(format!("Invalid response body: {}", e)))
+    }
+
+    /// DELETEs `path` with the caller's `Authorization` header.
+    pub async fn delete_authorized(&self, path: &str, headers: &HeaderMap) -> Result<()> {
+        let request = with_caller_headers(self.http.delete(self.url(path)), headers);
+        check_status(request.send().await)?;
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+async fn send_authorized(
+    request: reqwest::RequestBuilder,
+    headers: &HeaderMap,
+) -> Result<Response> {
+    let response = with_caller_headers(request, headers)
+        .send()
+        .await
+        .map_err(|e| ApiError::ServiceError(e.to_string()))?;
+
+    relay(response).await
+}
+
+/// Passes on the caller's `Authorization` header, and where they are
+/// calling from for the auth service to record.
+fn with_caller_headers(
+    mut request: reqwest::RequestBuilder,
+    headers: &HeaderMap,
+) -> reqwest::RequestBuilder {
+    if let Some(authorization) = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        request = request.header(reqwest::header::AUTHORIZATION, authorization);
+    }
+    if let Some(forwarded) = headers.get(X_FORWARDED_FOR).and_then(|v| v.to_str().ok()) {
+        request = request.header(X_FORWARDED_FOR, forwarded);
+    }
+    // Recorded on sessions and in the audit log so users can tell their
+    // devices apart.
+    if let Some(user_agent) = headers.get(USER_AGENT).and_then(|v| v.to_str().ok()) {
+        request = request.header(reqwest::header::USER_AGENT, user_agent);
+    }
+    request
+}
+
+/// Turns a backend's error status into the matching `ApiError`, for calls
+/// whose response the gateway uses itself rather than relaying.
+fn check_status(response: reqwest::Result<reqwest::Response>) -> Result<reqwest::Response> {
+    let response = response.map_err(|e| ApiError::ServiceError(e.to_string()))?;
+    match response.status() {
+        reqwest::StatusCode::UNAUTHORIZED => Err(ApiError::Unauthorized),
+        reqwest::StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        reqwest::StatusCode::NOT_FOUND => Err(ApiError::NotFound),
+        _ => response
+            .error_for_status()
+            .map_err(|e| ApiError::ServiceError(e.to_string())),
+    }
+}
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// `headers` with `X-Forwarded-For` set to the caller's address, so the
+/// backend can tell clients apart (e.g. to throttle logins per address).
+/// Whatever the caller sent in the header is replaced: the gateway is the
+/// edge, and clients must not pick their own address.
+pub fn with_forwarded_for(headers: &HeaderMap, peer: SocketAddr) -> HeaderMap {
+    let mut headers = headers.clone();
+    let ip = HeaderValue::from_str(&peer.ip().to_string())
+        .expect("an IP address is a valid header value");
+    headers.insert(X_FORWARDED_FOR, ip);
+    headers
+}
+
+async fn relay(response: reqwest::Response) -> Result<Response> {
+    let status =
+        StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| HeaderValue::from_bytes(v.as_bytes()).ok());
+    // Token responses must not be cached on the way to the client.
+    let cache_control = response
+        .headers()
+        .get(reqwest::header::CACHE_CONTROL)
+        .and_then(|v| HeaderValue::from_bytes(v.as_bytes()).ok());
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| ApiError::ServiceError(e.to_string()))?;
+
+    if bytes.is_empty() {
+        return Ok(status.into_response());
+    }
+
+    let body: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| ApiError::ServiceError(format!("Invalid response body: {}", e)))?;
+    let mut response = (status, Json(body)).into_response();
+    if let Some(retry_after) = retry_after {
+        response.headers_mut().insert(RETRY_AFTER, retry_after);
+    }
+    if let Some(cache_control) = cache_control {
+        response.headers_mut().insert(CACHE_CONTROL, cache_control);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_for_replaces_what_the_caller_sent() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("10.0.0.1, 10.0.0.2"),
+        );
+
+        let peer: SocketAddr = "203.0.113.7:54321".parse().unwrap();
+        let headers = with_forwarded_for(&headers, peer);
+
+        assert_eq!(headers.get_all(X_FORWARDED_FOR).iter().count(), 1);
+        assert_eq!(headers[X_FORWARDED_FOR], "203.0.113.7");
+    }
+}
@@ -1,3 +1,5 @@
+pub mod client;
+
 use async_trait::async_trait;
 use uuid::Uuid;
 
@@ -9,11 +11,6 @@ use crate::{
     },
 };
 
-#[async_trait]
-pub trait AuthService: Send + Sync {
-    async fn validate_token(&self, token: &str) -> Result<User>;
-}
-
 #[async_trait]
 pub trait PostService: Send + Sync {
     async fn get_posts(
@@ -35,8 +32,6 @@ pub trait PostService: Send + Sync {
 
 #[async_trait]
 pub trait UserService: Send + Sync {
-    async fn get_user_by_id(&self, id: Uuid) -> Result<User>;
-
     async fn get_user_by_username(&self, username: &str) -> Result<User>;
 
     async fn update_user(&self, id: Uuid, user: User) -> Result<User>;
@@ -74,27 +69,13 @@ pub mod mock {
     use super::*;
     use chrono::Utc;
 
-    pub struct MockAuthService;
+    #[derive(Clone)]
     pub struct MockPostService;
+    #[derive(Clone)]
     pub struct MockUserService;
+    #[derive(Clone)]
     pub struct MockCommentService;
 
-    #[async_trait]
-    impl AuthService for MockAuthService {
-        async fn validate_token(&self, _token: &str) -> Result<User> {
-            Ok(User {
-                id: Uuid::new_v4(),
-                email: "test@example.com".to_string(),
-                username: "testuser".to_string(),
-                display_name: Some("Test User".to_string()),
-                bio: None,
-                avatar_url: None,
-                created_at: Utc::now(),
-                updated_at: Utc::now(),
-            })
-        }
-    }
-
     #[async_trait]
     impl PostService for MockPostService {
         async fn get_posts(
@@ -122,16 +103,16 @@ pub mod mock {
             })
         }
 
-        async fn create_post(&self, _author_id: Uuid, _req: CreatePostRequest) -> Result<Post> {
+        async fn create_post(&self, author_id: Uuid, req: CreatePostRequest) -> Result<Post> {
             Ok(Post {
                 id: Uuid::new_v4(),
-                author_id: Uuid::new_v4(),
-                title: "Test Post".to_string(),
-                slug: "test-post".to_string(),
-                content: "Test content".to_string(),
-                excerpt: None,
-                status: PostStatus::Draft,
-                published_at: None,
+                author_id,
+                slug: slugify(&req.title),
+                title: req.title,
+                content: req.content,
+                excerpt: req.excerpt,
+                published_at: (req.status == PostStatus::Published).then(Utc::now),
+                status: req.status,
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
             })
@@ -139,19 +120,21 @@ pub mod mock {
 
         async fn update_post(
             &self,
-            _id: Uuid,
-            _author_id: Uuid,
-            _req: UpdatePostRequest,
+            id: Uuid,
+            author_id: Uuid,
+            req: UpdatePostRequest,
         ) -> Result<Post> {
+            let title = req.title.unwrap_or_else(|| "Updated Post".to_string());
+            let status = req.status.unwrap_or(PostStatus::Published);
             Ok(Post {
-                id: Uuid::new_v4(),
-                author_id: Uuid::new_v4(),
-                title: "Updated Post".to_string(),
-                slug: "updated-post".to_string(),
-                content: "Updated content".to_string(),
-                excerpt: None,
-                status: PostStatus::Published,
-                published_at: Some(Utc::now()),
+                id,
+                author_id,
+                slug: slugify(&title),
+                title,
+                content: req.content.unwrap_or_else(|| "Updated content".to_string()),
+                excerpt: req.excerpt,
+                published_at: (status == PostStatus::Published).then(Utc::now),
+                status,
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
             })
@@ -164,19 +147,6 @@ pub mod mock {
 
     #[async_trait]
     impl UserService for MockUserService {
-        async fn get_user_by_id(&self, _id: Uuid) -> Result<User> {
-            Ok(User {
-                id: Uuid::new_v4(),
-                email: "test@example.com".to_string(),
-                username: "testuser".to_string(),
-                display_name: Some("Test User".to_string()),
-                bio: None,
-                avatar_url: None,
-                created_at: Utc::now(),
-                updated_at: Utc::now(),
-            })
-        }
-
         async fn get_user_by_username(&self, _username: &str) -> Result<User> {
             Ok(User {
                 id: Uuid::new_v4(),
@@ -209,16 +179,16 @@ pub mod mock {
 
         async fn create_comment(
             &self,
-            _post_id: Uuid,
-            _author_id: Option<Uuid>,
-            _req: CreateCommentRequest,
+            post_id: Uuid,
+            author_id: Option<Uuid>,
+            req: CreateCommentRequest,
         ) -> Result<Comment> {
             Ok(Comment {
                 id: Uuid::new_v4(),
-                post_id: Uuid::new_v4(),
-                author_id: Some(Uuid::new_v4()),
-                parent_id: None,
-                content: "Test comment".to_string(),
+                post_id,
+                author_id,
+                parent_id: req.parent_id,
+                content: req.content,
                 status: CommentStatus::Pending,
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
@@ -227,17 +197,17 @@ pub mod mock {
 
         async fn update_comment(
             &self,
-            _id: Uuid,
-            _author_id: Option<Uuid>,
-            _req: UpdateCommentRequest,
+            id: Uuid,
+            author_id: Option<Uuid>,
+            req: UpdateCommentRequest,
         ) -> Result<Comment> {
             Ok(Comment {
-                id: Uuid::new_v4(),
+                id,
                 post_id: Uuid::new_v4(),
-                author_id: Some(Uuid::new_v4()),
+                author_id,
                 parent_id: None,
-                content: "Updated comment".to_string(),
-                status: CommentStatus::Approved,
+                content: req.content.unwrap_or_else(|| "Updated comment".to_string()),
+                status: req.status.unwrap_or(CommentStatus::Approved),
                 created_at: Utc::now(),
                 updated_at: Utc::now(),
             })
@@ -247,4 +217,14 @@ pub mod mock {
             Ok(())
         }
     }
+
+    /// Lowercase words joined by hyphens, e.g. "Hello, World" -> "hello-world".
+    fn slugify(title: &str) -> String {
+        title
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+            .join("-")
+    }
 }
\ No newline at end of file
@@ -1,11 +1,13 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
 use thiserror::Error;
 
+use crate::password_policy::PasswordViolation;
+
 #[derive(Error, Debug)]
 pub enum AuthError {
     #[error("Invalid credentials")]
@@ -20,9 +22,53 @@ pub enum AuthError {
     #[error("Token expired")]
     TokenExpired,
 
+    #[error("Invalid two-factor code")]
+    InvalidTwoFactorCode,
+
+    /// Too many failed logins: backing off (429) or locked (423). Clients
+    /// may try again after `retry_after` seconds.
+    #[error("Too many failed login attempts")]
+    LoginThrottled { retry_after: u64, locked: bool },
+
+    #[error("Permission denied")]
+    Forbidden,
+
+    #[error("User not found")]
+    UserNotFound,
+
+    #[error("Passkey not found")]
+    PasskeyNotFound,
+
+    #[error("Session not found")]
+    SessionNotFound,
+
+    #[error("Access token not found")]
+    AccessTokenNotFound,
+
+    #[error("OAuth client not found")]
+    OAuthClientNotFound,
+
+    #[error("Identity provider not found")]
+    ProviderNotFound,
+
+    #[error("Identity provider unavailable")]
+    IdentityProviderUnavailable,
+
     #[error("Invalid input: {0}")]
     BadRequest(String),
 
+    /// A refused OAuth request, answered in the RFC 6749 error format with
+    /// one of its error codes.
+    #[error("{description}")]
+    OAuth {
+        error: &'static str,
+        description: String,
+    },
+
+    /// A new password broke the password policy, for each listed reason.
+    #[error("Password does not meet the requirements")]
+    WeakPassword(Vec<PasswordViolation>),
+
     #[error("Database error: {0}")]
     Database(String),
 
@@ -32,12 +78,48 @@ pub enum AuthError {
 
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
+        if let AuthError::OAuth { error, description } = &self {
+            let status = match *error {
+                "invalid_client" => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::BAD_REQUEST,
+            };
+            let body = Json(json!({ "error": error, "error_description": description }));
+            return (status, body).into_response();
+        }
+
+        let retry_after = match &self {
+            AuthError::LoginThrottled { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        };
+        let violations = match &self {
+            AuthError::WeakPassword(violations) => Some(violations.clone()),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AuthError::InvalidCredentials => (StatusCode::UNAUTHORIZED, self.to_string()),
             AuthError::UserExists => (StatusCode::CONFLICT, self.to_string()),
             AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, self.to_string()),
             AuthError::TokenExpired => (StatusCode::UNAUTHORIZED, self.to_string()),
+            AuthError::InvalidTwoFactorCode => (StatusCode::UNAUTHORIZED, self.to_string()),
+            AuthError::LoginThrottled { locked: true, .. } => (
+                StatusCode::LOCKED,
+                "Account is temporarily locked".to_string(),
+            ),
+            AuthError::LoginThrottled { locked: false, .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, self.to_string())
+            }
+            AuthError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
+            AuthError::UserNotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            AuthError::PasskeyNotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            AuthError::SessionNotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            AuthError::AccessTokenNotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            AuthError::OAuthClientNotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            AuthError::ProviderNotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            AuthError::IdentityProviderUnavailable => (StatusCode::BAD_GATEWAY, self.to_string()),
             AuthError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AuthError::OAuth { .. } => (StatusCode::BAD_REQUEST, self.to_string()),
+            AuthError::WeakPassword(_) => (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()),
             AuthError::Database(msg) => {
                 tracing::error!("Database error: {}", msg);
                 (
@@ -54,15 +136,22 @@ impl IntoResponse for AuthError {
             }
         };
 
-        let body = Json(json!({
+        let mut body = json!({
             "error": {
                 "message": error_message,
                 "code": status.as_u16()
             }
-        }));
+        });
+        if let Some(violations) = violations {
+            body["error"]["violations"] = json!(violations);
+        }
+        let body = Json(body);
 
-        (status, body).into_response()
+        match retry_after {
+            Some(seconds) => (status, [(RETRY_AFTER, seconds.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
     }
 }
 
-pub type Result<T> = std::result::Result<T, AuthError>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, AuthError>;
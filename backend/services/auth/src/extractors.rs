@@ -0,0 +1,145 @@
+use std::marker::PhantomData;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{
+        header::{AUTHORIZATION, USER_AGENT},
+        request::Parts,
+    },
+};
+
+use crate::{
+    error::AuthError,
+    models::{Claims, ClientInfo},
+    rbac::{has_permission, RequiredPermission},
+    services::AuthService,
+};
+
+/// The caller identified by a valid, unrevoked `Authorization: Bearer` token.
+/// Tokens issued to OAuth clients or to an admin impersonating the user are
+/// refused: they only act for the user through the gateway, and never manage
+/// the account.
+pub struct CurrentUser(pub Claims);
+
+#[async_trait]
+impl FromRequestParts<Arc<AuthService>> for CurrentUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        service: &Arc<AuthService>,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthError::InvalidToken)?;
+
+        let claims = service.validate_token(token).await?;
+        if claims.client_id.is_some() || claims.act.is_some() {
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(CurrentUser(claims))
+    }
+}
+
+/// The address of the client behind the request, when known.
+///
+/// This is the peer address unless `TRUST_FORWARDED_FOR=true`, in which case
+/// the last `X-Forwarded-For` entry is used. Only set it when the service is
+/// reachable solely through the API gateway, which replaces the header on
+/// every request it proxies; otherwise clients could pick their own address.
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let forwarded = trust_forwarded_for()
+            .then(|| {
+                parts
+                    .headers
+                    .get_all("x-forwarded-for")
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .flat_map(|value| value.split(','))
+                    .last()
+                    .and_then(|ip| ip.trim().parse().ok())
+            })
+            .flatten();
+        let peer = || {
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip())
+        };
+
+        Ok(ClientIp(forwarded.or_else(peer)))
+    }
+}
+
+/// Longest `User-Agent` kept on a session.
+const MAX_USER_AGENT_LEN: usize = 512;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ClientIp(ip) = ClientIp::from_request_parts(parts, state).await?;
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LEN).collect());
+
+        Ok(ClientInfo { ip, user_agent })
+    }
+}
+
+fn trust_forwarded_for() -> bool {
+    static TRUST: OnceLock<bool> = OnceLock::new();
+    *TRUST.get_or_init(|| std::env::var("TRUST_FORWARDED_FOR").is_ok_and(|value| value == "true"))
+}
+
+/// A [`CurrentUser`] whose roles grant `P`. Anyone else is rejected with
+/// `Forbidden`.
+pub struct Require<P> {
+    pub claims: Claims,
+    _permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<P> FromRequestParts<Arc<AuthService>> for Require<P>
+where
+    P: RequiredPermission,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        service: &Arc<AuthService>,
+    ) -> Result<Self, Self::Rejection> {
+        let CurrentUser(claims) = CurrentUser::from_request_parts(parts, service).await?;
+        if !has_permission(&claims.roles, P::PERMISSION) {
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(Require {
+            claims,
+            _permission: PhantomData,
+        })
+    }
+}
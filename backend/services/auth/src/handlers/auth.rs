@@ -1,39 +1,424 @@
-use axum::{extract::Json, http::StatusCode};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::StatusCode,
+};
+use jsonwebtoken::jwk::JwkSet;
+use uuid::Uuid;
 
 use crate::{
     error::Result,
-    models::{AuthResponse, LoginRequest, RegisterRequest, ValidateTokenRequest},
+    extractors::{CurrentUser, Require},
+    models::{
+        AccountExport, AuditEvent, AuditLogQuery, AuthResponse, ChangePasswordRequest, ClientInfo,
+        ConsumeMagicLinkRequest, CreatePersonalAccessTokenRequest, DisableTwoFactorRequest,
+        FinishPasskeyLoginRequest, FinishPasskeyRegistrationRequest, ForgotPasswordRequest,
+        ImpersonationResponse, LoginRequest, LoginResponse, LogoutRequest, MagicLinkRequest,
+        MagicLinkResponse, OidcAuthorizationResponse, OidcCallbackRequest, OidcProviderResponse,
+        PasskeyResponse, PersonalAccessTokenIdentity, PersonalAccessTokenResponse,
+        RecoveryCodesResponse, RefreshTokenRequest, RegisterRequest, ResetPasswordRequest,
+        RevocationListResponse, SessionResponse, StartPasskeyLoginRequest, TwoFactorCodeRequest,
+        TwoFactorSetupResponse, UnlockAccountRequest, UpdateRolesRequest, UserResponse,
+        ValidateTokenRequest, VerifyEmailQuery, VerifyTwoFactorRequest,
+    },
+    rbac::perm,
     services::AuthService,
+    webauthn::{CreationOptions, RequestOptions},
 };
 
 pub async fn register(
+    State(service): State<Arc<AuthService>>,
+    client: ClientInfo,
     Json(req): Json<RegisterRequest>,
 ) -> Result<(StatusCode, Json<AuthResponse>)> {
-    let service = AuthService::new();
-    let (token, user) = service.register(req).await?;
+    let response = service.register(req, &client).await?;
 
-    Ok((
-        StatusCode::CREATED,
-        Json(AuthResponse { token, user }),
-    ))
+    Ok((StatusCode::CREATED, Json(response)))
 }
 
 pub async fn login(
+    State(service): State<Arc<AuthService>>,
+    client: ClientInfo,
     Json(req): Json<LoginRequest>,
-) -> Result<(StatusCode, Json<AuthResponse>)> {
-    let service = AuthService::new();
-    let (token, user) = service.login(req).await?;
+) -> Result<(StatusCode, Json<LoginResponse>)> {
+    let response = service.login(req, &client).await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+pub async fn refresh(
+    State(service): State<Arc<AuthService>>,
+    client: ClientInfo,
+    Json(req): Json<RefreshTokenRequest>,
+) -> Result<Json<AuthResponse>> {
+    let response = service.refresh(&req.refresh_token, &client).await?;
+    Ok(Json(response))
+}
+
+pub async fn logout(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+    req: Option<Json<LogoutRequest>>,
+) -> Result<StatusCode> {
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    service
+        .logout(&claims, req.refresh_token.as_deref(), &client)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn logout_all(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+) -> Result<StatusCode> {
+    service.logout_all(claims.sub, &client).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The caller's account data, for the export the API gateway assembles.
+pub async fn export_account(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<Json<AccountExport>> {
+    let export = service.export_account(claims.sub).await?;
+    Ok(Json(export))
+}
+
+pub async fn delete_account(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+) -> Result<StatusCode> {
+    service.delete_account(claims.sub, &client).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_sessions(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<Json<Vec<SessionResponse>>> {
+    let sessions = service.list_sessions(claims.sub, claims.sid).await?;
+    Ok(Json(sessions))
+}
+
+pub async fn revoke_session(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    service
+        .revoke_session(claims.sub, session_id, &client)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    Ok((
-        StatusCode::OK,
-        Json(AuthResponse { token, user }),
-    ))
+pub async fn create_personal_access_token(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+    Json(req): Json<CreatePersonalAccessTokenRequest>,
+) -> Result<(StatusCode, Json<PersonalAccessTokenResponse>)> {
+    let token = service
+        .create_personal_access_token(claims.sub, req, &client)
+        .await?;
+    Ok((StatusCode::CREATED, Json(token)))
 }
 
-pub async fn validate_token(
+pub async fn list_personal_access_tokens(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<Json<Vec<PersonalAccessTokenResponse>>> {
+    let tokens = service.list_personal_access_tokens(claims.sub).await?;
+    Ok(Json(tokens))
+}
+
+pub async fn delete_personal_access_token(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    Path(token_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    service
+        .delete_personal_access_token(claims.sub, token_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn verify_personal_access_token(
+    State(service): State<Arc<AuthService>>,
     Json(req): Json<ValidateTokenRequest>,
+) -> Result<Json<PersonalAccessTokenIdentity>> {
+    let identity = service.verify_personal_access_token(&req.token).await?;
+    Ok(Json(identity))
+}
+
+pub async fn verify_two_factor(
+    State(service): State<Arc<AuthService>>,
+    client: ClientInfo,
+    Json(req): Json<VerifyTwoFactorRequest>,
+) -> Result<Json<AuthResponse>> {
+    let response = service
+        .verify_two_factor(&req.two_factor_token, &req.code, &client)
+        .await?;
+    Ok(Json(response))
+}
+
+pub async fn setup_two_factor(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<Json<TwoFactorSetupResponse>> {
+    let response = service.setup_two_factor(claims.sub).await?;
+    Ok(Json(response))
+}
+
+pub async fn confirm_two_factor(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>> {
+    let response = service
+        .confirm_two_factor(claims.sub, &req.code, &client)
+        .await?;
+    Ok(Json(response))
+}
+
+pub async fn disable_two_factor(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+    Json(req): Json<DisableTwoFactorRequest>,
+) -> Result<StatusCode> {
+    service
+        .disable_two_factor(claims.sub, &req.password, &req.code, &client)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn regenerate_recovery_codes(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>> {
+    let response = service
+        .regenerate_recovery_codes(claims.sub, &req.code, &client)
+        .await?;
+    Ok(Json(response))
+}
+
+pub async fn oidc_providers(
+    State(service): State<Arc<AuthService>>,
+) -> Json<Vec<OidcProviderResponse>> {
+    Json(service.oidc_providers())
+}
+
+pub async fn start_oidc_login(
+    State(service): State<Arc<AuthService>>,
+    Path(provider): Path<String>,
+) -> Result<Json<OidcAuthorizationResponse>> {
+    let response = service.start_oidc_login(&provider).await?;
+    Ok(Json(response))
+}
+
+pub async fn finish_oidc_login(
+    State(service): State<Arc<AuthService>>,
+    Path(provider): Path<String>,
+    client: ClientInfo,
+    Json(req): Json<OidcCallbackRequest>,
+) -> Result<Json<LoginResponse>> {
+    let response = service.finish_oidc_login(&provider, req, &client).await?;
+    Ok(Json(response))
+}
+
+pub async fn start_passkey_registration(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<Json<CreationOptions>> {
+    let options = service.start_passkey_registration(claims.sub).await?;
+    Ok(Json(options))
+}
+
+pub async fn finish_passkey_registration(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    Json(req): Json<FinishPasskeyRegistrationRequest>,
+) -> Result<(StatusCode, Json<PasskeyResponse>)> {
+    let passkey = service.finish_passkey_registration(claims.sub, req).await?;
+    Ok((StatusCode::CREATED, Json(passkey)))
+}
+
+pub async fn start_passkey_login(
+    State(service): State<Arc<AuthService>>,
+    req: Option<Json<StartPasskeyLoginRequest>>,
+) -> Result<Json<RequestOptions>> {
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let options = service.start_passkey_login(req.email.as_deref()).await?;
+    Ok(Json(options))
+}
+
+pub async fn finish_passkey_login(
+    State(service): State<Arc<AuthService>>,
+    client: ClientInfo,
+    Json(req): Json<FinishPasskeyLoginRequest>,
+) -> Result<Json<AuthResponse>> {
+    let response = service
+        .finish_passkey_login(req.credential, &client)
+        .await?;
+    Ok(Json(response))
+}
+
+pub async fn list_passkeys(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<Json<Vec<PasskeyResponse>>> {
+    let passkeys = service.list_passkeys(claims.sub).await?;
+    Ok(Json(passkeys))
+}
+
+pub async fn delete_passkey(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    Path(passkey_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    service.delete_passkey(claims.sub, passkey_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn forgot_password(
+    State(service): State<Arc<AuthService>>,
+    Json(req): Json<ForgotPasswordRequest>,
+) -> Result<StatusCode> {
+    service.forgot_password(&req.email).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn send_magic_link(
+    State(service): State<Arc<AuthService>>,
+    Json(req): Json<MagicLinkRequest>,
+) -> Result<(StatusCode, Json<MagicLinkResponse>)> {
+    let response = service.send_magic_link(&req.email, req.bind_device).await?;
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+pub async fn consume_magic_link(
+    State(service): State<Arc<AuthService>>,
+    client: ClientInfo,
+    Json(req): Json<ConsumeMagicLinkRequest>,
+) -> Result<Json<LoginResponse>> {
+    let response = service
+        .consume_magic_link(&req.token, req.device_token.as_deref(), &client)
+        .await?;
+    Ok(Json(response))
+}
+
+pub async fn reset_password(
+    State(service): State<Arc<AuthService>>,
+    client: ClientInfo,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<StatusCode> {
+    service
+        .reset_password(&req.token, &req.password, &client)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn change_password(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    client: ClientInfo,
+    Json(req): Json<ChangePasswordRequest>,
 ) -> Result<StatusCode> {
-    let service = AuthService::new();
-    service.validate_token(&req.token)?;
-    Ok(StatusCode::OK)
-}
\ No newline at end of file
+    service
+        .change_password(
+            claims.sub,
+            claims.sid,
+            &req.current_password,
+            &req.new_password,
+            &client,
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn verify_email(
+    State(service): State<Arc<AuthService>>,
+    Query(query): Query<VerifyEmailQuery>,
+) -> Result<StatusCode> {
+    service.verify_email(&query.token).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn resend_verification(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<StatusCode> {
+    service.resend_verification(claims.sub).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn update_roles(
+    State(service): State<Arc<AuthService>>,
+    Require { claims: admin, .. }: Require<perm::ManageUsers>,
+    client: ClientInfo,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<UpdateRolesRequest>,
+) -> Result<Json<UserResponse>> {
+    let user = service
+        .update_roles(admin.sub, user_id, req.roles, &client)
+        .await?;
+    Ok(Json(user.into()))
+}
+
+pub async fn unlock_account(
+    State(service): State<Arc<AuthService>>,
+    client: ClientInfo,
+    Json(req): Json<UnlockAccountRequest>,
+) -> Result<StatusCode> {
+    service.unlock_account(&req.token, &client).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unlock_user(
+    State(service): State<Arc<AuthService>>,
+    Require { claims: admin, .. }: Require<perm::ManageUsers>,
+    client: ClientInfo,
+    Path(user_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    service.unlock_user(admin.sub, user_id, &client).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn impersonate(
+    State(service): State<Arc<AuthService>>,
+    Require { claims: admin, .. }: Require<perm::ManageUsers>,
+    client: ClientInfo,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<ImpersonationResponse>> {
+    let response = service.impersonate(&admin, user_id, &client).await?;
+    Ok(Json(response))
+}
+
+pub async fn audit_log(
+    State(service): State<Arc<AuthService>>,
+    Require { .. }: Require<perm::ManageUsers>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditEvent>>> {
+    let events = service.query_audit_log(query).await?;
+    Ok(Json(events))
+}
+
+pub async fn revocations(
+    State(service): State<Arc<AuthService>>,
+) -> Result<Json<RevocationListResponse>> {
+    let list = service.revocation_list().await?;
+    Ok(Json(list))
+}
+
+pub async fn jwks(State(service): State<Arc<AuthService>>) -> Json<JwkSet> {
+    Json(service.jwks())
+}
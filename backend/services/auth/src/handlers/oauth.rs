@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Form, Json, Path, Query, State},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL},
+        HeaderMap, HeaderName, StatusCode,
+    },
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use percent_encoding::percent_decode_str;
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    extractors::CurrentUser,
+    models::{
+        AuthorizationDecision, AuthorizationDecisionResponse, AuthorizationPreviewResponse,
+        AuthorizationRequest, ClientInfo, IntrospectionRequest, IntrospectionResponse,
+        OAuthClientResponse, OAuthConsentResponse, OAuthTokenResponse, RegisterOAuthClientRequest,
+        RevokeTokenRequest, TokenRequest,
+    },
+    services::AuthService,
+};
+
+pub async fn register_client(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    Json(req): Json<RegisterOAuthClientRequest>,
+) -> Result<(StatusCode, Json<OAuthClientResponse>)> {
+    let client = service.register_oauth_client(claims.sub, req).await?;
+    Ok((StatusCode::CREATED, Json(client)))
+}
+
+pub async fn list_clients(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<Json<Vec<OAuthClientResponse>>> {
+    let clients = service.list_oauth_clients(claims.sub).await?;
+    Ok(Json(clients))
+}
+
+pub async fn delete_client(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    Path(client_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    service.delete_oauth_client(claims.sub, client_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Called by the consent page with the client's query string.
+pub async fn preview_authorization(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    Query(req): Query<AuthorizationRequest>,
+) -> Result<Json<AuthorizationPreviewResponse>> {
+    let preview = service.preview_authorization(claims.sub, &req).await?;
+    Ok(Json(preview))
+}
+
+pub async fn decide_authorization(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    Json(decision): Json<AuthorizationDecision>,
+) -> Result<Json<AuthorizationDecisionResponse>> {
+    let response = service.decide_authorization(claims.sub, decision).await?;
+    Ok(Json(response))
+}
+
+pub async fn token(
+    State(service): State<Arc<AuthService>>,
+    headers: HeaderMap,
+    client: ClientInfo,
+    Form(req): Form<TokenRequest>,
+) -> Result<([(HeaderName, &'static str); 1], Json<OAuthTokenResponse>)> {
+    let (client_id, client_secret) =
+        client_credentials(&headers, req.client_id.clone(), req.client_secret.clone());
+    let response = service
+        .exchange_token(
+            client_id.as_deref(),
+            client_secret.as_deref(),
+            &req,
+            &client,
+        )
+        .await?;
+
+    Ok(([(CACHE_CONTROL, "no-store")], Json(response)))
+}
+
+pub async fn revoke(
+    State(service): State<Arc<AuthService>>,
+    headers: HeaderMap,
+    Form(req): Form<RevokeTokenRequest>,
+) -> Result<StatusCode> {
+    let (client_id, client_secret) = client_credentials(&headers, req.client_id, req.client_secret);
+    service
+        .revoke_oauth_token(client_id.as_deref(), client_secret.as_deref(), &req.token)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+/// For other services checking a bearer token. Not exposed by the gateway.
+pub async fn introspect(
+    State(service): State<Arc<AuthService>>,
+    Form(req): Form<IntrospectionRequest>,
+) -> Result<Json<IntrospectionResponse>> {
+    let response = service.introspect(&req.token).await?;
+    Ok(Json(response))
+}
+
+pub async fn list_consents(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+) -> Result<Json<Vec<OAuthConsentResponse>>> {
+    let consents = service.list_oauth_consents(claims.sub).await?;
+    Ok(Json(consents))
+}
+
+pub async fn revoke_consent(
+    State(service): State<Arc<AuthService>>,
+    CurrentUser(claims): CurrentUser,
+    Path(client_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    service.revoke_oauth_consent(claims.sub, client_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Client credentials from an `Authorization: Basic` header, falling back to
+/// the form body. RFC 6749 has both halves of the header form-urlencoded.
+fn client_credentials(
+    headers: &HeaderMap,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+) -> (Option<String>, Option<String>) {
+    let basic = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|value| STANDARD.decode(value.trim()).ok())
+        .and_then(|value| String::from_utf8(value).ok());
+    let Some((id, secret)) = basic.as_deref().and_then(|value| value.split_once(':')) else {
+        return (client_id, client_secret);
+    };
+
+    (Some(form_decode(id)), Some(form_decode(secret)))
+}
+
+fn form_decode(value: &str) -> String {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned()
+}
@@ -0,0 +1,254 @@
+use std::path::Path;
+
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::{
+    pkcs8::{DecodePrivateKey, EncodePrivateKey},
+    SigningKey as Ed25519SigningKey,
+};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::Error as JwtError,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{AuthError, Result};
+
+struct SigningKey {
+    kid: String,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// Base64url-encoded Ed25519 public key, the JWK `x` parameter.
+    public_key: String,
+}
+
+impl SigningKey {
+    fn from_ed25519(kid: String, key: &Ed25519SigningKey) -> anyhow::Result<Self> {
+        let der = key
+            .to_pkcs8_der()
+            .context("failed to encode signing key as PKCS#8")?;
+        let public_key = URL_SAFE_NO_PAD.encode(key.verifying_key().to_bytes());
+        let decoding = DecodingKey::from_ed_components(&public_key)?;
+
+        Ok(Self {
+            kid,
+            encoding: EncodingKey::from_ed_der(der.as_bytes()),
+            decoding,
+            public_key,
+        })
+    }
+
+    fn jwk(&self) -> Jwk {
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::EdDSA),
+                key_id: Some(self.kid.clone()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: self.public_key.clone(),
+            }),
+        }
+    }
+}
+
+/// The Ed25519 keys the auth service signs access tokens with.
+///
+/// Exactly one key is active and used for signing. The others are kept so
+/// tokens they signed still verify, and are published in the JWKS until they
+/// are removed. To rotate, add a new key, make it active, and remove the old
+/// one once every token it signed has expired.
+pub struct KeySet {
+    active: usize,
+    keys: Vec<SigningKey>,
+}
+
+impl KeySet {
+    /// Loads every `<kid>.pem` (PKCS#8 Ed25519 private key) in
+    /// `JWT_KEYS_DIR`. `JWT_ACTIVE_KID` picks the signing key and defaults to
+    /// the last kid in lexical order, so date-named keys rotate naturally.
+    /// Without `JWT_KEYS_DIR` a throwaway key is generated.
+    pub fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("JWT_KEYS_DIR") {
+            Ok(dir) => Self::load_dir(
+                Path::new(&dir),
+                std::env::var("JWT_ACTIVE_KID").ok().as_deref(),
+            ),
+            Err(_) => {
+                tracing::warn!("JWT_KEYS_DIR is not set, signing tokens with an ephemeral key");
+                Self::generate()
+            }
+        }
+    }
+
+    pub fn generate() -> anyhow::Result<Self> {
+        let key = Ed25519SigningKey::generate(&mut rand::rngs::OsRng);
+        let kid = format!("ephemeral-{}", uuid::Uuid::new_v4().simple());
+
+        Ok(Self {
+            active: 0,
+            keys: vec![SigningKey::from_ed25519(kid, &key)?],
+        })
+    }
+
+    fn load_dir(dir: &Path, active_kid: Option<&str>) -> anyhow::Result<Self> {
+        let mut keys = Vec::new();
+        for entry in
+            std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+                continue;
+            }
+
+            let kid = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .context("key file name is not valid UTF-8")?
+                .to_string();
+            let pem = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let key = Ed25519SigningKey::from_pkcs8_pem(&pem)
+                .map_err(|e| anyhow::anyhow!("{} is not an Ed25519 key: {}", path.display(), e))?;
+
+            keys.push(SigningKey::from_ed25519(kid, &key)?);
+        }
+        keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+
+        let active = match active_kid {
+            Some(kid) => keys
+                .iter()
+                .position(|key| key.kid == kid)
+                .with_context(|| {
+                    format!("JWT_ACTIVE_KID {} not found in {}", kid, dir.display())
+                })?,
+            None => keys
+                .len()
+                .checked_sub(1)
+                .with_context(|| format!("no signing keys found in {}", dir.display()))?,
+        };
+
+        tracing::info!(
+            "Loaded {} signing key(s), active kid {}",
+            keys.len(),
+            keys[active].kid
+        );
+        Ok(Self { active, keys })
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let key = &self.keys[self.active];
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(key.kid.clone());
+
+        encode(&header, claims, &key.encoding)
+            .map_err(|e| AuthError::Internal(anyhow::anyhow!("Failed to create token: {}", e)))
+    }
+
+    /// Verifies a token against the key named by its `kid` header.
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> std::result::Result<TokenData<T>, JwtError> {
+        let kid = decode_header(token)?.kid;
+        let key = self
+            .keys
+            .iter()
+            .find(|key| Some(&key.kid) == kid.as_ref())
+            .ok_or(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat)?;
+
+        decode(token, &key.decoding, &Validation::new(Algorithm::EdDSA))
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().map(SigningKey::jwk).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims {
+            sub: "alice".to_string(),
+            exp: (chrono::Utc::now() + chrono::Duration::minutes(5)).timestamp() as usize,
+        }
+    }
+
+    fn add_key(dir: &Path, kid: &str) {
+        let key = Ed25519SigningKey::generate(&mut rand::rngs::OsRng);
+        let pem = key.to_pkcs8_pem(LineEnding::LF).unwrap();
+        std::fs::write(dir.join(format!("{}.pem", kid)), pem.as_bytes()).unwrap();
+    }
+
+    fn kids(keys: &KeySet) -> Vec<String> {
+        keys.jwks()
+            .keys
+            .into_iter()
+            .filter_map(|jwk| jwk.common.key_id)
+            .collect()
+    }
+
+    #[test]
+    fn retired_keys_verify_until_they_are_removed() {
+        let dir = std::env::temp_dir().join(format!("keys-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir(&dir).unwrap();
+        add_key(&dir, "2025-01-01");
+        let old = KeySet::load_dir(&dir, None).unwrap();
+        let old_token = old.sign(&claims()).unwrap();
+
+        // Rotate: the newer kid sorts last, so it becomes the signing key.
+        add_key(&dir, "2025-06-01");
+        let rotated = KeySet::load_dir(&dir, None).unwrap();
+        let new_token = rotated.sign(&claims()).unwrap();
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid.as_deref(),
+            Some("2025-06-01")
+        );
+        assert_eq!(kids(&rotated), ["2025-01-01", "2025-06-01"]);
+        assert_eq!(
+            rotated.verify::<TestClaims>(&old_token).unwrap().claims.sub,
+            "alice"
+        );
+        rotated.verify::<TestClaims>(&new_token).unwrap();
+
+        // Pruned once its tokens have expired.
+        std::fs::remove_file(dir.join("2025-01-01.pem")).unwrap();
+        let pruned = KeySet::load_dir(&dir, None).unwrap();
+        assert_eq!(kids(&pruned), ["2025-06-01"]);
+        assert!(pruned.verify::<TestClaims>(&old_token).is_err());
+        pruned.verify::<TestClaims>(&new_token).unwrap();
+
+        // Pinning the active kid keeps signing with the old key.
+        add_key(&dir, "2025-01-01");
+        let pinned = KeySet::load_dir(&dir, Some("2025-01-01")).unwrap();
+        let token = pinned.sign(&claims()).unwrap();
+        assert_eq!(
+            decode_header(&token).unwrap().kid.as_deref(),
+            Some("2025-01-01")
+        );
+        assert!(KeySet::load_dir(&dir, Some("2024-01-01")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
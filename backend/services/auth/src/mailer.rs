@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> anyhow::Result<()>;
+}
+
+/// Picks SMTP when `SMTP_HOST` is set. Otherwise mail goes to `.eml` files in
+/// `MAIL_OUTBOX_DIR`, or to stdout when that is unset too.
+pub fn from_env() -> anyhow::Result<Arc<dyn Mailer>> {
+    if std::env::var("SMTP_HOST").is_ok() {
+        return Ok(Arc::new(SmtpMailer::from_env()?));
+    }
+
+    let dir = std::env::var("MAIL_OUTBOX_DIR").ok().map(PathBuf::from);
+    match &dir {
+        Some(dir) => tracing::warn!("SMTP_HOST is not set, writing mail to {}", dir.display()),
+        None => tracing::warn!("SMTP_HOST is not set, printing mail to stdout"),
+    }
+    Ok(Arc::new(OutboxMailer { dir }))
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// `SMTP_TLS` selects `starttls` (default), `tls` for implicit TLS, or
+    /// `none` for local catch-all servers.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let host = std::env::var("SMTP_HOST").context("SMTP_HOST must be set")?;
+        let from = std::env::var("SMTP_FROM_EMAIL")
+            .context("SMTP_FROM_EMAIL must be set")?
+            .parse()
+            .context("SMTP_FROM_EMAIL is not a valid address")?;
+
+        let mut builder = match std::env::var("SMTP_TLS").as_deref() {
+            Ok("none") => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            Ok("tls") => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+        };
+        if let Some(port) = std::env::var("SMTP_PORT").ok().and_then(|p| p.parse().ok()) {
+            builder = builder.port(port);
+        }
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("SMTP_USERNAME"),
+            std::env::var("SMTP_PASSWORD"),
+        ) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse().context("invalid recipient address")?)
+            .subject(email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body)?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Development mailer that keeps every message where a developer can read it.
+pub struct OutboxMailer {
+    dir: Option<PathBuf>,
+}
+
+#[async_trait]
+impl Mailer for OutboxMailer {
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        let message = format!(
+            "Date: {}\nTo: {}\nSubject: {}\n\n{}\n",
+            Utc::now().to_rfc2822(),
+            email.to,
+            email.subject,
+            email.body
+        );
+
+        match &self.dir {
+            Some(dir) => {
+                tokio::fs::create_dir_all(dir).await?;
+                let path = dir.join(format!(
+                    "{}-{}.eml",
+                    Utc::now().format("%Y%m%dT%H%M%S"),
+                    Uuid::new_v4().simple()
+                ));
+                tokio::fs::write(&path, message).await?;
+                tracing::info!("Wrote mail to {}", path.display());
+            }
+            None => println!("----- outgoing mail -----\n{}", message),
+        }
+
+        Ok(())
+    }
+}
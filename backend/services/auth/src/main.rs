@@ -1,14 +1,29 @@
 mod error;
+mod extractors;
 mod handlers;
+mod keys;
+mod mailer;
 mod models;
+mod oauth;
+mod oidc;
+mod password;
+mod password_policy;
+mod rbac;
+mod repositories;
 mod services;
+mod strength;
+mod throttle;
+mod tokens;
+mod totp;
+mod webauthn;
 
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use dotenv::dotenv;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -32,14 +47,131 @@ async fn main() {
         .allow_methods(Any)
         .allow_headers(Any);
 
+    let keys = keys::KeySet::from_env().expect("Failed to load JWT signing keys");
+    let mailer = mailer::from_env().expect("Failed to configure mailer");
+    let auth_service = Arc::new(services::AuthService::new(
+        repositories::Repositories::from_env(),
+        keys,
+        mailer,
+    ));
+
+    spawn_audit_log_pruning(auth_service.clone());
+
     // Build the router
     let app = Router::new()
         .route("/health", get(health_check))
+        .route("/.well-known/jwks.json", get(handlers::auth::jwks))
         .route("/auth/register", post(handlers::auth::register))
         .route("/auth/login", post(handlers::auth::login))
-        .route("/auth/validate", post(handlers::auth::validate_token))
+        .route("/auth/2fa/verify", post(handlers::auth::verify_two_factor))
+        .route("/auth/2fa/setup", post(handlers::auth::setup_two_factor))
+        .route(
+            "/auth/2fa/confirm",
+            post(handlers::auth::confirm_two_factor),
+        )
+        .route(
+            "/auth/2fa/disable",
+            post(handlers::auth::disable_two_factor),
+        )
+        .route(
+            "/auth/2fa/recovery-codes",
+            post(handlers::auth::regenerate_recovery_codes),
+        )
+        .route("/auth/oidc/providers", get(handlers::auth::oidc_providers))
+        .route(
+            "/auth/oidc/:provider/authorize",
+            get(handlers::auth::start_oidc_login),
+        )
+        .route(
+            "/auth/oidc/:provider/callback",
+            post(handlers::auth::finish_oidc_login),
+        )
+        .route("/auth/passkeys", get(handlers::auth::list_passkeys))
+        .route("/auth/passkeys/:id", delete(handlers::auth::delete_passkey))
+        .route(
+            "/auth/passkeys/register/start",
+            post(handlers::auth::start_passkey_registration),
+        )
+        .route(
+            "/auth/passkeys/register/finish",
+            post(handlers::auth::finish_passkey_registration),
+        )
+        .route(
+            "/auth/passkeys/login/start",
+            post(handlers::auth::start_passkey_login),
+        )
+        .route(
+            "/auth/passkeys/login/finish",
+            post(handlers::auth::finish_passkey_login),
+        )
+        .route("/auth/refresh", post(handlers::auth::refresh))
+        .route("/auth/logout", post(handlers::auth::logout))
+        .route("/auth/logout/all", post(handlers::auth::logout_all))
+        .route("/auth/me", delete(handlers::auth::delete_account))
+        .route("/auth/me/export", get(handlers::auth::export_account))
+        .route("/auth/sessions", get(handlers::auth::list_sessions))
+        .route("/auth/sessions/:id", delete(handlers::auth::revoke_session))
+        .route(
+            "/auth/tokens",
+            get(handlers::auth::list_personal_access_tokens)
+                .post(handlers::auth::create_personal_access_token),
+        )
+        .route(
+            "/auth/tokens/:id",
+            delete(handlers::auth::delete_personal_access_token),
+        )
+        .route(
+            "/auth/tokens/verify",
+            post(handlers::auth::verify_personal_access_token),
+        )
+        .route(
+            "/auth/password/forgot",
+            post(handlers::auth::forgot_password),
+        )
+        .route("/auth/password/reset", post(handlers::auth::reset_password))
+        .route(
+            "/auth/password/change",
+            post(handlers::auth::change_password),
+        )
+        .route("/auth/magic-link", post(handlers::auth::send_magic_link))
+        .route(
+            "/auth/magic-link/consume",
+            post(handlers::auth::consume_magic_link),
+        )
+        .route("/auth/verify", get(handlers::auth::verify_email))
+        .route(
+            "/auth/verify/resend",
+            post(handlers::auth::resend_verification),
+        )
+        .route("/auth/unlock", post(handlers::auth::unlock_account))
+        .route("/auth/users/:id/roles", put(handlers::auth::update_roles))
+        .route("/auth/users/:id/unlock", post(handlers::auth::unlock_user))
+        .route(
+            "/auth/users/:id/impersonate",
+            post(handlers::auth::impersonate),
+        )
+        .route("/auth/revocations", get(handlers::auth::revocations))
+        .route("/auth/audit", get(handlers::auth::audit_log))
+        .route(
+            "/oauth/clients",
+            get(handlers::oauth::list_clients).post(handlers::oauth::register_client),
+        )
+        .route("/oauth/clients/:id", delete(handlers::oauth::delete_client))
+        .route(
+            "/oauth/authorize",
+            get(handlers::oauth::preview_authorization).post(handlers::oauth::decide_authorization),
+        )
+        .route("/oauth/token", post(handlers::oauth::token))
+        .route("/oauth/revoke", post(handlers::oauth::revoke))
+        .route("/oauth/introspect", post(handlers::oauth::introspect))
+        .route("/oauth/consents", get(handlers::oauth::list_consents))
+        .route(
+            "/oauth/consents/:client_id",
+            delete(handlers::oauth::revoke_consent),
+        )
         .layer(TraceLayer::new_for_http())
-        .layer(cors);
+        .layer(cors)
+        .with_state(auth_service);
 
     // Get the port from environment variable or use default
     let port = std::env::var("PORT")
@@ -50,12 +182,30 @@ async fn main() {
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
     tracing::info!("listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 async fn health_check() -> &'static str {
     "OK"
-}
\ No newline at end of file
+}
+
+/// Drops audit events past their retention period once an hour.
+fn spawn_audit_log_pruning(service: Arc<services::AuthService>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match service.prune_audit_log().await {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!(removed, "Pruned old audit events"),
+                Err(err) => tracing::error!("Failed to prune audit events: {:?}", err),
+            }
+        }
+    });
+}
@@ -1,8 +1,15 @@
+use std::net::IpAddr;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::{
+    rbac::{self, Permission, Role, Scope},
+    webauthn::{AuthenticationCredential, RegistrationCredential},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: Uuid,
     pub email: String,
@@ -11,15 +18,395 @@ pub struct User {
     pub display_name: Option<String>,
     pub bio: Option<String>,
     pub avatar_url: Option<String>,
+    pub roles: Vec<Role>,
+    /// When the user proved they own `email`. Unverified accounts can sign in
+    /// but cannot publish until they do.
+    #[serde(default)]
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Every token issued by rotating the same login shares a family.
+    pub family_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A signed-in device: one login and every token refreshed from it. Shares
+/// its id with the refresh token family.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// The refresh token currently issued to the session.
+    pub refresh_token_id: Uuid,
+    /// The latest access token, revoked along with the session.
+    pub access_token_jti: Uuid,
+    pub access_token_expires_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// When the current refresh token expires, ending the session unless it
+    /// is refreshed first.
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Set when the session is an OAuth client acting for the user.
+    #[serde(default)]
+    pub client_id: Option<Uuid>,
+    /// What the OAuth client was granted. `None` for the user's own sessions.
+    #[serde(default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+/// A long-lived token for scripts such as CI pipelines, acting as its owner
+/// within `scopes`. Only the hash of the token is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonalAccessToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A third-party application registered to act for users through OAuth.
+/// Confidential clients authenticate with a secret, kept only as a hash;
+/// public clients such as mobile apps rely on PKCE alone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthClient {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub secret_hash: Option<String>,
+    /// The most a user can grant this client.
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A code from the authorization endpoint, exchanged once for tokens.
+/// `session_id` is fixed up front so a replayed code can end the session the
+/// first exchange started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthAuthorizationCode {
+    pub id: Uuid,
+    pub code_hash: String,
+    pub client_id: Uuid,
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    pub redirect_uri: String,
+    pub scopes: Vec<Scope>,
+    pub code_challenge: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Scopes a user has approved for a client, so they aren't asked again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthConsent {
+    pub user_id: Uuid,
+    pub client_id: Uuid,
+    pub scopes: Vec<Scope>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Where a request came from, recorded on the session it starts or
+/// refreshes.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordResetToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// A one-time sign-in link. When `device_hash` is set, the link only works
+/// together with the device token handed to whoever asked for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MagicLinkToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub device_hash: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailVerificationToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// A user's TOTP authenticator. It only guards logins once confirmed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorSecret {
+    pub user_id: Uuid,
+    /// Base32-encoded shared secret.
+    pub secret: String,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// Time step of the last accepted code, so a code works only once.
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryCode {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub code_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Issued after a correct password when the account has 2FA enabled. It is
+/// exchanged, together with a code, for the real token pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorChallenge {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub failed_attempts: i32,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// A WebAuthn credential registered to a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Passkey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Base64url credential id chosen by the authenticator.
+    pub credential_id: String,
+    /// Base64url SEC1 P-256 public key.
+    pub public_key: String,
+    /// Last signature counter reported by the authenticator. A counter that
+    /// fails to increase points to a cloned authenticator.
+    pub sign_count: i64,
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasskeyCeremony {
+    Registration,
+    Authentication,
+}
+
+/// A challenge handed to the browser for one passkey ceremony.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyChallenge {
+    pub id: Uuid,
+    /// The registering user, or for logins the account the challenge was
+    /// narrowed to. `None` for a discoverable-credential login.
+    pub user_id: Option<Uuid>,
+    pub challenge_hash: String,
+    pub ceremony: PasskeyCeremony,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Failed password logins counted against one key: an account's email
+/// (`account:<email>`) or a client address (`ip:<addr>`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginThrottle {
+    pub key: String,
+    pub failed_attempts: i32,
+    pub last_failure_at: DateTime<Utc>,
+    /// Logins for the key are refused until then.
+    pub blocked_until: Option<DateTime<Utc>>,
+    /// Set when the block is a lockout that the account owner is told about.
+    #[serde(default)]
+    pub locked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountUnlockToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// A sign-in with an external identity provider that has been started but
+/// not yet completed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcLoginState {
+    pub id: Uuid,
+    pub provider: String,
+    /// Hash of the `state` parameter round-tripped through the provider.
+    pub state_hash: String,
+    pub nonce: String,
+    /// PKCE verifier; the provider only saw its hash.
+    pub code_verifier: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Links an account to the subject (`sub`) an identity provider knows it by.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    /// Email the provider reported when the link was made.
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevokedToken {
+    pub jti: Uuid,
+    pub user_id: Uuid,
+    /// Expiry of the revoked token; the entry can be dropped after this.
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: DateTime<Utc>,
+}
+
+/// "Log out everywhere": every token for the user issued at or before
+/// `revoked_before` is rejected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserRevocation {
+    pub user_id: Uuid,
+    pub revoked_before: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    Register,
+    LoginSucceeded,
+    LoginFailed,
+    TokenRefreshed,
+    /// A spent refresh token came back, so its session was ended.
+    RefreshTokenReused,
+    PasswordChanged,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    RecoveryCodesRegenerated,
+    Logout,
+    SessionRevoked,
+    AllSessionsRevoked,
+    AccountDeleted,
+    /// An admin was issued a token to act as the user.
+    ImpersonationStarted,
+    /// An admin changed the user's roles.
+    RolesUpdated,
+    /// A lockout was lifted, from the emailed link or by an admin.
+    AccountUnlocked,
+    PersonalAccessTokenCreated,
+}
+
+impl AuditEventType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditEventType::Register => "register",
+            AuditEventType::LoginSucceeded => "login_succeeded",
+            AuditEventType::LoginFailed => "login_failed",
+            AuditEventType::TokenRefreshed => "token_refreshed",
+            AuditEventType::RefreshTokenReused => "refresh_token_reused",
+            AuditEventType::PasswordChanged => "password_changed",
+            AuditEventType::TwoFactorEnabled => "two_factor_enabled",
+            AuditEventType::TwoFactorDisabled => "two_factor_disabled",
+            AuditEventType::RecoveryCodesRegenerated => "recovery_codes_regenerated",
+            AuditEventType::Logout => "logout",
+            AuditEventType::SessionRevoked => "session_revoked",
+            AuditEventType::AllSessionsRevoked => "all_sessions_revoked",
+            AuditEventType::AccountDeleted => "account_deleted",
+            AuditEventType::ImpersonationStarted => "impersonation_started",
+            AuditEventType::RolesUpdated => "roles_updated",
+            AuditEventType::AccountUnlocked => "account_unlocked",
+            AuditEventType::PersonalAccessTokenCreated => "personal_access_token_created",
+        }
+    }
+}
+
+/// A security-relevant event, kept in an append-only log. `user_id` is
+/// missing when a failed login named an account that doesn't exist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub event_type: AuditEventType,
+    pub user_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
     pub email: String,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    #[serde(default)]
+    pub email_verified: bool,
+    /// Seconds since the epoch, to the millisecond: a token issued in the
+    /// same second as a revocation, but after it, must stay valid.
+    pub iat: f64,
     pub exp: usize,
+    pub jti: Uuid,
+    /// The session the token was issued to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+    /// Space-separated scopes of a token issued to an OAuth client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<Uuid>,
+    /// The admin acting as `sub`, on impersonation tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+}
+
+impl Claims {
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis((self.iat * 1000.0).round() as i64)
+            .unwrap_or(DateTime::UNIX_EPOCH)
+    }
+}
+
+/// Who is really behind a token issued to act as another user (RFC 8693).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: Uuid,
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,12 +422,432 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogoutRequest {
+    /// Also revokes the refresh token family of this session when given.
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: String,
+    /// Only let the browser that asked for the link use it.
+    #[serde(default)]
+    pub bind_device: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConsumeMagicLinkRequest {
+    pub token: String,
+    pub device_token: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MagicLinkResponse {
+    /// Returned when `bind_device` was requested; must be sent back with the
+    /// link's token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnlockAccountRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+/// Filters for the audit log. `from` is inclusive and `to` exclusive.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditLogQuery {
+    pub user_id: Option<Uuid>,
+    pub event_type: Option<AuditEventType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyTwoFactorRequest {
+    pub two_factor_token: String,
+    /// A current TOTP code or an unused recovery code.
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableTwoFactorRequest {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FinishPasskeyRegistrationRequest {
+    /// Label to tell passkeys apart, e.g. "MacBook".
+    pub name: Option<String>,
+    pub credential: RegistrationCredential,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StartPasskeyLoginRequest {
+    /// Limits the login to this account's passkeys. Without it the browser
+    /// offers any discoverable passkey for the site.
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FinishPasskeyLoginRequest {
+    pub credential: AuthenticationCredential,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackRequest {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRolesRequest {
+    pub roles: Vec<Role>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevocationListResponse {
+    pub tokens: Vec<Uuid>,
+    pub users: Vec<UserRevocation>,
+    pub generated_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub token: String,
+    pub refresh_token: String,
+    /// Lifetime of `token` in seconds.
+    pub expires_in: i64,
     pub user: UserResponse,
 }
 
+/// An access token to act as another user. There is no refresh token: the
+/// admin asks again once it expires.
+#[derive(Debug, Serialize)]
+pub struct ImpersonationResponse {
+    pub token: String,
+    /// Lifetime of `token` in seconds.
+    pub expires_in: i64,
+    pub user: UserResponse,
+    pub actor_id: Uuid,
+}
+
+/// `login` either signs the user in or, with 2FA enabled, asks for a code.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(Box<AuthResponse>),
+    TwoFactorRequired(TwoFactorChallengeResponse),
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    pub two_factor_token: String,
+    /// Lifetime of `two_factor_token` in seconds.
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetupResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+    /// `otpauth_uri` as an SVG QR code.
+    pub qr_code_svg: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    /// Shown only once; only their hashes are stored.
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasskeyResponse {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<Passkey> for PasskeyResponse {
+    fn from(passkey: Passkey) -> Self {
+        Self {
+            id: passkey.id,
+            name: passkey.name,
+            created_at: passkey.created_at,
+            last_used_at: passkey.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterOAuthClientRequest {
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub scopes: Vec<Scope>,
+    /// Whether the client can keep a secret, like a server-side app.
+    #[serde(default)]
+    pub confidential: bool,
+}
+
+/// The parameters of an OAuth authorization request, as the client sent
+/// them to the consent page.
+#[derive(Debug, Deserialize)]
+pub struct AuthorizationRequest {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorizationDecision {
+    #[serde(flatten)]
+    pub request: AuthorizationRequest,
+    pub approve: bool,
+}
+
+/// Form parameters of the token endpoint. Client credentials may come here
+/// or in an `Authorization: Basic` header.
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeTokenRequest {
+    pub token: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OAuthClientResponse {
+    pub client_id: Uuid,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub scopes: Vec<Scope>,
+    pub confidential: bool,
+    pub created_at: DateTime<Utc>,
+    /// Only returned when a confidential client is registered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+impl From<OAuthClient> for OAuthClientResponse {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            client_id: client.id,
+            name: client.name,
+            redirect_uris: client.redirect_uris,
+            scopes: client.scopes,
+            confidential: client.secret_hash.is_some(),
+            created_at: client.created_at,
+            client_secret: None,
+        }
+    }
+}
+
+/// What the consent page shows for an authorization request.
+#[derive(Debug, Serialize)]
+pub struct AuthorizationPreviewResponse {
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub scopes: Vec<Scope>,
+    /// False when the user already approved these scopes for the client.
+    pub consent_required: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthorizationDecisionResponse {
+    /// The client's redirect URI with either `code` or `error` added.
+    pub redirect_to: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OAuthTokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub refresh_token: String,
+    pub scope: String,
+}
+
+/// RFC 7662 introspection result. Everything but `active` is left out for
+/// inactive tokens. `scope` and `client_id` are absent for the user's own
+/// sessions, which are not limited.
+#[derive(Debug, Default, Serialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<Role>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OAuthConsentResponse {
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PersonalAccessTokenResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// The token itself, only returned when it is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<PersonalAccessToken> for PersonalAccessTokenResponse {
+    fn from(token: PersonalAccessToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            scopes: token.scopes,
+            expires_at: token.expires_at,
+            created_at: token.created_at,
+            last_used_at: token.last_used_at,
+            token: None,
+        }
+    }
+}
+
+/// Who a valid personal access token acts for, for the gateway.
+#[derive(Debug, Serialize)]
+pub struct PersonalAccessTokenIdentity {
+    pub token_id: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+    pub roles: Vec<Role>,
+    pub email_verified: bool,
+    pub scopes: Vec<Scope>,
+}
+
+/// Everything the auth service holds about a user, for their data export.
+/// Secrets such as password and token hashes are left out.
+#[derive(Debug, Serialize)]
+pub struct AccountExport {
+    pub user: UserResponse,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub two_factor_enabled: bool,
+    pub sessions: Vec<SessionResponse>,
+    pub passkeys: Vec<PasskeyResponse>,
+    pub identities: Vec<OidcIdentity>,
+    pub personal_access_tokens: Vec<PersonalAccessTokenResponse>,
+    pub oauth_clients: Vec<OAuthClientResponse>,
+    pub oauth_consents: Vec<OAuthConsentResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// The OAuth client acting through this session, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<Uuid>,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OidcProviderResponse {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OidcAuthorizationResponse {
+    /// Where to send the browser to sign in with the provider.
+    pub authorization_url: String,
+    /// Also embedded in `authorization_url`. The frontend keeps it to check
+    /// that the callback answers a sign-in it started.
+    pub state: String,
+    /// Seconds left to complete the sign-in.
+    pub expires_in: i64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
     pub id: Uuid,
@@ -49,6 +856,10 @@ pub struct UserResponse {
     pub display_name: Option<String>,
     pub bio: Option<String>,
     pub avatar_url: Option<String>,
+    pub roles: Vec<Role>,
+    /// What `roles` allow, so clients don't need their own copy of the matrix.
+    pub permissions: Vec<Permission>,
+    pub email_verified_at: Option<DateTime<Utc>>,
 }
 
 impl From<User> for UserResponse {
@@ -60,6 +871,9 @@ impl From<User> for UserResponse {
             display_name: user.display_name,
             bio: user.bio,
             avatar_url: user.avatar_url,
+            permissions: rbac::permissions(&user.roles),
+            roles: user.roles,
+            email_verified_at: user.email_verified_at,
         }
     }
 }
@@ -67,4 +881,12 @@ impl From<User> for UserResponse {
 #[derive(Debug, Deserialize)]
 pub struct ValidateTokenRequest {
     pub token: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePersonalAccessTokenRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Defaults to 90 days.
+    pub expires_in_days: Option<i64>,
+}
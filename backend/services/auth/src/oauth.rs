@@ -0,0 +1,118 @@
+//! Helpers for the OAuth 2.1 authorization server: scope strings, redirect
+//! URIs and PKCE.
+//!
+//! Clients are expected to follow RFC 8252 for native apps: redirect to a
+//! loopback address on any port, or to a private-use URI scheme named after
+//! a domain they control (`com.example.editor:/callback`).
+
+use std::net::IpAddr;
+
+use reqwest::Url;
+
+use crate::{error::AuthError, oidc, rbac::Scope};
+
+/// An OAuth error response with the given RFC 6749 error code.
+pub fn error(code: &'static str, description: impl Into<String>) -> AuthError {
+    AuthError::OAuth {
+        error: code,
+        description: description.into(),
+    }
+}
+
+/// Parses a space-separated `scope` parameter. Unknown scopes are an error.
+pub fn parse_scope(scope: &str) -> Option<Vec<Scope>> {
+    let mut scopes = Vec::new();
+    for value in scope.split(' ').filter(|value| !value.is_empty()) {
+        let scope = Scope::parse(value)?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    Some(scopes)
+}
+
+pub fn format_scope(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Checks a redirect URI a client wants to register: HTTPS, HTTP on a
+/// loopback address, or a private-use scheme containing a dot.
+pub fn validate_redirect_uri(uri: &str) -> Result<(), String> {
+    let url = Url::parse(uri).map_err(|_| format!("{} is not a valid URI", uri))?;
+    if url.fragment().is_some() {
+        return Err(format!("{} must not contain a fragment", uri));
+    }
+
+    let allowed = match url.scheme() {
+        "https" => true,
+        "http" => is_loopback(&url),
+        scheme => scheme.contains('.'),
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} must use https, http on a loopback address, or a reverse-domain scheme",
+            uri
+        ))
+    }
+}
+
+/// Whether `requested` is one of the client's redirect URIs. They must match
+/// exactly, except that loopback IP addresses may use any port, since native
+/// apps pick a free one when they start listening.
+pub fn redirect_uri_matches(registered: &[String], requested: &str) -> bool {
+    if registered.iter().any(|uri| uri == requested) {
+        return true;
+    }
+
+    let Ok(mut requested) = Url::parse(requested) else {
+        return false;
+    };
+    if requested.scheme() != "http" || !is_loopback_ip(&requested) {
+        return false;
+    }
+    let _ = requested.set_port(None);
+    registered
+        .iter()
+        .filter_map(|uri| Url::parse(uri).ok())
+        .any(|mut uri| {
+            let _ = uri.set_port(None);
+            uri == requested
+        })
+}
+
+/// `uri` with `params` added to its query string.
+pub fn redirect_with(uri: &str, params: &[(&str, &str)]) -> Result<String, AuthError> {
+    let mut url =
+        Url::parse(uri).map_err(|e| anyhow::anyhow!("invalid stored redirect URI: {}", e))?;
+    url.query_pairs_mut().extend_pairs(params);
+    Ok(url.into())
+}
+
+/// RFC 7636: 43 to 128 unreserved characters.
+pub fn is_valid_code_verifier(verifier: &str) -> bool {
+    (43..=128).contains(&verifier.len())
+        && verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'))
+}
+
+pub fn verify_code_challenge(verifier: &str, challenge: &str) -> bool {
+    is_valid_code_verifier(verifier) && oidc::code_challenge(verifier) == challenge
+}
+
+fn is_loopback(url: &Url) -> bool {
+    url.host_str() == Some("localhost") || is_loopback_ip(url)
+}
+
+fn is_loopback_ip(url: &Url) -> bool {
+    url.host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .and_then(|host| host.parse::<IpAddr>().ok())
+        .is_some_and(|ip| ip.is_loopback())
+}
@@ -0,0 +1,613 @@
+//! OpenID Connect relying party: authorization code flow with PKCE against
+//! configurable identity providers.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+const DEFAULT_SCOPES: &str = "openid email profile";
+
+/// Minimum time between JWKS refetches triggered by an unknown `kid`.
+const MIN_FORCED_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum OidcError {
+    /// The provider could not be reached or answered with something unusable.
+    #[error("identity provider request failed: {0}")]
+    Provider(String),
+
+    /// The provider refused the code, or its ID token did not check out.
+    #[error("{0}")]
+    Rejected(String),
+}
+
+type Result<T> = std::result::Result<T, OidcError>;
+
+/// Identity providers configured with `OIDC_PROVIDERS`, keyed by id.
+pub struct Providers {
+    providers: HashMap<String, Arc<Provider>>,
+}
+
+impl Providers {
+    /// `OIDC_PROVIDERS` is a comma-separated list of ids. Each id is
+    /// configured with `OIDC_<ID>_*` variables, see `Provider::from_env`.
+    pub fn from_env(app_url: &str) -> anyhow::Result<Self> {
+        let http = reqwest::Client::new();
+        let ids = std::env::var("OIDC_PROVIDERS").unwrap_or_default();
+
+        let mut providers = HashMap::new();
+        for id in ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+            let provider = Provider::from_env(id, app_url, http.clone())
+                .with_context(|| format!("invalid configuration for identity provider {}", id))?;
+            providers.insert(id.to_string(), Arc::new(provider));
+        }
+
+        Ok(Self { providers })
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Provider>> {
+        self.providers.get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Arc<Provider>> {
+        let mut providers: Vec<_> = self.providers.values().cloned().collect();
+        providers.sort_by(|a, b| a.id.cmp(&b.id));
+        providers
+    }
+}
+
+pub struct Provider {
+    pub id: String,
+    pub name: String,
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    scopes: String,
+    /// Where the provider sends the browser back to. The frontend page there
+    /// posts `code` and `state` to the callback endpoint.
+    redirect_uri: String,
+    /// Endpoints given explicitly; anything missing comes from discovery.
+    configured: PartialMetadata,
+    metadata: OnceCell<Metadata>,
+    http: reqwest::Client,
+    keys: RwLock<HashMap<String, Arc<VerificationKey>>>,
+    last_refresh: Mutex<Option<Instant>>,
+}
+
+#[derive(Default, Deserialize)]
+struct PartialMetadata {
+    authorization_endpoint: Option<String>,
+    token_endpoint: Option<String>,
+    jwks_uri: Option<String>,
+}
+
+struct Metadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+impl Provider {
+    /// Reads `OIDC_<ID>_ISSUER`, `_CLIENT_ID`, `_CLIENT_SECRET`, `_NAME`,
+    /// `_SCOPES` and `_REDIRECT_URI`. `_AUTHORIZATION_ENDPOINT`,
+    /// `_TOKEN_ENDPOINT` and `_JWKS_URI` override the issuer's discovery
+    /// document.
+    fn from_env(id: &str, app_url: &str, http: reqwest::Client) -> anyhow::Result<Self> {
+        if !id
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+        {
+            bail!("provider ids may only contain a-z, 0-9, '-' and '_'");
+        }
+        let prefix = format!("OIDC_{}_", id.to_ascii_uppercase().replace('-', "_"));
+        let var = |name: &str| {
+            std::env::var(format!("{}{}", prefix, name))
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+        let required =
+            |name: &str| var(name).with_context(|| format!("{}{} is not set", prefix, name));
+
+        Ok(Self {
+            id: id.to_string(),
+            name: var("NAME").unwrap_or_else(|| id.to_string()),
+            issuer: required("ISSUER")?.trim_end_matches('/').to_string(),
+            client_id: required("CLIENT_ID")?,
+            client_secret: var("CLIENT_SECRET"),
+            scopes: var("SCOPES").unwrap_or_else(|| DEFAULT_SCOPES.to_string()),
+            redirect_uri: var("REDIRECT_URI")
+                .unwrap_or_else(|| format!("{}/auth/callback/{}", app_url, id)),
+            configured: PartialMetadata {
+                authorization_endpoint: var("AUTHORIZATION_ENDPOINT"),
+                token_endpoint: var("TOKEN_ENDPOINT"),
+                jwks_uri: var("JWKS_URI"),
+            },
+            metadata: OnceCell::new(),
+            http,
+            keys: RwLock::new(HashMap::new()),
+            last_refresh: Mutex::new(None),
+        })
+    }
+
+    /// The URL to send the browser to. `state` and `nonce` are echoed back
+    /// by the provider; the code can only be redeemed with the verifier
+    /// behind `code_challenge`.
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<String> {
+        let metadata = self.metadata().await?;
+        let mut url = reqwest::Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| OidcError::Provider(format!("invalid authorization endpoint: {}", e)))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", &self.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.into())
+    }
+
+    /// Redeems an authorization code and returns the validated ID token
+    /// claims.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            id_token: Option<String>,
+        }
+
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_uri),
+            ("code_verifier", code_verifier),
+            ("client_id", &self.client_id),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| OidcError::Provider(e.to_string()))?;
+        if response.status().is_client_error() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError::Rejected(format!(
+                "code exchange refused: {}",
+                body
+            )));
+        }
+        let tokens: TokenResponse = response
+            .error_for_status()
+            .map_err(|e| OidcError::Provider(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::Provider(format!("invalid token response: {}", e)))?;
+        let id_token = tokens
+            .id_token
+            .ok_or_else(|| OidcError::Provider("token response has no id_token".to_string()))?;
+
+        self.validate_id_token(&id_token, nonce).await
+    }
+
+    async fn validate_id_token(&self, id_token: &str, nonce: &str) -> Result<IdTokenClaims> {
+        let rejected = |reason: &str| OidcError::Rejected(format!("invalid ID token: {}", reason));
+
+        let header = decode_header(id_token).map_err(|_| rejected("malformed"))?;
+        let kid = header.kid.unwrap_or_default();
+        let key = match self.key(&kid) {
+            Some(key) => key,
+            None => {
+                self.refresh_unknown_kid().await?;
+                self.key(&kid)
+                    .ok_or_else(|| rejected("unknown signing key"))?
+            }
+        };
+
+        let mut validation = Validation::new(key.algorithm);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = decode::<IdTokenClaims>(id_token, &key.key, &validation)
+            .map_err(|e| rejected(&e.to_string()))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(rejected("nonce mismatch"));
+        }
+
+        Ok(claims)
+    }
+
+    async fn metadata(&self) -> Result<&Metadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let configured = &self.configured;
+                let discovered = if configured.authorization_endpoint.is_some()
+                    && configured.token_endpoint.is_some()
+                    && configured.jwks_uri.is_some()
+                {
+                    PartialMetadata::default()
+                } else {
+                    let url = format!("{}/.well-known/openid-configuration", self.issuer);
+                    self.get_json(&url).await?
+                };
+
+                let pick = |configured: &Option<String>, discovered: Option<String>, name: &str| {
+                    configured.clone().or(discovered).ok_or_else(|| {
+                        OidcError::Provider(format!("{} has no {}", self.issuer, name))
+                    })
+                };
+                Ok(Metadata {
+                    authorization_endpoint: pick(
+                        &configured.authorization_endpoint,
+                        discovered.authorization_endpoint,
+                        "authorization_endpoint",
+                    )?,
+                    token_endpoint: pick(
+                        &configured.token_endpoint,
+                        discovered.token_endpoint,
+                        "token_endpoint",
+                    )?,
+                    jwks_uri: pick(&configured.jwks_uri, discovered.jwks_uri, "jwks_uri")?,
+                })
+            })
+            .await
+    }
+
+    fn key(&self, kid: &str) -> Option<Arc<VerificationKey>> {
+        self.keys.read().unwrap().get(kid).cloned()
+    }
+
+    /// Providers rotate keys without notice, so an unknown `kid` triggers a
+    /// refetch, rate limited so forged tokens cannot hammer the provider.
+    async fn refresh_unknown_kid(&self) -> Result<()> {
+        {
+            let mut last_refresh = self.last_refresh.lock().unwrap();
+            if last_refresh.is_some_and(|at| at.elapsed() < MIN_FORCED_REFRESH_INTERVAL) {
+                return Ok(());
+            }
+            *last_refresh = Some(Instant::now());
+        }
+
+        let jwks_uri = &self.metadata().await?.jwks_uri;
+        let jwks: JwkSet = self.get_json(jwks_uri).await?;
+        let keys = jwks
+            .keys
+            .iter()
+            .filter_map(|jwk| {
+                let key = VerificationKey::from_jwk(jwk)?;
+                Some((jwk.common.key_id.clone().unwrap_or_default(), Arc::new(key)))
+            })
+            .collect();
+        *self.keys.write().unwrap() = keys;
+
+        Ok(())
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| OidcError::Provider(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::Provider(format!("invalid response from {}: {}", url, e)))
+    }
+}
+
+/// What the service reads from a validated ID token.
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "lenient_bool")]
+    pub email_verified: bool,
+    pub nonce: Option<String>,
+    pub name: Option<String>,
+    pub preferred_username: Option<String>,
+    pub picture: Option<String>,
+}
+
+/// Some providers send `email_verified` as the string `"true"`.
+fn lenient_bool<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<bool, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Bool(verified) => verified,
+        serde_json::Value::String(verified) => verified == "true",
+        _ => false,
+    })
+}
+
+/// S256 PKCE challenge for `verifier`.
+pub fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+struct VerificationKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+impl VerificationKey {
+    /// Only asymmetric keys are accepted, and the algorithm comes from the key
+    /// rather than from the token header.
+    fn from_jwk(jwk: &Jwk) -> Option<Self> {
+        let algorithm = match (&jwk.algorithm, jwk.common.key_algorithm) {
+            (AlgorithmParameters::OctetKey(_), _) => return None,
+            (_, Some(algorithm)) => asymmetric_algorithm(algorithm)?,
+            (AlgorithmParameters::OctetKeyPair(_), None) => Algorithm::EdDSA,
+            (AlgorithmParameters::RSA(_), None) => Algorithm::RS256,
+            (AlgorithmParameters::EllipticCurve(params), None) => match params.curve {
+                EllipticCurve::P256 => Algorithm::ES256,
+                EllipticCurve::P384 => Algorithm::ES384,
+                _ => return None,
+            },
+        };
+
+        let key = DecodingKey::from_jwk(jwk).ok()?;
+        Some(Self { algorithm, key })
+    }
+}
+
+fn asymmetric_algorithm(algorithm: KeyAlgorithm) -> Option<Algorithm> {
+    match algorithm {
+        KeyAlgorithm::ES256 => Some(Algorithm::ES256),
+        KeyAlgorithm::ES384 => Some(Algorithm::ES384),
+        KeyAlgorithm::RS256 => Some(Algorithm::RS256),
+        KeyAlgorithm::RS384 => Some(Algorithm::RS384),
+        KeyAlgorithm::RS512 => Some(Algorithm::RS512),
+        KeyAlgorithm::PS256 => Some(Algorithm::PS256),
+        KeyAlgorithm::PS384 => Some(Algorithm::PS384),
+        KeyAlgorithm::PS512 => Some(Algorithm::PS512),
+        KeyAlgorithm::EdDSA => Some(Algorithm::EdDSA),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use axum::{
+        extract::State,
+        http::StatusCode,
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::keys::KeySet;
+
+    const CLIENT_ID: &str = "blog";
+
+    /// A sign-in the user finished at the provider, waiting for the relying
+    /// party to redeem its code.
+    struct PendingLogin {
+        code_challenge: String,
+        claims: Value,
+    }
+
+    #[derive(Clone)]
+    struct IdpState {
+        keys: Arc<KeySet>,
+        logins: Arc<Mutex<HashMap<String, PendingLogin>>>,
+    }
+
+    /// An identity provider on a local port: a token endpoint that checks
+    /// PKCE and signs ID tokens, and the JWKS to verify them.
+    pub(crate) struct MockIdp {
+        issuer: String,
+        state: IdpState,
+    }
+
+    impl MockIdp {
+        pub async fn start() -> Self {
+            let state = IdpState {
+                keys: Arc::new(KeySet::generate().unwrap()),
+                logins: Arc::default(),
+            };
+            let app = Router::new()
+                .route("/token", post(token))
+                .route("/jwks", get(jwks))
+                .with_state(state.clone());
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let issuer = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+
+            Self { issuer, state }
+        }
+
+        pub fn provider(&self, id: &str) -> Provider {
+            Provider {
+                id: id.to_string(),
+                name: id.to_string(),
+                issuer: self.issuer.clone(),
+                client_id: CLIENT_ID.to_string(),
+                client_secret: None,
+                scopes: DEFAULT_SCOPES.to_string(),
+                redirect_uri: format!("http://localhost:5173/auth/callback/{}", id),
+                configured: PartialMetadata {
+                    authorization_endpoint: Some(format!("{}/authorize", self.issuer)),
+                    token_endpoint: Some(format!("{}/token", self.issuer)),
+                    jwks_uri: Some(format!("{}/jwks", self.issuer)),
+                },
+                metadata: OnceCell::new(),
+                http: reqwest::Client::new(),
+                keys: RwLock::new(HashMap::new()),
+                last_refresh: Mutex::new(None),
+            }
+        }
+
+        pub fn providers(&self, id: &str) -> Providers {
+            Providers {
+                providers: HashMap::from([(id.to_string(), Arc::new(self.provider(id)))]),
+            }
+        }
+
+        /// Signs the user in at the provider, as the browser would after
+        /// following `authorization_url`, and returns the code. `claims`
+        /// goes into the ID token, with `iss`, `aud`, `exp` and the nonce
+        /// from the URL added unless given.
+        pub fn sign_in(&self, authorization_url: &str, claims: Value) -> String {
+            let url = reqwest::Url::parse(authorization_url).unwrap();
+            let param = |name: &str| {
+                url.query_pairs()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.into_owned())
+                    .unwrap()
+            };
+            assert_eq!(param("code_challenge_method"), "S256");
+
+            let mut claims = claims;
+            let fields = claims.as_object_mut().unwrap();
+            let defaults = [
+                ("iss", json!(self.issuer)),
+                ("aud", json!(param("client_id"))),
+                ("exp", json!(chrono::Utc::now().timestamp() + 300)),
+                ("nonce", json!(param("nonce"))),
+            ];
+            for (name, value) in defaults {
+                fields.entry(name).or_insert(value);
+            }
+
+            let code = uuid::Uuid::new_v4().to_string();
+            self.state.logins.lock().unwrap().insert(
+                code.clone(),
+                PendingLogin {
+                    code_challenge: param("code_challenge"),
+                    claims,
+                },
+            );
+            code
+        }
+    }
+
+    async fn token(
+        State(state): State<IdpState>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> std::result::Result<Json<Value>, (StatusCode, Json<Value>)> {
+        let invalid_grant = || {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "invalid_grant" })),
+            )
+        };
+        let login = form
+            .get("code")
+            .and_then(|code| state.logins.lock().unwrap().remove(code))
+            .ok_or_else(invalid_grant)?;
+        let verifier = form.get("code_verifier").ok_or_else(invalid_grant)?;
+        if code_challenge(verifier) != login.code_challenge {
+            return Err(invalid_grant());
+        }
+
+        Ok(Json(json!({
+            "access_token": "unused",
+            "token_type": "Bearer",
+            "id_token": state.keys.sign(&login.claims).unwrap(),
+        })))
+    }
+
+    async fn jwks(State(state): State<IdpState>) -> Json<JwkSet> {
+        Json(state.keys.jwks())
+    }
+
+    async fn authorize(provider: &Provider) -> (String, String, String) {
+        let (state, nonce, verifier) = ("state", "nonce-1", "verifier-1");
+        let url = provider
+            .authorization_url(state, nonce, &code_challenge(verifier))
+            .await
+            .unwrap();
+        (url, nonce.to_string(), verifier.to_string())
+    }
+
+    #[tokio::test]
+    async fn exchanges_codes_for_validated_claims() {
+        let idp = MockIdp::start().await;
+        let provider = idp.provider("mock");
+        let (url, nonce, verifier) = authorize(&provider).await;
+        let code = idp.sign_in(
+            &url,
+            json!({ "sub": "123", "email": "alice@example.com", "email_verified": "true" }),
+        );
+
+        let claims = provider
+            .exchange_code(&code, &verifier, &nonce)
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, "123");
+        assert_eq!(claims.email.as_deref(), Some("alice@example.com"));
+        assert!(claims.email_verified);
+
+        // Codes are single use.
+        assert!(matches!(
+            provider.exchange_code(&code, &verifier, &nonce).await,
+            Err(OidcError::Rejected(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn codes_need_the_pkce_verifier() {
+        let idp = MockIdp::start().await;
+        let provider = idp.provider("mock");
+        let (url, nonce, _) = authorize(&provider).await;
+        let code = idp.sign_in(&url, json!({ "sub": "123" }));
+
+        assert!(matches!(
+            provider
+                .exchange_code(&code, "another-verifier", &nonce)
+                .await,
+            Err(OidcError::Rejected(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn id_tokens_must_carry_the_nonce_and_audience() {
+        let idp = MockIdp::start().await;
+        let provider = idp.provider("mock");
+
+        let (url, nonce, verifier) = authorize(&provider).await;
+        let code = idp.sign_in(&url, json!({ "sub": "123", "nonce": "replayed" }));
+        assert!(matches!(
+            provider.exchange_code(&code, &verifier, &nonce).await,
+            Err(OidcError::Rejected(reason)) if reason.contains("nonce")
+        ));
+
+        let (url, nonce, verifier) = authorize(&provider).await;
+        let code = idp.sign_in(&url, json!({ "sub": "123", "aud": "another-client" }));
+        assert!(matches!(
+            provider.exchange_code(&code, &verifier, &nonce).await,
+            Err(OidcError::Rejected(_))
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+//! Password hashing.
+//!
+//! Hashes are self-describing strings, so `users.password_hash` can hold
+//! either scheme: Argon2id in PHC format (`$argon2id$v=19$m=..,t=..,p=..$salt$hash`)
+//! for everything hashed now, and bcrypt (`$2b$..`) from before the switch.
+//! A hash that isn't Argon2id with the current parameters still verifies, and
+//! is reported as outdated so the caller can replace it.
+
+use anyhow::Context;
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use rand::RngCore;
+
+// OWASP's baseline for Argon2id: 19 MiB, two passes, one lane.
+const DEFAULT_MEMORY_KIB: u32 = 19 * 1024;
+const DEFAULT_ITERATIONS: u32 = 2;
+const DEFAULT_PARALLELISM: u32 = 1;
+
+const SALT_BYTES: usize = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordCheck {
+    Mismatch,
+    Match,
+    /// Correct, but hashed with bcrypt or other Argon2 parameters.
+    MatchOutdated,
+}
+
+pub struct PasswordHasher {
+    argon2: Argon2<'static>,
+    /// A hash of no one's password, with the current parameters.
+    dummy_hash: String,
+}
+
+impl PasswordHasher {
+    /// Reads `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and
+    /// `ARGON2_PARALLELISM`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let env = |key: &str, default: u32| -> anyhow::Result<u32> {
+            match std::env::var(key) {
+                Ok(value) => value
+                    .parse()
+                    .with_context(|| format!("{} must be a positive integer", key)),
+                Err(_) => Ok(default),
+            }
+        };
+
+        let params = Params::new(
+            env("ARGON2_MEMORY_KIB", DEFAULT_MEMORY_KIB)?,
+            env("ARGON2_ITERATIONS", DEFAULT_ITERATIONS)?,
+            env("ARGON2_PARALLELISM", DEFAULT_PARALLELISM)?,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("invalid Argon2 parameters: {}", e))?;
+
+        let mut hasher = Self {
+            argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+            dummy_hash: String::new(),
+        };
+        hasher.dummy_hash = hasher.hash("not anyone's password")?;
+        Ok(hasher)
+    }
+
+    pub fn hash(&self, password: &str) -> anyhow::Result<String> {
+        let mut salt = [0u8; SALT_BYTES];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let salt = SaltString::encode_b64(&salt).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        self.argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| anyhow::anyhow!("failed to hash password: {}", e))
+    }
+
+    pub fn verify(&self, password: &str, stored: &str) -> anyhow::Result<PasswordCheck> {
+        if is_bcrypt(stored) {
+            let matches = bcrypt::verify(password.as_bytes(), stored)
+                .context("failed to verify bcrypt hash")?;
+            return Ok(if matches {
+                PasswordCheck::MatchOutdated
+            } else {
+                PasswordCheck::Mismatch
+            });
+        }
+
+        let hash = PasswordHash::new(stored)
+            .map_err(|e| anyhow::anyhow!("unrecognised password hash: {}", e))?;
+        // Verification takes the algorithm and parameters from the hash
+        // itself, so hashes made with older settings keep working.
+        match self.argon2.verify_password(password.as_bytes(), &hash) {
+            Ok(()) if self.is_current(&hash) => Ok(PasswordCheck::Match),
+            Ok(()) => Ok(PasswordCheck::MatchOutdated),
+            Err(argon2::password_hash::Error::Password) => Ok(PasswordCheck::Mismatch),
+            Err(e) => Err(anyhow::anyhow!("failed to verify password: {}", e)),
+        }
+    }
+
+    /// Verifies `password` against the dummy hash, so refusing an unknown
+    /// account takes as long as refusing a wrong password.
+    pub fn verify_dummy(&self, password: &str) {
+        let _ = self.verify(password, &self.dummy_hash);
+    }
+
+    fn is_current(&self, hash: &PasswordHash<'_>) -> bool {
+        hash.algorithm == Algorithm::Argon2id.ident()
+            && hash.version == Some(Version::V0x13.into())
+            && Params::try_from(hash).is_ok_and(|params| {
+                let current = self.argon2.params();
+                params.m_cost() == current.m_cost()
+                    && params.t_cost() == current.t_cost()
+                    && params.p_cost() == current.p_cost()
+            })
+    }
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
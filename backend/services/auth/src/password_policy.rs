@@ -0,0 +1,172 @@
+//! What new passwords must satisfy, checked on registration and reset.
+//!
+//! The breached-password check works offline against SHA-1 prefix files in
+//! the layout Have I Been Pwned's k-anonymity range API serves: one file per
+//! five-hex-digit prefix (`21BD1.txt`) holding `SUFFIX:COUNT` lines for the
+//! remaining 35 digits. The official downloader writes exactly this with
+//! `--single false`.
+
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::strength;
+
+const DEFAULT_MIN_LENGTH: usize = 10;
+const DEFAULT_MIN_STRENGTH: u8 = 3;
+
+/// Usernames or email local parts shorter than this are too likely to turn
+/// up in unrelated passwords to be worth rejecting.
+const MIN_PERSONAL_INFO_LEN: usize = 3;
+
+/// One reason a password was refused, returned to the client as-is.
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordViolation {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl PasswordViolation {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+pub struct PasswordPolicy {
+    min_length: usize,
+    /// Lowest acceptable `strength::score`, from 0 to 4.
+    min_strength: u8,
+    breached_passwords_dir: Option<PathBuf>,
+}
+
+impl PasswordPolicy {
+    /// Reads `PASSWORD_MIN_LENGTH`, `PASSWORD_MIN_STRENGTH` and
+    /// `BREACHED_PASSWORDS_DIR`. Without the directory the breach check is
+    /// skipped.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let min_length = match std::env::var("PASSWORD_MIN_LENGTH") {
+            Ok(value) => value
+                .parse::<usize>()
+                .ok()
+                .filter(|length| *length > 0)
+                .context("PASSWORD_MIN_LENGTH must be a positive integer")?,
+            Err(_) => DEFAULT_MIN_LENGTH,
+        };
+        let min_strength = match std::env::var("PASSWORD_MIN_STRENGTH") {
+            Ok(value) => value
+                .parse::<u8>()
+                .ok()
+                .filter(|strength| *strength <= 4)
+                .context("PASSWORD_MIN_STRENGTH must be between 0 and 4")?,
+            Err(_) => DEFAULT_MIN_STRENGTH,
+        };
+
+        let breached_passwords_dir = std::env::var("BREACHED_PASSWORDS_DIR")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+        match &breached_passwords_dir {
+            Some(dir) if !dir.is_dir() => {
+                anyhow::bail!(
+                    "BREACHED_PASSWORDS_DIR {} is not a directory",
+                    dir.display()
+                )
+            }
+            Some(_) => {}
+            None => tracing::warn!("BREACHED_PASSWORDS_DIR is not set; skipping breach checks"),
+        }
+
+        Ok(Self::new(min_length, min_strength, breached_passwords_dir))
+    }
+
+    pub fn new(
+        min_length: usize,
+        min_strength: u8,
+        breached_passwords_dir: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            min_length,
+            min_strength,
+            breached_passwords_dir,
+        }
+    }
+
+    /// Everything wrong with `password` for the account with this username
+    /// and email. Empty when it is acceptable.
+    pub async fn check(
+        &self,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> anyhow::Result<Vec<PasswordViolation>> {
+        let mut violations = Vec::new();
+
+        if password.chars().count() < self.min_length {
+            violations.push(PasswordViolation::new(
+                "too_short",
+                format!("Password must be at least {} characters", self.min_length),
+            ));
+        }
+
+        let lower = password.to_lowercase();
+        let contains = |value: &str| {
+            let value = value.trim().to_lowercase();
+            value.chars().count() >= MIN_PERSONAL_INFO_LEN && lower.contains(&value)
+        };
+        if contains(username) {
+            violations.push(PasswordViolation::new(
+                "contains_username",
+                "Password must not contain your username",
+            ));
+        }
+        let local_part = email.split('@').next().unwrap_or_default();
+        if contains(email) || contains(local_part) {
+            violations.push(PasswordViolation::new(
+                "contains_email",
+                "Password must not contain your email address",
+            ));
+        }
+
+        if strength::score(password) < self.min_strength {
+            violations.push(PasswordViolation::new(
+                "too_weak",
+                "Password is too easy to guess; try a longer phrase or fewer common words",
+            ));
+        }
+
+        if self.is_breached(password).await? {
+            violations.push(PasswordViolation::new(
+                "breached",
+                "Password has appeared in a data breach; choose a different one",
+            ));
+        }
+
+        Ok(violations)
+    }
+
+    async fn is_breached(&self, password: &str) -> anyhow::Result<bool> {
+        let Some(dir) = &self.breached_passwords_dir else {
+            return Ok(false);
+        };
+
+        let digest = data_encoding::HEXUPPER.encode(&Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = digest.split_at(5);
+        let path = dir.join(format!("{}.txt", prefix));
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+
+        Ok(contents.lines().any(|line| {
+            line.split(':')
+                .next()
+                .is_some_and(|hash| hash.trim().eq_ignore_ascii_case(suffix))
+        }))
+    }
+}
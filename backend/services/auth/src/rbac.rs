@@ -0,0 +1,6 @@
+//! The role/permission matrix lives in the `service-auth` crate so that the
+//! tokens this service issues are checked the same way everywhere.
+
+pub use service_auth::rbac::{
+    has_permission, perm, permissions, Permission, RequiredPermission, Role, Scope,
+};
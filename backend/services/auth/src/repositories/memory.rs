@@ -0,0 +1,1093 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use super::{
+    AccountUnlockRepository, AuditLogRepository, EmailVerificationRepository,
+    LoginThrottleRepository, MagicLinkRepository, OAuthAuthorizationCodeRepository,
+    OAuthClientRepository, OAuthConsentRepository, OidcIdentityRepository,
+    OidcLoginStateRepository, PasskeyChallengeRepository, PasskeyRepository,
+    PasswordResetRepository, PersonalAccessTokenRepository, RefreshTokenRepository,
+    RevocationRepository, SessionRepository, TwoFactorChallengeRepository, TwoFactorRepository,
+    UserRepository,
+};
+use crate::{
+    error::{AuthError, Result},
+    models::{
+        AccountUnlockToken, AuditEvent, AuditLogQuery, EmailVerificationToken, LoginThrottle,
+        MagicLinkToken, OAuthAuthorizationCode, OAuthClient, OAuthConsent, OidcIdentity,
+        OidcLoginState, Passkey, PasskeyChallenge, PasswordResetToken, PersonalAccessToken,
+        RecoveryCode, RefreshToken, RevokedToken, Session, TwoFactorChallenge, TwoFactorSecret,
+        User, UserRevocation,
+    },
+    rbac::Role,
+};
+
+/// Process-local user store, used for tests and local development.
+#[derive(Default)]
+pub struct InMemoryUserRepository {
+    users: RwLock<HashMap<Uuid, User>>,
+}
+
+impl InMemoryUserRepository {
+    fn find<F>(&self, predicate: F) -> Option<User>
+    where
+        F: Fn(&User) -> bool,
+    {
+        self.users
+            .read()
+            .unwrap()
+            .values()
+            .find(|user| predicate(user))
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>> {
+        Ok(self.users.read().unwrap().get(&id).cloned())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>> {
+        Ok(self.find(|user| user.email == email))
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>> {
+        Ok(self.find(|user| user.username == username))
+    }
+
+    async fn create(&self, user: User) -> Result<User> {
+        let mut users = self.users.write().unwrap();
+        if users
+            .values()
+            .any(|existing| existing.email == user.email || existing.username == user.username)
+        {
+            return Err(AuthError::UserExists);
+        }
+
+        users.insert(user.id, user.clone());
+        Ok(user)
+    }
+
+    async fn update_roles(&self, id: Uuid, roles: Vec<Role>) -> Result<Option<User>> {
+        Ok(self.users.write().unwrap().get_mut(&id).map(|user| {
+            user.roles = roles;
+            user.updated_at = Utc::now();
+            user.clone()
+        }))
+    }
+
+    async fn update_password(&self, id: Uuid, password_hash: &str) -> Result<()> {
+        if let Some(user) = self.users.write().unwrap().get_mut(&id) {
+            user.password_hash = password_hash.to_string();
+            user.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+
+    async fn rehash_password(&self, id: Uuid, current_hash: &str, new_hash: &str) -> Result<()> {
+        if let Some(user) = self.users.write().unwrap().get_mut(&id) {
+            if user.password_hash == current_hash {
+                user.password_hash = new_hash.to_string();
+            }
+        }
+        Ok(())
+    }
+
+    async fn mark_email_verified(&self, id: Uuid, verified_at: DateTime<Utc>) -> Result<()> {
+        if let Some(user) = self.users.write().unwrap().get_mut(&id) {
+            if user.email_verified_at.is_none() {
+                user.email_verified_at = Some(verified_at);
+                user.updated_at = Utc::now();
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool> {
+        Ok(self.users.write().unwrap().remove(&id).is_some())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryRefreshTokenRepository {
+    tokens: RwLock<HashMap<Uuid, RefreshToken>>,
+}
+
+#[async_trait]
+impl RefreshTokenRepository for InMemoryRefreshTokenRepository {
+    async fn create(&self, token: RefreshToken) -> Result<RefreshToken> {
+        self.tokens.write().unwrap().insert(token.id, token.clone());
+        Ok(token)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>> {
+        Ok(self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(&id) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_family(&self, family_id: Uuid) -> Result<()> {
+        let now = Utc::now();
+        for token in self.tokens.write().unwrap().values_mut() {
+            if token.family_id == family_id && token.revoked_at.is_none() {
+                token.revoked_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: Uuid) -> Result<()> {
+        let now = Utc::now();
+        for token in self.tokens.write().unwrap().values_mut() {
+            if token.user_id == user_id && token.revoked_at.is_none() {
+                token.revoked_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemorySessionRepository {
+    sessions: RwLock<HashMap<Uuid, Session>>,
+}
+
+#[async_trait]
+impl SessionRepository for InMemorySessionRepository {
+    async fn create(&self, session: Session) -> Result<Session> {
+        self.sessions
+            .write()
+            .unwrap()
+            .insert(session.id, session.clone());
+        Ok(session)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Session>> {
+        Ok(self.sessions.read().unwrap().get(&id).cloned())
+    }
+
+    async fn list_active(&self, user_id: Uuid, now: DateTime<Utc>) -> Result<Vec<Session>> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|session| {
+                session.user_id == user_id
+                    && session.revoked_at.is_none()
+                    && session.expires_at > now
+            })
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    async fn list_active_for_client(
+        &self,
+        client_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>> {
+        Ok(self
+            .sessions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|session| {
+                session.client_id == Some(client_id)
+                    && session.revoked_at.is_none()
+                    && session.expires_at > now
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn update(&self, session: &Session) -> Result<()> {
+        if let Some(stored) = self.sessions.write().unwrap().get_mut(&session.id) {
+            *stored = Session {
+                revoked_at: stored.revoked_at,
+                ..session.clone()
+            };
+        }
+        Ok(())
+    }
+
+    async fn revoke(&self, id: Uuid, revoked_at: DateTime<Utc>) -> Result<()> {
+        if let Some(session) = self.sessions.write().unwrap().get_mut(&id) {
+            session.revoked_at.get_or_insert(revoked_at);
+        }
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: Uuid, revoked_at: DateTime<Utc>) -> Result<()> {
+        for session in self.sessions.write().unwrap().values_mut() {
+            if session.user_id == user_id {
+                session.revoked_at.get_or_insert(revoked_at);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPasswordResetRepository {
+    tokens: RwLock<HashMap<Uuid, PasswordResetToken>>,
+}
+
+#[async_trait]
+impl PasswordResetRepository for InMemoryPasswordResetRepository {
+    async fn create(&self, token: PasswordResetToken) -> Result<PasswordResetToken> {
+        self.tokens.write().unwrap().insert(token.id, token.clone());
+        Ok(token)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PasswordResetToken>> {
+        Ok(self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(&id) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()> {
+        let now = Utc::now();
+        for token in self.tokens.write().unwrap().values_mut() {
+            if token.user_id == user_id && token.used_at.is_none() {
+                token.used_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryMagicLinkRepository {
+    tokens: RwLock<HashMap<Uuid, MagicLinkToken>>,
+}
+
+#[async_trait]
+impl MagicLinkRepository for InMemoryMagicLinkRepository {
+    async fn create(&self, token: MagicLinkToken) -> Result<MagicLinkToken> {
+        self.tokens.write().unwrap().insert(token.id, token.clone());
+        Ok(token)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<MagicLinkToken>> {
+        Ok(self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(&id) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()> {
+        let now = Utc::now();
+        for token in self.tokens.write().unwrap().values_mut() {
+            if token.user_id == user_id && token.used_at.is_none() {
+                token.used_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryEmailVerificationRepository {
+    tokens: RwLock<HashMap<Uuid, EmailVerificationToken>>,
+}
+
+#[async_trait]
+impl EmailVerificationRepository for InMemoryEmailVerificationRepository {
+    async fn create(&self, token: EmailVerificationToken) -> Result<EmailVerificationToken> {
+        self.tokens.write().unwrap().insert(token.id, token.clone());
+        Ok(token)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<EmailVerificationToken>> {
+        Ok(self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(&id) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()> {
+        let now = Utc::now();
+        for token in self.tokens.write().unwrap().values_mut() {
+            if token.user_id == user_id && token.used_at.is_none() {
+                token.used_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryTwoFactorRepository {
+    secrets: RwLock<HashMap<Uuid, TwoFactorSecret>>,
+    recovery_codes: RwLock<HashMap<Uuid, RecoveryCode>>,
+}
+
+#[async_trait]
+impl TwoFactorRepository for InMemoryTwoFactorRepository {
+    async fn find(&self, user_id: Uuid) -> Result<Option<TwoFactorSecret>> {
+        Ok(self.secrets.read().unwrap().get(&user_id).cloned())
+    }
+
+    async fn save(&self, secret: TwoFactorSecret) -> Result<()> {
+        self.secrets.write().unwrap().insert(secret.user_id, secret);
+        Ok(())
+    }
+
+    async fn confirm(&self, user_id: Uuid, confirmed_at: DateTime<Utc>) -> Result<()> {
+        if let Some(secret) = self.secrets.write().unwrap().get_mut(&user_id) {
+            secret.confirmed_at = Some(confirmed_at);
+        }
+        Ok(())
+    }
+
+    async fn use_step(&self, user_id: Uuid, step: i64) -> Result<bool> {
+        let mut secrets = self.secrets.write().unwrap();
+        match secrets.get_mut(&user_id) {
+            Some(secret) if secret.last_used_step.is_none_or(|last| last < step) => {
+                secret.last_used_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete(&self, user_id: Uuid) -> Result<()> {
+        self.secrets.write().unwrap().remove(&user_id);
+        self.recovery_codes
+            .write()
+            .unwrap()
+            .retain(|_, code| code.user_id != user_id);
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(&self, user_id: Uuid, codes: Vec<RecoveryCode>) -> Result<()> {
+        let mut recovery_codes = self.recovery_codes.write().unwrap();
+        recovery_codes.retain(|_, code| code.user_id != user_id);
+        recovery_codes.extend(codes.into_iter().map(|code| (code.id, code)));
+        Ok(())
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool> {
+        let mut recovery_codes = self.recovery_codes.write().unwrap();
+        match recovery_codes.values_mut().find(|code| {
+            code.user_id == user_id && code.code_hash == code_hash && code.used_at.is_none()
+        }) {
+            Some(code) => {
+                code.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryTwoFactorChallengeRepository {
+    challenges: RwLock<HashMap<Uuid, TwoFactorChallenge>>,
+}
+
+#[async_trait]
+impl TwoFactorChallengeRepository for InMemoryTwoFactorChallengeRepository {
+    async fn create(&self, challenge: TwoFactorChallenge) -> Result<TwoFactorChallenge> {
+        self.challenges
+            .write()
+            .unwrap()
+            .insert(challenge.id, challenge.clone());
+        Ok(challenge)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<TwoFactorChallenge>> {
+        Ok(self
+            .challenges
+            .read()
+            .unwrap()
+            .values()
+            .find(|challenge| challenge.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn record_failure(&self, id: Uuid) -> Result<i32> {
+        Ok(self
+            .challenges
+            .write()
+            .unwrap()
+            .get_mut(&id)
+            .map(|challenge| {
+                challenge.failed_attempts += 1;
+                challenge.failed_attempts
+            })
+            .unwrap_or_default())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut challenges = self.challenges.write().unwrap();
+        match challenges.get_mut(&id) {
+            Some(challenge) if challenge.used_at.is_none() => {
+                challenge.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPasskeyRepository {
+    passkeys: RwLock<HashMap<Uuid, Passkey>>,
+}
+
+#[async_trait]
+impl PasskeyRepository for InMemoryPasskeyRepository {
+    async fn create(&self, passkey: Passkey) -> Result<Passkey> {
+        let mut passkeys = self.passkeys.write().unwrap();
+        if passkeys
+            .values()
+            .any(|existing| existing.credential_id == passkey.credential_id)
+        {
+            return Err(AuthError::Database(
+                "unique constraint violation".to_string(),
+            ));
+        }
+        passkeys.insert(passkey.id, passkey.clone());
+        Ok(passkey)
+    }
+
+    async fn find_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>> {
+        Ok(self
+            .passkeys
+            .read()
+            .unwrap()
+            .values()
+            .find(|passkey| passkey.credential_id == credential_id)
+            .cloned())
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<Passkey>> {
+        let mut passkeys: Vec<Passkey> = self
+            .passkeys
+            .read()
+            .unwrap()
+            .values()
+            .filter(|passkey| passkey.user_id == user_id)
+            .cloned()
+            .collect();
+        passkeys.sort_by_key(|passkey| passkey.created_at);
+        Ok(passkeys)
+    }
+
+    async fn record_use(&self, id: Uuid, previous: i64, sign_count: i64) -> Result<bool> {
+        let mut passkeys = self.passkeys.write().unwrap();
+        match passkeys.get_mut(&id) {
+            Some(passkey) if passkey.sign_count == previous => {
+                passkey.sign_count = sign_count;
+                passkey.last_used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool> {
+        let mut passkeys = self.passkeys.write().unwrap();
+        match passkeys.get(&id) {
+            Some(passkey) if passkey.user_id == user_id => {
+                passkeys.remove(&id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPersonalAccessTokenRepository {
+    tokens: RwLock<HashMap<Uuid, PersonalAccessToken>>,
+}
+
+#[async_trait]
+impl PersonalAccessTokenRepository for InMemoryPersonalAccessTokenRepository {
+    async fn create(&self, token: PersonalAccessToken) -> Result<PersonalAccessToken> {
+        self.tokens.write().unwrap().insert(token.id, token.clone());
+        Ok(token)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PersonalAccessToken>> {
+        Ok(self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<PersonalAccessToken>> {
+        let mut tokens: Vec<PersonalAccessToken> = self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .filter(|token| token.user_id == user_id)
+            .cloned()
+            .collect();
+        tokens.sort_by_key(|token| token.created_at);
+        Ok(tokens)
+    }
+
+    async fn record_use(&self, id: Uuid, used_at: DateTime<Utc>) -> Result<()> {
+        if let Some(token) = self.tokens.write().unwrap().get_mut(&id) {
+            token.last_used_at = Some(used_at);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get(&id) {
+            Some(token) if token.user_id == user_id => {
+                tokens.remove(&id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPasskeyChallengeRepository {
+    challenges: RwLock<HashMap<Uuid, PasskeyChallenge>>,
+}
+
+#[async_trait]
+impl PasskeyChallengeRepository for InMemoryPasskeyChallengeRepository {
+    async fn create(&self, challenge: PasskeyChallenge) -> Result<PasskeyChallenge> {
+        self.challenges
+            .write()
+            .unwrap()
+            .insert(challenge.id, challenge.clone());
+        Ok(challenge)
+    }
+
+    async fn find_by_hash(&self, challenge_hash: &str) -> Result<Option<PasskeyChallenge>> {
+        Ok(self
+            .challenges
+            .read()
+            .unwrap()
+            .values()
+            .find(|challenge| challenge.challenge_hash == challenge_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut challenges = self.challenges.write().unwrap();
+        match challenges.get_mut(&id) {
+            Some(challenge) if challenge.used_at.is_none() => {
+                challenge.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryLoginThrottleRepository {
+    throttles: RwLock<HashMap<String, LoginThrottle>>,
+}
+
+#[async_trait]
+impl LoginThrottleRepository for InMemoryLoginThrottleRepository {
+    async fn find(&self, key: &str) -> Result<Option<LoginThrottle>> {
+        Ok(self.throttles.read().unwrap().get(key).cloned())
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+        window: Duration,
+    ) -> Result<LoginThrottle> {
+        let mut throttles = self.throttles.write().unwrap();
+        let throttle = throttles
+            .entry(key.to_string())
+            .or_insert_with(|| LoginThrottle {
+                key: key.to_string(),
+                failed_attempts: 0,
+                last_failure_at: now,
+                blocked_until: None,
+                locked: false,
+            });
+        if throttle.last_failure_at < now - window {
+            throttle.failed_attempts = 0;
+        }
+        throttle.failed_attempts += 1;
+        throttle.last_failure_at = now;
+        Ok(throttle.clone())
+    }
+
+    async fn block(&self, key: &str, until: DateTime<Utc>, locked: bool) -> Result<()> {
+        if let Some(throttle) = self.throttles.write().unwrap().get_mut(key) {
+            throttle.blocked_until = Some(until);
+            throttle.locked = locked;
+            if locked {
+                throttle.failed_attempts = 0;
+            }
+        }
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<()> {
+        self.throttles.write().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryAccountUnlockRepository {
+    tokens: RwLock<HashMap<Uuid, AccountUnlockToken>>,
+}
+
+#[async_trait]
+impl AccountUnlockRepository for InMemoryAccountUnlockRepository {
+    async fn create(&self, token: AccountUnlockToken) -> Result<AccountUnlockToken> {
+        self.tokens.write().unwrap().insert(token.id, token.clone());
+        Ok(token)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<AccountUnlockToken>> {
+        Ok(self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(&id) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryOidcLoginStateRepository {
+    states: RwLock<HashMap<Uuid, OidcLoginState>>,
+}
+
+#[async_trait]
+impl OidcLoginStateRepository for InMemoryOidcLoginStateRepository {
+    async fn create(&self, state: OidcLoginState) -> Result<OidcLoginState> {
+        self.states.write().unwrap().insert(state.id, state.clone());
+        Ok(state)
+    }
+
+    async fn find_by_hash(&self, state_hash: &str) -> Result<Option<OidcLoginState>> {
+        Ok(self
+            .states
+            .read()
+            .unwrap()
+            .values()
+            .find(|state| state.state_hash == state_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut states = self.states.write().unwrap();
+        match states.get_mut(&id) {
+            Some(state) if state.used_at.is_none() => {
+                state.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryOidcIdentityRepository {
+    identities: RwLock<HashMap<Uuid, OidcIdentity>>,
+}
+
+#[async_trait]
+impl OidcIdentityRepository for InMemoryOidcIdentityRepository {
+    async fn find(&self, provider: &str, subject: &str) -> Result<Option<OidcIdentity>> {
+        Ok(self
+            .identities
+            .read()
+            .unwrap()
+            .values()
+            .find(|identity| identity.provider == provider && identity.subject == subject)
+            .cloned())
+    }
+
+    async fn create(&self, identity: OidcIdentity) -> Result<OidcIdentity> {
+        let mut identities = self.identities.write().unwrap();
+        if identities.values().any(|existing| {
+            existing.provider == identity.provider && existing.subject == identity.subject
+        }) {
+            return Err(AuthError::Database(
+                "unique constraint violation".to_string(),
+            ));
+        }
+        identities.insert(identity.id, identity.clone());
+        Ok(identity)
+    }
+
+    async fn record_login(&self, id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        if let Some(identity) = self.identities.write().unwrap().get_mut(&id) {
+            identity.last_login_at = Some(at);
+        }
+        Ok(())
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<OidcIdentity>> {
+        let mut identities: Vec<OidcIdentity> = self
+            .identities
+            .read()
+            .unwrap()
+            .values()
+            .filter(|identity| identity.user_id == user_id)
+            .cloned()
+            .collect();
+        identities.sort_by_key(|identity| identity.created_at);
+        Ok(identities)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryOAuthClientRepository {
+    clients: RwLock<HashMap<Uuid, OAuthClient>>,
+}
+
+#[async_trait]
+impl OAuthClientRepository for InMemoryOAuthClientRepository {
+    async fn create(&self, client: OAuthClient) -> Result<OAuthClient> {
+        self.clients
+            .write()
+            .unwrap()
+            .insert(client.id, client.clone());
+        Ok(client)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<OAuthClient>> {
+        Ok(self.clients.read().unwrap().get(&id).cloned())
+    }
+
+    async fn list_for_owner(&self, owner_id: Uuid) -> Result<Vec<OAuthClient>> {
+        let mut clients: Vec<OAuthClient> = self
+            .clients
+            .read()
+            .unwrap()
+            .values()
+            .filter(|client| client.owner_id == owner_id)
+            .cloned()
+            .collect();
+        clients.sort_by_key(|client| client.created_at);
+        Ok(clients)
+    }
+
+    async fn delete(&self, owner_id: Uuid, id: Uuid) -> Result<bool> {
+        let mut clients = self.clients.write().unwrap();
+        match clients.get(&id) {
+            Some(client) if client.owner_id == owner_id => {
+                clients.remove(&id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryOAuthAuthorizationCodeRepository {
+    codes: RwLock<HashMap<Uuid, OAuthAuthorizationCode>>,
+}
+
+#[async_trait]
+impl OAuthAuthorizationCodeRepository for InMemoryOAuthAuthorizationCodeRepository {
+    async fn create(&self, code: OAuthAuthorizationCode) -> Result<OAuthAuthorizationCode> {
+        self.codes.write().unwrap().insert(code.id, code.clone());
+        Ok(code)
+    }
+
+    async fn find_by_hash(&self, code_hash: &str) -> Result<Option<OAuthAuthorizationCode>> {
+        Ok(self
+            .codes
+            .read()
+            .unwrap()
+            .values()
+            .find(|code| code.code_hash == code_hash)
+            .cloned())
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let mut codes = self.codes.write().unwrap();
+        match codes.get_mut(&id) {
+            Some(code) if code.used_at.is_none() => {
+                code.used_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryOAuthConsentRepository {
+    consents: RwLock<HashMap<(Uuid, Uuid), OAuthConsent>>,
+}
+
+#[async_trait]
+impl OAuthConsentRepository for InMemoryOAuthConsentRepository {
+    async fn find(&self, user_id: Uuid, client_id: Uuid) -> Result<Option<OAuthConsent>> {
+        Ok(self
+            .consents
+            .read()
+            .unwrap()
+            .get(&(user_id, client_id))
+            .cloned())
+    }
+
+    async fn upsert(&self, consent: OAuthConsent) -> Result<()> {
+        self.consents
+            .write()
+            .unwrap()
+            .insert((consent.user_id, consent.client_id), consent);
+        Ok(())
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<OAuthConsent>> {
+        let mut consents: Vec<OAuthConsent> = self
+            .consents
+            .read()
+            .unwrap()
+            .values()
+            .filter(|consent| consent.user_id == user_id)
+            .cloned()
+            .collect();
+        consents.sort_by_key(|consent| consent.created_at);
+        Ok(consents)
+    }
+
+    async fn delete(&self, user_id: Uuid, client_id: Uuid) -> Result<bool> {
+        Ok(self
+            .consents
+            .write()
+            .unwrap()
+            .remove(&(user_id, client_id))
+            .is_some())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryRevocationRepository {
+    tokens: RwLock<HashMap<Uuid, RevokedToken>>,
+    users: RwLock<HashMap<Uuid, UserRevocation>>,
+}
+
+#[async_trait]
+impl RevocationRepository for InMemoryRevocationRepository {
+    async fn revoke_token(&self, revoked: RevokedToken) -> Result<()> {
+        self.tokens.write().unwrap().insert(revoked.jti, revoked);
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: Uuid) -> Result<bool> {
+        Ok(self.tokens.read().unwrap().contains_key(&jti))
+    }
+
+    async fn revoke_user(&self, revocation: UserRevocation) -> Result<()> {
+        self.users
+            .write()
+            .unwrap()
+            .insert(revocation.user_id, revocation);
+        Ok(())
+    }
+
+    async fn find_user_revocation(&self, user_id: Uuid) -> Result<Option<UserRevocation>> {
+        Ok(self.users.read().unwrap().get(&user_id).cloned())
+    }
+
+    async fn list_tokens(&self, now: DateTime<Utc>) -> Result<Vec<RevokedToken>> {
+        Ok(self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .filter(|revoked| revoked.expires_at > now)
+            .cloned()
+            .collect())
+    }
+
+    async fn list_users(&self, since: DateTime<Utc>) -> Result<Vec<UserRevocation>> {
+        Ok(self
+            .users
+            .read()
+            .unwrap()
+            .values()
+            .filter(|revocation| revocation.revoked_before > since)
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryAuditLogRepository {
+    events: RwLock<Vec<AuditEvent>>,
+}
+
+#[async_trait]
+impl AuditLogRepository for InMemoryAuditLogRepository {
+    async fn append(&self, event: AuditEvent) -> Result<()> {
+        self.events.write().unwrap().push(event);
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditLogQuery, limit: usize) -> Result<Vec<AuditEvent>> {
+        Ok(self
+            .events
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|event| query.user_id.is_none_or(|id| event.user_id == Some(id)))
+            .filter(|event| {
+                query
+                    .event_type
+                    .is_none_or(|event_type| event.event_type == event_type)
+            })
+            .filter(|event| query.from.is_none_or(|from| event.created_at >= from))
+            .filter(|event| query.to.is_none_or(|to| event.created_at < to))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let mut events = self.events.write().unwrap();
+        let before = events.len();
+        events.retain(|event| event.created_at >= cutoff);
+        Ok((before - events.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(email: &str, username: &str) -> User {
+        User {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            username: username.to_string(),
+            password_hash: String::new(),
+            display_name: None,
+            bio: None,
+            avatar_url: None,
+            roles: vec![Role::Author],
+            email_verified_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn users_need_a_unique_email_and_username() {
+        let users = InMemoryUserRepository::default();
+        let alice = users
+            .create(user("alice@example.com", "alice"))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            users.create(user("alice@example.com", "alice2")).await,
+            Err(AuthError::UserExists)
+        ));
+        assert!(matches!(
+            users.create(user("alice2@example.com", "alice")).await,
+            Err(AuthError::UserExists)
+        ));
+        users.create(user("bob@example.com", "bob")).await.unwrap();
+
+        let found = users.find_by_email("alice@example.com").await.unwrap();
+        assert_eq!(found.map(|user| user.id), Some(alice.id));
+        let found = users.find_by_username("bob").await.unwrap();
+        assert_eq!(
+            found.map(|user| user.email).as_deref(),
+            Some("bob@example.com")
+        );
+        assert!(users.find_by_username("carol").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,418 @@
+mod memory;
+mod postgrest;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    models::{
+        AccountUnlockToken, AuditEvent, AuditLogQuery, EmailVerificationToken, LoginThrottle,
+        MagicLinkToken, OAuthAuthorizationCode, OAuthClient, OAuthConsent, OidcIdentity,
+        OidcLoginState, Passkey, PasskeyChallenge, PasswordResetToken, PersonalAccessToken,
+        RecoveryCode, RefreshToken, RevokedToken, Session, TwoFactorChallenge, TwoFactorSecret,
+        User, UserRevocation,
+    },
+    rbac::Role,
+};
+
+pub use self::memory::{
+    InMemoryAccountUnlockRepository, InMemoryAuditLogRepository,
+    InMemoryEmailVerificationRepository, InMemoryLoginThrottleRepository,
+    InMemoryMagicLinkRepository, InMemoryOAuthAuthorizationCodeRepository,
+    InMemoryOAuthClientRepository, InMemoryOAuthConsentRepository, InMemoryOidcIdentityRepository,
+    InMemoryOidcLoginStateRepository, InMemoryPasskeyChallengeRepository,
+    InMemoryPasskeyRepository, InMemoryPasswordResetRepository,
+    InMemoryPersonalAccessTokenRepository, InMemoryRefreshTokenRepository,
+    InMemoryRevocationRepository, InMemorySessionRepository, InMemoryTwoFactorChallengeRepository,
+    InMemoryTwoFactorRepository, InMemoryUserRepository,
+};
+pub use self::postgrest::{
+    PostgrestAccountUnlockRepository, PostgrestAuditLogRepository,
+    PostgrestEmailVerificationRepository, PostgrestLoginThrottleRepository,
+    PostgrestMagicLinkRepository, PostgrestOAuthAuthorizationCodeRepository,
+    PostgrestOAuthClientRepository, PostgrestOAuthConsentRepository,
+    PostgrestOidcIdentityRepository, PostgrestOidcLoginStateRepository,
+    PostgrestPasskeyChallengeRepository, PostgrestPasskeyRepository,
+    PostgrestPasswordResetRepository, PostgrestPersonalAccessTokenRepository,
+    PostgrestRefreshTokenRepository, PostgrestRevocationRepository, PostgrestSessionRepository,
+    PostgrestTwoFactorChallengeRepository, PostgrestTwoFactorRepository, PostgrestUserRepository,
+    SupabaseClient,
+};
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>>;
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>>;
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>>;
+
+    /// Inserts a new user. Returns `AuthError::UserExists` when the email or
+    /// username is already taken.
+    async fn create(&self, user: User) -> Result<User>;
+
+    /// Replaces the user's roles. Returns `None` when the user does not exist.
+    async fn update_roles(&self, id: Uuid, roles: Vec<Role>) -> Result<Option<User>>;
+
+    async fn update_password(&self, id: Uuid, password_hash: &str) -> Result<()>;
+
+    /// Replaces the hash only if it is still `current_hash`, so an upgrade
+    /// on login can't undo a password change made in the meantime.
+    async fn rehash_password(&self, id: Uuid, current_hash: &str, new_hash: &str) -> Result<()>;
+
+    /// Records that the user's email was verified at `verified_at`, unless it
+    /// already was.
+    async fn mark_email_verified(&self, id: Uuid, verified_at: DateTime<Utc>) -> Result<()>;
+
+    /// Deletes the user. In the database, the rows that belong to them go
+    /// with it. Returns `false` when there was no such user.
+    async fn delete(&self, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait RefreshTokenRepository: Send + Sync {
+    async fn create(&self, token: RefreshToken) -> Result<RefreshToken>;
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>>;
+
+    /// Atomically marks an unused token as used. Returns `false` when the
+    /// token had already been used, which callers treat as reuse.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+
+    async fn revoke_family(&self, family_id: Uuid) -> Result<()>;
+    async fn revoke_all_for_user(&self, user_id: Uuid) -> Result<()>;
+}
+
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    async fn create(&self, session: Session) -> Result<Session>;
+    async fn find(&self, id: Uuid) -> Result<Option<Session>>;
+
+    /// The user's sessions that are neither revoked nor expired at `now`,
+    /// most recently seen first.
+    async fn list_active(&self, user_id: Uuid, now: DateTime<Utc>) -> Result<Vec<Session>>;
+
+    /// Active sessions of an OAuth client, across all users.
+    async fn list_active_for_client(
+        &self,
+        client_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>>;
+
+    /// Stores the tokens and client details of a refresh. Leaves
+    /// `revoked_at` alone, so a refresh racing a revocation can't undo it.
+    async fn update(&self, session: &Session) -> Result<()>;
+
+    async fn revoke(&self, id: Uuid, revoked_at: DateTime<Utc>) -> Result<()>;
+    async fn revoke_all_for_user(&self, user_id: Uuid, revoked_at: DateTime<Utc>) -> Result<()>;
+}
+
+#[async_trait]
+pub trait PasswordResetRepository: Send + Sync {
+    async fn create(&self, token: PasswordResetToken) -> Result<PasswordResetToken>;
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PasswordResetToken>>;
+
+    /// Atomically marks an unused token as used. Returns `false` when it had
+    /// already been used, so a token can only ever reset one password.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+
+    /// Marks every outstanding token of the user as used.
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()>;
+}
+
+#[async_trait]
+pub trait MagicLinkRepository: Send + Sync {
+    async fn create(&self, token: MagicLinkToken) -> Result<MagicLinkToken>;
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<MagicLinkToken>>;
+
+    /// Atomically marks an unused link as used. Returns `false` when it had
+    /// already been used, so a link signs in at most once.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+
+    /// Marks every outstanding link of the user as used.
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()>;
+}
+
+#[async_trait]
+pub trait EmailVerificationRepository: Send + Sync {
+    async fn create(&self, token: EmailVerificationToken) -> Result<EmailVerificationToken>;
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<EmailVerificationToken>>;
+
+    /// Atomically marks an unused token as used. Returns `false` when it had
+    /// already been used.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+
+    /// Marks every outstanding token of the user as used.
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()>;
+}
+
+/// TOTP secrets and recovery codes.
+#[async_trait]
+pub trait TwoFactorRepository: Send + Sync {
+    async fn find(&self, user_id: Uuid) -> Result<Option<TwoFactorSecret>>;
+
+    /// Stores a new secret for the user, replacing any existing one.
+    async fn save(&self, secret: TwoFactorSecret) -> Result<()>;
+    async fn confirm(&self, user_id: Uuid, confirmed_at: DateTime<Utc>) -> Result<()>;
+
+    /// Atomically records `step` as used if it is later than the last used
+    /// one. Returns `false` for a replayed code.
+    async fn use_step(&self, user_id: Uuid, step: i64) -> Result<bool>;
+
+    /// Removes the secret and every recovery code of the user.
+    async fn delete(&self, user_id: Uuid) -> Result<()>;
+
+    async fn replace_recovery_codes(&self, user_id: Uuid, codes: Vec<RecoveryCode>) -> Result<()>;
+
+    /// Atomically marks a matching unused recovery code as used.
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait TwoFactorChallengeRepository: Send + Sync {
+    async fn create(&self, challenge: TwoFactorChallenge) -> Result<TwoFactorChallenge>;
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<TwoFactorChallenge>>;
+
+    /// Counts a wrong code against the challenge and returns the new total.
+    async fn record_failure(&self, id: Uuid) -> Result<i32>;
+
+    /// Atomically marks an unused challenge as used.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait PasskeyRepository: Send + Sync {
+    async fn create(&self, passkey: Passkey) -> Result<Passkey>;
+    async fn find_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>>;
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<Passkey>>;
+
+    /// Moves the signature counter from `previous` to `sign_count` and
+    /// records the use. Returns `false` if the counter changed in between.
+    async fn record_use(&self, id: Uuid, previous: i64, sign_count: i64) -> Result<bool>;
+
+    /// Returns `false` when the user has no passkey with this id.
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait PersonalAccessTokenRepository: Send + Sync {
+    async fn create(&self, token: PersonalAccessToken) -> Result<PersonalAccessToken>;
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PersonalAccessToken>>;
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<PersonalAccessToken>>;
+    async fn record_use(&self, id: Uuid, used_at: DateTime<Utc>) -> Result<()>;
+
+    /// Deletes the token if it belongs to `user_id`. Returns whether it did.
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait PasskeyChallengeRepository: Send + Sync {
+    async fn create(&self, challenge: PasskeyChallenge) -> Result<PasskeyChallenge>;
+    async fn find_by_hash(&self, challenge_hash: &str) -> Result<Option<PasskeyChallenge>>;
+
+    /// Atomically marks an unused challenge as used.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait LoginThrottleRepository: Send + Sync {
+    async fn find(&self, key: &str) -> Result<Option<LoginThrottle>>;
+
+    /// Atomically counts a failed login. The count starts over when the
+    /// previous failure is older than `window`.
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+        window: Duration,
+    ) -> Result<LoginThrottle>;
+
+    /// Refuses logins for the key until `until`. A lockout also resets the
+    /// failure count, so the key starts afresh once it expires.
+    async fn block(&self, key: &str, until: DateTime<Utc>, locked: bool) -> Result<()>;
+
+    async fn clear(&self, key: &str) -> Result<()>;
+}
+
+#[async_trait]
+pub trait AccountUnlockRepository: Send + Sync {
+    async fn create(&self, token: AccountUnlockToken) -> Result<AccountUnlockToken>;
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<AccountUnlockToken>>;
+
+    /// Atomically marks an unused token as used.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait OidcLoginStateRepository: Send + Sync {
+    async fn create(&self, state: OidcLoginState) -> Result<OidcLoginState>;
+    async fn find_by_hash(&self, state_hash: &str) -> Result<Option<OidcLoginState>>;
+
+    /// Atomically marks an unused login state as used.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait OidcIdentityRepository: Send + Sync {
+    async fn find(&self, provider: &str, subject: &str) -> Result<Option<OidcIdentity>>;
+    async fn create(&self, identity: OidcIdentity) -> Result<OidcIdentity>;
+    async fn record_login(&self, id: Uuid, at: DateTime<Utc>) -> Result<()>;
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<OidcIdentity>>;
+}
+
+#[async_trait]
+pub trait OAuthClientRepository: Send + Sync {
+    async fn create(&self, client: OAuthClient) -> Result<OAuthClient>;
+    async fn find(&self, id: Uuid) -> Result<Option<OAuthClient>>;
+    async fn list_for_owner(&self, owner_id: Uuid) -> Result<Vec<OAuthClient>>;
+
+    /// Deletes the client if `owner_id` registered it. Returns whether it did.
+    async fn delete(&self, owner_id: Uuid, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait OAuthAuthorizationCodeRepository: Send + Sync {
+    async fn create(&self, code: OAuthAuthorizationCode) -> Result<OAuthAuthorizationCode>;
+    async fn find_by_hash(&self, code_hash: &str) -> Result<Option<OAuthAuthorizationCode>>;
+
+    /// Atomically marks an unused code as used. Returns `false` when it had
+    /// already been used, so a code is exchanged at most once.
+    async fn mark_used(&self, id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait OAuthConsentRepository: Send + Sync {
+    async fn find(&self, user_id: Uuid, client_id: Uuid) -> Result<Option<OAuthConsent>>;
+    async fn upsert(&self, consent: OAuthConsent) -> Result<()>;
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<OAuthConsent>>;
+
+    /// Returns whether there was a consent to delete.
+    async fn delete(&self, user_id: Uuid, client_id: Uuid) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait RevocationRepository: Send + Sync {
+    async fn revoke_token(&self, revoked: RevokedToken) -> Result<()>;
+    async fn is_token_revoked(&self, jti: Uuid) -> Result<bool>;
+
+    /// Records (or moves forward) the user's "log out everywhere" cutoff.
+    async fn revoke_user(&self, revocation: UserRevocation) -> Result<()>;
+    async fn find_user_revocation(&self, user_id: Uuid) -> Result<Option<UserRevocation>>;
+
+    /// Revoked tokens that have not expired yet.
+    async fn list_tokens(&self, now: DateTime<Utc>) -> Result<Vec<RevokedToken>>;
+
+    /// User cutoffs newer than `since`; older ones cannot affect live tokens.
+    async fn list_users(&self, since: DateTime<Utc>) -> Result<Vec<UserRevocation>>;
+}
+
+#[async_trait]
+pub trait AuditLogRepository: Send + Sync {
+    /// Events are only ever added, never changed.
+    async fn append(&self, event: AuditEvent) -> Result<()>;
+
+    /// Events matching the filters in `query`, newest first, at most `limit`.
+    async fn query(&self, query: &AuditLogQuery, limit: usize) -> Result<Vec<AuditEvent>>;
+
+    /// Removes events older than `cutoff` once they fall out of retention.
+    /// Returns how many were removed.
+    async fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<u64>;
+}
+
+/// The set of stores the auth service works against.
+#[derive(Clone)]
+pub struct Repositories {
+    pub users: Arc<dyn UserRepository>,
+    pub refresh_tokens: Arc<dyn RefreshTokenRepository>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub revocations: Arc<dyn RevocationRepository>,
+    pub password_resets: Arc<dyn PasswordResetRepository>,
+    pub magic_links: Arc<dyn MagicLinkRepository>,
+    pub email_verifications: Arc<dyn EmailVerificationRepository>,
+    pub two_factor: Arc<dyn TwoFactorRepository>,
+    pub two_factor_challenges: Arc<dyn TwoFactorChallengeRepository>,
+    pub passkeys: Arc<dyn PasskeyRepository>,
+    pub passkey_challenges: Arc<dyn PasskeyChallengeRepository>,
+    pub personal_access_tokens: Arc<dyn PersonalAccessTokenRepository>,
+    pub oidc_login_states: Arc<dyn OidcLoginStateRepository>,
+    pub oidc_identities: Arc<dyn OidcIdentityRepository>,
+    pub oauth_clients: Arc<dyn OAuthClientRepository>,
+    pub oauth_codes: Arc<dyn OAuthAuthorizationCodeRepository>,
+    pub oauth_consents: Arc<dyn OAuthConsentRepository>,
+    pub login_throttles: Arc<dyn LoginThrottleRepository>,
+    pub account_unlocks: Arc<dyn AccountUnlockRepository>,
+    pub audit_log: Arc<dyn AuditLogRepository>,
+}
+
+impl Repositories {
+    pub fn in_memory() -> Self {
+        Self {
+            users: Arc::new(InMemoryUserRepository::default()),
+            refresh_tokens: Arc::new(InMemoryRefreshTokenRepository::default()),
+            sessions: Arc::new(InMemorySessionRepository::default()),
+            revocations: Arc::new(InMemoryRevocationRepository::default()),
+            password_resets: Arc::new(InMemoryPasswordResetRepository::default()),
+            magic_links: Arc::new(InMemoryMagicLinkRepository::default()),
+            email_verifications: Arc::new(InMemoryEmailVerificationRepository::default()),
+            two_factor: Arc::new(InMemoryTwoFactorRepository::default()),
+            two_factor_challenges: Arc::new(InMemoryTwoFactorChallengeRepository::default()),
+            passkeys: Arc::new(InMemoryPasskeyRepository::default()),
+            passkey_challenges: Arc::new(InMemoryPasskeyChallengeRepository::default()),
+            personal_access_tokens: Arc::new(InMemoryPersonalAccessTokenRepository::default()),
+            oidc_login_states: Arc::new(InMemoryOidcLoginStateRepository::default()),
+            oidc_identities: Arc::new(InMemoryOidcIdentityRepository::default()),
+            oauth_clients: Arc::new(InMemoryOAuthClientRepository::default()),
+            oauth_codes: Arc::new(InMemoryOAuthAuthorizationCodeRepository::default()),
+            oauth_consents: Arc::new(InMemoryOAuthConsentRepository::default()),
+            login_throttles: Arc::new(InMemoryLoginThrottleRepository::default()),
+            account_unlocks: Arc::new(InMemoryAccountUnlockRepository::default()),
+            audit_log: Arc::new(InMemoryAuditLogRepository::default()),
+        }
+    }
+
+    pub fn postgrest(client: SupabaseClient) -> Self {
+        Self {
+            users: Arc::new(PostgrestUserRepository::new(client.clone())),
+            refresh_tokens: Arc::new(PostgrestRefreshTokenRepository::new(client.clone())),
+            sessions: Arc::new(PostgrestSessionRepository::new(client.clone())),
+            revocations: Arc::new(PostgrestRevocationRepository::new(client.clone())),
+            password_resets: Arc::new(PostgrestPasswordResetRepository::new(client.clone())),
+            magic_links: Arc::new(PostgrestMagicLinkRepository::new(client.clone())),
+            email_verifications: Arc::new(PostgrestEmailVerificationRepository::new(
+                client.clone(),
+            )),
+            two_factor: Arc::new(PostgrestTwoFactorRepository::new(client.clone())),
+            two_factor_challenges: Arc::new(PostgrestTwoFactorChallengeRepository::new(
+                client.clone(),
+            )),
+            passkeys: Arc::new(PostgrestPasskeyRepository::new(client.clone())),
+            passkey_challenges: Arc::new(PostgrestPasskeyChallengeRepository::new(client.clone())),
+            personal_access_tokens: Arc::new(PostgrestPersonalAccessTokenRepository::new(
+                client.clone(),
+            )),
+            oidc_login_states: Arc::new(PostgrestOidcLoginStateRepository::new(client.clone())),
+            oidc_identities: Arc::new(PostgrestOidcIdentityRepository::new(client.clone())),
+            oauth_clients: Arc::new(PostgrestOAuthClientRepository::new(client.clone())),
+            oauth_codes: Arc::new(PostgrestOAuthAuthorizationCodeRepository::new(
+                client.clone(),
+            )),
+            oauth_consents: Arc::new(PostgrestOAuthConsentRepository::new(client.clone())),
+            login_throttles: Arc::new(PostgrestLoginThrottleRepository::new(client.clone())),
+            account_unlocks: Arc::new(PostgrestAccountUnlockRepository::new(client.clone())),
+            audit_log: Arc::new(PostgrestAuditLogRepository::new(client)),
+        }
+    }
+
+    /// Uses Supabase when `SUPABASE_URL` is set and falls back to the
+    /// in-memory store for local development otherwise.
+    pub fn from_env() -> Self {
+        match SupabaseClient::from_env() {
+            Some(client) => Self::postgrest(client),
+            None => {
+                tracing::warn!("SUPABASE_URL is not set, using in-memory stores");
+                Self::in_memory()
+            }
+        }
+    }
+}
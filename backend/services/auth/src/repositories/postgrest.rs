@@ -0,0 +1,1427 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use postgrest::{Builder, Postgrest};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{
+    AccountUnlockRepository, AuditLogRepository, EmailVerificationRepository,
+    LoginThrottleRepository, MagicLinkRepository, OAuthAuthorizationCodeRepository,
+    OAuthClientRepository, OAuthConsentRepository, OidcIdentityRepository,
+    OidcLoginStateRepository, PasskeyChallengeRepository, PasskeyRepository,
+    PasswordResetRepository, PersonalAccessTokenRepository, RefreshTokenRepository,
+    RevocationRepository, SessionRepository, TwoFactorChallengeRepository, TwoFactorRepository,
+    UserRepository,
+};
+use crate::{
+    error::{AuthError, Result},
+    models::{
+        AccountUnlockToken, AuditEvent, AuditLogQuery, EmailVerificationToken, LoginThrottle,
+        MagicLinkToken, OAuthAuthorizationCode, OAuthClient, OAuthConsent, OidcIdentity,
+        OidcLoginState, Passkey, PasskeyChallenge, PasswordResetToken, PersonalAccessToken,
+        RecoveryCode, RefreshToken, RevokedToken, Session, TwoFactorChallenge, TwoFactorSecret,
+        User, UserRevocation,
+    },
+    rbac::Role,
+};
+
+/// Postgres error code PostgREST reports for unique constraint violations.
+const UNIQUE_VIOLATION: &str = "23505";
+
+#[derive(Clone)]
+pub struct SupabaseClient {
+    client: Postgrest,
+}
+
+impl SupabaseClient {
+    pub fn new(url: &str, service_key: &str) -> Self {
+        let client = Postgrest::new(format!("{}/rest/v1", url.trim_end_matches('/')))
+            .insert_header("apikey", service_key)
+            .insert_header("Authorization", format!("Bearer {}", service_key));
+
+        Self { client }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("SUPABASE_URL").ok()?;
+        let service_key = std::env::var("SUPABASE_SERVICE_KEY")
+            .or_else(|_| std::env::var("SUPABASE_ANON_KEY"))
+            .ok()?;
+
+        Some(Self::new(&url, &service_key))
+    }
+
+    fn from(&self, table: &str) -> Builder {
+        self.client.from(table)
+    }
+}
+
+enum QueryError {
+    Conflict,
+    Failed(String),
+}
+
+impl From<QueryError> for AuthError {
+    fn from(err: QueryError) -> Self {
+        match err {
+            QueryError::Conflict => AuthError::Database("unique constraint violation".to_string()),
+            QueryError::Failed(msg) => AuthError::Database(msg),
+        }
+    }
+}
+
+async fn fetch_rows<T: DeserializeOwned>(
+    builder: Builder,
+) -> std::result::Result<Vec<T>, QueryError> {
+    let response = builder
+        .execute()
+        .await
+        .map_err(|e| QueryError::Failed(e.to_string()))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| QueryError::Failed(e.to_string()))?;
+
+    if !status.is_success() {
+        if status == StatusCode::CONFLICT || body.contains(UNIQUE_VIOLATION) {
+            return Err(QueryError::Conflict);
+        }
+        return Err(QueryError::Failed(format!("{}: {}", status, body)));
+    }
+
+    serde_json::from_str(&body).map_err(|e| QueryError::Failed(e.to_string()))
+}
+
+async fn fetch_optional<T: DeserializeOwned>(builder: Builder) -> Result<Option<T>> {
+    Ok(fetch_rows(builder.limit(1)).await?.into_iter().next())
+}
+
+fn to_body<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value)
+        .map_err(|e| AuthError::Internal(anyhow::anyhow!("Failed to encode row: {}", e)))
+}
+
+fn first_row<T>(rows: Vec<T>) -> Result<T> {
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| AuthError::Database("insert returned no rows".to_string()))
+}
+
+pub struct PostgrestUserRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestUserRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl UserRepository for PostgrestUserRepository {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<User>> {
+        fetch_optional(self.db.from("users").select("*").eq("id", id.to_string())).await
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>> {
+        fetch_optional(self.db.from("users").select("*").eq("email", email)).await
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>> {
+        fetch_optional(self.db.from("users").select("*").eq("username", username)).await
+    }
+
+    async fn create(&self, user: User) -> Result<User> {
+        let rows = fetch_rows(self.db.from("users").insert(to_body(&user)?))
+            .await
+            .map_err(|e| match e {
+                QueryError::Conflict => AuthError::UserExists,
+                other => other.into(),
+            })?;
+
+        first_row(rows)
+    }
+
+    async fn update_roles(&self, id: Uuid, roles: Vec<Role>) -> Result<Option<User>> {
+        let rows = fetch_rows(
+            self.db
+                .from("users")
+                .eq("id", id.to_string())
+                .update(json!({ "roles": roles, "updated_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(rows.into_iter().next())
+    }
+
+    async fn update_password(&self, id: Uuid, password_hash: &str) -> Result<()> {
+        fetch_rows::<User>(self.db.from("users").eq("id", id.to_string()).update(
+            json!({ "password_hash": password_hash, "updated_at": Utc::now() }).to_string(),
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn rehash_password(&self, id: Uuid, current_hash: &str, new_hash: &str) -> Result<()> {
+        fetch_rows::<User>(
+            self.db
+                .from("users")
+                .eq("id", id.to_string())
+                .eq("password_hash", current_hash)
+                .update(json!({ "password_hash": new_hash }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_email_verified(&self, id: Uuid, verified_at: DateTime<Utc>) -> Result<()> {
+        fetch_rows::<User>(
+            self.db
+                .from("users")
+                .eq("id", id.to_string())
+                .is("email_verified_at", "null")
+                .update(
+                    json!({ "email_verified_at": verified_at, "updated_at": Utc::now() })
+                        .to_string(),
+                ),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<User> =
+            fetch_rows(self.db.from("users").eq("id", id.to_string()).delete()).await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestRefreshTokenRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestRefreshTokenRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for PostgrestRefreshTokenRepository {
+    async fn create(&self, token: RefreshToken) -> Result<RefreshToken> {
+        first_row(fetch_rows(self.db.from("refresh_tokens").insert(to_body(&token)?)).await?)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>> {
+        fetch_optional(
+            self.db
+                .from("refresh_tokens")
+                .select("*")
+                .eq("token_hash", token_hash),
+        )
+        .await
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        // The `used_at IS NULL` filter makes concurrent rotations race safely:
+        // only one of them gets the row back.
+        let rows: Vec<RefreshToken> = fetch_rows(
+            self.db
+                .from("refresh_tokens")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    async fn revoke_family(&self, family_id: Uuid) -> Result<()> {
+        fetch_rows::<RefreshToken>(
+            self.db
+                .from("refresh_tokens")
+                .eq("family_id", family_id.to_string())
+                .is("revoked_at", "null")
+                .update(json!({ "revoked_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: Uuid) -> Result<()> {
+        fetch_rows::<RefreshToken>(
+            self.db
+                .from("refresh_tokens")
+                .eq("user_id", user_id.to_string())
+                .is("revoked_at", "null")
+                .update(json!({ "revoked_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct PostgrestSessionRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestSessionRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl SessionRepository for PostgrestSessionRepository {
+    async fn create(&self, session: Session) -> Result<Session> {
+        first_row(fetch_rows(self.db.from("sessions").insert(to_body(&session)?)).await?)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<Session>> {
+        fetch_optional(
+            self.db
+                .from("sessions")
+                .select("*")
+                .eq("id", id.to_string()),
+        )
+        .await
+    }
+
+    async fn list_active(&self, user_id: Uuid, now: DateTime<Utc>) -> Result<Vec<Session>> {
+        Ok(fetch_rows(
+            self.db
+                .from("sessions")
+                .select("*")
+                .eq("user_id", user_id.to_string())
+                .is("revoked_at", "null")
+                .gt("expires_at", now.to_rfc3339())
+                .order("last_seen_at.desc"),
+        )
+        .await?)
+    }
+
+    async fn list_active_for_client(
+        &self,
+        client_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>> {
+        Ok(fetch_rows(
+            self.db
+                .from("sessions")
+                .select("*")
+                .eq("client_id", client_id.to_string())
+                .is("revoked_at", "null")
+                .gt("expires_at", now.to_rfc3339()),
+        )
+        .await?)
+    }
+
+    async fn update(&self, session: &Session) -> Result<()> {
+        fetch_rows::<Session>(
+            self.db
+                .from("sessions")
+                .eq("id", session.id.to_string())
+                .update(
+                    json!({
+                        "refresh_token_id": session.refresh_token_id,
+                        "access_token_jti": session.access_token_jti,
+                        "access_token_expires_at": session.access_token_expires_at,
+                        "user_agent": session.user_agent,
+                        "ip_address": session.ip_address,
+                        "last_seen_at": session.last_seen_at,
+                        "expires_at": session.expires_at,
+                    })
+                    .to_string(),
+                ),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke(&self, id: Uuid, revoked_at: DateTime<Utc>) -> Result<()> {
+        fetch_rows::<Session>(
+            self.db
+                .from("sessions")
+                .eq("id", id.to_string())
+                .is("revoked_at", "null")
+                .update(json!({ "revoked_at": revoked_at }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: Uuid, revoked_at: DateTime<Utc>) -> Result<()> {
+        fetch_rows::<Session>(
+            self.db
+                .from("sessions")
+                .eq("user_id", user_id.to_string())
+                .is("revoked_at", "null")
+                .update(json!({ "revoked_at": revoked_at }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct PostgrestPasswordResetRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestPasswordResetRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl PasswordResetRepository for PostgrestPasswordResetRepository {
+    async fn create(&self, token: PasswordResetToken) -> Result<PasswordResetToken> {
+        first_row(
+            fetch_rows(
+                self.db
+                    .from("password_reset_tokens")
+                    .insert(to_body(&token)?),
+            )
+            .await?,
+        )
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PasswordResetToken>> {
+        fetch_optional(
+            self.db
+                .from("password_reset_tokens")
+                .select("*")
+                .eq("token_hash", token_hash),
+        )
+        .await
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<PasswordResetToken> = fetch_rows(
+            self.db
+                .from("password_reset_tokens")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()> {
+        fetch_rows::<PasswordResetToken>(
+            self.db
+                .from("password_reset_tokens")
+                .eq("user_id", user_id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct PostgrestMagicLinkRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestMagicLinkRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl MagicLinkRepository for PostgrestMagicLinkRepository {
+    async fn create(&self, token: MagicLinkToken) -> Result<MagicLinkToken> {
+        first_row(fetch_rows(self.db.from("magic_link_tokens").insert(to_body(&token)?)).await?)
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<MagicLinkToken>> {
+        fetch_optional(
+            self.db
+                .from("magic_link_tokens")
+                .select("*")
+                .eq("token_hash", token_hash),
+        )
+        .await
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<MagicLinkToken> = fetch_rows(
+            self.db
+                .from("magic_link_tokens")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()> {
+        fetch_rows::<MagicLinkToken>(
+            self.db
+                .from("magic_link_tokens")
+                .eq("user_id", user_id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct PostgrestEmailVerificationRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestEmailVerificationRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl EmailVerificationRepository for PostgrestEmailVerificationRepository {
+    async fn create(&self, token: EmailVerificationToken) -> Result<EmailVerificationToken> {
+        first_row(
+            fetch_rows(
+                self.db
+                    .from("email_verification_tokens")
+                    .insert(to_body(&token)?),
+            )
+            .await?,
+        )
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<EmailVerificationToken>> {
+        fetch_optional(
+            self.db
+                .from("email_verification_tokens")
+                .select("*")
+                .eq("token_hash", token_hash),
+        )
+        .await
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<EmailVerificationToken> = fetch_rows(
+            self.db
+                .from("email_verification_tokens")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    async fn invalidate_for_user(&self, user_id: Uuid) -> Result<()> {
+        fetch_rows::<EmailVerificationToken>(
+            self.db
+                .from("email_verification_tokens")
+                .eq("user_id", user_id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct PostgrestTwoFactorRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestTwoFactorRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepository for PostgrestTwoFactorRepository {
+    async fn find(&self, user_id: Uuid) -> Result<Option<TwoFactorSecret>> {
+        fetch_optional(
+            self.db
+                .from("two_factor_secrets")
+                .select("*")
+                .eq("user_id", user_id.to_string()),
+        )
+        .await
+    }
+
+    async fn save(&self, secret: TwoFactorSecret) -> Result<()> {
+        fetch_rows::<TwoFactorSecret>(
+            self.db
+                .from("two_factor_secrets")
+                .upsert(to_body(&secret)?)
+                .on_conflict("user_id"),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn confirm(&self, user_id: Uuid, confirmed_at: DateTime<Utc>) -> Result<()> {
+        fetch_rows::<TwoFactorSecret>(
+            self.db
+                .from("two_factor_secrets")
+                .eq("user_id", user_id.to_string())
+                .update(json!({ "confirmed_at": confirmed_at }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn use_step(&self, user_id: Uuid, step: i64) -> Result<bool> {
+        let rows: Vec<TwoFactorSecret> = fetch_rows(
+            self.db
+                .from("two_factor_secrets")
+                .eq("user_id", user_id.to_string())
+                .or(format!("last_used_step.is.null,last_used_step.lt.{}", step))
+                .update(json!({ "last_used_step": step }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    async fn delete(&self, user_id: Uuid) -> Result<()> {
+        fetch_rows::<RecoveryCode>(
+            self.db
+                .from("recovery_codes")
+                .eq("user_id", user_id.to_string())
+                .delete(),
+        )
+        .await?;
+        fetch_rows::<TwoFactorSecret>(
+            self.db
+                .from("two_factor_secrets")
+                .eq("user_id", user_id.to_string())
+                .delete(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(&self, user_id: Uuid, codes: Vec<RecoveryCode>) -> Result<()> {
+        fetch_rows::<RecoveryCode>(
+            self.db
+                .from("recovery_codes")
+                .eq("user_id", user_id.to_string())
+                .delete(),
+        )
+        .await?;
+        fetch_rows::<RecoveryCode>(self.db.from("recovery_codes").insert(to_body(&codes)?)).await?;
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool> {
+        let rows: Vec<RecoveryCode> = fetch_rows(
+            self.db
+                .from("recovery_codes")
+                .eq("user_id", user_id.to_string())
+                .eq("code_hash", code_hash)
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestTwoFactorChallengeRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestTwoFactorChallengeRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl TwoFactorChallengeRepository for PostgrestTwoFactorChallengeRepository {
+    async fn create(&self, challenge: TwoFactorChallenge) -> Result<TwoFactorChallenge> {
+        first_row(
+            fetch_rows(
+                self.db
+                    .from("two_factor_challenges")
+                    .insert(to_body(&challenge)?),
+            )
+            .await?,
+        )
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<TwoFactorChallenge>> {
+        fetch_optional(
+            self.db
+                .from("two_factor_challenges")
+                .select("*")
+                .eq("token_hash", token_hash),
+        )
+        .await
+    }
+
+    /// PostgREST cannot increment in place, so this is a compare-and-set on
+    /// the count that was read, retried until it wins.
+    async fn record_failure(&self, id: Uuid) -> Result<i32> {
+        loop {
+            let Some(current) = fetch_optional::<TwoFactorChallenge>(
+                self.db
+                    .from("two_factor_challenges")
+                    .select("*")
+                    .eq("id", id.to_string()),
+            )
+            .await?
+            else {
+                return Ok(0);
+            };
+
+            let failed_attempts = current.failed_attempts + 1;
+            let rows: Vec<TwoFactorChallenge> = fetch_rows(
+                self.db
+                    .from("two_factor_challenges")
+                    .eq("id", id.to_string())
+                    .eq("failed_attempts", current.failed_attempts.to_string())
+                    .update(json!({ "failed_attempts": failed_attempts }).to_string()),
+            )
+            .await?;
+            if !rows.is_empty() {
+                return Ok(failed_attempts);
+            }
+        }
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<TwoFactorChallenge> = fetch_rows(
+            self.db
+                .from("two_factor_challenges")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestPasskeyRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestPasskeyRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl PasskeyRepository for PostgrestPasskeyRepository {
+    async fn create(&self, passkey: Passkey) -> Result<Passkey> {
+        first_row(fetch_rows(self.db.from("passkeys").insert(to_body(&passkey)?)).await?)
+    }
+
+    async fn find_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>> {
+        fetch_optional(
+            self.db
+                .from("passkeys")
+                .select("*")
+                .eq("credential_id", credential_id),
+        )
+        .await
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<Passkey>> {
+        Ok(fetch_rows(
+            self.db
+                .from("passkeys")
+                .select("*")
+                .eq("user_id", user_id.to_string())
+                .order("created_at.asc"),
+        )
+        .await?)
+    }
+
+    async fn record_use(&self, id: Uuid, previous: i64, sign_count: i64) -> Result<bool> {
+        let rows: Vec<Passkey> = fetch_rows(
+            self.db
+                .from("passkeys")
+                .eq("id", id.to_string())
+                .eq("sign_count", previous.to_string())
+                .update(
+                    json!({ "sign_count": sign_count, "last_used_at": Utc::now() }).to_string(),
+                ),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool> {
+        let rows: Vec<Passkey> = fetch_rows(
+            self.db
+                .from("passkeys")
+                .eq("id", id.to_string())
+                .eq("user_id", user_id.to_string())
+                .delete(),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestPersonalAccessTokenRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestPersonalAccessTokenRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl PersonalAccessTokenRepository for PostgrestPersonalAccessTokenRepository {
+    async fn create(&self, token: PersonalAccessToken) -> Result<PersonalAccessToken> {
+        first_row(
+            fetch_rows(
+                self.db
+                    .from("personal_access_tokens")
+                    .insert(to_body(&token)?),
+            )
+            .await?,
+        )
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<PersonalAccessToken>> {
+        fetch_optional(
+            self.db
+                .from("personal_access_tokens")
+                .select("*")
+                .eq("token_hash", token_hash),
+        )
+        .await
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<PersonalAccessToken>> {
+        Ok(fetch_rows(
+            self.db
+                .from("personal_access_tokens")
+                .select("*")
+                .eq("user_id", user_id.to_string())
+                .order("created_at.asc"),
+        )
+        .await?)
+    }
+
+    async fn record_use(&self, id: Uuid, used_at: DateTime<Utc>) -> Result<()> {
+        fetch_rows::<PersonalAccessToken>(
+            self.db
+                .from("personal_access_tokens")
+                .eq("id", id.to_string())
+                .update(json!({ "last_used_at": used_at }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: Uuid, id: Uuid) -> Result<bool> {
+        let rows: Vec<PersonalAccessToken> = fetch_rows(
+            self.db
+                .from("personal_access_tokens")
+                .eq("id", id.to_string())
+                .eq("user_id", user_id.to_string())
+                .delete(),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestPasskeyChallengeRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestPasskeyChallengeRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl PasskeyChallengeRepository for PostgrestPasskeyChallengeRepository {
+    async fn create(&self, challenge: PasskeyChallenge) -> Result<PasskeyChallenge> {
+        first_row(
+            fetch_rows(
+                self.db
+                    .from("passkey_challenges")
+                    .insert(to_body(&challenge)?),
+            )
+            .await?,
+        )
+    }
+
+    async fn find_by_hash(&self, challenge_hash: &str) -> Result<Option<PasskeyChallenge>> {
+        fetch_optional(
+            self.db
+                .from("passkey_challenges")
+                .select("*")
+                .eq("challenge_hash", challenge_hash),
+        )
+        .await
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<PasskeyChallenge> = fetch_rows(
+            self.db
+                .from("passkey_challenges")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestLoginThrottleRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestLoginThrottleRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl LoginThrottleRepository for PostgrestLoginThrottleRepository {
+    async fn find(&self, key: &str) -> Result<Option<LoginThrottle>> {
+        fetch_optional(self.db.from("login_throttles").select("*").eq("key", key)).await
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+        window: Duration,
+    ) -> Result<LoginThrottle> {
+        loop {
+            let Some(current) = self.find(key).await? else {
+                let throttle = LoginThrottle {
+                    key: key.to_string(),
+                    failed_attempts: 1,
+                    last_failure_at: now,
+                    blocked_until: None,
+                    locked: false,
+                };
+                match fetch_rows(self.db.from("login_throttles").insert(to_body(&throttle)?)).await
+                {
+                    Ok(rows) => return first_row(rows),
+                    // Someone else recorded the first failure; count on top of it.
+                    Err(QueryError::Conflict) => continue,
+                    Err(err) => return Err(err.into()),
+                }
+            };
+
+            let failed_attempts = if current.last_failure_at < now - window {
+                1
+            } else {
+                current.failed_attempts + 1
+            };
+            let rows: Vec<LoginThrottle> = fetch_rows(
+                self.db
+                    .from("login_throttles")
+                    .eq("key", key)
+                    .eq("failed_attempts", current.failed_attempts.to_string())
+                    .update(
+                        json!({ "failed_attempts": failed_attempts, "last_failure_at": now })
+                            .to_string(),
+                    ),
+            )
+            .await?;
+            if let Some(throttle) = rows.into_iter().next() {
+                return Ok(throttle);
+            }
+        }
+    }
+
+    async fn block(&self, key: &str, until: DateTime<Utc>, locked: bool) -> Result<()> {
+        let mut update = json!({ "blocked_until": until, "locked": locked });
+        if locked {
+            update["failed_attempts"] = json!(0);
+        }
+        fetch_rows::<LoginThrottle>(
+            self.db
+                .from("login_throttles")
+                .eq("key", key)
+                .update(update.to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<()> {
+        fetch_rows::<LoginThrottle>(self.db.from("login_throttles").eq("key", key).delete())
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub struct PostgrestAccountUnlockRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestAccountUnlockRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl AccountUnlockRepository for PostgrestAccountUnlockRepository {
+    async fn create(&self, token: AccountUnlockToken) -> Result<AccountUnlockToken> {
+        first_row(
+            fetch_rows(
+                self.db
+                    .from("account_unlock_tokens")
+                    .insert(to_body(&token)?),
+            )
+            .await?,
+        )
+    }
+
+    async fn find_by_hash(&self, token_hash: &str) -> Result<Option<AccountUnlockToken>> {
+        fetch_optional(
+            self.db
+                .from("account_unlock_tokens")
+                .select("*")
+                .eq("token_hash", token_hash),
+        )
+        .await
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<AccountUnlockToken> = fetch_rows(
+            self.db
+                .from("account_unlock_tokens")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestOidcLoginStateRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestOidcLoginStateRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl OidcLoginStateRepository for PostgrestOidcLoginStateRepository {
+    async fn create(&self, state: OidcLoginState) -> Result<OidcLoginState> {
+        first_row(fetch_rows(self.db.from("oidc_login_states").insert(to_body(&state)?)).await?)
+    }
+
+    async fn find_by_hash(&self, state_hash: &str) -> Result<Option<OidcLoginState>> {
+        fetch_optional(
+            self.db
+                .from("oidc_login_states")
+                .select("*")
+                .eq("state_hash", state_hash),
+        )
+        .await
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<OidcLoginState> = fetch_rows(
+            self.db
+                .from("oidc_login_states")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestOidcIdentityRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestOidcIdentityRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl OidcIdentityRepository for PostgrestOidcIdentityRepository {
+    async fn find(&self, provider: &str, subject: &str) -> Result<Option<OidcIdentity>> {
+        fetch_optional(
+            self.db
+                .from("oidc_identities")
+                .select("*")
+                .eq("provider", provider)
+                .eq("subject", subject),
+        )
+        .await
+    }
+
+    async fn create(&self, identity: OidcIdentity) -> Result<OidcIdentity> {
+        first_row(fetch_rows(self.db.from("oidc_identities").insert(to_body(&identity)?)).await?)
+    }
+
+    async fn record_login(&self, id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        let _: Vec<OidcIdentity> = fetch_rows(
+            self.db
+                .from("oidc_identities")
+                .eq("id", id.to_string())
+                .update(json!({ "last_login_at": at }).to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<OidcIdentity>> {
+        Ok(fetch_rows(
+            self.db
+                .from("oidc_identities")
+                .select("*")
+                .eq("user_id", user_id.to_string())
+                .order("created_at.asc"),
+        )
+        .await?)
+    }
+}
+
+pub struct PostgrestOAuthClientRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestOAuthClientRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl OAuthClientRepository for PostgrestOAuthClientRepository {
+    async fn create(&self, client: OAuthClient) -> Result<OAuthClient> {
+        first_row(fetch_rows(self.db.from("oauth_clients").insert(to_body(&client)?)).await?)
+    }
+
+    async fn find(&self, id: Uuid) -> Result<Option<OAuthClient>> {
+        fetch_optional(
+            self.db
+                .from("oauth_clients")
+                .select("*")
+                .eq("id", id.to_string()),
+        )
+        .await
+    }
+
+    async fn list_for_owner(&self, owner_id: Uuid) -> Result<Vec<OAuthClient>> {
+        Ok(fetch_rows(
+            self.db
+                .from("oauth_clients")
+                .select("*")
+                .eq("owner_id", owner_id.to_string())
+                .order("created_at.asc"),
+        )
+        .await?)
+    }
+
+    async fn delete(&self, owner_id: Uuid, id: Uuid) -> Result<bool> {
+        let rows: Vec<OAuthClient> = fetch_rows(
+            self.db
+                .from("oauth_clients")
+                .eq("id", id.to_string())
+                .eq("owner_id", owner_id.to_string())
+                .delete(),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestOAuthAuthorizationCodeRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestOAuthAuthorizationCodeRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl OAuthAuthorizationCodeRepository for PostgrestOAuthAuthorizationCodeRepository {
+    async fn create(&self, code: OAuthAuthorizationCode) -> Result<OAuthAuthorizationCode> {
+        first_row(
+            fetch_rows(
+                self.db
+                    .from("oauth_authorization_codes")
+                    .insert(to_body(&code)?),
+            )
+            .await?,
+        )
+    }
+
+    async fn find_by_hash(&self, code_hash: &str) -> Result<Option<OAuthAuthorizationCode>> {
+        fetch_optional(
+            self.db
+                .from("oauth_authorization_codes")
+                .select("*")
+                .eq("code_hash", code_hash),
+        )
+        .await
+    }
+
+    async fn mark_used(&self, id: Uuid) -> Result<bool> {
+        let rows: Vec<OAuthAuthorizationCode> = fetch_rows(
+            self.db
+                .from("oauth_authorization_codes")
+                .eq("id", id.to_string())
+                .is("used_at", "null")
+                .update(json!({ "used_at": Utc::now() }).to_string()),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestOAuthConsentRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestOAuthConsentRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl OAuthConsentRepository for PostgrestOAuthConsentRepository {
+    async fn find(&self, user_id: Uuid, client_id: Uuid) -> Result<Option<OAuthConsent>> {
+        fetch_optional(
+            self.db
+                .from("oauth_consents")
+                .select("*")
+                .eq("user_id", user_id.to_string())
+                .eq("client_id", client_id.to_string()),
+        )
+        .await
+    }
+
+    async fn upsert(&self, consent: OAuthConsent) -> Result<()> {
+        fetch_rows::<OAuthConsent>(
+            self.db
+                .from("oauth_consents")
+                .upsert(to_body(&consent)?)
+                .on_conflict("user_id,client_id"),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list_for_user(&self, user_id: Uuid) -> Result<Vec<OAuthConsent>> {
+        Ok(fetch_rows(
+            self.db
+                .from("oauth_consents")
+                .select("*")
+                .eq("user_id", user_id.to_string())
+                .order("created_at.asc"),
+        )
+        .await?)
+    }
+
+    async fn delete(&self, user_id: Uuid, client_id: Uuid) -> Result<bool> {
+        let rows: Vec<OAuthConsent> = fetch_rows(
+            self.db
+                .from("oauth_consents")
+                .eq("user_id", user_id.to_string())
+                .eq("client_id", client_id.to_string())
+                .delete(),
+        )
+        .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
+
+pub struct PostgrestRevocationRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestRevocationRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl RevocationRepository for PostgrestRevocationRepository {
+    async fn revoke_token(&self, revoked: RevokedToken) -> Result<()> {
+        fetch_rows::<RevokedToken>(
+            self.db
+                .from("revoked_tokens")
+                .upsert(to_body(&revoked)?)
+                .on_conflict("jti"),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: Uuid) -> Result<bool> {
+        let revoked: Option<RevokedToken> = fetch_optional(
+            self.db
+                .from("revoked_tokens")
+                .select("*")
+                .eq("jti", jti.to_string()),
+        )
+        .await?;
+
+        Ok(revoked.is_some())
+    }
+
+    async fn revoke_user(&self, revocation: UserRevocation) -> Result<()> {
+        fetch_rows::<UserRevocation>(
+            self.db
+                .from("user_token_revocations")
+                .upsert(to_body(&revocation)?)
+                .on_conflict("user_id"),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_user_revocation(&self, user_id: Uuid) -> Result<Option<UserRevocation>> {
+        fetch_optional(
+            self.db
+                .from("user_token_revocations")
+                .select("*")
+                .eq("user_id", user_id.to_string()),
+        )
+        .await
+    }
+
+    async fn list_tokens(&self, now: DateTime<Utc>) -> Result<Vec<RevokedToken>> {
+        Ok(fetch_rows(
+            self.db
+                .from("revoked_tokens")
+                .select("*")
+                .gt("expires_at", now.to_rfc3339()),
+        )
+        .await?)
+    }
+
+    async fn list_users(&self, since: DateTime<Utc>) -> Result<Vec<UserRevocation>> {
+        Ok(fetch_rows(
+            self.db
+                .from("user_token_revocations")
+                .select("*")
+                .gt("revoked_before", since.to_rfc3339()),
+        )
+        .await?)
+    }
+}
+
+pub struct PostgrestAuditLogRepository {
+    db: SupabaseClient,
+}
+
+impl PostgrestAuditLogRepository {
+    pub fn new(db: SupabaseClient) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl AuditLogRepository for PostgrestAuditLogRepository {
+    async fn append(&self, event: AuditEvent) -> Result<()> {
+        fetch_rows::<AuditEvent>(self.db.from("audit_events").insert(to_body(&event)?)).await?;
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditLogQuery, limit: usize) -> Result<Vec<AuditEvent>> {
+        let mut builder = self.db.from("audit_events").select("*");
+        if let Some(user_id) = query.user_id {
+            builder = builder.eq("user_id", user_id.to_string());
+        }
+        if let Some(event_type) = query.event_type {
+            builder = builder.eq("event_type", event_type.as_str());
+        }
+        if let Some(from) = query.from {
+            builder = builder.gte("created_at", from.to_rfc3339());
+        }
+        if let Some(to) = query.to {
+            builder = builder.lt("created_at", to.to_rfc3339());
+        }
+
+        Ok(fetch_rows(builder.order("created_at.desc").limit(limit)).await?)
+    }
+
+    async fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let rows: Vec<serde_json::Value> = fetch_rows(
+            self.db
+                .from("audit_events")
+                .select("id")
+                .lt("created_at", cutoff.to_rfc3339())
+                .delete(),
+        )
+        .await?;
+
+        Ok(rows.len() as u64)
+    }
+}
@@ -1,95 +1,4785 @@
-use bcrypt::{hash, verify, DEFAULT_COST};
-use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{errors::ErrorKind, jwk::JwkSet};
+use serde_json::json;
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 
 use crate::{
     error::{AuthError, Result},
-    models::{Claims, LoginRequest, RegisterRequest, User, UserResponse},
+    keys::KeySet,
+    mailer::{Email, Mailer},
+    models::{
+        AccountExport, AccountUnlockToken, Actor, AuditEvent, AuditEventType, AuditLogQuery,
+        AuthResponse, AuthorizationDecision, AuthorizationDecisionResponse,
+        AuthorizationPreviewResponse, AuthorizationRequest, Claims, ClientInfo,
+        CreatePersonalAccessTokenRequest, EmailVerificationToken, FinishPasskeyRegistrationRequest,
+        ImpersonationResponse, IntrospectionResponse, LoginRequest, LoginResponse,
+        MagicLinkResponse, MagicLinkToken, OAuthAuthorizationCode, OAuthClient,
+        OAuthClientResponse, OAuthConsent, OAuthConsentResponse, OAuthTokenResponse,
+        OidcAuthorizationResponse, OidcCallbackRequest, OidcIdentity, OidcLoginState,
+        OidcProviderResponse, Passkey, PasskeyCeremony, PasskeyChallenge, PasskeyResponse,
+        PasswordResetToken, PersonalAccessToken, PersonalAccessTokenIdentity,
+        PersonalAccessTokenResponse, RecoveryCode, RecoveryCodesResponse, RefreshToken,
+        RegisterOAuthClientRequest, RegisterRequest, RevocationListResponse, RevokedToken, Session,
+        SessionResponse, TokenRequest, TwoFactorChallenge, TwoFactorChallengeResponse,
+        TwoFactorSecret, TwoFactorSetupResponse, User, UserRevocation,
+    },
+    oauth,
+    oidc::{self, IdTokenClaims, OidcError, Provider},
+    password::{PasswordCheck, PasswordHasher},
+    password_policy::PasswordPolicy,
+    rbac::{self, Role, Scope},
+    repositories::Repositories,
+    throttle::LoginThrottling,
+    tokens::{generate_opaque_token, hash_token},
+    totp,
+    webauthn::{self, AuthenticationCredential, ClientData, RelyingParty},
 };
 
+const DEFAULT_ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+const DEFAULT_REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+const DEFAULT_PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+const DEFAULT_EMAIL_VERIFICATION_TTL_HOURS: i64 = 24;
+const DEFAULT_MAGIC_LINK_TTL_MINUTES: i64 = 15;
+const DEFAULT_IMPERSONATION_TTL_MINUTES: i64 = 15;
+const DEFAULT_APP_URL: &str = "http://localhost:5173";
+const DEFAULT_TOTP_ISSUER: &str = "Blog";
+
+/// How long a user has to enter their code after the password step.
+const TWO_FACTOR_CHALLENGE_TTL_MINUTES: i64 = 5;
+
+/// Wrong codes allowed per password step before the password is asked again.
+const MAX_TWO_FACTOR_ATTEMPTS: i32 = 5;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// How long a user has to finish signing in at an identity provider.
+const OIDC_LOGIN_TTL_MINUTES: i64 = 10;
+
+/// Lets the gateway tell personal access tokens from JWTs without parsing
+/// them, and makes leaked tokens easy to scan for.
+const ACCESS_TOKEN_PREFIX: &str = "blog_pat_";
+const DEFAULT_ACCESS_TOKEN_TTL_DAYS: i64 = 90;
+const MAX_ACCESS_TOKEN_TTL_DAYS: i64 = 365;
+const MAX_ACCESS_TOKEN_NAME_LEN: usize = 100;
+
+/// How long a client has to exchange an authorization code.
+const OAUTH_CODE_TTL_SECONDS: i64 = 60;
+const MAX_OAUTH_CLIENT_NAME_LEN: usize = 100;
+
+/// Audit events older than this are pruned. 0 keeps them forever.
+const DEFAULT_AUDIT_LOG_RETENTION_DAYS: i64 = 365;
+const DEFAULT_AUDIT_LOG_LIMIT: usize = 100;
+const MAX_AUDIT_LOG_LIMIT: usize = 1000;
+
+/// Roles given to new accounts: members can comment and write their own posts.
+const DEFAULT_ROLES: &[Role] = &[Role::Author];
+
 pub struct AuthService {
-    jwt_secret: String,
+    keys: KeySet,
+    access_token_ttl: Duration,
+    refresh_token_ttl: Duration,
+    password_reset_ttl: Duration,
+    email_verification_ttl: Duration,
+    magic_link_ttl: Duration,
+    impersonation_ttl: Duration,
+    /// `None` keeps audit events forever.
+    audit_log_retention: Option<Duration>,
+    /// Frontend origin that links in emails point to.
+    app_url: String,
+    /// Account issuer shown in authenticator apps.
+    totp_issuer: String,
+    relying_party: RelyingParty,
+    identity_providers: oidc::Providers,
+    login_throttling: LoginThrottling,
+    passwords: Arc<PasswordHasher>,
+    password_policy: PasswordPolicy,
+    repos: Repositories,
+    mailer: Arc<dyn Mailer>,
 }
 
 impl AuthService {
-    pub fn new() -> Self {
-        let jwt_secret = std::env::var("JWT_SECRET")
-            .unwrap_or_else(|_| "your-secret-key".to_string());
-        Self { jwt_secret }
+    pub fn new(repos: Repositories, keys: KeySet, mailer: Arc<dyn Mailer>) -> Self {
+        let access_token_ttl = Duration::minutes(env_i64(
+            "ACCESS_TOKEN_TTL_MINUTES",
+            DEFAULT_ACCESS_TOKEN_TTL_MINUTES,
+        ));
+        let refresh_token_ttl = Duration::days(env_i64(
+            "REFRESH_TOKEN_TTL_DAYS",
+            DEFAULT_REFRESH_TOKEN_TTL_DAYS,
+        ));
+        let password_reset_ttl = Duration::minutes(env_i64(
+            "PASSWORD_RESET_TTL_MINUTES",
+            DEFAULT_PASSWORD_RESET_TTL_MINUTES,
+        ));
+        let email_verification_ttl = Duration::hours(env_i64(
+            "EMAIL_VERIFICATION_TTL_HOURS",
+            DEFAULT_EMAIL_VERIFICATION_TTL_HOURS,
+        ));
+        let magic_link_ttl = Duration::minutes(env_i64(
+            "MAGIC_LINK_TTL_MINUTES",
+            DEFAULT_MAGIC_LINK_TTL_MINUTES,
+        ));
+        let impersonation_ttl = Duration::minutes(env_i64(
+            "IMPERSONATION_TTL_MINUTES",
+            DEFAULT_IMPERSONATION_TTL_MINUTES,
+        ));
+        let audit_log_retention =
+            match env_i64("AUDIT_LOG_RETENTION_DAYS", DEFAULT_AUDIT_LOG_RETENTION_DAYS) {
+                days if days > 0 => Some(Duration::days(days)),
+                _ => None,
+            };
+        let app_url = std::env::var("APP_URL")
+            .unwrap_or_else(|_| DEFAULT_APP_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let relying_party = RelyingParty::from_env(&app_url);
+        let identity_providers =
+            oidc::Providers::from_env(&app_url).expect("Failed to configure identity providers");
+        let totp_issuer =
+            std::env::var("TOTP_ISSUER").unwrap_or_else(|_| DEFAULT_TOTP_ISSUER.to_string());
+
+        Self {
+            keys,
+            access_token_ttl,
+            refresh_token_ttl,
+            password_reset_ttl,
+            email_verification_ttl,
+            magic_link_ttl,
+            impersonation_ttl,
+            audit_log_retention,
+            app_url,
+            totp_issuer,
+            relying_party,
+            identity_providers,
+            login_throttling: LoginThrottling::from_env(),
+            passwords: Arc::new(
+                PasswordHasher::from_env().expect("Failed to configure password hashing"),
+            ),
+            password_policy: PasswordPolicy::from_env()
+                .expect("Failed to configure password policy"),
+            repos,
+            mailer,
+        }
     }
 
-    pub async fn register(&self, req: RegisterRequest) -> Result<(String, UserResponse)> {
-        // TODO: Check if user exists in database
-        // This is a mock implementation
-        let password_hash = hash(req.password.as_bytes(), DEFAULT_COST)
-            .map_err(|e| AuthError::Internal(anyhow::anyhow!("Failed to hash password: {}", e)))?;
+    pub async fn register(
+        &self,
+        req: RegisterRequest,
+        client: &ClientInfo,
+    ) -> Result<AuthResponse> {
+        let email = normalize_email(&req.email);
+        let username = req.username.trim().to_string();
+        if email.is_empty() || username.is_empty() {
+            return Err(AuthError::BadRequest(
+                "Email and username are required".to_string(),
+            ));
+        }
+        self.check_password_policy(&req.password, &username, &email)
+            .await?;
+
+        if self.repos.users.find_by_email(&email).await?.is_some()
+            || self
+                .repos
+                .users
+                .find_by_username(&username)
+                .await?
+                .is_some()
+        {
+            return Err(AuthError::UserExists);
+        }
+
+        let password_hash = self.hash_password(&req.password).await?;
 
         let user = User {
             id: Uuid::new_v4(),
-            email: req.email.clone(),
-            username: req.username,
+            email,
+            username,
             password_hash,
             display_name: None,
             bio: None,
             avatar_url: None,
+            roles: DEFAULT_ROLES.to_vec(),
+            email_verified_at: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
+        let user = self.repos.users.create(user).await?;
+        self.audit(AuditEventType::Register, Some(user.id), client, json!({}))
+            .await;
+        self.send_verification_email(&user).await?;
+
+        self.issue_tokens(user, Uuid::new_v4(), client).await
+    }
+
+    /// Checks the password. Accounts with 2FA get a short-lived
+    /// `two_factor_token` to exchange at [`Self::verify_two_factor`] instead
+    /// of a token pair. Failures of either factor are counted per account
+    /// and per client address; see `LoginThrottling` for how they slow
+    /// further attempts. The count is cleared once a login completes.
+    pub async fn login(&self, req: LoginRequest, client: &ClientInfo) -> Result<LoginResponse> {
+        let email = normalize_email(&req.email);
+        self.check_login_throttle(&email, client.ip).await?;
+
+        let Some(user) = self.repos.users.find_by_email(&email).await? else {
+            let password = req.password;
+            self.with_hasher(move |passwords| passwords.verify_dummy(&password))
+                .await?;
+            return self.reject_login(&email, None, client).await;
+        };
+        match self.check_password(&user, &req.password).await {
+            Ok(()) => {}
+            Err(AuthError::InvalidCredentials) => {
+                return self.reject_login(&email, Some(user.id), client).await;
+            }
+            Err(err) => return Err(err),
+        }
+
+        let response = self.complete_first_factor(user, client).await?;
+        // With 2FA, the password alone must not reset the count, or each new
+        // challenge would bring a fresh set of guesses at the code.
+        if let LoginResponse::Authenticated(_) = response {
+            self.repos
+                .login_throttles
+                .clear(&account_throttle_key(&email))
+                .await?;
+        }
+        Ok(response)
+    }
+
+    /// Records a wrong email or password against the account and the client
+    /// address, then refuses the login.
+    async fn reject_login(
+        &self,
+        email: &str,
+        user_id: Option<Uuid>,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse> {
+        self.audit(
+            AuditEventType::LoginFailed,
+            user_id,
+            client,
+            json!({ "email": email, "reason": "invalid_credentials" }),
+        )
+        .await;
+        self.record_login_failure(email, client.ip).await?;
+        Err(AuthError::InvalidCredentials)
+    }
+
+    /// Refuses the attempt while the account or the client address is
+    /// backing off or locked, without looking at the password.
+    async fn check_login_throttle(&self, email: &str, client_ip: Option<IpAddr>) -> Result<()> {
+        let now = Utc::now();
+        let keys =
+            std::iter::once(account_throttle_key(email)).chain(client_ip.map(client_throttle_key));
+
+        // A lockout wins over a backoff, then the longest wait.
+        let mut blocked = None;
+        for key in keys {
+            if let Some(throttle) = self.repos.login_throttles.find(&key).await? {
+                if let Some(until) = throttle.blocked_until.filter(|until| *until > now) {
+                    blocked = blocked.max(Some((throttle.locked, until)));
+                }
+            }
+        }
+
+        match blocked {
+            Some((locked, until)) => Err(login_throttled(until - now, locked)),
+            None => Ok(()),
+        }
+    }
+
+    async fn record_login_failure(&self, email: &str, client_ip: Option<IpAddr>) -> Result<()> {
+        let now = Utc::now();
+        let policy = &self.login_throttling;
+
+        if let Some(ip) = client_ip {
+            let key = client_throttle_key(ip);
+            let client = self
+                .repos
+                .login_throttles
+                .record_failure(&key, now, policy.window)
+                .await?;
+            if let Some(delay) = policy.client.delay(client.failed_attempts) {
+                self.repos
+                    .login_throttles
+                    .block(&key, now + delay, false)
+                    .await?;
+            }
+        }
+
+        let key = account_throttle_key(email);
+        let account = self
+            .repos
+            .login_throttles
+            .record_failure(&key, now, policy.window)
+            .await?;
+        if account.failed_attempts >= policy.lockout_threshold {
+            let until = now + policy.lockout;
+            self.repos.login_throttles.block(&key, until, true).await?;
+            tracing::warn!(email, "Account locked after repeated failed logins");
+            if let Some(user) = self.repos.users.find_by_email(email).await? {
+                self.send_unlock_email(&user, until).await?;
+            }
+            return Err(login_throttled(policy.lockout, true));
+        }
+        if let Some(delay) = policy.account.delay(account.failed_attempts) {
+            self.repos
+                .login_throttles
+                .block(&key, now + delay, false)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_unlock_email(&self, user: &User, locked_until: DateTime<Utc>) -> Result<()> {
+        let token = generate_opaque_token();
+        let now = Utc::now();
+        self.repos
+            .account_unlocks
+            .create(AccountUnlockToken {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                token_hash: hash_token(&token),
+                expires_at: locked_until,
+                created_at: now,
+                used_at: None,
+            })
+            .await?;
+
+        self.send_in_background(Email {
+            to: user.email.clone(),
+            subject: "Your account has been locked".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                 We locked your account after several failed sign-in attempts. \
+                 It unlocks by itself in {} minutes, or you can unlock it now:\n\n\
+                 {}/unlock-account?token={}\n\n\
+                 If these attempts weren't you, consider changing your password.",
+                user.username,
+                self.login_throttling.lockout.num_minutes(),
+                self.app_url,
+                token
+            ),
+        });
+
+        Ok(())
+    }
+
+    /// Lifts a lockout with the link from the lockout email.
+    pub async fn unlock_account(&self, token: &str, client: &ClientInfo) -> Result<()> {
+        let stored = self
+            .repos
+            .account_unlocks
+            .find_by_hash(&hash_token(token))
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+
+        if stored.used_at.is_some() {
+            return Err(AuthError::InvalidToken);
+        }
+        if stored.expires_at <= Utc::now() {
+            return Err(AuthError::TokenExpired);
+        }
+        if !self.repos.account_unlocks.mark_used(stored.id).await? {
+            return Err(AuthError::InvalidToken);
+        }
+
+        self.clear_account_throttle(stored.user_id).await?;
+        self.audit(
+            AuditEventType::AccountUnlocked,
+            Some(stored.user_id),
+            client,
+            json!({ "method": "email" }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Lifts a lockout on an admin's behalf.
+    pub async fn unlock_user(
+        &self,
+        admin_id: Uuid,
+        user_id: Uuid,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        self.clear_account_throttle(user_id).await?;
+        self.audit(
+            AuditEventType::AccountUnlocked,
+            Some(user_id),
+            client,
+            json!({ "method": "admin", "actor_id": admin_id }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Clears the account's failed logins and any backoff or lockout.
+    /// Limits on the client addresses involved stay in place.
+    async fn clear_account_throttle(&self, user_id: Uuid) -> Result<()> {
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+
+        self.repos
+            .login_throttles
+            .clear(&account_throttle_key(&user.email))
+            .await
+    }
+
+    /// Signs in a user whose first factor checked out, or asks for their
+    /// TOTP code when 2FA is enabled.
+    async fn complete_first_factor(
+        &self,
+        user: User,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse> {
+        if self.confirmed_two_factor(user.id).await?.is_some() {
+            return self
+                .create_two_factor_challenge(user.id)
+                .await
+                .map(LoginResponse::TwoFactorRequired);
+        }
+
+        self.issue_tokens(user, Uuid::new_v4(), client)
+            .await
+            .map(|response| LoginResponse::Authenticated(Box::new(response)))
+    }
+
+    /// Completes a login that stopped at the 2FA step.
+    pub async fn verify_two_factor(
+        &self,
+        two_factor_token: &str,
+        code: &str,
+        client: &ClientInfo,
+    ) -> Result<AuthResponse> {
+        let challenge = self
+            .repos
+            .two_factor_challenges
+            .find_by_hash(&hash_token(two_factor_token))
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+
+        if challenge.used_at.is_some() {
+            return Err(AuthError::InvalidToken);
+        }
+        if challenge.expires_at <= Utc::now() {
+            return Err(AuthError::TokenExpired);
+        }
+
+        let user = self
+            .repos
+            .users
+            .find_by_id(challenge.user_id)
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+        let secret = self
+            .confirmed_two_factor(user.id)
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+        self.check_login_throttle(&user.email, client.ip).await?;
+
+        if !self.check_second_factor(&secret, code).await? {
+            self.audit(
+                AuditEventType::LoginFailed,
+                Some(user.id),
+                client,
+                json!({ "reason": "invalid_two_factor_code" }),
+            )
+            .await;
+            let failed_attempts = self
+                .repos
+                .two_factor_challenges
+                .record_failure(challenge.id)
+                .await?;
+            if failed_attempts >= MAX_TWO_FACTOR_ATTEMPTS {
+                self.repos
+                    .two_factor_challenges
+                    .mark_used(challenge.id)
+                    .await?;
+            }
+            self.record_login_failure(&user.email, client.ip).await?;
+            return Err(AuthError::InvalidTwoFactorCode);
+        }
+
+        if !self
+            .repos
+            .two_factor_challenges
+            .mark_used(challenge.id)
+            .await?
+        {
+            return Err(AuthError::InvalidToken);
+        }
+        self.repos
+            .login_throttles
+            .clear(&account_throttle_key(&user.email))
+            .await?;
+
+        self.issue_tokens(user, Uuid::new_v4(), client).await
+    }
+
+    /// Starts 2FA enrollment with a new secret. Logins are not affected until
+    /// the user proves their authenticator works with [`Self::confirm_two_factor`].
+    pub async fn setup_two_factor(&self, user_id: Uuid) -> Result<TwoFactorSetupResponse> {
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+
+        if self.confirmed_two_factor(user_id).await?.is_some() {
+            return Err(AuthError::BadRequest(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret = totp::encode_secret(&totp::generate_secret());
+        self.repos
+            .two_factor
+            .save(TwoFactorSecret {
+                user_id,
+                secret: secret.clone(),
+                confirmed_at: None,
+                last_used_step: None,
+                created_at: Utc::now(),
+            })
+            .await?;
+
+        let otpauth_uri = totp::provisioning_uri(&secret, &self.totp_issuer, &user.email);
+        let qr_code_svg = totp::qr_code_svg(&otpauth_uri)?;
+
+        Ok(TwoFactorSetupResponse {
+            secret,
+            otpauth_uri,
+            qr_code_svg,
+        })
+    }
+
+    /// Turns 2FA on once the user enters a code from their authenticator,
+    /// and hands out the first set of recovery codes.
+    pub async fn confirm_two_factor(
+        &self,
+        user_id: Uuid,
+        code: &str,
+        client: &ClientInfo,
+    ) -> Result<RecoveryCodesResponse> {
+        let secret = self.repos.two_factor.find(user_id).await?.ok_or_else(|| {
+            AuthError::BadRequest("Two-factor setup has not been started".to_string())
+        })?;
+
+        if secret.confirmed_at.is_some() {
+            return Err(AuthError::BadRequest(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+        if !self.check_totp(&secret, code).await? {
+            return Err(AuthError::InvalidTwoFactorCode);
+        }
+
+        self.repos.two_factor.confirm(user_id, Utc::now()).await?;
+        self.audit(
+            AuditEventType::TwoFactorEnabled,
+            Some(user_id),
+            client,
+            json!({}),
+        )
+        .await;
+        self.generate_recovery_codes(user_id).await
+    }
+
+    /// Turns 2FA off. Both the password and a second factor are required, so
+    /// a stolen session alone cannot remove it.
+    pub async fn disable_two_factor(
+        &self,
+        user_id: Uuid,
+        password: &str,
+        code: &str,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+        let secret = self.require_two_factor(user_id).await?;
+
+        self.check_password(&user, password).await?;
+        if !self.check_second_factor(&secret, code).await? {
+            return Err(AuthError::InvalidTwoFactorCode);
+        }
+
+        self.repos.two_factor.delete(user_id).await?;
+        self.audit(
+            AuditEventType::TwoFactorDisabled,
+            Some(user_id),
+            client,
+            json!({}),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Replaces every recovery code, used or not, with a new set.
+    pub async fn regenerate_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code: &str,
+        client: &ClientInfo,
+    ) -> Result<RecoveryCodesResponse> {
+        let secret = self.require_two_factor(user_id).await?;
+        if !self.check_totp(&secret, code).await? {
+            return Err(AuthError::InvalidTwoFactorCode);
+        }
+
+        let codes = self.generate_recovery_codes(user_id).await?;
+        self.audit(
+            AuditEventType::RecoveryCodesRegenerated,
+            Some(user_id),
+            client,
+            json!({}),
+        )
+        .await;
+        Ok(codes)
+    }
+
+    pub fn oidc_providers(&self) -> Vec<OidcProviderResponse> {
+        self.identity_providers
+            .list()
+            .into_iter()
+            .map(|provider| OidcProviderResponse {
+                id: provider.id.clone(),
+                name: provider.name.clone(),
+            })
+            .collect()
+    }
+
+    /// Starts a sign-in with an identity provider. The PKCE verifier and
+    /// nonce stay here; the browser only carries `state`.
+    pub async fn start_oidc_login(&self, provider_id: &str) -> Result<OidcAuthorizationResponse> {
+        let provider = self.identity_provider(provider_id)?;
+
+        let state = generate_opaque_token();
+        let nonce = generate_opaque_token();
+        let code_verifier = generate_opaque_token();
+        let authorization_url = provider
+            .authorization_url(&state, &nonce, &oidc::code_challenge(&code_verifier))
+            .await
+            .map_err(|e| oidc_error(&provider, e))?;
+
+        let now = Utc::now();
+        let ttl = Duration::minutes(OIDC_LOGIN_TTL_MINUTES);
+        self.repos
+            .oidc_login_states
+            .create(OidcLoginState {
+                id: Uuid::new_v4(),
+                provider: provider.id.clone(),
+                state_hash: hash_token(&state),
+                nonce,
+                code_verifier,
+                expires_at: now + ttl,
+                created_at: now,
+                used_at: None,
+            })
+            .await?;
+
+        Ok(OidcAuthorizationResponse {
+            authorization_url,
+            state,
+            expires_in: ttl.num_seconds(),
+        })
+    }
+
+    /// Completes a sign-in with the `code` and `state` the provider sent the
+    /// browser back with. 2FA still applies to accounts that have it enabled.
+    pub async fn finish_oidc_login(
+        &self,
+        provider_id: &str,
+        req: OidcCallbackRequest,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse> {
+        let provider = self.identity_provider(provider_id)?;
+
+        let login = self
+            .repos
+            .oidc_login_states
+            .find_by_hash(&hash_token(&req.state))
+            .await?
+            .filter(|login| login.provider == provider.id)
+            .ok_or(AuthError::InvalidToken)?;
+        if login.used_at.is_some() {
+            return Err(AuthError::InvalidToken);
+        }
+        if login.expires_at <= Utc::now() {
+            return Err(AuthError::TokenExpired);
+        }
+        if !self.repos.oidc_login_states.mark_used(login.id).await? {
+            return Err(AuthError::InvalidToken);
+        }
 
-        let token = self.create_token(&user)?;
-        Ok((token, user.into()))
+        let claims = provider
+            .exchange_code(&req.code, &login.code_verifier, &login.nonce)
+            .await
+            .map_err(|e| oidc_error(&provider, e))?;
+        let user = self.oidc_account(&provider, claims).await?;
+
+        self.complete_first_factor(user, client).await
     }
 
-    pub async fn login(&self, req: LoginRequest) -> Result<(String, UserResponse)> {
-        // TODO: Get user from database
-        // This is a mock implementation
-        let stored_password_hash = hash("password123", DEFAULT_COST).unwrap();
-        if !verify(req.password.as_bytes(), &stored_password_hash)
-            .map_err(|e| AuthError::Internal(anyhow::anyhow!("Failed to verify password: {}", e)))?
+    /// Finds the account behind a provider identity. A new identity is
+    /// linked to the account with the same email when the provider has
+    /// verified it, and gets a new account when there is none.
+    async fn oidc_account(&self, provider: &Provider, claims: IdTokenClaims) -> Result<User> {
+        let now = Utc::now();
+        if let Some(identity) = self
+            .repos
+            .oidc_identities
+            .find(&provider.id, &claims.sub)
+            .await?
         {
-            return Err(AuthError::InvalidCredentials);
+            self.repos
+                .oidc_identities
+                .record_login(identity.id, now)
+                .await?;
+            return self
+                .repos
+                .users
+                .find_by_id(identity.user_id)
+                .await?
+                .ok_or(AuthError::InvalidCredentials);
         }
 
-        let user = User {
-            id: Uuid::new_v4(),
-            email: req.email.clone(),
-            username: "mockuser".to_string(),
-            password_hash: stored_password_hash,
-            display_name: None,
-            bio: None,
-            avatar_url: None,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
+        let email = claims
+            .email
+            .as_deref()
+            .map(normalize_email)
+            .filter(|email| !email.is_empty())
+            .ok_or_else(|| {
+                AuthError::BadRequest(
+                    "The identity provider did not share an email address".to_string(),
+                )
+            })?;
+        if !claims.email_verified {
+            return Err(AuthError::BadRequest(
+                "The identity provider has not verified this email address".to_string(),
+            ));
+        }
+
+        let user = match self.repos.users.find_by_email(&email).await? {
+            // Linking to an account whose owner never proved the address
+            // would hand it to whoever registered it first.
+            Some(user) if user.email_verified_at.is_none() => return Err(AuthError::UserExists),
+            Some(user) => user,
+            None => {
+                let username = self
+                    .available_username(claims.preferred_username.as_deref(), &email)
+                    .await?;
+                // The account has no usable password until the user sets
+                // one through the reset flow.
+                let password_hash = self.hash_password(&generate_opaque_token()).await?;
+                self.repos
+                    .users
+                    .create(User {
+                        id: Uuid::new_v4(),
+                        email: email.clone(),
+                        username,
+                        password_hash,
+                        display_name: claims.name.clone(),
+                        bio: None,
+                        avatar_url: claims.picture.clone(),
+                        roles: DEFAULT_ROLES.to_vec(),
+                        email_verified_at: Some(now),
+                        created_at: now,
+                        updated_at: now,
+                    })
+                    .await?
+            }
         };
 
-        let token = self.create_token(&user)?;
-        Ok((token, user.into()))
+        self.repos
+            .oidc_identities
+            .create(OidcIdentity {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                provider: provider.id.clone(),
+                subject: claims.sub,
+                email: Some(email),
+                created_at: now,
+                last_login_at: Some(now),
+            })
+            .await?;
+
+        Ok(user)
     }
 
-    pub fn validate_token(&self, token: &str) -> Result<Claims> {
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
-            &Validation::default(),
+    /// Derives a username from the provider's suggestion or the email's
+    /// local part, adding a number when it is taken.
+    async fn available_username(&self, preferred: Option<&str>, email: &str) -> Result<String> {
+        let base: String = preferred
+            .unwrap_or_else(|| email.split('@').next().unwrap_or_default())
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+            .collect();
+        let base = if base.is_empty() {
+            "user".to_string()
+        } else {
+            base
+        };
+
+        let mut candidate = base.clone();
+        let mut suffix = 1;
+        while self
+            .repos
+            .users
+            .find_by_username(&candidate)
+            .await?
+            .is_some()
+        {
+            suffix += 1;
+            candidate = format!("{}{}", base, suffix);
+        }
+
+        Ok(candidate)
+    }
+
+    fn identity_provider(&self, provider_id: &str) -> Result<Arc<Provider>> {
+        self.identity_providers
+            .get(provider_id)
+            .ok_or(AuthError::ProviderNotFound)
+    }
+
+    /// Options for `navigator.credentials.create()` to add a passkey to the
+    /// signed-in user's account.
+    pub async fn start_passkey_registration(
+        &self,
+        user_id: Uuid,
+    ) -> Result<webauthn::CreationOptions> {
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+        let existing = self.repos.passkeys.list_for_user(user_id).await?;
+        let challenge = self
+            .create_passkey_challenge(Some(user_id), PasskeyCeremony::Registration)
+            .await?;
+
+        Ok(webauthn::CreationOptions {
+            challenge,
+            rp: webauthn::RpEntity {
+                id: self.relying_party.id.clone(),
+                name: self.relying_party.name.clone(),
+            },
+            user: webauthn::UserEntity {
+                id: webauthn::user_handle(user.id),
+                display_name: user.display_name.unwrap_or_else(|| user.username.clone()),
+                name: user.email,
+            },
+            pub_key_cred_params: vec![webauthn::CredentialParameters::es256()],
+            timeout: webauthn::CEREMONY_TIMEOUT_MS,
+            attestation: "none",
+            exclude_credentials: existing
+                .into_iter()
+                .map(|passkey| webauthn::CredentialDescriptor::new(passkey.credential_id))
+                .collect(),
+            authenticator_selection: webauthn::AuthenticatorSelection {
+                resident_key: "required",
+                user_verification: "required",
+            },
+        })
+    }
+
+    pub async fn finish_passkey_registration(
+        &self,
+        user_id: Uuid,
+        req: FinishPasskeyRegistrationRequest,
+    ) -> Result<PasskeyResponse> {
+        let rejected = |reason: &dyn std::fmt::Display| {
+            AuthError::BadRequest(format!("Passkey registration failed: {}", reason))
+        };
+
+        let client_data = ClientData::parse(&req.credential.response.client_data_json)
+            .map_err(|e| rejected(&e))?;
+        self.consume_passkey_challenge(&client_data, PasskeyCeremony::Registration, Some(user_id))
+            .await?;
+        let credential =
+            webauthn::verify_registration(&self.relying_party, &req.credential, &client_data)
+                .map_err(|e| rejected(&e))?;
+
+        if self
+            .repos
+            .passkeys
+            .find_by_credential_id(&credential.credential_id)
+            .await?
+            .is_some()
+        {
+            return Err(AuthError::BadRequest(
+                "Passkey is already registered".to_string(),
+            ));
+        }
+
+        let passkey = self
+            .repos
+            .passkeys
+            .create(Passkey {
+                id: Uuid::new_v4(),
+                user_id,
+                credential_id: credential.credential_id,
+                public_key: credential.public_key,
+                sign_count: credential.sign_count.into(),
+                name: req
+                    .name
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty()),
+                created_at: Utc::now(),
+                last_used_at: None,
+            })
+            .await?;
+
+        Ok(passkey.into())
+    }
+
+    /// Options for `navigator.credentials.get()`. With an email the browser
+    /// is pointed at that account's passkeys. Unknown addresses get the same
+    /// answer as no address, so this does not reveal who has an account.
+    pub async fn start_passkey_login(
+        &self,
+        email: Option<&str>,
+    ) -> Result<webauthn::RequestOptions> {
+        let user = match email {
+            Some(email) => {
+                self.repos
+                    .users
+                    .find_by_email(&normalize_email(email))
+                    .await?
+            }
+            None => None,
+        };
+        let allow_credentials = match &user {
+            Some(user) => self
+                .repos
+                .passkeys
+                .list_for_user(user.id)
+                .await?
+                .into_iter()
+                .map(|passkey| webauthn::CredentialDescriptor::new(passkey.credential_id))
+                .collect(),
+            None => Vec::new(),
+        };
+        let challenge = self
+            .create_passkey_challenge(user.map(|user| user.id), PasskeyCeremony::Authentication)
+            .await?;
+
+        Ok(webauthn::RequestOptions {
+            challenge,
+            rp_id: self.relying_party.id.clone(),
+            timeout: webauthn::CEREMONY_TIMEOUT_MS,
+            allow_credentials,
+            user_verification: "required",
+        })
+    }
+
+    /// Signs in with a passkey assertion. The authenticator has verified the
+    /// user itself, so this does not go through the TOTP step.
+    pub async fn finish_passkey_login(
+        &self,
+        credential: AuthenticationCredential,
+        client: &ClientInfo,
+    ) -> Result<AuthResponse> {
+        let client_data = ClientData::parse(&credential.response.client_data_json)
+            .map_err(|_| AuthError::InvalidCredentials)?;
+        let challenge = self
+            .consume_passkey_challenge(&client_data, PasskeyCeremony::Authentication, None)
+            .await?;
+
+        let passkey = self
+            .repos
+            .passkeys
+            .find_by_credential_id(&credential.id)
+            .await?
+            .ok_or(AuthError::InvalidCredentials)?;
+        if challenge
+            .user_id
+            .is_some_and(|user_id| user_id != passkey.user_id)
+            || credential
+                .response
+                .user_handle
+                .as_deref()
+                .is_some_and(|handle| handle != webauthn::user_handle(passkey.user_id))
+        {
+            return Err(AuthError::InvalidCredentials);
+        }
+
+        let sign_count: i64 = webauthn::verify_authentication(
+            &self.relying_party,
+            &credential,
+            &client_data,
+            &passkey.public_key,
         )
-        .map_err(|_| AuthError::InvalidToken)?;
+        .map_err(|e| {
+            tracing::debug!(passkey_id = %passkey.id, "Passkey assertion rejected: {}", e);
+            AuthError::InvalidCredentials
+        })?
+        .into();
+
+        // Authenticators that don't keep a counter always report zero.
+        if (sign_count != 0 || passkey.sign_count != 0) && sign_count <= passkey.sign_count {
+            tracing::warn!(
+                passkey_id = %passkey.id,
+                user_id = %passkey.user_id,
+                "Passkey signature counter went backwards, possible cloned authenticator"
+            );
+            return Err(AuthError::InvalidCredentials);
+        }
+        if !self
+            .repos
+            .passkeys
+            .record_use(passkey.id, passkey.sign_count, sign_count)
+            .await?
+        {
+            return Err(AuthError::InvalidCredentials);
+        }
 
-        Ok(token_data.claims)
+        let user = self
+            .repos
+            .users
+            .find_by_id(passkey.user_id)
+            .await?
+            .ok_or(AuthError::InvalidCredentials)?;
+
+        self.issue_tokens(user, Uuid::new_v4(), client).await
     }
 
-    fn create_token(&self, user: &User) -> Result<String> {
-        let claims = Claims {
-            sub: user.id,
-            email: user.email.clone(),
-            exp: (Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
+    pub async fn list_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyResponse>> {
+        Ok(self
+            .repos
+            .passkeys
+            .list_for_user(user_id)
+            .await?
+            .into_iter()
+            .map(PasskeyResponse::from)
+            .collect())
+    }
+
+    pub async fn delete_passkey(&self, user_id: Uuid, passkey_id: Uuid) -> Result<()> {
+        if !self.repos.passkeys.delete(user_id, passkey_id).await? {
+            return Err(AuthError::PasskeyNotFound);
+        }
+        Ok(())
+    }
+
+    /// Returns the base64url challenge for the browser; only its hash is kept.
+    async fn create_passkey_challenge(
+        &self,
+        user_id: Option<Uuid>,
+        ceremony: PasskeyCeremony,
+    ) -> Result<String> {
+        let challenge = generate_opaque_token();
+        let now = Utc::now();
+        self.repos
+            .passkey_challenges
+            .create(PasskeyChallenge {
+                id: Uuid::new_v4(),
+                user_id,
+                challenge_hash: hash_token(&challenge),
+                ceremony,
+                expires_at: now + Duration::milliseconds(webauthn::CEREMONY_TIMEOUT_MS as i64),
+                created_at: now,
+                used_at: None,
+            })
+            .await?;
+
+        Ok(challenge)
+    }
+
+    /// Finds the challenge echoed in `client_data` and uses it up, so every
+    /// ceremony response can be submitted only once.
+    async fn consume_passkey_challenge(
+        &self,
+        client_data: &ClientData,
+        ceremony: PasskeyCeremony,
+        user_id: Option<Uuid>,
+    ) -> Result<PasskeyChallenge> {
+        let challenge = self
+            .repos
+            .passkey_challenges
+            .find_by_hash(&hash_token(&client_data.challenge))
+            .await?
+            .filter(|challenge| challenge.ceremony == ceremony)
+            .filter(|challenge| user_id.is_none() || challenge.user_id == user_id)
+            .ok_or(AuthError::InvalidToken)?;
+
+        if challenge.used_at.is_some() {
+            return Err(AuthError::InvalidToken);
+        }
+        if challenge.expires_at <= Utc::now() {
+            return Err(AuthError::TokenExpired);
+        }
+        if !self
+            .repos
+            .passkey_challenges
+            .mark_used(challenge.id)
+            .await?
+        {
+            return Err(AuthError::InvalidToken);
+        }
+
+        Ok(challenge)
+    }
+
+    async fn confirmed_two_factor(&self, user_id: Uuid) -> Result<Option<TwoFactorSecret>> {
+        Ok(self
+            .repos
+            .two_factor
+            .find(user_id)
+            .await?
+            .filter(|secret| secret.confirmed_at.is_some()))
+    }
+
+    async fn require_two_factor(&self, user_id: Uuid) -> Result<TwoFactorSecret> {
+        self.confirmed_two_factor(user_id).await?.ok_or_else(|| {
+            AuthError::BadRequest("Two-factor authentication is not enabled".to_string())
+        })
+    }
+
+    async fn create_two_factor_challenge(
+        &self,
+        user_id: Uuid,
+    ) -> Result<TwoFactorChallengeResponse> {
+        let token = generate_opaque_token();
+        let now = Utc::now();
+        let ttl = Duration::minutes(TWO_FACTOR_CHALLENGE_TTL_MINUTES);
+        self.repos
+            .two_factor_challenges
+            .create(TwoFactorChallenge {
+                id: Uuid::new_v4(),
+                user_id,
+                token_hash: hash_token(&token),
+                expires_at: now + ttl,
+                created_at: now,
+                failed_attempts: 0,
+                used_at: None,
+            })
+            .await?;
+
+        Ok(TwoFactorChallengeResponse {
+            two_factor_required: true,
+            two_factor_token: token,
+            expires_in: ttl.num_seconds(),
+        })
+    }
+
+    /// Accepts either a TOTP code or an unused recovery code.
+    async fn check_second_factor(&self, secret: &TwoFactorSecret, code: &str) -> Result<bool> {
+        if code.trim().bytes().all(|b| b.is_ascii_digit()) {
+            return self.check_totp(secret, code).await;
+        }
+
+        self.repos
+            .two_factor
+            .use_recovery_code(secret.user_id, &hash_token(&normalize_recovery_code(code)))
+            .await
+    }
+
+    /// A code is accepted once; replaying it in the same time window fails.
+    async fn check_totp(&self, secret: &TwoFactorSecret, code: &str) -> Result<bool> {
+        let key = totp::decode_secret(&secret.secret).ok_or_else(|| {
+            AuthError::Internal(anyhow::anyhow!("Stored TOTP secret is not valid base32"))
+        })?;
+
+        match totp::verify(&key, code, Utc::now()) {
+            Some(step) => self.repos.two_factor.use_step(secret.user_id, step).await,
+            None => Ok(false),
+        }
+    }
+
+    async fn generate_recovery_codes(&self, user_id: Uuid) -> Result<RecoveryCodesResponse> {
+        let now = Utc::now();
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+
+        self.repos
+            .two_factor
+            .replace_recovery_codes(
+                user_id,
+                recovery_codes
+                    .iter()
+                    .map(|code| RecoveryCode {
+                        id: Uuid::new_v4(),
+                        user_id,
+                        code_hash: hash_token(&normalize_recovery_code(code)),
+                        created_at: now,
+                        used_at: None,
+                    })
+                    .collect(),
+            )
+            .await?;
+
+        Ok(RecoveryCodesResponse { recovery_codes })
+    }
+
+    /// Exchanges a refresh token for a new token pair. The presented token is
+    /// consumed; presenting it again revokes every token in its family.
+    pub async fn refresh(&self, refresh_token: &str, client: &ClientInfo) -> Result<AuthResponse> {
+        self.rotate_refresh_token(refresh_token, client, None)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    /// Swaps a refresh token for a new token pair. `oauth_client` is the
+    /// authenticated OAuth client refreshing, if any: a refresh token only
+    /// works for whoever it was issued to. Also returns the scopes the
+    /// session was granted.
+    async fn rotate_refresh_token(
+        &self,
+        refresh_token: &str,
+        client: &ClientInfo,
+        oauth_client: Option<Uuid>,
+    ) -> Result<(AuthResponse, Option<Vec<Scope>>)> {
+        let stored = self
+            .repos
+            .refresh_tokens
+            .find_by_hash(&hash_token(refresh_token))
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+
+        if stored.revoked_at.is_some() {
+            return Err(AuthError::InvalidToken);
+        }
+        let session = self.repos.sessions.find(stored.family_id).await?;
+        if session.as_ref().and_then(|session| session.client_id) != oauth_client {
+            return Err(AuthError::InvalidToken);
+        }
+
+        if stored.used_at.is_none() && stored.expires_at <= Utc::now() {
+            return Err(AuthError::TokenExpired);
+        }
+
+        if stored.used_at.is_some() || !self.repos.refresh_tokens.mark_used(stored.id).await? {
+            tracing::warn!(
+                user_id = %stored.user_id,
+                family_id = %stored.family_id,
+                "Refresh token reuse detected, ending the session"
+            );
+            self.audit(
+                AuditEventType::RefreshTokenReused,
+                Some(stored.user_id),
+                client,
+                json!({ "session_id": stored.family_id }),
+            )
+            .await;
+            self.end_session(stored.family_id).await?;
+            return Err(AuthError::InvalidToken);
+        }
+
+        let user = self
+            .repos
+            .users
+            .find_by_id(stored.user_id)
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+
+        let response = self.issue_tokens(user, stored.family_id, client).await?;
+        Ok((response, session.and_then(|session| session.scopes)))
+    }
+
+    /// Decodes an access token and rejects it if it has been revoked.
+    pub async fn validate_token(&self, token: &str) -> Result<Claims> {
+        let token_data = self
+            .keys
+            .verify::<Claims>(token)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+                _ => AuthError::InvalidToken,
+            })?;
+        let claims = token_data.claims;
+
+        if self.repos.revocations.is_token_revoked(claims.jti).await? {
+            return Err(AuthError::InvalidToken);
+        }
+        if let Some(revocation) = self
+            .repos
+            .revocations
+            .find_user_revocation(claims.sub)
+            .await?
+        {
+            if claims.issued_at() <= revocation.revoked_before {
+                return Err(AuthError::InvalidToken);
+            }
+        }
+
+        Ok(claims)
+    }
+
+    /// Revokes the presented access token and ends its session. A refresh
+    /// token, if given, ends the session it belongs to as well, which covers
+    /// access tokens issued before sessions carried an id.
+    pub async fn logout(
+        &self,
+        claims: &Claims,
+        refresh_token: Option<&str>,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        self.repos
+            .revocations
+            .revoke_token(RevokedToken {
+                jti: claims.jti,
+                user_id: claims.sub,
+                expires_at: timestamp_to_datetime(claims.exp),
+                revoked_at: Utc::now(),
+            })
+            .await?;
+
+        if let Some(refresh_token) = refresh_token {
+            if let Some(stored) = self
+                .repos
+                .refresh_tokens
+                .find_by_hash(&hash_token(refresh_token))
+                .await?
+            {
+                if stored.user_id == claims.sub {
+                    self.end_session(stored.family_id).await?;
+                }
+            }
+        }
+        if let Some(session_id) = claims.sid {
+            self.end_session(session_id).await?;
+        }
+        self.audit(
+            AuditEventType::Logout,
+            Some(claims.sub),
+            client,
+            json!({ "session_id": claims.sid }),
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// Signs the user out on every device.
+    pub async fn logout_all(&self, user_id: Uuid, client: &ClientInfo) -> Result<()> {
+        self.revoke_all_sessions(user_id).await?;
+        self.audit(
+            AuditEventType::AllSessionsRevoked,
+            Some(user_id),
+            client,
+            json!({}),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Revokes every access and refresh token the user currently holds.
+    async fn revoke_all_sessions(&self, user_id: Uuid) -> Result<()> {
+        self.repos
+            .revocations
+            .revoke_user(UserRevocation {
+                user_id,
+                revoked_before: Utc::now(),
+            })
+            .await?;
+        self.repos
+            .sessions
+            .revoke_all_for_user(user_id, Utc::now())
+            .await?;
+        self.repos.refresh_tokens.revoke_all_for_user(user_id).await
+    }
+
+    /// The devices the user is signed in on. `current_session` is the one
+    /// making the request, if known.
+    pub async fn list_sessions(
+        &self,
+        user_id: Uuid,
+        current_session: Option<Uuid>,
+    ) -> Result<Vec<SessionResponse>> {
+        let sessions = self.repos.sessions.list_active(user_id, Utc::now()).await?;
+
+        Ok(sessions
+            .into_iter()
+            .map(|session| SessionResponse {
+                current: current_session == Some(session.id),
+                id: session.id,
+                user_agent: session.user_agent,
+                ip_address: session.ip_address,
+                created_at: session.created_at,
+                last_seen_at: session.last_seen_at,
+                expires_at: session.expires_at,
+                client_id: session.client_id,
+            })
+            .collect())
+    }
+
+    /// Signs one of the user's devices out.
+    pub async fn revoke_session(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        let session = self
+            .repos
+            .sessions
+            .find(session_id)
+            .await?
+            .filter(|session| session.user_id == user_id && session.revoked_at.is_none())
+            .ok_or(AuthError::SessionNotFound)?;
+
+        self.end_session(session.id).await?;
+        self.audit(
+            AuditEventType::SessionRevoked,
+            Some(user_id),
+            client,
+            json!({ "session_id": session.id }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Ends a session: its refresh tokens stop working and its latest access
+    /// token is revoked.
+    async fn end_session(&self, session_id: Uuid) -> Result<()> {
+        self.repos.refresh_tokens.revoke_family(session_id).await?;
+
+        let Some(session) = self.repos.sessions.find(session_id).await? else {
+            return Ok(());
         };
+        let now = Utc::now();
+        self.repos.sessions.revoke(session.id, now).await?;
+        if session.access_token_expires_at > now {
+            self.repos
+                .revocations
+                .revoke_token(RevokedToken {
+                    jti: session.access_token_jti,
+                    user_id: session.user_id,
+                    expires_at: session.access_token_expires_at,
+                    revoked_at: now,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates a token for scripts to act as the user within `scopes`. The
+    /// token is only ever returned here.
+    pub async fn create_personal_access_token(
+        &self,
+        user_id: Uuid,
+        req: CreatePersonalAccessTokenRequest,
+        client: &ClientInfo,
+    ) -> Result<PersonalAccessTokenResponse> {
+        let name = req.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_ACCESS_TOKEN_NAME_LEN {
+            return Err(AuthError::BadRequest(format!(
+                "Token name must be 1 to {} characters",
+                MAX_ACCESS_TOKEN_NAME_LEN
+            )));
+        }
+        let scopes = dedup_scopes(req.scopes);
+        if scopes.is_empty() {
+            return Err(AuthError::BadRequest(
+                "At least one scope is required".to_string(),
+            ));
+        }
+        let days = req.expires_in_days.unwrap_or(DEFAULT_ACCESS_TOKEN_TTL_DAYS);
+        if !(1..=MAX_ACCESS_TOKEN_TTL_DAYS).contains(&days) {
+            return Err(AuthError::BadRequest(format!(
+                "Tokens must expire within 1 to {} days",
+                MAX_ACCESS_TOKEN_TTL_DAYS
+            )));
+        }
+
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+        if let Some(scope) = scopes.iter().find(|scope| {
+            scope
+                .permission()
+                .is_some_and(|permission| !rbac::has_permission(&user.roles, permission))
+        }) {
+            return Err(AuthError::BadRequest(format!(
+                "Your roles do not allow the {} scope",
+                scope.as_str()
+            )));
+        }
 
-        encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        let token = format!("{}{}", ACCESS_TOKEN_PREFIX, generate_opaque_token());
+        let now = Utc::now();
+        let stored = self
+            .repos
+            .personal_access_tokens
+            .create(PersonalAccessToken {
+                id: Uuid::new_v4(),
+                user_id,
+                name,
+                token_hash: hash_token(&token),
+                scopes,
+                expires_at: now + Duration::days(days),
+                created_at: now,
+                last_used_at: None,
+            })
+            .await?;
+        self.audit(
+            AuditEventType::PersonalAccessTokenCreated,
+            Some(user_id),
+            client,
+            json!({ "token_id": stored.id, "name": stored.name, "scopes": stored.scopes }),
         )
-        .map_err(|e| AuthError::Internal(anyhow::anyhow!("Failed to create token: {}", e)))
+        .await;
+
+        Ok(PersonalAccessTokenResponse {
+            token: Some(token),
+            ..stored.into()
+        })
+    }
+
+    pub async fn list_personal_access_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<PersonalAccessTokenResponse>> {
+        Ok(self
+            .repos
+            .personal_access_tokens
+            .list_for_user(user_id)
+            .await?
+            .into_iter()
+            .map(PersonalAccessTokenResponse::from)
+            .collect())
     }
-}
\ No newline at end of file
+
+    pub async fn delete_personal_access_token(&self, user_id: Uuid, token_id: Uuid) -> Result<()> {
+        if !self
+            .repos
+            .personal_access_tokens
+            .delete(user_id, token_id)
+            .await?
+        {
+            return Err(AuthError::AccessTokenNotFound);
+        }
+        Ok(())
+    }
+
+    /// Resolves a personal access token to the user it acts for, recording
+    /// that it was used. The gateway calls this for every request carrying
+    /// one.
+    pub async fn verify_personal_access_token(
+        &self,
+        token: &str,
+    ) -> Result<PersonalAccessTokenIdentity> {
+        if !token.starts_with(ACCESS_TOKEN_PREFIX) {
+            return Err(AuthError::InvalidToken);
+        }
+        let stored = self
+            .repos
+            .personal_access_tokens
+            .find_by_hash(&hash_token(token))
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+        let now = Utc::now();
+        if stored.expires_at <= now {
+            return Err(AuthError::TokenExpired);
+        }
+
+        let user = self
+            .repos
+            .users
+            .find_by_id(stored.user_id)
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+        self.repos
+            .personal_access_tokens
+            .record_use(stored.id, now)
+            .await?;
+
+        Ok(PersonalAccessTokenIdentity {
+            token_id: stored.id,
+            user_id: user.id,
+            email: user.email,
+            roles: user.roles,
+            email_verified: user.email_verified_at.is_some(),
+            scopes: stored.scopes,
+        })
+    }
+
+    /// Registers an application that can ask users for access. A secret is
+    /// generated for confidential clients and only ever returned here.
+    pub async fn register_oauth_client(
+        &self,
+        owner_id: Uuid,
+        req: RegisterOAuthClientRequest,
+    ) -> Result<OAuthClientResponse> {
+        let name = req.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_OAUTH_CLIENT_NAME_LEN {
+            return Err(AuthError::BadRequest(format!(
+                "Client name must be 1 to {} characters",
+                MAX_OAUTH_CLIENT_NAME_LEN
+            )));
+        }
+        if req.redirect_uris.is_empty() {
+            return Err(AuthError::BadRequest(
+                "At least one redirect URI is required".to_string(),
+            ));
+        }
+        for uri in &req.redirect_uris {
+            oauth::validate_redirect_uri(uri).map_err(AuthError::BadRequest)?;
+        }
+        let scopes = dedup_scopes(req.scopes);
+        if scopes.is_empty() {
+            return Err(AuthError::BadRequest(
+                "At least one scope is required".to_string(),
+            ));
+        }
+
+        let secret = req.confidential.then(generate_opaque_token);
+        let client = self
+            .repos
+            .oauth_clients
+            .create(OAuthClient {
+                id: Uuid::new_v4(),
+                owner_id,
+                name,
+                redirect_uris: req.redirect_uris,
+                secret_hash: secret.as_deref().map(hash_token),
+                scopes,
+                created_at: Utc::now(),
+            })
+            .await?;
+
+        Ok(OAuthClientResponse {
+            client_secret: secret,
+            ..client.into()
+        })
+    }
+
+    pub async fn list_oauth_clients(&self, owner_id: Uuid) -> Result<Vec<OAuthClientResponse>> {
+        Ok(self
+            .repos
+            .oauth_clients
+            .list_for_owner(owner_id)
+            .await?
+            .into_iter()
+            .map(OAuthClientResponse::from)
+            .collect())
+    }
+
+    /// Deletes a client and ends every session it holds.
+    pub async fn delete_oauth_client(&self, owner_id: Uuid, client_id: Uuid) -> Result<()> {
+        self.repos
+            .oauth_clients
+            .find(client_id)
+            .await?
+            .filter(|client| client.owner_id == owner_id)
+            .ok_or(AuthError::OAuthClientNotFound)?;
+
+        // Sessions go first: the database drops them along with the client,
+        // and their access tokens have to be revoked while we can find them.
+        let sessions = self
+            .repos
+            .sessions
+            .list_active_for_client(client_id, Utc::now())
+            .await?;
+        for session in sessions {
+            self.end_session(session.id).await?;
+        }
+        if !self.repos.oauth_clients.delete(owner_id, client_id).await? {
+            return Err(AuthError::OAuthClientNotFound);
+        }
+        Ok(())
+    }
+
+    /// What the consent page should ask the user to approve.
+    pub async fn preview_authorization(
+        &self,
+        user_id: Uuid,
+        req: &AuthorizationRequest,
+    ) -> Result<AuthorizationPreviewResponse> {
+        let (client, scopes) = self.check_authorization_request(user_id, req).await?;
+        let consent = self.repos.oauth_consents.find(user_id, client.id).await?;
+        let consent_required = !consent
+            .is_some_and(|consent| scopes.iter().all(|scope| consent.scopes.contains(scope)));
+
+        Ok(AuthorizationPreviewResponse {
+            client_id: client.id,
+            client_name: client.name,
+            scopes,
+            consent_required,
+        })
+    }
+
+    /// Records the user's answer to an authorization request and tells the
+    /// consent page where to send them: back to the client with a code, or
+    /// with `access_denied`.
+    pub async fn decide_authorization(
+        &self,
+        user_id: Uuid,
+        decision: AuthorizationDecision,
+    ) -> Result<AuthorizationDecisionResponse> {
+        let req = decision.request;
+        let (client, scopes) = self.check_authorization_request(user_id, &req).await?;
+        let state = req.state.as_deref();
+
+        if !decision.approve {
+            let mut params = vec![("error", "access_denied")];
+            params.extend(state.map(|state| ("state", state)));
+            return Ok(AuthorizationDecisionResponse {
+                redirect_to: oauth::redirect_with(&req.redirect_uri, &params)?,
+            });
+        }
+
+        let now = Utc::now();
+        let consent = self.repos.oauth_consents.find(user_id, client.id).await?;
+        let (granted, created_at) = match consent {
+            Some(consent) => (consent.scopes, consent.created_at),
+            None => (Vec::new(), now),
+        };
+        self.repos
+            .oauth_consents
+            .upsert(OAuthConsent {
+                user_id,
+                client_id: client.id,
+                scopes: dedup_scopes(granted.into_iter().chain(scopes.clone()).collect()),
+                created_at,
+                updated_at: now,
+            })
+            .await?;
+
+        let code = generate_opaque_token();
+        self.repos
+            .oauth_codes
+            .create(OAuthAuthorizationCode {
+                id: Uuid::new_v4(),
+                code_hash: hash_token(&code),
+                client_id: client.id,
+                user_id,
+                session_id: Uuid::new_v4(),
+                redirect_uri: req.redirect_uri.clone(),
+                scopes,
+                code_challenge: req.code_challenge.clone().unwrap_or_default(),
+                expires_at: now + Duration::seconds(OAUTH_CODE_TTL_SECONDS),
+                created_at: now,
+                used_at: None,
+            })
+            .await?;
+
+        let mut params = vec![("code", code.as_str())];
+        params.extend(state.map(|state| ("state", state)));
+        Ok(AuthorizationDecisionResponse {
+            redirect_to: oauth::redirect_with(&req.redirect_uri, &params)?,
+        })
+    }
+
+    /// Validates an authorization request and works out the scopes to ask
+    /// for: those requested (or the client's defaults) that the user's roles
+    /// allow.
+    async fn check_authorization_request(
+        &self,
+        user_id: Uuid,
+        req: &AuthorizationRequest,
+    ) -> Result<(OAuthClient, Vec<Scope>)> {
+        let client = match Uuid::parse_str(&req.client_id) {
+            Ok(client_id) => self.repos.oauth_clients.find(client_id).await?,
+            Err(_) => None,
+        }
+        .ok_or_else(|| oauth::error("invalid_request", "Unknown client"))?;
+        if !oauth::redirect_uri_matches(&client.redirect_uris, &req.redirect_uri) {
+            return Err(oauth::error(
+                "invalid_request",
+                "redirect_uri is not registered for this client",
+            ));
+        }
+
+        if req.response_type != "code" {
+            return Err(oauth::error(
+                "unsupported_response_type",
+                "Only the code response type is supported",
+            ));
+        }
+        if req.code_challenge_method.as_deref() != Some("S256")
+            || req
+                .code_challenge
+                .as_ref()
+                .is_none_or(|challenge| challenge.len() != 43)
+        {
+            return Err(oauth::error(
+                "invalid_request",
+                "PKCE with an S256 code_challenge is required",
+            ));
+        }
+
+        let requested = match req.scope.as_deref().map(str::trim) {
+            Some(scope) if !scope.is_empty() => oauth::parse_scope(scope)
+                .ok_or_else(|| oauth::error("invalid_scope", "Unknown scope"))?,
+            _ => client.scopes.clone(),
+        };
+        if let Some(scope) = requested
+            .iter()
+            .find(|scope| !client.scopes.contains(scope))
+        {
+            return Err(oauth::error(
+                "invalid_scope",
+                format!("The client may not request the {} scope", scope.as_str()),
+            ));
+        }
+
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+        let scopes: Vec<Scope> = requested
+            .into_iter()
+            .filter(|scope| {
+                scope
+                    .permission()
+                    .is_none_or(|permission| rbac::has_permission(&user.roles, permission))
+            })
+            .collect();
+        if scopes.is_empty() {
+            return Err(oauth::error(
+                "invalid_scope",
+                "Your roles do not allow any of the requested scopes",
+            ));
+        }
+
+        Ok((client, scopes))
+    }
+
+    /// Identifies the client calling the token or revocation endpoint.
+    /// Confidential clients must present their secret; public clients must
+    /// not have one.
+    async fn authenticate_oauth_client(
+        &self,
+        client_id: Option<&str>,
+        client_secret: Option<&str>,
+    ) -> Result<OAuthClient> {
+        let invalid_client = || oauth::error("invalid_client", "Client authentication failed");
+        let client_id = client_id
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .ok_or_else(invalid_client)?;
+        let client = self
+            .repos
+            .oauth_clients
+            .find(client_id)
+            .await?
+            .ok_or_else(invalid_client)?;
+
+        let authenticated = match (&client.secret_hash, client_secret) {
+            (Some(secret_hash), Some(secret)) => secret_hash
+                .as_bytes()
+                .ct_eq(hash_token(secret).as_bytes())
+                .into(),
+            (None, None) => true,
+            _ => false,
+        };
+        if !authenticated {
+            return Err(invalid_client());
+        }
+        Ok(client)
+    }
+
+    /// The token endpoint: redeems an authorization code or rotates a
+    /// refresh token issued to the client.
+    pub async fn exchange_token(
+        &self,
+        client_id: Option<&str>,
+        client_secret: Option<&str>,
+        req: &TokenRequest,
+        client_info: &ClientInfo,
+    ) -> Result<OAuthTokenResponse> {
+        let client = self
+            .authenticate_oauth_client(client_id, client_secret)
+            .await?;
+
+        match req.grant_type.as_str() {
+            "authorization_code" => {
+                self.redeem_authorization_code(&client, req, client_info)
+                    .await
+            }
+            "refresh_token" => {
+                let refresh_token = req
+                    .refresh_token
+                    .as_deref()
+                    .ok_or_else(|| oauth::error("invalid_request", "refresh_token is required"))?;
+                let (response, scopes) = self
+                    .rotate_refresh_token(refresh_token, client_info, Some(client.id))
+                    .await
+                    .map_err(|e| match e {
+                        AuthError::InvalidToken | AuthError::TokenExpired => {
+                            oauth::error("invalid_grant", "Refresh token is invalid or expired")
+                        }
+                        e => e,
+                    })?;
+                Ok(oauth_token_response(response, &scopes.unwrap_or_default()))
+            }
+            _ => Err(oauth::error(
+                "unsupported_grant_type",
+                "Only authorization_code and refresh_token grants are supported",
+            )),
+        }
+    }
+
+    async fn redeem_authorization_code(
+        &self,
+        client: &OAuthClient,
+        req: &TokenRequest,
+        client_info: &ClientInfo,
+    ) -> Result<OAuthTokenResponse> {
+        let invalid_grant = |description: &str| oauth::error("invalid_grant", description);
+        let code = req
+            .code
+            .as_deref()
+            .ok_or_else(|| oauth::error("invalid_request", "code is required"))?;
+        let stored = self
+            .repos
+            .oauth_codes
+            .find_by_hash(&hash_token(code))
+            .await?
+            .filter(|stored| stored.client_id == client.id)
+            .ok_or_else(|| invalid_grant("Authorization code is invalid"))?;
+
+        // A code presented twice has leaked; whatever the first exchange
+        // obtained is revoked along with it.
+        if stored.used_at.is_some() || !self.repos.oauth_codes.mark_used(stored.id).await? {
+            tracing::warn!(
+                client_id = %client.id,
+                user_id = %stored.user_id,
+                "Authorization code reuse detected, ending the session"
+            );
+            self.end_session(stored.session_id).await?;
+            return Err(invalid_grant("Authorization code was already used"));
+        }
+        if stored.expires_at <= Utc::now() {
+            return Err(invalid_grant("Authorization code has expired"));
+        }
+        if req.redirect_uri.as_deref() != Some(stored.redirect_uri.as_str()) {
+            return Err(invalid_grant(
+                "redirect_uri does not match the authorization request",
+            ));
+        }
+        if !req
+            .code_verifier
+            .as_deref()
+            .is_some_and(|verifier| oauth::verify_code_challenge(verifier, &stored.code_challenge))
+        {
+            return Err(invalid_grant(
+                "code_verifier does not match the code_challenge",
+            ));
+        }
+
+        let user = self
+            .repos
+            .users
+            .find_by_id(stored.user_id)
+            .await?
+            .ok_or_else(|| invalid_grant("Authorization code is invalid"))?;
+        let grant = OAuthGrant {
+            client_id: client.id,
+            scopes: stored.scopes.clone(),
+        };
+        let response = self
+            .issue_grant_tokens(user, stored.session_id, client_info, Some(grant))
+            .await?;
+        Ok(oauth_token_response(response, &stored.scopes))
+    }
+
+    /// RFC 7009 revocation. Either kind of token ends the session it belongs
+    /// to. Tokens that are unknown or issued to another client are ignored,
+    /// as the RFC asks.
+    pub async fn revoke_oauth_token(
+        &self,
+        client_id: Option<&str>,
+        client_secret: Option<&str>,
+        token: &str,
+    ) -> Result<()> {
+        let client = self
+            .authenticate_oauth_client(client_id, client_secret)
+            .await?;
+
+        if let Some(stored) = self
+            .repos
+            .refresh_tokens
+            .find_by_hash(&hash_token(token))
+            .await?
+        {
+            let session = self.repos.sessions.find(stored.family_id).await?;
+            if session.is_some_and(|session| session.client_id == Some(client.id)) {
+                self.end_session(stored.family_id).await?;
+            }
+            return Ok(());
+        }
+
+        let Ok(token_data) = self.keys.verify::<Claims>(token) else {
+            return Ok(());
+        };
+        let claims = token_data.claims;
+        if claims.client_id != Some(client.id) {
+            return Ok(());
+        }
+        self.repos
+            .revocations
+            .revoke_token(RevokedToken {
+                jti: claims.jti,
+                user_id: claims.sub,
+                expires_at: timestamp_to_datetime(claims.exp),
+                revoked_at: Utc::now(),
+            })
+            .await?;
+        if let Some(session_id) = claims.sid {
+            self.end_session(session_id).await?;
+        }
+        Ok(())
+    }
+
+    /// RFC 7662 introspection of an access token or personal access token,
+    /// for services deciding whether to honour a request.
+    pub async fn introspect(&self, token: &str) -> Result<IntrospectionResponse> {
+        if token.starts_with(ACCESS_TOKEN_PREFIX) {
+            let Some(stored) = self
+                .repos
+                .personal_access_tokens
+                .find_by_hash(&hash_token(token))
+                .await?
+            else {
+                return Ok(IntrospectionResponse::default());
+            };
+            let now = Utc::now();
+            if stored.expires_at <= now {
+                return Ok(IntrospectionResponse::default());
+            }
+            let Some(user) = self.repos.users.find_by_id(stored.user_id).await? else {
+                return Ok(IntrospectionResponse::default());
+            };
+            self.repos
+                .personal_access_tokens
+                .record_use(stored.id, now)
+                .await?;
+
+            return Ok(IntrospectionResponse {
+                active: true,
+                scope: Some(oauth::format_scope(&stored.scopes)),
+                username: Some(user.username),
+                token_type: Some("Bearer"),
+                exp: Some(stored.expires_at.timestamp()),
+                iat: Some(stored.created_at.timestamp()),
+                sub: Some(user.id),
+                jti: Some(stored.id),
+                roles: Some(user.roles),
+                email_verified: Some(user.email_verified_at.is_some()),
+                ..Default::default()
+            });
+        }
+
+        let claims = match self.validate_token(token).await {
+            Ok(claims) => claims,
+            Err(AuthError::InvalidToken | AuthError::TokenExpired) => {
+                return Ok(IntrospectionResponse::default())
+            }
+            Err(e) => return Err(e),
+        };
+        let Some(user) = self.repos.users.find_by_id(claims.sub).await? else {
+            return Ok(IntrospectionResponse::default());
+        };
+
+        Ok(IntrospectionResponse {
+            active: true,
+            scope: claims.scope,
+            client_id: claims.client_id,
+            username: Some(user.username),
+            token_type: Some("Bearer"),
+            exp: Some(claims.exp as i64),
+            iat: Some(claims.iat as i64),
+            sub: Some(claims.sub),
+            jti: Some(claims.jti),
+            roles: Some(claims.roles),
+            email_verified: Some(claims.email_verified),
+            act: claims.act,
+        })
+    }
+
+    /// The applications the user has authorized.
+    pub async fn list_oauth_consents(&self, user_id: Uuid) -> Result<Vec<OAuthConsentResponse>> {
+        let consents = self.repos.oauth_consents.list_for_user(user_id).await?;
+
+        let mut responses = Vec::with_capacity(consents.len());
+        for consent in consents {
+            // The client may have been deleted since.
+            let Some(client) = self.repos.oauth_clients.find(consent.client_id).await? else {
+                continue;
+            };
+            responses.push(OAuthConsentResponse {
+                client_id: client.id,
+                client_name: client.name,
+                scopes: consent.scopes,
+                created_at: consent.created_at,
+                updated_at: consent.updated_at,
+            });
+        }
+        Ok(responses)
+    }
+
+    /// Withdraws the user's consent for a client and signs the client out.
+    pub async fn revoke_oauth_consent(&self, user_id: Uuid, client_id: Uuid) -> Result<()> {
+        if !self.repos.oauth_consents.delete(user_id, client_id).await? {
+            return Err(AuthError::OAuthClientNotFound);
+        }
+        let sessions = self.repos.sessions.list_active(user_id, Utc::now()).await?;
+        for session in sessions {
+            if session.client_id == Some(client_id) {
+                self.end_session(session.id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the user's account data for their export.
+    pub async fn export_account(&self, user_id: Uuid) -> Result<AccountExport> {
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+
+        Ok(AccountExport {
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            two_factor_enabled: self.confirmed_two_factor(user_id).await?.is_some(),
+            sessions: self.list_sessions(user_id, None).await?,
+            passkeys: self.list_passkeys(user_id).await?,
+            identities: self.repos.oidc_identities.list_for_user(user_id).await?,
+            personal_access_tokens: self.list_personal_access_tokens(user_id).await?,
+            oauth_clients: self.list_oauth_clients(user_id).await?,
+            oauth_consents: self.list_oauth_consents(user_id).await?,
+            user: user.into(),
+        })
+    }
+
+    /// Deletes the account. The rows tied to it go with the user row, except
+    /// token revocations: those are recorded first and kept, so tokens
+    /// already issued stop working.
+    pub async fn delete_account(&self, user_id: Uuid, client: &ClientInfo) -> Result<()> {
+        // Clients the user registered also hold other users' sessions.
+        let oauth_clients = self.repos.oauth_clients.list_for_owner(user_id).await?;
+        for oauth_client in oauth_clients {
+            self.delete_oauth_client(user_id, oauth_client.id).await?;
+        }
+        self.revoke_all_sessions(user_id).await?;
+
+        if !self.repos.users.delete(user_id).await? {
+            return Err(AuthError::UserNotFound);
+        }
+        tracing::info!(%user_id, "Deleted account");
+        self.audit(
+            AuditEventType::AccountDeleted,
+            Some(user_id),
+            client,
+            json!({}),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Emails a password reset link if the address belongs to an account.
+    /// The result is the same either way, so the endpoint cannot be used to
+    /// find out which addresses are registered.
+    pub async fn forgot_password(&self, email: &str) -> Result<()> {
+        let Some(user) = self
+            .repos
+            .users
+            .find_by_email(&normalize_email(email))
+            .await?
+        else {
+            return Ok(());
+        };
+
+        let token = generate_opaque_token();
+        let now = Utc::now();
+        self.repos
+            .password_resets
+            .create(PasswordResetToken {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                token_hash: hash_token(&token),
+                expires_at: now + self.password_reset_ttl,
+                created_at: now,
+                used_at: None,
+            })
+            .await?;
+
+        self.send_in_background(Email {
+            to: user.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Someone asked to reset the password for your account.\n\n\
+                 Open this link to choose a new one. It expires in {} minutes:\n\n\
+                 {}/reset-password?token={}\n\n\
+                 If this wasn't you, you can ignore this email.",
+                self.password_reset_ttl.num_minutes(),
+                self.app_url,
+                token
+            ),
+        });
+
+        Ok(())
+    }
+
+    /// Emails a one-time sign-in link if the address belongs to an account.
+    /// With `bind_device`, the returned device token has to accompany the
+    /// link, so it only works in the browser that asked for it. The response
+    /// is the same whether or not the account exists.
+    pub async fn send_magic_link(
+        &self,
+        email: &str,
+        bind_device: bool,
+    ) -> Result<MagicLinkResponse> {
+        let device_token = bind_device.then(generate_opaque_token);
+        let response = MagicLinkResponse {
+            device_token: device_token.clone(),
+        };
+        let Some(user) = self
+            .repos
+            .users
+            .find_by_email(&normalize_email(email))
+            .await?
+        else {
+            return Ok(response);
+        };
+
+        // Only the latest link works.
+        self.repos.magic_links.invalidate_for_user(user.id).await?;
+        let token = generate_opaque_token();
+        let now = Utc::now();
+        self.repos
+            .magic_links
+            .create(MagicLinkToken {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                token_hash: hash_token(&token),
+                device_hash: device_token.as_deref().map(hash_token),
+                expires_at: now + self.magic_link_ttl,
+                created_at: now,
+                used_at: None,
+            })
+            .await?;
+
+        self.send_in_background(Email {
+            to: user.email,
+            subject: "Your sign-in link".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                 Open this link to sign in. It expires in {} minutes and works once:\n\n\
+                 {}/magic-link?token={}\n\n\
+                 If you didn't ask to sign in, you can ignore this email.",
+                user.username,
+                self.magic_link_ttl.num_minutes(),
+                self.app_url,
+                token
+            ),
+        });
+
+        Ok(response)
+    }
+
+    /// Signs in with a link from `send_magic_link`. Users with 2FA are still
+    /// asked for their code.
+    pub async fn consume_magic_link(
+        &self,
+        token: &str,
+        device_token: Option<&str>,
+        client: &ClientInfo,
+    ) -> Result<LoginResponse> {
+        let stored = self
+            .repos
+            .magic_links
+            .find_by_hash(&hash_token(token))
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+
+        if stored.used_at.is_some() {
+            return Err(AuthError::InvalidToken);
+        }
+        if stored.expires_at <= Utc::now() {
+            return Err(AuthError::TokenExpired);
+        }
+        // Checked before the link is spent, so opening it on another device
+        // doesn't stop it working on the right one.
+        if let Some(device_hash) = &stored.device_hash {
+            if device_token.map(hash_token).as_ref() != Some(device_hash) {
+                return Err(AuthError::InvalidToken);
+            }
+        }
+        if !self.repos.magic_links.mark_used(stored.id).await? {
+            return Err(AuthError::InvalidToken);
+        }
+
+        let mut user = self
+            .repos
+            .users
+            .find_by_id(stored.user_id)
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+        // The link proves control of the mailbox.
+        if user.email_verified_at.is_none() {
+            let now = Utc::now();
+            self.repos.users.mark_email_verified(user.id, now).await?;
+            user.email_verified_at = Some(now);
+        }
+
+        self.complete_first_factor(user, client).await
+    }
+
+    /// Sets a new password using a reset token. The token is consumed, and
+    /// every session the user had is signed out.
+    pub async fn reset_password(
+        &self,
+        token: &str,
+        new_password: &str,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        let stored = self
+            .repos
+            .password_resets
+            .find_by_hash(&hash_token(token))
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+
+        if stored.used_at.is_some() {
+            return Err(AuthError::InvalidToken);
+        }
+        if stored.expires_at <= Utc::now() {
+            return Err(AuthError::TokenExpired);
+        }
+        // Checked before the link is spent so a refused password can be
+        // retried with the same link.
+        let user = self
+            .repos
+            .users
+            .find_by_id(stored.user_id)
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+        self.check_password_policy(new_password, &user.username, &user.email)
+            .await?;
+        if !self.repos.password_resets.mark_used(stored.id).await? {
+            return Err(AuthError::InvalidToken);
+        }
+
+        self.set_password(stored.user_id, new_password).await?;
+        self.audit(
+            AuditEventType::PasswordChanged,
+            Some(stored.user_id),
+            client,
+            json!({ "method": "reset" }),
+        )
+        .await;
+        // The reset link proves control of the mailbox just like the unlock
+        // link does.
+        self.clear_account_throttle(stored.user_id).await
+    }
+
+    /// Replaces the signed-in user's password once they confirm the current
+    /// one. Wrong guesses count against the account like failed logins. The
+    /// user's other sessions are signed out; `current_session` is kept.
+    pub async fn change_password(
+        &self,
+        user_id: Uuid,
+        current_session: Option<Uuid>,
+        current_password: &str,
+        new_password: &str,
+        client: &ClientInfo,
+    ) -> Result<()> {
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+        self.check_login_throttle(&user.email, client.ip).await?;
+        if let Err(err) = self.check_password(&user, current_password).await {
+            if matches!(err, AuthError::InvalidCredentials) {
+                self.record_login_failure(&user.email, client.ip).await?;
+            }
+            return Err(err);
+        }
+        self.check_password_policy(new_password, &user.username, &user.email)
+            .await?;
+
+        let password_hash = self.hash_password(new_password).await?;
+        self.repos
+            .users
+            .update_password(user_id, &password_hash)
+            .await?;
+        self.repos
+            .password_resets
+            .invalidate_for_user(user_id)
+            .await?;
+        for session in self.repos.sessions.list_active(user_id, Utc::now()).await? {
+            if Some(session.id) != current_session {
+                self.end_session(session.id).await?;
+            }
+        }
+        self.audit(
+            AuditEventType::PasswordChanged,
+            Some(user_id),
+            client,
+            json!({ "method": "change" }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Stores a new password, voids any outstanding reset links and signs the
+    /// user out everywhere.
+    async fn set_password(&self, user_id: Uuid, password: &str) -> Result<()> {
+        let password_hash = self.hash_password(password).await?;
+        self.repos
+            .users
+            .update_password(user_id, &password_hash)
+            .await?;
+        self.repos
+            .password_resets
+            .invalidate_for_user(user_id)
+            .await?;
+        self.revoke_all_sessions(user_id).await
+    }
+
+    /// Marks the email address of the token's user as verified. Access tokens
+    /// issued before this still say unverified; the next refresh picks it up.
+    pub async fn verify_email(&self, token: &str) -> Result<()> {
+        let stored = self
+            .repos
+            .email_verifications
+            .find_by_hash(&hash_token(token))
+            .await?
+            .ok_or(AuthError::InvalidToken)?;
+
+        if stored.used_at.is_some() {
+            return Err(AuthError::InvalidToken);
+        }
+        if stored.expires_at <= Utc::now() {
+            return Err(AuthError::TokenExpired);
+        }
+        if !self.repos.email_verifications.mark_used(stored.id).await? {
+            return Err(AuthError::InvalidToken);
+        }
+
+        self.repos
+            .users
+            .mark_email_verified(stored.user_id, Utc::now())
+            .await?;
+        self.repos
+            .email_verifications
+            .invalidate_for_user(stored.user_id)
+            .await
+    }
+
+    /// Sends a fresh verification link. Links sent earlier stop working.
+    pub async fn resend_verification(&self, user_id: Uuid) -> Result<()> {
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+
+        if user.email_verified_at.is_some() {
+            return Err(AuthError::BadRequest(
+                "Email is already verified".to_string(),
+            ));
+        }
+
+        self.repos
+            .email_verifications
+            .invalidate_for_user(user.id)
+            .await?;
+        self.send_verification_email(&user).await
+    }
+
+    async fn send_verification_email(&self, user: &User) -> Result<()> {
+        let token = generate_opaque_token();
+        let now = Utc::now();
+        self.repos
+            .email_verifications
+            .create(EmailVerificationToken {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                token_hash: hash_token(&token),
+                expires_at: now + self.email_verification_ttl,
+                created_at: now,
+                used_at: None,
+            })
+            .await?;
+
+        self.send_in_background(Email {
+            to: user.email.clone(),
+            subject: "Confirm your email address".to_string(),
+            body: format!(
+                "Welcome, {}!\n\n\
+                 Open this link to confirm your email address. It expires in {} hours:\n\n\
+                 {}/verify-email?token={}\n\n\
+                 If you didn't create an account, you can ignore this email.",
+                user.username,
+                self.email_verification_ttl.num_hours(),
+                self.app_url,
+                token
+            ),
+        });
+
+        Ok(())
+    }
+
+    /// Mail is sent off the request path: a slow SMTP server shouldn't hold
+    /// up the response, and for password resets the response time shouldn't
+    /// reveal whether the account exists.
+    fn send_in_background(&self, email: Email) {
+        let mailer = self.mailer.clone();
+        tokio::spawn(async move {
+            if let Err(err) = mailer.send(email).await {
+                tracing::error!("Failed to send email: {:?}", err);
+            }
+        });
+    }
+
+    /// Replaces a user's roles. Access tokens issued before the change are
+    /// revoked so the old roles stop applying; refreshing picks up the new ones.
+    pub async fn update_roles(
+        &self,
+        admin_id: Uuid,
+        user_id: Uuid,
+        roles: Vec<Role>,
+        client: &ClientInfo,
+    ) -> Result<User> {
+        if roles.is_empty() {
+            return Err(AuthError::BadRequest(
+                "At least one role is required".to_string(),
+            ));
+        }
+
+        let user = self
+            .repos
+            .users
+            .update_roles(user_id, roles)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+
+        self.repos
+            .revocations
+            .revoke_user(UserRevocation {
+                user_id,
+                revoked_before: Utc::now(),
+            })
+            .await?;
+        self.audit(
+            AuditEventType::RolesUpdated,
+            Some(user_id),
+            client,
+            json!({ "actor_id": admin_id, "roles": user.roles }),
+        )
+        .await;
+
+        Ok(user)
+    }
+
+    /// Issues `admin` a short-lived access token to act as the user, so
+    /// support staff can see what the user sees. The token names the admin in
+    /// its `act` claim and carries no session: it cannot be refreshed, and the
+    /// auth service refuses it for account management. Other admins cannot be
+    /// impersonated.
+    pub async fn impersonate(
+        &self,
+        admin: &Claims,
+        user_id: Uuid,
+        client: &ClientInfo,
+    ) -> Result<ImpersonationResponse> {
+        let user = self
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await?
+            .ok_or(AuthError::UserNotFound)?;
+        if user.roles.contains(&Role::Admin) {
+            return Err(AuthError::Forbidden);
+        }
+
+        let now = Utc::now();
+        let claims = Claims {
+            sub: user.id,
+            email: user.email.clone(),
+            roles: user.roles.clone(),
+            email_verified: user.email_verified_at.is_some(),
+            iat: numeric_date(now),
+            exp: (now + self.impersonation_ttl).timestamp() as usize,
+            jti: Uuid::new_v4(),
+            sid: None,
+            scope: None,
+            client_id: None,
+            act: Some(Actor { sub: admin.sub }),
+        };
+        let token = self.keys.sign(&claims)?;
+
+        tracing::warn!(admin_id = %admin.sub, %user_id, "Issued impersonation token");
+        self.audit(
+            AuditEventType::ImpersonationStarted,
+            Some(user.id),
+            client,
+            json!({ "actor_id": admin.sub, "jti": claims.jti }),
+        )
+        .await;
+
+        Ok(ImpersonationResponse {
+            token,
+            expires_in: self.impersonation_ttl.num_seconds(),
+            user: user.into(),
+            actor_id: admin.sub,
+        })
+    }
+
+    /// Revocations that can still affect unexpired access tokens. The
+    /// gateway polls this to keep its local revocation cache current.
+    pub async fn revocation_list(&self) -> Result<RevocationListResponse> {
+        let now = Utc::now();
+        let tokens = self.repos.revocations.list_tokens(now).await?;
+        // Impersonation tokens may outlive ordinary access tokens, and a
+        // user revocation has to be listed until every token it covers
+        // has expired.
+        let longest_ttl = self.access_token_ttl.max(self.impersonation_ttl);
+        let users = self.repos.revocations.list_users(now - longest_ttl).await?;
+
+        Ok(RevocationListResponse {
+            tokens: tokens.into_iter().map(|revoked| revoked.jti).collect(),
+            users,
+            generated_at: now,
+        })
+    }
+
+    /// Audit events matching `query`, newest first.
+    pub async fn query_audit_log(&self, query: AuditLogQuery) -> Result<Vec<AuditEvent>> {
+        if let (Some(from), Some(to)) = (query.from, query.to) {
+            if from >= to {
+                return Err(AuthError::BadRequest(
+                    "'from' must be before 'to'".to_string(),
+                ));
+            }
+        }
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_AUDIT_LOG_LIMIT)
+            .clamp(1, MAX_AUDIT_LOG_LIMIT);
+
+        self.repos.audit_log.query(&query, limit).await
+    }
+
+    /// Removes audit events older than the retention period. Returns how
+    /// many were removed.
+    pub async fn prune_audit_log(&self) -> Result<u64> {
+        match self.audit_log_retention {
+            Some(retention) => {
+                self.repos
+                    .audit_log
+                    .delete_before(Utc::now() - retention)
+                    .await
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Appends an event to the audit log. A failure to record it is logged
+    /// rather than returned, so an unavailable log doesn't lock everyone out.
+    async fn audit(
+        &self,
+        event_type: AuditEventType,
+        user_id: Option<Uuid>,
+        client: &ClientInfo,
+        details: serde_json::Value,
+    ) {
+        let event = AuditEvent {
+            id: Uuid::new_v4(),
+            event_type,
+            user_id,
+            ip_address: client.ip.map(|ip| ip.to_string()),
+            user_agent: client.user_agent.clone(),
+            details,
+            created_at: Utc::now(),
+        };
+        if let Err(err) = self.repos.audit_log.append(event).await {
+            tracing::error!(
+                event_type = event_type.as_str(),
+                "Failed to record audit event: {:?}",
+                err
+            );
+        }
+    }
+
+    /// Issues a token pair for the session `session_id`, starting the session
+    /// if this is its first pair and recording the refresh otherwise.
+    async fn issue_tokens(
+        &self,
+        user: User,
+        session_id: Uuid,
+        client: &ClientInfo,
+    ) -> Result<AuthResponse> {
+        self.issue_grant_tokens(user, session_id, client, None)
+            .await
+    }
+
+    /// Like `issue_tokens`, for sessions an OAuth client may act through. A
+    /// new session takes `grant`; an existing one keeps what it was granted.
+    async fn issue_grant_tokens(
+        &self,
+        user: User,
+        session_id: Uuid,
+        client: &ClientInfo,
+        grant: Option<OAuthGrant>,
+    ) -> Result<AuthResponse> {
+        let existing = self.repos.sessions.find(session_id).await?;
+        let grant = match &existing {
+            Some(session) => session.client_id.map(|client_id| OAuthGrant {
+                client_id,
+                scopes: session.scopes.clone().unwrap_or_default(),
+            }),
+            None => grant,
+        };
+        let claims = self.access_claims(&user, session_id, grant.as_ref());
+        let token = self.keys.sign(&claims)?;
+
+        let refresh_token = generate_opaque_token();
+        let now = Utc::now();
+        let stored = self
+            .repos
+            .refresh_tokens
+            .create(RefreshToken {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                family_id: session_id,
+                token_hash: hash_token(&refresh_token),
+                expires_at: now + self.refresh_token_ttl,
+                created_at: now,
+                used_at: None,
+                revoked_at: None,
+            })
+            .await?;
+
+        let ip_address = client.ip.map(|ip| ip.to_string());
+        let access_token_expires_at = timestamp_to_datetime(claims.exp);
+        match existing {
+            Some(session) => {
+                self.repos
+                    .sessions
+                    .update(&Session {
+                        refresh_token_id: stored.id,
+                        access_token_jti: claims.jti,
+                        access_token_expires_at,
+                        user_agent: client.user_agent.clone().or(session.user_agent),
+                        ip_address: ip_address.or(session.ip_address),
+                        last_seen_at: now,
+                        expires_at: stored.expires_at,
+                        ..session
+                    })
+                    .await?;
+                self.audit(
+                    AuditEventType::TokenRefreshed,
+                    Some(user.id),
+                    client,
+                    json!({ "session_id": session_id }),
+                )
+                .await;
+            }
+            // Also reached by refresh token families from before sessions
+            // were tracked.
+            None => {
+                self.repos
+                    .sessions
+                    .create(Session {
+                        id: session_id,
+                        user_id: user.id,
+                        refresh_token_id: stored.id,
+                        access_token_jti: claims.jti,
+                        access_token_expires_at,
+                        user_agent: client.user_agent.clone(),
+                        ip_address,
+                        created_at: now,
+                        last_seen_at: now,
+                        expires_at: stored.expires_at,
+                        revoked_at: None,
+                        client_id: grant.as_ref().map(|grant| grant.client_id),
+                        scopes: grant.as_ref().map(|grant| grant.scopes.clone()),
+                    })
+                    .await?;
+                self.audit(
+                    AuditEventType::LoginSucceeded,
+                    Some(user.id),
+                    client,
+                    json!({
+                        "session_id": session_id,
+                        "client_id": grant.map(|grant| grant.client_id),
+                    }),
+                )
+                .await;
+            }
+        }
+
+        Ok(AuthResponse {
+            token,
+            refresh_token,
+            expires_in: self.access_token_ttl.num_seconds(),
+            user: user.into(),
+        })
+    }
+
+    fn access_claims(&self, user: &User, session_id: Uuid, grant: Option<&OAuthGrant>) -> Claims {
+        let now = Utc::now();
+        Claims {
+            sub: user.id,
+            email: user.email.clone(),
+            roles: user.roles.clone(),
+            email_verified: user.email_verified_at.is_some(),
+            iat: numeric_date(now),
+            exp: (now + self.access_token_ttl).timestamp() as usize,
+            jti: Uuid::new_v4(),
+            sid: Some(session_id),
+            scope: grant.map(|grant| oauth::format_scope(&grant.scopes)),
+            client_id: grant.map(|grant| grant.client_id),
+            act: None,
+        }
+    }
+
+    async fn check_password_policy(
+        &self,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> Result<()> {
+        let violations = self
+            .password_policy
+            .check(password, username, email)
+            .await?;
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(AuthError::WeakPassword(violations))
+        }
+    }
+
+    /// Runs `f` on the blocking thread pool. Argon2 and bcrypt are slow on
+    /// purpose, and would otherwise stall every request sharing the worker.
+    async fn with_hasher<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&PasswordHasher) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let passwords = self.passwords.clone();
+        tokio::task::spawn_blocking(move || f(&passwords))
+            .await
+            .map_err(|e| AuthError::Internal(anyhow::anyhow!("password hashing failed: {}", e)))
+    }
+
+    async fn hash_password(&self, password: &str) -> Result<String> {
+        let password = password.to_string();
+        Ok(self
+            .with_hasher(move |passwords| passwords.hash(&password))
+            .await??)
+    }
+
+    /// Checks the user's password. A correct password stored as bcrypt or
+    /// with outdated Argon2 parameters is re-hashed on the way through;
+    /// failing to do so doesn't fail the check.
+    async fn check_password(&self, user: &User, password: &str) -> Result<()> {
+        let (candidate, stored) = (password.to_string(), user.password_hash.clone());
+        let check = self
+            .with_hasher(move |passwords| passwords.verify(&candidate, &stored))
+            .await??;
+        match check {
+            PasswordCheck::Match => Ok(()),
+            PasswordCheck::Mismatch => Err(AuthError::InvalidCredentials),
+            PasswordCheck::MatchOutdated => {
+                let rehashed = match self.hash_password(password).await {
+                    Ok(new_hash) => {
+                        self.repos
+                            .users
+                            .rehash_password(user.id, &user.password_hash, &new_hash)
+                            .await
+                    }
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = rehashed {
+                    tracing::warn!(user_id = %user.id, "Failed to upgrade password hash: {}", err);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        self.keys.jwks()
+    }
+}
+
+/// An OAuth client's hold on a session, carried into its access tokens.
+struct OAuthGrant {
+    client_id: Uuid,
+    scopes: Vec<Scope>,
+}
+
+fn oauth_token_response(response: AuthResponse, scopes: &[Scope]) -> OAuthTokenResponse {
+    OAuthTokenResponse {
+        access_token: response.token,
+        token_type: "Bearer",
+        expires_in: response.expires_in,
+        refresh_token: response.refresh_token,
+        scope: oauth::format_scope(scopes),
+    }
+}
+
+/// `scopes` without repeats, in the order first given.
+fn dedup_scopes(mut scopes: Vec<Scope>) -> Vec<Scope> {
+    let mut seen = Vec::with_capacity(scopes.len());
+    scopes.retain(|scope| {
+        let new = !seen.contains(scope);
+        seen.push(*scope);
+        new
+    });
+    scopes
+}
+
+/// Ten lowercase base32 characters in two groups, e.g. `k3j7q-m2xpa`.
+fn generate_recovery_code() -> String {
+    let code = data_encoding::BASE32_NOPAD
+        .encode(&rand::random::<[u8; 10]>())
+        .to_lowercase();
+    format!("{}-{}", &code[..5], &code[5..10])
+}
+
+/// Users may type recovery codes without the dash or in upper case.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn account_throttle_key(email: &str) -> String {
+    format!("account:{}", email)
+}
+
+fn client_throttle_key(ip: IpAddr) -> String {
+    format!("ip:{}", ip)
+}
+
+fn login_throttled(wait: Duration, locked: bool) -> AuthError {
+    // Whole seconds for `Retry-After`, rounded up so clients don't retry early.
+    let retry_after = (wait.num_milliseconds() + 999) / 1000;
+    AuthError::LoginThrottled {
+        retry_after: retry_after.max(1) as u64,
+        locked,
+    }
+}
+
+fn oidc_error(provider: &Provider, err: OidcError) -> AuthError {
+    match err {
+        OidcError::Provider(message) => {
+            tracing::error!(provider = %provider.id, "Identity provider error: {}", message);
+            AuthError::IdentityProviderUnavailable
+        }
+        OidcError::Rejected(message) => {
+            tracing::warn!(provider = %provider.id, "Identity provider sign-in rejected: {}", message);
+            AuthError::InvalidCredentials
+        }
+    }
+}
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// A JWT NumericDate to the millisecond.
+fn numeric_date(time: DateTime<Utc>) -> f64 {
+    time.timestamp_millis() as f64 / 1000.0
+}
+
+fn timestamp_to_datetime(timestamp: usize) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_else(Utc::now)
+}
+
+fn env_i64(key: &str, default: i64) -> i64 {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+
+    const PASSWORD: &str = "plum-violin-Ocean-47-drift";
+
+    /// Drops every email.
+    struct NoMail;
+
+    #[async_trait]
+    impl Mailer for NoMail {
+        async fn send(&self, _email: Email) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn service() -> AuthService {
+        AuthService::new(
+            Repositories::in_memory(),
+            KeySet::generate().unwrap(),
+            Arc::new(NoMail),
+        )
+    }
+
+    /// Keeps every email, so tests can follow the links in them.
+    #[derive(Default)]
+    struct Outbox(std::sync::Mutex<Vec<Email>>);
+
+    #[async_trait]
+    impl Mailer for Outbox {
+        async fn send(&self, email: Email) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(email);
+            Ok(())
+        }
+    }
+
+    impl Outbox {
+        /// Takes the first email to `to` with a `{path}?token=` link out of
+        /// the outbox and returns its token. Some mail goes out in the
+        /// background, so this waits for it to arrive.
+        async fn token(&self, to: &str, path: &str) -> String {
+            let marker = format!("{}?token=", path);
+            for _ in 0..100 {
+                {
+                    let mut emails = self.0.lock().unwrap();
+                    let found = emails
+                        .iter()
+                        .position(|email| email.to == to && email.body.contains(&marker));
+                    if let Some(index) = found {
+                        let email = emails.remove(index);
+                        let (_, link) = email.body.split_once(&marker).unwrap();
+                        return link.split_whitespace().next().unwrap().to_string();
+                    }
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            panic!("no {} link was sent to {}", path, to);
+        }
+    }
+
+    fn service_with_outbox() -> (AuthService, Arc<Outbox>) {
+        let outbox = Arc::new(Outbox::default());
+        let service = AuthService::new(
+            Repositories::in_memory(),
+            KeySet::generate().unwrap(),
+            outbox.clone(),
+        );
+        (service, outbox)
+    }
+
+    fn register_request(email: &str, username: &str, password: &str) -> RegisterRequest {
+        RegisterRequest {
+            email: email.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    fn login_request(email: &str, password: &str) -> LoginRequest {
+        LoginRequest {
+            email: email.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn register_refuses_taken_emails_and_usernames() {
+        let service = service();
+        let client = ClientInfo::default();
+        service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service
+                .register(
+                    register_request("Alice@Example.com", "alice2", PASSWORD),
+                    &client
+                )
+                .await,
+            Err(AuthError::UserExists)
+        ));
+        assert!(matches!(
+            service
+                .register(
+                    register_request("alice2@example.com", "alice", PASSWORD),
+                    &client
+                )
+                .await,
+            Err(AuthError::UserExists)
+        ));
+    }
+
+    #[tokio::test]
+    async fn login_refuses_unknown_emails_and_wrong_passwords() {
+        let service = service();
+        let client = ClientInfo::default();
+        service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", PASSWORD), &client)
+                .await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+        assert!(matches!(
+            service
+                .login(login_request("nobody@example.com", PASSWORD), &client)
+                .await,
+            Err(AuthError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            service
+                .login(
+                    login_request("alice@example.com", "not-the-password"),
+                    &client
+                )
+                .await,
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+
+    /// A verified user without a usable password.
+    fn new_user(email: &str) -> User {
+        User {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            username: email.split('@').next().unwrap().to_string(),
+            password_hash: String::new(),
+            display_name: None,
+            bio: None,
+            avatar_url: None,
+            roles: DEFAULT_ROLES.to_vec(),
+            email_verified_at: Some(Utc::now()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    async fn add_user(service: &AuthService, email: &str) -> User {
+        service.repos.users.create(new_user(email)).await.unwrap()
+    }
+
+    /// Registers a software passkey for the user.
+    async fn add_passkey(service: &AuthService, user: &User) -> webauthn::tests::Authenticator {
+        let rp = &service.relying_party;
+        let mut authenticator = webauthn::tests::Authenticator::new(rp);
+        let options = service.start_passkey_registration(user.id).await.unwrap();
+        let credential = authenticator.register(rp, &options.challenge, "packed");
+        let request = FinishPasskeyRegistrationRequest {
+            name: Some("Laptop".to_string()),
+            credential,
+        };
+        let passkey = service
+            .finish_passkey_registration(user.id, request)
+            .await
+            .unwrap();
+        assert_eq!(passkey.name.as_deref(), Some("Laptop"));
+        authenticator
+    }
+
+    #[tokio::test]
+    async fn passkeys_sign_in_their_user() {
+        let service = service();
+        let client = ClientInfo::default();
+        let user = add_user(&service, "alice@example.com").await;
+        let mut authenticator = add_passkey(&service, &user).await;
+
+        for _ in 0..2 {
+            let options = service
+                .start_passkey_login(Some("alice@example.com"))
+                .await
+                .unwrap();
+            assert_eq!(options.allow_credentials.len(), 1);
+            assert_eq!(options.allow_credentials[0].id, authenticator.id());
+
+            let assertion = authenticator.assert(
+                &service.relying_party,
+                &options.challenge,
+                Some(webauthn::user_handle(user.id)),
+            );
+            let response = service
+                .finish_passkey_login(assertion, &client)
+                .await
+                .unwrap();
+            assert_eq!(response.user.id, user.id);
+        }
+    }
+
+    #[tokio::test]
+    async fn passkey_counters_must_move_forward() {
+        let service = service();
+        let client = ClientInfo::default();
+        let user = add_user(&service, "alice@example.com").await;
+        let mut authenticator = add_passkey(&service, &user).await;
+
+        let options = service.start_passkey_login(None).await.unwrap();
+        let assertion = authenticator.assert(&service.relying_party, &options.challenge, None);
+        service
+            .finish_passkey_login(assertion, &client)
+            .await
+            .unwrap();
+
+        // A clone of the authenticator still counting from where it was copied.
+        authenticator.sign_count = 0;
+        let options = service.start_passkey_login(None).await.unwrap();
+        let assertion = authenticator.assert(&service.relying_party, &options.challenge, None);
+        assert!(matches!(
+            service.finish_passkey_login(assertion, &client).await,
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+
+    #[tokio::test]
+    async fn passkey_challenges_are_single_use() {
+        let service = service();
+        let client = ClientInfo::default();
+        let user = add_user(&service, "alice@example.com").await;
+        let rp = &service.relying_party;
+
+        let mut authenticator = webauthn::tests::Authenticator::new(rp);
+        let options = service.start_passkey_registration(user.id).await.unwrap();
+        let registration =
+            |authenticator: &mut webauthn::tests::Authenticator| FinishPasskeyRegistrationRequest {
+                name: None,
+                credential: authenticator.register(rp, &options.challenge, "none"),
+            };
+        service
+            .finish_passkey_registration(user.id, registration(&mut authenticator))
+            .await
+            .unwrap();
+        let mut second = webauthn::tests::Authenticator::new(rp);
+        assert!(matches!(
+            service
+                .finish_passkey_registration(user.id, registration(&mut second))
+                .await,
+            Err(AuthError::InvalidToken)
+        ));
+
+        let options = service.start_passkey_login(None).await.unwrap();
+        let assertion = authenticator.assert(rp, &options.challenge, None);
+        let replayed = AuthenticationCredential {
+            id: assertion.id.clone(),
+            response: webauthn::AssertionResponse {
+                client_data_json: assertion.response.client_data_json.clone(),
+                authenticator_data: assertion.response.authenticator_data.clone(),
+                signature: assertion.response.signature.clone(),
+                user_handle: None,
+            },
+        };
+        service
+            .finish_passkey_login(assertion, &client)
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.finish_passkey_login(replayed, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    async fn oidc_login(
+        service: &AuthService,
+        idp: &oidc::tests::MockIdp,
+        claims: serde_json::Value,
+    ) -> Result<LoginResponse> {
+        let start = service.start_oidc_login("mock").await.unwrap();
+        let code = idp.sign_in(&start.authorization_url, claims);
+        let callback = OidcCallbackRequest {
+            code,
+            state: start.state,
+        };
+        service
+            .finish_oidc_login("mock", callback, &ClientInfo::default())
+            .await
+    }
+
+    fn signed_in(response: Result<LoginResponse>) -> Uuid {
+        match response {
+            Ok(LoginResponse::Authenticated(auth)) => auth.user.id,
+            Ok(LoginResponse::TwoFactorRequired(_)) => panic!("unexpected 2FA step"),
+            Err(err) => panic!("sign-in failed: {:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn oidc_sign_in_creates_an_account_once() {
+        let idp = oidc::tests::MockIdp::start().await;
+        let mut service = service();
+        service.identity_providers = idp.providers("mock");
+
+        let claims = json!({
+            "sub": "123",
+            "email": "Alice@Example.com",
+            "email_verified": true,
+            "preferred_username": "alice",
+        });
+        let user_id = signed_in(oidc_login(&service, &idp, claims).await);
+        let user = service
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.email, "alice@example.com");
+        assert_eq!(user.username, "alice");
+        assert!(user.email_verified_at.is_some());
+
+        // The identity is found by its subject, whatever email it has now.
+        let claims = json!({ "sub": "123", "email": "alice@elsewhere.example" });
+        assert_eq!(signed_in(oidc_login(&service, &idp, claims).await), user_id);
+    }
+
+    #[tokio::test]
+    async fn oidc_links_accounts_by_verified_email_only() {
+        let idp = oidc::tests::MockIdp::start().await;
+        let mut service = service();
+        service.identity_providers = idp.providers("mock");
+        let alice = add_user(&service, "alice@example.com").await;
+
+        let claims = json!({ "sub": "1", "email": "alice@example.com", "email_verified": false });
+        assert!(matches!(
+            oidc_login(&service, &idp, claims).await,
+            Err(AuthError::BadRequest(_))
+        ));
+        let claims = json!({ "sub": "1", "email": "alice@example.com", "email_verified": true });
+        assert_eq!(
+            signed_in(oidc_login(&service, &idp, claims).await),
+            alice.id
+        );
+
+        // Nobody has proved they own bob's address yet.
+        let bob = User {
+            email_verified_at: None,
+            ..new_user("bob@example.com")
+        };
+        service.repos.users.create(bob).await.unwrap();
+        let claims = json!({ "sub": "2", "email": "bob@example.com", "email_verified": true });
+        assert!(matches!(
+            oidc_login(&service, &idp, claims).await,
+            Err(AuthError::UserExists)
+        ));
+    }
+
+    #[tokio::test]
+    async fn oidc_callbacks_check_state_and_nonce() {
+        let idp = oidc::tests::MockIdp::start().await;
+        let mut service = service();
+        service.identity_providers = idp.providers("mock");
+        let client = ClientInfo::default();
+        let claims = json!({ "sub": "1", "email": "alice@example.com", "email_verified": true });
+
+        let start = service.start_oidc_login("mock").await.unwrap();
+        let code = idp.sign_in(&start.authorization_url, claims.clone());
+        let callback = || OidcCallbackRequest {
+            code: code.clone(),
+            state: start.state.clone(),
+        };
+        signed_in(service.finish_oidc_login("mock", callback(), &client).await);
+        assert!(matches!(
+            service.finish_oidc_login("mock", callback(), &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+
+        let start = service.start_oidc_login("mock").await.unwrap();
+        let code = idp.sign_in(&start.authorization_url, claims.clone());
+        assert!(matches!(
+            service
+                .finish_oidc_login(
+                    "mock",
+                    OidcCallbackRequest {
+                        code,
+                        state: "forged".to_string(),
+                    },
+                    &client,
+                )
+                .await,
+            Err(AuthError::InvalidToken)
+        ));
+
+        let mut replayed = claims;
+        replayed["nonce"] = json!("from-another-sign-in");
+        assert!(matches!(
+            oidc_login(&service, &idp, replayed).await,
+            Err(AuthError::InvalidCredentials)
+        ));
+    }
+
+    /// A breached-password directory listing only `password`, in the
+    /// layout of the Have I Been Pwned range API.
+    fn breached_passwords_dir(password: &str) -> std::path::PathBuf {
+        use sha1::{Digest, Sha1};
+
+        let dir = std::env::temp_dir().join(format!("breached-{}", Uuid::new_v4().simple()));
+        std::fs::create_dir(&dir).unwrap();
+        let digest = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = digest.split_at(5);
+        std::fs::write(
+            dir.join(format!("{}.txt", prefix)),
+            format!("0000000000000000000000000000000000A:3\r\n{}:42\r\n", suffix),
+        )
+        .unwrap();
+        dir
+    }
+
+    const BREACHED: &str = "tangerine-Harbor-93-quilt";
+
+    fn policy_service() -> AuthService {
+        let mut service = service();
+        service.password_policy =
+            PasswordPolicy::new(10, 3, Some(breached_passwords_dir(BREACHED)));
+        service
+    }
+
+    fn violations(result: Result<()>) -> Vec<&'static str> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(AuthError::WeakPassword(violations)) => {
+                violations.iter().map(|violation| violation.code).collect()
+            }
+            Err(err) => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn password_policy_names_each_problem() {
+        let service = policy_service();
+        let check = |password: &'static str| {
+            service.check_password_policy(password, "alice", "wonder@example.com")
+        };
+
+        assert_eq!(violations(check(PASSWORD).await), Vec::<&str>::new());
+        assert_eq!(violations(check("Qx7!vL2#p").await), ["too_short"]);
+        assert_eq!(violations(check("password1234").await), ["too_weak"]);
+        assert_eq!(
+            violations(check("plum-Alice-violin-47").await),
+            ["contains_username"]
+        );
+        assert_eq!(
+            violations(check("plum-WONDER-violin-47").await),
+            ["contains_email"]
+        );
+        assert_eq!(violations(check(BREACHED).await), ["breached"]);
+    }
+
+    #[tokio::test]
+    async fn weak_passwords_are_refused_on_register_and_reset() {
+        let service = policy_service();
+        let client = ClientInfo::default();
+
+        let result = service
+            .register(
+                register_request("wonder@example.com", "alice", BREACHED),
+                &client,
+            )
+            .await
+            .map(|_| ());
+        assert_eq!(violations(result), ["breached"]);
+        assert!(service
+            .repos
+            .users
+            .find_by_email("wonder@example.com")
+            .await
+            .unwrap()
+            .is_none());
+
+        let user = User {
+            username: "alice".to_string(),
+            ..new_user("wonder@example.com")
+        };
+        let user = service.repos.users.create(user).await.unwrap();
+        let token = generate_opaque_token();
+        service
+            .repos
+            .password_resets
+            .create(PasswordResetToken {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                token_hash: hash_token(&token),
+                expires_at: Utc::now() + Duration::minutes(5),
+                created_at: Utc::now(),
+                used_at: None,
+            })
+            .await
+            .unwrap();
+
+        let error = service
+            .reset_password(&token, "wonder-plum-violin-47", &client)
+            .await
+            .unwrap_err();
+        let response = axum::response::IntoResponse::into_response(error);
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["violations"][0]["code"], "contains_email");
+
+        // The refused password didn't spend the link.
+        service
+            .reset_password(&token, PASSWORD, &client)
+            .await
+            .unwrap();
+    }
+
+    /// One digit short, so it can never match whatever the clock says.
+    const WRONG_CODE: &str = "00000";
+
+    /// Registers a user with [`PASSWORD`] and turns on 2FA for them.
+    async fn add_two_factor_user(
+        service: &AuthService,
+        email: &str,
+    ) -> (User, String, RecoveryCodesResponse) {
+        let client = ClientInfo::default();
+        let username = email.split('@').next().unwrap();
+        service
+            .register(register_request(email, username, PASSWORD), &client)
+            .await
+            .unwrap();
+        let user = service
+            .repos
+            .users
+            .find_by_email(email)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let setup = service.setup_two_factor(user.id).await.unwrap();
+        let code = totp::tests::code_at(&setup.secret, Utc::now());
+        let recovery_codes = service
+            .confirm_two_factor(user.id, &code, &client)
+            .await
+            .unwrap();
+        (user, setup.secret, recovery_codes)
+    }
+
+    async fn two_factor_token(service: &AuthService, email: &str) -> String {
+        match service
+            .login(login_request(email, PASSWORD), &ClientInfo::default())
+            .await
+            .unwrap()
+        {
+            LoginResponse::TwoFactorRequired(challenge) => challenge.two_factor_token,
+            LoginResponse::Authenticated(_) => panic!("2FA was not asked for"),
+        }
+    }
+
+    #[tokio::test]
+    async fn wrong_codes_count_against_the_account_across_challenges() {
+        let service = service();
+        let client = ClientInfo::default();
+        add_two_factor_user(&service, "alice@example.com").await;
+
+        // Each password login brings a new challenge, but the wrong codes
+        // entered on all of them add up.
+        for _ in 0..2 {
+            let token = two_factor_token(&service, "alice@example.com").await;
+            for _ in 0..2 {
+                assert!(matches!(
+                    service.verify_two_factor(&token, WRONG_CODE, &client).await,
+                    Err(AuthError::InvalidTwoFactorCode)
+                ));
+            }
+        }
+
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", PASSWORD), &client)
+                .await,
+            Err(AuthError::LoginThrottled { locked: false, .. })
+        ));
+    }
+
+    /// The user's audit events of one type, newest first.
+    async fn audit_events(
+        service: &AuthService,
+        user_id: Uuid,
+        event_type: AuditEventType,
+    ) -> Vec<AuditEvent> {
+        service
+            .query_audit_log(AuditLogQuery {
+                user_id: Some(user_id),
+                event_type: Some(event_type),
+                from: None,
+                to: None,
+                limit: None,
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn refresh_tokens_rotate_and_replays_end_the_session() {
+        let service = service();
+        let client = ClientInfo::default();
+        let first = service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+
+        let second = service
+            .refresh(&first.refresh_token, &client)
+            .await
+            .unwrap();
+        assert_ne!(second.refresh_token, first.refresh_token);
+        service.validate_token(&second.token).await.unwrap();
+
+        // Replaying the spent token ends the session it belonged to...
+        assert!(matches!(
+            service.refresh(&first.refresh_token, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert_eq!(
+            audit_events(&service, first.user.id, AuditEventType::RefreshTokenReused)
+                .await
+                .len(),
+            1
+        );
+
+        // ...so the current refresh and access tokens stop working too.
+        assert!(matches!(
+            service.refresh(&second.refresh_token, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            service.validate_token(&second.token).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(service
+            .list_sessions(first.user.id, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    const REDIRECT_URI: &str = "https://app.example.com/callback";
+
+    /// A PKCE code verifier of the shortest allowed length.
+    fn code_verifier(c: char) -> String {
+        c.to_string().repeat(43)
+    }
+
+    async fn add_oauth_client(service: &AuthService, owner: &User) -> OAuthClientResponse {
+        service
+            .register_oauth_client(
+                owner.id,
+                RegisterOAuthClientRequest {
+                    name: "Reader app".to_string(),
+                    redirect_uris: vec![REDIRECT_URI.to_string()],
+                    scopes: vec![Scope::CommentsWrite],
+                    confidential: true,
+                },
+            )
+            .await
+            .unwrap()
+    }
+
+    /// Has `user` approve `client`, and returns the code the client gets.
+    async fn authorization_code(
+        service: &AuthService,
+        user: &User,
+        client: &OAuthClientResponse,
+        verifier: &str,
+    ) -> String {
+        let decision = AuthorizationDecision {
+            request: AuthorizationRequest {
+                response_type: "code".to_string(),
+                client_id: client.client_id.to_string(),
+                redirect_uri: REDIRECT_URI.to_string(),
+                scope: None,
+                state: Some("xyz".to_string()),
+                code_challenge: Some(oidc::code_challenge(verifier)),
+                code_challenge_method: Some("S256".to_string()),
+            },
+            approve: true,
+        };
+        let response = service
+            .decide_authorization(user.id, decision)
+            .await
+            .unwrap();
+        let redirect = reqwest::Url::parse(&response.redirect_to).unwrap();
+        redirect
+            .query_pairs()
+            .find(|(name, _)| name == "code")
+            .map(|(_, code)| code.into_owned())
+            .unwrap()
+    }
+
+    fn code_request(code: &str, redirect_uri: &str, verifier: &str) -> TokenRequest {
+        TokenRequest {
+            grant_type: "authorization_code".to_string(),
+            code: Some(code.to_string()),
+            redirect_uri: Some(redirect_uri.to_string()),
+            code_verifier: Some(verifier.to_string()),
+            refresh_token: None,
+            client_id: None,
+            client_secret: None,
+        }
+    }
+
+    async fn exchange(
+        service: &AuthService,
+        client: &OAuthClientResponse,
+        req: &TokenRequest,
+    ) -> Result<OAuthTokenResponse> {
+        let client_id = client.client_id.to_string();
+        service
+            .exchange_token(
+                Some(&client_id),
+                client.client_secret.as_deref(),
+                req,
+                &ClientInfo::default(),
+            )
+            .await
+    }
+
+    fn oauth_error<T: std::fmt::Debug>(result: Result<T>) -> &'static str {
+        match result {
+            Err(AuthError::OAuth { error, .. }) => error,
+            other => panic!("expected an OAuth error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn oauth_codes_need_the_client_secret_verifier_and_redirect_uri() {
+        let service = service();
+        let user = add_user(&service, "alice@example.com").await;
+        let client = add_oauth_client(&service, &user).await;
+        let verifier = code_verifier('v');
+
+        let code = authorization_code(&service, &user, &client, &verifier).await;
+        let client_id = client.client_id.to_string();
+        let wrong_secret = service
+            .exchange_token(
+                Some(&client_id),
+                Some("not-the-secret"),
+                &code_request(&code, REDIRECT_URI, &verifier),
+                &ClientInfo::default(),
+            )
+            .await;
+        assert_eq!(oauth_error(wrong_secret), "invalid_client");
+
+        let code = authorization_code(&service, &user, &client, &verifier).await;
+        assert_eq!(
+            oauth_error(
+                exchange(
+                    &service,
+                    &client,
+                    &code_request(&code, REDIRECT_URI, &code_verifier('w'))
+                )
+                .await
+            ),
+            "invalid_grant"
+        );
+
+        let code = authorization_code(&service, &user, &client, &verifier).await;
+        assert_eq!(
+            oauth_error(
+                exchange(
+                    &service,
+                    &client,
+                    &code_request(&code, "https://app.example.com/other", &verifier)
+                )
+                .await
+            ),
+            "invalid_grant"
+        );
+
+        let code = authorization_code(&service, &user, &client, &verifier).await;
+        let tokens = exchange(
+            &service,
+            &client,
+            &code_request(&code, REDIRECT_URI, &verifier),
+        )
+        .await
+        .unwrap();
+        assert_eq!(tokens.scope, "comments:write");
+    }
+
+    #[tokio::test]
+    async fn reused_authorization_codes_end_the_session() {
+        let service = service();
+        let user = add_user(&service, "alice@example.com").await;
+        let client = add_oauth_client(&service, &user).await;
+        let verifier = code_verifier('v');
+        let code = authorization_code(&service, &user, &client, &verifier).await;
+        let req = code_request(&code, REDIRECT_URI, &verifier);
+
+        let tokens = exchange(&service, &client, &req).await.unwrap();
+        service.validate_token(&tokens.access_token).await.unwrap();
+
+        assert_eq!(
+            oauth_error(exchange(&service, &client, &req).await),
+            "invalid_grant"
+        );
+        assert!(matches!(
+            service.validate_token(&tokens.access_token).await,
+            Err(AuthError::InvalidToken)
+        ));
+        let refresh = TokenRequest {
+            grant_type: "refresh_token".to_string(),
+            code: None,
+            redirect_uri: None,
+            code_verifier: None,
+            refresh_token: Some(tokens.refresh_token),
+            client_id: None,
+            client_secret: None,
+        };
+        assert_eq!(
+            oauth_error(exchange(&service, &client, &refresh).await),
+            "invalid_grant"
+        );
+    }
+
+    #[tokio::test]
+    async fn introspection_reports_revoked_tokens_inactive() {
+        let service = service();
+        let user = add_user(&service, "alice@example.com").await;
+        let client = add_oauth_client(&service, &user).await;
+        let verifier = code_verifier('v');
+        let code = authorization_code(&service, &user, &client, &verifier).await;
+        let tokens = exchange(
+            &service,
+            &client,
+            &code_request(&code, REDIRECT_URI, &verifier),
+        )
+        .await
+        .unwrap();
+
+        let introspection = service.introspect(&tokens.access_token).await.unwrap();
+        assert!(introspection.active);
+        assert_eq!(introspection.client_id, Some(client.client_id));
+        assert_eq!(introspection.scope.as_deref(), Some("comments:write"));
+
+        let client_id = client.client_id.to_string();
+        service
+            .revoke_oauth_token(
+                Some(&client_id),
+                client.client_secret.as_deref(),
+                &tokens.access_token,
+            )
+            .await
+            .unwrap();
+        let introspection = service.introspect(&tokens.access_token).await.unwrap();
+        assert!(!introspection.active);
+        assert_eq!(introspection.sub, None);
+    }
+
+    fn retry_after(err: AuthError) -> (axum::http::StatusCode, String) {
+        let response = axum::response::IntoResponse::into_response(err);
+        let retry_after = response.headers()[axum::http::header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        (response.status(), retry_after)
+    }
+
+    #[tokio::test]
+    async fn failed_logins_back_off_then_lock_the_account() {
+        let (mut service, outbox) = service_with_outbox();
+        service.login_throttling.lockout_threshold = 5;
+        let client = ClientInfo::default();
+        service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+        let wrong = || login_request("alice@example.com", "not-the-password");
+        let right = || login_request("alice@example.com", PASSWORD);
+
+        // Three free attempts; the fourth failure starts a one second wait.
+        for _ in 0..4 {
+            assert!(matches!(
+                service.login(wrong(), &client).await,
+                Err(AuthError::InvalidCredentials)
+            ));
+        }
+        let err = service.login(right(), &client).await.unwrap_err();
+        assert!(matches!(
+            err,
+            AuthError::LoginThrottled {
+                retry_after: 1,
+                locked: false
+            }
+        ));
+        assert_eq!(
+            retry_after(err),
+            (axum::http::StatusCode::TOO_MANY_REQUESTS, "1".to_string())
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let err = service.login(wrong(), &client).await.unwrap_err();
+        assert!(matches!(
+            err,
+            AuthError::LoginThrottled { locked: true, .. }
+        ));
+        let lockout = service.login_throttling.lockout.num_seconds().to_string();
+        assert_eq!(retry_after(err), (axum::http::StatusCode::LOCKED, lockout));
+        assert!(matches!(
+            service.login(right(), &client).await,
+            Err(AuthError::LoginThrottled { locked: true, .. })
+        ));
+
+        // The owner unlocks it with the link we emailed them.
+        let token = outbox.token("alice@example.com", "/unlock-account").await;
+        service.unlock_account(&token, &client).await.unwrap();
+        let user = service
+            .repos
+            .users
+            .find_by_email("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        let unlocks = audit_events(&service, user.id, AuditEventType::AccountUnlocked).await;
+        assert_eq!(unlocks[0].details, json!({ "method": "email" }));
+        assert!(matches!(
+            service.login(right(), &client).await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+        assert!(matches!(
+            service.unlock_account(&token, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn admins_can_unlock_accounts() {
+        let mut service = service();
+        service.login_throttling.lockout_threshold = 1;
+        let client = ClientInfo::default();
+        service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+        let user = service
+            .repos
+            .users
+            .find_by_email("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        let admin = add_user(&service, "admin@example.com").await;
+
+        assert!(matches!(
+            service
+                .login(
+                    login_request("alice@example.com", "nope-nope-nope"),
+                    &client
+                )
+                .await,
+            Err(AuthError::LoginThrottled { locked: true, .. })
+        ));
+        service
+            .unlock_user(admin.id, user.id, &client)
+            .await
+            .unwrap();
+        let unlocks = audit_events(&service, user.id, AuditEventType::AccountUnlocked).await;
+        assert_eq!(unlocks.len(), 1);
+        assert_eq!(
+            unlocks[0].details,
+            json!({ "method": "admin", "actor_id": admin.id })
+        );
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", PASSWORD), &client)
+                .await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_emails_take_as_long_as_wrong_passwords() {
+        let service = service();
+        let client = ClientInfo::default();
+        service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+
+        let mut known = std::time::Duration::MAX;
+        let mut unknown = std::time::Duration::MAX;
+        for _ in 0..2 {
+            for (email, fastest) in [
+                ("alice@example.com", &mut known),
+                ("nobody@example.com", &mut unknown),
+            ] {
+                let started = std::time::Instant::now();
+                let result = service
+                    .login(login_request(email, "not-the-password"), &client)
+                    .await;
+                assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+                *fastest = (*fastest).min(started.elapsed());
+            }
+        }
+
+        // Both verify a hash, which dwarfs everything else a login does.
+        assert!(
+            unknown * 2 > known,
+            "unknown email took {:?}, wrong password {:?}",
+            unknown,
+            known
+        );
+    }
+
+    #[tokio::test]
+    async fn bcrypt_hashes_are_upgraded_to_argon2id_on_login() {
+        let service = service();
+        let client = ClientInfo::default();
+        let user = service
+            .repos
+            .users
+            .create(User {
+                password_hash: bcrypt::hash(PASSWORD, 4).unwrap(),
+                ..new_user("alice@example.com")
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service
+                .login(
+                    login_request("alice@example.com", "not-the-password"),
+                    &client
+                )
+                .await,
+            Err(AuthError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", PASSWORD), &client)
+                .await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+
+        let stored = service
+            .repos
+            .users
+            .find_by_id(user.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .password_hash;
+        assert!(stored.starts_with("$argon2id$"), "{}", stored);
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", PASSWORD), &client)
+                .await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn user_revocations_are_listed_until_impersonation_tokens_expire() {
+        let mut service = service();
+        service.access_token_ttl = Duration::minutes(15);
+        service.impersonation_ttl = Duration::minutes(60);
+        let (recent, old) = (Uuid::new_v4(), Uuid::new_v4());
+        for (user_id, minutes_ago) in [(recent, 30), (old, 61)] {
+            service
+                .repos
+                .revocations
+                .revoke_user(UserRevocation {
+                    user_id,
+                    revoked_before: Utc::now() - Duration::minutes(minutes_ago),
+                })
+                .await
+                .unwrap();
+        }
+
+        let listed: Vec<Uuid> = service
+            .revocation_list()
+            .await
+            .unwrap()
+            .users
+            .iter()
+            .map(|revocation| revocation.user_id)
+            .collect();
+        assert_eq!(listed, vec![recent]);
+    }
+
+    #[tokio::test]
+    async fn changing_the_password_signs_out_other_sessions() {
+        let service = service();
+        let client = ClientInfo::default();
+        let current = service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+        let Ok(LoginResponse::Authenticated(other)) = service
+            .login(login_request("alice@example.com", PASSWORD), &client)
+            .await
+        else {
+            panic!("login failed");
+        };
+        let user_id = current.user.id;
+        let sid = service.validate_token(&current.token).await.unwrap().sid;
+        let new_password = "copper-Lantern-58-meadow";
+
+        assert!(matches!(
+            service
+                .change_password(user_id, sid, "not-the-password", new_password, &client)
+                .await,
+            Err(AuthError::InvalidCredentials)
+        ));
+        assert!(violations(
+            service
+                .change_password(user_id, sid, PASSWORD, "short", &client)
+                .await
+        )
+        .contains(&"too_short"));
+
+        service
+            .change_password(user_id, sid, PASSWORD, new_password, &client)
+            .await
+            .unwrap();
+
+        // The device that made the change stays signed in; the other doesn't.
+        service.validate_token(&current.token).await.unwrap();
+        service
+            .refresh(&current.refresh_token, &client)
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.validate_token(&other.token).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            service.refresh(&other.refresh_token, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", PASSWORD), &client)
+                .await,
+            Err(AuthError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", new_password), &client)
+                .await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+        let changes = audit_events(&service, user_id, AuditEventType::PasswordChanged).await;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].details["method"], "change");
+    }
+
+    #[tokio::test]
+    async fn two_factor_is_enabled_only_with_a_current_code() {
+        let service = service();
+        let client = ClientInfo::default();
+        service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+        let user = service
+            .repos
+            .users
+            .find_by_email("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let setup = service.setup_two_factor(user.id).await.unwrap();
+        assert!(setup.otpauth_uri.contains(&setup.secret));
+        let stale = totp::tests::code_at(&setup.secret, Utc::now() - Duration::minutes(2));
+        assert!(matches!(
+            service.confirm_two_factor(user.id, &stale, &client).await,
+            Err(AuthError::InvalidTwoFactorCode)
+        ));
+        // Not confirmed yet, so the password alone still signs in.
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", PASSWORD), &client)
+                .await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+
+        let code = totp::tests::code_at(&setup.secret, Utc::now());
+        let recovery = service
+            .confirm_two_factor(user.id, &code, &client)
+            .await
+            .unwrap();
+        assert_eq!(recovery.recovery_codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(
+            audit_events(&service, user.id, AuditEventType::TwoFactorEnabled)
+                .await
+                .len(),
+            1
+        );
+        assert!(matches!(
+            service.setup_two_factor(user.id).await,
+            Err(AuthError::BadRequest(_))
+        ));
+
+        // The code used to confirm can't be replayed to log in.
+        let token = two_factor_token(&service, "alice@example.com").await;
+        assert!(matches!(
+            service.verify_two_factor(&token, &code, &client).await,
+            Err(AuthError::InvalidTwoFactorCode)
+        ));
+    }
+
+    #[tokio::test]
+    async fn recovery_codes_work_once() {
+        let service = service();
+        let client = ClientInfo::default();
+        let (_, _, recovery) = add_two_factor_user(&service, "alice@example.com").await;
+        let code = &recovery.recovery_codes[0];
+
+        let token = two_factor_token(&service, "alice@example.com").await;
+        service
+            .verify_two_factor(&token, code, &client)
+            .await
+            .unwrap();
+
+        let token = two_factor_token(&service, "alice@example.com").await;
+        assert!(matches!(
+            service.verify_two_factor(&token, code, &client).await,
+            Err(AuthError::InvalidTwoFactorCode)
+        ));
+        service
+            .verify_two_factor(&token, &recovery.recovery_codes[1], &client)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_challenge_is_spent_after_too_many_wrong_codes() {
+        let service = service();
+        let client = ClientInfo::default();
+        let (_, secret, _) = add_two_factor_user(&service, "alice@example.com").await;
+        let token = two_factor_token(&service, "alice@example.com").await;
+
+        for attempt in 1..=MAX_TWO_FACTOR_ATTEMPTS {
+            // The account backoff starts after the fourth failure.
+            if attempt == 5 {
+                tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+            }
+            assert!(matches!(
+                service.verify_two_factor(&token, WRONG_CODE, &client).await,
+                Err(AuthError::InvalidTwoFactorCode)
+            ));
+        }
+
+        // Even the right code is refused now; the user has to log in again.
+        let code = totp::tests::code_at(&secret, Utc::now());
+        assert!(matches!(
+            service.verify_two_factor(&token, &code, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn revoking_a_session_ends_its_refresh_tokens() {
+        let service = service();
+        let client = ClientInfo::default();
+        let laptop = service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+        let Ok(LoginResponse::Authenticated(phone)) = service
+            .login(login_request("alice@example.com", PASSWORD), &client)
+            .await
+        else {
+            panic!("login failed");
+        };
+        let user_id = laptop.user.id;
+        let laptop_sid = service.validate_token(&laptop.token).await.unwrap().sid;
+        let phone_sid = service
+            .validate_token(&phone.token)
+            .await
+            .unwrap()
+            .sid
+            .unwrap();
+
+        let sessions = service.list_sessions(user_id, laptop_sid).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions
+            .iter()
+            .all(|session| session.current == (Some(session.id) == laptop_sid)));
+
+        // Rotated once, so the family holds more than one token.
+        let phone = service
+            .refresh(&phone.refresh_token, &client)
+            .await
+            .unwrap();
+
+        let mallory = add_user(&service, "mallory@example.com").await;
+        assert!(matches!(
+            service.revoke_session(mallory.id, phone_sid, &client).await,
+            Err(AuthError::SessionNotFound)
+        ));
+        service
+            .revoke_session(user_id, phone_sid, &client)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service.refresh(&phone.refresh_token, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            service.validate_token(&phone.token).await,
+            Err(AuthError::InvalidToken)
+        ));
+        service
+            .refresh(&laptop.refresh_token, &client)
+            .await
+            .unwrap();
+        assert_eq!(service.list_sessions(user_id, None).await.unwrap().len(), 1);
+        assert!(matches!(
+            service.revoke_session(user_id, phone_sid, &client).await,
+            Err(AuthError::SessionNotFound)
+        ));
+        assert_eq!(
+            audit_events(&service, user_id, AuditEventType::SessionRevoked)
+                .await
+                .len(),
+            1
+        );
+    }
+
+    fn access_token_request(scopes: Vec<Scope>) -> CreatePersonalAccessTokenRequest {
+        CreatePersonalAccessTokenRequest {
+            name: "CI".to_string(),
+            scopes,
+            expires_in_days: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn personal_access_tokens_carry_their_scopes_until_deleted() {
+        let service = service();
+        let client = ClientInfo::default();
+        let alice = add_user(&service, "alice@example.com").await;
+        let mallory = add_user(&service, "mallory@example.com").await;
+
+        // Authors can't hand out moderation rights they don't have.
+        assert!(matches!(
+            service
+                .create_personal_access_token(
+                    alice.id,
+                    access_token_request(vec![Scope::CommentsModerate]),
+                    &client
+                )
+                .await,
+            Err(AuthError::BadRequest(_))
+        ));
+
+        let created = service
+            .create_personal_access_token(
+                alice.id,
+                access_token_request(vec![Scope::CommentsWrite, Scope::CommentsWrite]),
+                &client,
+            )
+            .await
+            .unwrap();
+        let token = created.token.unwrap();
+        let creations = audit_events(
+            &service,
+            alice.id,
+            AuditEventType::PersonalAccessTokenCreated,
+        )
+        .await;
+        assert_eq!(creations.len(), 1);
+        assert_eq!(
+            creations[0].details,
+            json!({ "token_id": created.id, "name": "CI", "scopes": ["comments:write"] })
+        );
+        let identity = service.verify_personal_access_token(&token).await.unwrap();
+        assert_eq!(identity.user_id, alice.id);
+        assert_eq!(identity.scopes, vec![Scope::CommentsWrite]);
+        assert!(
+            service.list_personal_access_tokens(alice.id).await.unwrap()[0]
+                .last_used_at
+                .is_some()
+        );
+        // Not a session token: it can't manage the account.
+        assert!(matches!(
+            service.validate_token(&token).await,
+            Err(AuthError::InvalidToken)
+        ));
+
+        assert!(matches!(
+            service
+                .delete_personal_access_token(mallory.id, created.id)
+                .await,
+            Err(AuthError::AccessTokenNotFound)
+        ));
+        service
+            .delete_personal_access_token(alice.id, created.id)
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.verify_personal_access_token(&token).await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn expired_personal_access_tokens_are_refused() {
+        let service = service();
+        let alice = add_user(&service, "alice@example.com").await;
+        let token = format!("{}{}", ACCESS_TOKEN_PREFIX, generate_opaque_token());
+        service
+            .repos
+            .personal_access_tokens
+            .create(PersonalAccessToken {
+                id: Uuid::new_v4(),
+                user_id: alice.id,
+                name: "CI".to_string(),
+                token_hash: hash_token(&token),
+                scopes: vec![Scope::CommentsWrite],
+                expires_at: Utc::now() - Duration::seconds(1),
+                created_at: Utc::now() - Duration::days(90),
+                last_used_at: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service.verify_personal_access_token(&token).await,
+            Err(AuthError::TokenExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn magic_links_work_once_and_only_on_the_device_that_asked() {
+        let (service, outbox) = service_with_outbox();
+        let client = ClientInfo::default();
+        add_user(&service, "alice@example.com").await;
+
+        // A plain link works from anywhere, once.
+        service
+            .send_magic_link("alice@example.com", false)
+            .await
+            .unwrap();
+        let token = outbox.token("alice@example.com", "/magic-link").await;
+        assert!(matches!(
+            service.consume_magic_link(&token, None, &client).await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+        assert!(matches!(
+            service.consume_magic_link(&token, None, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+
+        let device_token = service
+            .send_magic_link("alice@example.com", true)
+            .await
+            .unwrap()
+            .device_token
+            .unwrap();
+        let token = outbox.token("alice@example.com", "/magic-link").await;
+        for other_device in [None, Some("someone-elses-device")] {
+            assert!(matches!(
+                service
+                    .consume_magic_link(&token, other_device, &client)
+                    .await,
+                Err(AuthError::InvalidToken)
+            ));
+        }
+        // Refusing the other device didn't spend the link.
+        assert!(matches!(
+            service
+                .consume_magic_link(&token, Some(&device_token), &client)
+                .await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn only_the_latest_magic_link_works() {
+        let (service, outbox) = service_with_outbox();
+        let client = ClientInfo::default();
+        add_user(&service, "alice@example.com").await;
+
+        service
+            .send_magic_link("alice@example.com", false)
+            .await
+            .unwrap();
+        let first = outbox.token("alice@example.com", "/magic-link").await;
+        service
+            .send_magic_link("alice@example.com", false)
+            .await
+            .unwrap();
+        let second = outbox.token("alice@example.com", "/magic-link").await;
+
+        assert!(matches!(
+            service.consume_magic_link(&first, None, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(service
+            .consume_magic_link(&second, None, &client)
+            .await
+            .is_ok());
+
+        // Unknown addresses get the same answer and no mail.
+        assert!(service
+            .send_magic_link("nobody@example.com", false)
+            .await
+            .unwrap()
+            .device_token
+            .is_none());
+        assert!(outbox.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reset_links_work_once_and_sign_out_every_session() {
+        let (service, outbox) = service_with_outbox();
+        let client = ClientInfo::default();
+        let session = service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+        let new_password = "copper-Lantern-58-meadow";
+
+        service.forgot_password("alice@example.com").await.unwrap();
+        let token = outbox.token("alice@example.com", "/reset-password").await;
+        service
+            .reset_password(&token, new_password, &client)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service.validate_token(&session.token).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            service.refresh(&session.refresh_token, &client).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            service
+                .reset_password(&token, "another-Fine-61-password", &client)
+                .await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            service
+                .login(login_request("alice@example.com", new_password), &client)
+                .await,
+            Ok(LoginResponse::Authenticated(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn expired_reset_links_are_refused() {
+        let (mut service, outbox) = service_with_outbox();
+        service.password_reset_ttl = Duration::zero();
+        add_user(&service, "alice@example.com").await;
+
+        service.forgot_password("alice@example.com").await.unwrap();
+        let token = outbox.token("alice@example.com", "/reset-password").await;
+        assert!(matches!(
+            service
+                .reset_password(&token, "copper-Lantern-58-meadow", &ClientInfo::default())
+                .await,
+            Err(AuthError::TokenExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn verification_links_expire_and_can_be_sent_again() {
+        let (mut service, outbox) = service_with_outbox();
+        service.email_verification_ttl = Duration::zero();
+        let client = ClientInfo::default();
+        let registered = service
+            .register(
+                register_request("alice@example.com", "alice", PASSWORD),
+                &client,
+            )
+            .await
+            .unwrap();
+        let user_id = registered.user.id;
+
+        let expired = outbox.token("alice@example.com", "/verify-email").await;
+        assert!(matches!(
+            service.verify_email(&expired).await,
+            Err(AuthError::TokenExpired)
+        ));
+
+        service.email_verification_ttl = Duration::hours(24);
+        service.resend_verification(user_id).await.unwrap();
+        let token = outbox.token("alice@example.com", "/verify-email").await;
+        service.verify_email(&token).await.unwrap();
+
+        let user = service
+            .repos
+            .users
+            .find_by_id(user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(user.email_verified_at.is_some());
+        assert!(matches!(
+            service.verify_email(&token).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            service.resend_verification(user_id).await,
+            Err(AuthError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn role_updates_are_audited_and_end_existing_sessions() {
+        let service = service();
+        let client = ClientInfo::default();
+        let alice = add_user(&service, "alice@example.com").await;
+        let admin = add_user(&service, "admin@example.com").await;
+        let tokens = service
+            .issue_tokens(alice.clone(), Uuid::new_v4(), &client)
+            .await
+            .unwrap();
+        assert!(service.validate_token(&tokens.token).await.is_ok());
+
+        let updated = service
+            .update_roles(admin.id, alice.id, vec![Role::Editor], &client)
+            .await
+            .unwrap();
+        assert_eq!(updated.roles, vec![Role::Editor]);
+
+        let changes = audit_events(&service, alice.id, AuditEventType::RolesUpdated).await;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].details,
+            json!({ "actor_id": admin.id, "roles": ["editor"] })
+        );
+        assert!(matches!(
+            service.validate_token(&tokens.token).await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn impersonation_tokens_name_the_admin_and_can_be_revoked() {
+        use axum::{extract::FromRequestParts, http::header::AUTHORIZATION};
+
+        use crate::extractors::CurrentUser;
+
+        let service = Arc::new(service());
+        let client = ClientInfo::default();
+        let alice = add_user(&service, "alice@example.com").await;
+        let mut admin = new_user("admin@example.com");
+        admin.roles = vec![Role::Admin];
+        let admin = service.repos.users.create(admin).await.unwrap();
+        let admin_tokens = service
+            .issue_tokens(admin.clone(), Uuid::new_v4(), &client)
+            .await
+            .unwrap();
+        let admin_claims = service.validate_token(&admin_tokens.token).await.unwrap();
+
+        // Admins can't be impersonated, not even by another admin.
+        let mut other = new_user("other-admin@example.com");
+        other.roles = vec![Role::Admin];
+        let other = service.repos.users.create(other).await.unwrap();
+        assert!(matches!(
+            service.impersonate(&admin_claims, other.id, &client).await,
+            Err(AuthError::Forbidden)
+        ));
+
+        let issued = service
+            .impersonate(&admin_claims, alice.id, &client)
+            .await
+            .unwrap();
+        assert_eq!(issued.actor_id, admin.id);
+        let claims = service.validate_token(&issued.token).await.unwrap();
+        assert_eq!(claims.sub, alice.id);
+        assert_eq!(claims.act.as_ref().map(|act| act.sub), Some(admin.id));
+        assert!(claims.sid.is_none());
+        let started = audit_events(&service, alice.id, AuditEventType::ImpersonationStarted).await;
+        assert_eq!(
+            started[0].details,
+            json!({ "actor_id": admin.id, "jti": claims.jti })
+        );
+
+        // It acts through the gateway only; the account itself is off limits.
+        let (mut parts, ()) = axum::http::Request::builder()
+            .header(AUTHORIZATION, format!("Bearer {}", issued.token))
+            .body(())
+            .unwrap()
+            .into_parts();
+        assert!(matches!(
+            CurrentUser::from_request_parts(&mut parts, &service).await,
+            Err(AuthError::Forbidden)
+        ));
+
+        // Signing the user out everywhere revokes it like any other token.
+        service.logout_all(alice.id, &client).await.unwrap();
+        assert!(matches!(
+            service.validate_token(&issued.token).await,
+            Err(AuthError::InvalidToken)
+        ));
+        let revocations = service.revocation_list().await.unwrap();
+        assert!(revocations
+            .users
+            .iter()
+            .any(|revoked| revoked.user_id == alice.id
+                && revoked.revoked_before.timestamp_millis()
+                    >= (claims.iat * 1000.0).round() as i64));
+    }
+}
@@ -0,0 +1,465 @@
+//! Password strength estimate in the spirit of zxcvbn.
+//!
+//! The password is split into the cheapest run of patterns an attacker would
+//! try first: common passwords and words (also reversed, capitalised or in
+//! l33t), keyboard walks, alphabetical or numeric sequences, repeats, years
+//! and dates. Whatever is left is counted as brute force. The resulting
+//! number of guesses maps onto zxcvbn's 0 (trivial) to 4 (strong) score.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use chrono::{Datelike, Utc};
+
+/// Characters past this can only add guesses, so they aren't analysed.
+const MAX_ANALYSED_CHARS: usize = 100;
+
+const MIN_WORD_LEN: usize = 3;
+const MAX_WORD_LEN: usize = 32;
+
+/// Guesses per brute-forced character. Deliberately low, as zxcvbn does,
+/// since real attackers don't search the full character set uniformly.
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES: f64 = 50.0;
+const MIN_YEAR_SPACE: i32 = 20;
+
+/// Key positions and average neighbour count on a QWERTY keyboard.
+const KEYBOARD_STARTING_POSITIONS: f64 = 94.0;
+const KEYBOARD_AVERAGE_DEGREE: f64 = 4.6;
+
+const KEYBOARD_ROWS: [&str; 4] = [
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+];
+const SHIFTED_KEYBOARD_ROWS: [&str; 4] = [
+    "~!@#$%^&*()_+",
+    "QWERTYUIOP{}|",
+    "ASDFGHJKL:\"",
+    "ZXCVBNM<>?",
+];
+
+/// Scores `password` from 0 (guessable in under a thousand tries) to 4
+/// (more than ten billion).
+pub fn score(password: &str) -> u8 {
+    match log10_guesses(password) {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+/// A pattern found at `start..end`, with guesses kept as a base-10 logarithm
+/// throughout so long passwords can't overflow.
+struct Match {
+    start: usize,
+    end: usize,
+    log10_guesses: f64,
+}
+
+impl Match {
+    fn new(start: usize, end: usize, guesses: f64) -> Self {
+        let floor = if end - start == 1 {
+            BRUTEFORCE_CARDINALITY
+        } else {
+            MIN_SUBMATCH_GUESSES
+        };
+        Self {
+            start,
+            end,
+            log10_guesses: guesses.max(floor).log10(),
+        }
+    }
+}
+
+fn log10_guesses(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().take(MAX_ANALYSED_CHARS).collect();
+    if chars.is_empty() {
+        return 0.0;
+    }
+
+    let mut matches = Vec::new();
+    dictionary_matches(&chars, &mut matches);
+    sequence_matches(&chars, &mut matches);
+    repeat_matches(&chars, &mut matches);
+    keyboard_matches(&chars, &mut matches);
+    date_matches(&chars, &mut matches);
+
+    most_guessable(chars.len(), &matches)
+}
+
+/// Finds the cover of the password with the fewest total guesses, using
+/// zxcvbn's cost for a run of `l` patterns: `l! * product + 10000^(l - 1)`.
+/// The factorial accounts for the order of the patterns; the additive term
+/// stops long runs of tiny patterns from looking cheap.
+fn most_guessable(len: usize, matches: &[Match]) -> f64 {
+    let mut ending_at: Vec<Vec<&Match>> = (0..=len).map(|_| Vec::new()).collect();
+    for m in matches {
+        ending_at[m.end].push(m);
+    }
+
+    // best[j][l]: smallest log10 product covering the first `j` characters
+    // with exactly `l` patterns.
+    let mut best = vec![vec![f64::INFINITY; len + 1]; len + 1];
+    best[0][0] = 0.0;
+    for j in 1..=len {
+        for l in 1..=j {
+            let mut cheapest = best[..j]
+                .iter()
+                .enumerate()
+                .map(|(i, row)| row[l - 1] + (j - i) as f64)
+                .fold(f64::INFINITY, f64::min);
+            for m in &ending_at[j] {
+                cheapest = cheapest.min(best[m.start][l - 1] + m.log10_guesses);
+            }
+            best[j][l] = cheapest;
+        }
+    }
+
+    (1..=len)
+        .filter(|&l| best[len][l].is_finite())
+        .map(|l| log10_sum(log10_factorial(l) + best[len][l], 4.0 * (l - 1) as f64))
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn ranked_words() -> &'static HashMap<&'static str, usize> {
+    static WORDS: OnceLock<HashMap<&'static str, usize>> = OnceLock::new();
+    WORDS.get_or_init(|| {
+        include_str!("../data/common_passwords.txt")
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(index, word)| (word, index + 1))
+            .collect()
+    })
+}
+
+fn dictionary_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let words = ranked_words();
+    let lower: Vec<char> = chars.iter().map(char::to_ascii_lowercase).collect();
+    let unleeted: Vec<char> = lower.iter().map(|&c| unleet(c).unwrap_or(c)).collect();
+
+    for start in 0..chars.len() {
+        let longest = (chars.len() - start).min(MAX_WORD_LEN);
+        for len in MIN_WORD_LEN..=longest {
+            let end = start + len;
+            let original = &chars[start..end];
+            let case_factor = uppercase_variations(original);
+
+            let word: String = lower[start..end].iter().collect();
+            if let Some(&rank) = words.get(word.as_str()) {
+                matches.push(Match::new(start, end, rank as f64 * case_factor));
+            }
+
+            let reversed: String = lower[start..end].iter().rev().collect();
+            if reversed != word {
+                if let Some(&rank) = words.get(reversed.as_str()) {
+                    matches.push(Match::new(start, end, rank as f64 * case_factor * 2.0));
+                }
+            }
+
+            let plain: String = unleeted[start..end].iter().collect();
+            if plain != word {
+                if let Some(&rank) = words.get(plain.as_str()) {
+                    let guesses = rank as f64 * case_factor * leet_variations(&lower[start..end]);
+                    matches.push(Match::new(start, end, guesses));
+                }
+            }
+        }
+    }
+}
+
+fn unleet(c: char) -> Option<char> {
+    Some(match c {
+        '4' | '@' => 'a',
+        '8' => 'b',
+        '(' | '{' | '[' | '<' => 'c',
+        '3' => 'e',
+        '6' | '9' => 'g',
+        '1' | '!' | '|' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' | '+' => 't',
+        '2' => 'z',
+        _ => return None,
+    })
+}
+
+/// Capitalising only the first or last letter, or all of them, barely adds
+/// guesses; arbitrary mixes count every way of choosing the upper-case ones.
+fn uppercase_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_ascii_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_ascii_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_only = upper == 1 && word.first().is_some_and(char::is_ascii_uppercase);
+    let last_only = upper == 1 && word.last().is_some_and(char::is_ascii_uppercase);
+    if lower == 0 || first_only || last_only {
+        return 2.0;
+    }
+    (1..=upper.min(lower))
+        .map(|k| binomial(upper + lower, k))
+        .sum()
+}
+
+fn leet_variations(word: &[char]) -> f64 {
+    let mut variations = 1.0;
+    let mut seen = Vec::new();
+    for &c in word {
+        let Some(letter) = unleet(c) else { continue };
+        if seen.contains(&c) {
+            continue;
+        }
+        seen.push(c);
+
+        let substituted = word.iter().filter(|&&x| x == c).count();
+        let unsubstituted = word.iter().filter(|&&x| x == letter).count();
+        variations *= if unsubstituted == 0 {
+            2.0
+        } else {
+            (1..=substituted.min(unsubstituted))
+                .map(|k| binomial(substituted + unsubstituted, k))
+                .sum()
+        };
+    }
+    variations
+}
+
+/// Runs like `abcd`, `9753` or `zyx` with a constant step of up to five.
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let codes: Vec<i64> = chars
+        .iter()
+        .map(|c| c.to_ascii_lowercase() as i64)
+        .collect();
+
+    let mut start = 0;
+    while start + 1 < codes.len() {
+        let delta = codes[start + 1] - codes[start];
+        let mut end = start + 1;
+        while end + 1 < codes.len() && codes[end + 1] - codes[end] == delta {
+            end += 1;
+        }
+
+        let len = end - start + 1;
+        if len >= 3 && (1..=5).contains(&delta.abs()) {
+            let first = chars[start];
+            let base = if matches!(first, 'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9') {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match::new(start, end + 1, base * len as f64 * direction));
+        }
+        start = end;
+    }
+}
+
+/// A block repeated back to back, like `aaaa` or `abcabc`, costs as much as
+/// the block times the number of repeats.
+fn repeat_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start < chars.len() {
+        let remaining = chars.len() - start;
+        let repeat = (1..=remaining / 2).find_map(|period| {
+            let block = &chars[start..start + period];
+            let count = chars[start..]
+                .chunks(period)
+                .take_while(|chunk| *chunk == block)
+                .count();
+            (count >= 2 && period * count >= 3).then_some((period, count))
+        });
+
+        match repeat {
+            Some((period, count)) => {
+                let block: String = chars[start..start + period].iter().collect();
+                let block_guesses = 10f64.powf(log10_guesses(&block));
+                let end = start + period * count;
+                matches.push(Match::new(start, end, block_guesses * count as f64));
+                start = end;
+            }
+            None => start += 1,
+        }
+    }
+}
+
+/// Walks across adjacent keys, like `qwerty` or `zaq1`, costed by length
+/// and the number of changes of direction.
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = start + 1;
+        let mut turns = 0;
+        let mut last_direction = None;
+        while end < chars.len() {
+            let Some(direction) = key_direction(chars[end - 1], chars[end]) else {
+                break;
+            };
+            if last_direction != Some(direction) {
+                turns += 1;
+                last_direction = Some(direction);
+            }
+            end += 1;
+        }
+
+        let len = end - start;
+        if len >= 3 {
+            let shifted = chars[start..end]
+                .iter()
+                .filter(|&&c| key_position(c).is_some_and(|(_, _, shifted)| shifted))
+                .count();
+            let guesses = keyboard_walk_guesses(len, turns) * shift_variations(shifted, len);
+            matches.push(Match::new(start, end, guesses));
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+fn keyboard_walk_guesses(len: usize, turns: usize) -> f64 {
+    let mut guesses = 0.0;
+    for i in 2..=len {
+        for j in 1..=turns.min(i - 1) {
+            guesses += binomial(i - 1, j - 1)
+                * KEYBOARD_STARTING_POSITIONS
+                * KEYBOARD_AVERAGE_DEGREE.powi(j as i32);
+        }
+    }
+    guesses
+}
+
+fn shift_variations(shifted: usize, len: usize) -> f64 {
+    let unshifted = len - shifted;
+    match (shifted, unshifted) {
+        (0, _) => 1.0,
+        (_, 0) => 2.0,
+        _ => (1..=shifted.min(unshifted)).map(|k| binomial(len, k)).sum(),
+    }
+}
+
+fn key_position(c: char) -> Option<(usize, usize, bool)> {
+    [(&KEYBOARD_ROWS, false), (&SHIFTED_KEYBOARD_ROWS, true)]
+        .into_iter()
+        .find_map(|(rows, shifted)| {
+            rows.iter().enumerate().find_map(|(row, keys)| {
+                keys.chars()
+                    .position(|key| key == c)
+                    .map(|col| (row, col, shifted))
+            })
+        })
+}
+
+/// Which of the six neighbouring positions `to` is in relative to `from`:
+/// left, right, then the two keys above and the two below. Each row is
+/// offset from the one above it, as on a real keyboard.
+fn key_direction(from: char, to: char) -> Option<u8> {
+    let (from_row, from_col, _) = key_position(from)?;
+    let (to_row, to_col, _) = key_position(to)?;
+    let (row, col) = (from_row as i64, from_col as i64);
+    let target = (to_row as i64, to_col as i64);
+
+    let above = if row == 1 {
+        [(0, col + 1), (0, col + 2)]
+    } else {
+        [(row - 1, col), (row - 1, col + 1)]
+    };
+    let below = if row == 0 {
+        [(1, col - 2), (1, col - 1)]
+    } else {
+        [(row + 1, col - 1), (row + 1, col)]
+    };
+
+    [
+        (row, col - 1),
+        (row, col + 1),
+        above[0],
+        above[1],
+        below[0],
+        below[1],
+    ]
+    .iter()
+    .position(|&neighbour| neighbour == target)
+    .map(|direction| direction as u8)
+}
+
+/// Four-digit years, and six- or eight-digit dates in day/month/year,
+/// month/day/year or year/month/day order.
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let current_year = Utc::now().year();
+    let year_space = |year: i32| (year - current_year).abs().max(MIN_YEAR_SPACE) as f64;
+
+    for start in 0..chars.len() {
+        for len in [4, 6, 8] {
+            let end = start + len;
+            if end > chars.len() || !chars[start..end].iter().all(char::is_ascii_digit) {
+                continue;
+            }
+            let digits: String = chars[start..end].iter().collect();
+
+            if len == 4 {
+                let year: i32 = digits.parse().unwrap_or_default();
+                if (1900..=2099).contains(&year) {
+                    matches.push(Match::new(start, end, year_space(year)));
+                }
+                continue;
+            }
+
+            if let Some(year) = parse_date(&digits) {
+                matches.push(Match::new(start, end, 365.0 * year_space(year)));
+            }
+        }
+    }
+}
+
+/// The year of the first reading of `digits` that is a plausible date.
+fn parse_date(digits: &str) -> Option<i32> {
+    let number = |range: std::ops::Range<usize>| digits[range].parse::<i32>().ok();
+    let year_len = digits.len() - 4;
+    let full_year = |year: i32| match year_len {
+        2 if year > 50 => 1900 + year,
+        2 => 2000 + year,
+        _ => year,
+    };
+    let valid = |day: i32, month: i32, year: i32| {
+        (1..=31).contains(&day) && (1..=12).contains(&month) && (1900..=2099).contains(&year)
+    };
+
+    let year_first = (
+        number(0..year_len)?,
+        number(year_len..year_len + 2)?,
+        number(year_len + 2..digits.len())?,
+    );
+    let year_last = (number(0..2)?, number(2..4)?, number(4..digits.len())?);
+
+    let (year, month, day) = year_first;
+    if valid(day, month, full_year(year)) {
+        return Some(full_year(year));
+    }
+    let (first, second, year) = year_last;
+    if valid(first, second, full_year(year)) || valid(second, first, full_year(year)) {
+        return Some(full_year(year));
+    }
+    None
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (1..=k).fold(1.0, |acc, i| acc * (n + 1 - i) as f64 / i as f64)
+}
+
+fn log10_factorial(n: usize) -> f64 {
+    (2..=n).map(|i| (i as f64).log10()).sum()
+}
+
+/// `log10(10^a + 10^b)` without leaving log space.
+fn log10_sum(a: f64, b: f64) -> f64 {
+    let (high, low) = if a > b { (a, b) } else { (b, a) };
+    high + (1.0 + 10f64.powf(low - high)).log10()
+}
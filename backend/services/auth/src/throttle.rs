@@ -0,0 +1,73 @@
+//! How repeated failed logins slow down, and eventually lock out, further
+//! attempts.
+
+use chrono::Duration;
+
+const DEFAULT_LOCKOUT_THRESHOLD: i64 = 10;
+const DEFAULT_LOCKOUT_MINUTES: i64 = 60;
+
+/// Failures older than this are forgotten.
+const FAILURE_WINDOW_HOURS: i64 = 24;
+
+/// Exponential backoff: after `free_attempts` failures each further one
+/// doubles the wait, starting at `base` and capped at `max`.
+pub struct Backoff {
+    free_attempts: i32,
+    base: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    /// The wait imposed after `failures` failed attempts in a row.
+    pub fn delay(&self, failures: i32) -> Option<Duration> {
+        let excess = failures - self.free_attempts;
+        if excess <= 0 {
+            return None;
+        }
+        let factor = 1i32 << (excess - 1).min(20);
+        Some((self.base * factor).min(self.max))
+    }
+}
+
+pub struct LoginThrottling {
+    /// Applied per account, keyed by email so unknown addresses behave the
+    /// same as real ones.
+    pub account: Backoff,
+    /// Applied per client address. More lenient, as many users can share
+    /// one address behind NAT.
+    pub client: Backoff,
+    /// Failures on one account that lock it until the owner unlocks it
+    /// from the email we send, an admin unlocks it, or `lockout` passes.
+    pub lockout_threshold: i32,
+    pub lockout: Duration,
+    pub window: Duration,
+}
+
+impl LoginThrottling {
+    /// Reads `LOGIN_LOCKOUT_THRESHOLD` and `LOGIN_LOCKOUT_MINUTES`.
+    pub fn from_env() -> Self {
+        let env = |key: &str, default: i64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default)
+        };
+
+        Self {
+            account: Backoff {
+                free_attempts: 3,
+                base: Duration::seconds(1),
+                max: Duration::minutes(5),
+            },
+            client: Backoff {
+                free_attempts: 20,
+                base: Duration::seconds(1),
+                max: Duration::minutes(15),
+            },
+            lockout_threshold: env("LOGIN_LOCKOUT_THRESHOLD", DEFAULT_LOCKOUT_THRESHOLD) as i32,
+            lockout: Duration::minutes(env("LOGIN_LOCKOUT_MINUTES", DEFAULT_LOCKOUT_MINUTES)),
+            window: Duration::hours(FAILURE_WINDOW_HOURS),
+        }
+    }
+}
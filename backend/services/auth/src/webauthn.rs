@@ -0,0 +1,698 @@
+//! The relying-party side of WebAuthn passkey ceremonies.
+//!
+//! Only ES256 (P-256) credentials are supported, which every platform
+//! authenticator and security key offers. Attestation is not requested: "none"
+//! and packed self attestation are accepted, anything carrying a certificate
+//! chain is refused rather than trusted unchecked.
+
+use base64::{
+    alphabet,
+    engine::{
+        general_purpose::URL_SAFE_NO_PAD, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig,
+    },
+    Engine,
+};
+use ciborium::value::Value;
+use p256::{
+    ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    EncodedPoint,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// COSE algorithm identifier for ECDSA with SHA-256 on P-256.
+const COSE_ALG_ES256: i64 = -7;
+
+/// How long a ceremony may take, in milliseconds.
+pub const CEREMONY_TIMEOUT_MS: u64 = 300_000;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// Browsers send unpadded base64url, but some client libraries pad it.
+const BASE64URL_LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct WebAuthnError(&'static str);
+
+type Result<T> = std::result::Result<T, WebAuthnError>;
+
+/// The site passkeys are bound to.
+pub struct RelyingParty {
+    /// Registrable domain, e.g. `example.com`.
+    pub id: String,
+    pub name: String,
+    /// Origin the browser reports in client data, e.g. `https://example.com`.
+    pub origin: String,
+}
+
+impl RelyingParty {
+    /// `WEBAUTHN_RP_ID`, `WEBAUTHN_RP_NAME` and `WEBAUTHN_ORIGIN`. The origin
+    /// defaults to the frontend URL and the id to its host.
+    pub fn from_env(app_url: &str) -> Self {
+        let origin = std::env::var("WEBAUTHN_ORIGIN").unwrap_or_else(|_| app_url.to_string());
+        let id = std::env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| host(&origin).to_string());
+        let name = std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "Blog".to_string());
+
+        Self { id, name, origin }
+    }
+}
+
+fn host(origin: &str) -> &str {
+    let without_scheme = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    let authority = without_scheme.split('/').next().unwrap_or_default();
+    authority
+        .rsplit_once(':')
+        .map_or(authority, |(host, _)| host)
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn decode(encoded: &str) -> Result<Vec<u8>> {
+    BASE64URL_LENIENT
+        .decode(encoded)
+        .map_err(|_| WebAuthnError("invalid base64url"))
+}
+
+/// The WebAuthn user handle for an account: the raw UUID bytes.
+pub fn user_handle(user_id: Uuid) -> String {
+    encode(user_id.as_bytes())
+}
+
+// Options handed to `navigator.credentials.create()` / `.get()`, in the JSON
+// form accepted by `PublicKeyCredential.parse*OptionsFromJSON`.
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    pub challenge: String,
+    pub rp: RpEntity,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: u64,
+    pub attestation: &'static str,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub alg: i64,
+}
+
+impl CredentialParameters {
+    pub fn es256() -> Self {
+        Self {
+            kind: "public-key",
+            alg: COSE_ALG_ES256,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: String,
+}
+
+impl CredentialDescriptor {
+    pub fn new(credential_id: String) -> Self {
+        Self {
+            kind: "public-key",
+            id: credential_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: &'static str,
+    pub user_verification: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: u64,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: &'static str,
+}
+
+// Credentials returned by the browser, as produced by `PublicKeyCredential.toJSON()`.
+
+#[derive(Debug, Deserialize)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthenticationCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+    #[serde(rename = "userHandle")]
+    pub user_handle: Option<String>,
+}
+
+/// The browser's `clientDataJSON`, kept with its raw bytes because the
+/// signature covers their hash.
+pub struct ClientData {
+    pub challenge: String,
+    kind: String,
+    origin: String,
+    raw: Vec<u8>,
+}
+
+impl ClientData {
+    pub fn parse(encoded: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Fields {
+            #[serde(rename = "type")]
+            kind: String,
+            challenge: String,
+            origin: String,
+        }
+
+        let raw = decode(encoded)?;
+        let fields: Fields =
+            serde_json::from_slice(&raw).map_err(|_| WebAuthnError("malformed client data"))?;
+
+        Ok(Self {
+            challenge: fields.challenge,
+            kind: fields.kind,
+            origin: fields.origin,
+            raw,
+        })
+    }
+
+    fn check(&self, rp: &RelyingParty, expected_kind: &str) -> Result<()> {
+        if self.kind != expected_kind {
+            return Err(WebAuthnError("unexpected ceremony type"));
+        }
+        if self.origin != rp.origin {
+            return Err(WebAuthnError("origin mismatch"));
+        }
+        Ok(())
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        Sha256::digest(&self.raw).into()
+    }
+}
+
+pub struct NewCredential {
+    pub credential_id: String,
+    /// SEC1 uncompressed P-256 point, base64url.
+    pub public_key: String,
+    pub sign_count: u32,
+}
+
+/// Checks an attestation from `navigator.credentials.create()` whose
+/// challenge the caller has already matched to `client_data`.
+pub fn verify_registration(
+    rp: &RelyingParty,
+    credential: &RegistrationCredential,
+    client_data: &ClientData,
+) -> Result<NewCredential> {
+    client_data.check(rp, "webauthn.create")?;
+
+    let attestation: Value =
+        ciborium::de::from_reader(decode(&credential.response.attestation_object)?.as_slice())
+            .map_err(|_| WebAuthnError("malformed attestation object"))?;
+    let fmt = map_get(&attestation, &Value::Text("fmt".into()))
+        .and_then(Value::as_text)
+        .ok_or(WebAuthnError("attestation format missing"))?;
+    let statement = map_get(&attestation, &Value::Text("attStmt".into()))
+        .and_then(Value::as_map)
+        .ok_or(WebAuthnError("attestation statement missing"))?;
+    let auth_data_bytes = map_get(&attestation, &Value::Text("authData".into()))
+        .and_then(Value::as_bytes)
+        .ok_or(WebAuthnError("authenticator data missing"))?;
+
+    let auth_data = AuthenticatorData::parse(auth_data_bytes)?;
+    auth_data.check(rp)?;
+    let attested = auth_data
+        .attested_credential
+        .ok_or(WebAuthnError("no credential in attestation"))?;
+    if encode(&attested.credential_id) != decode(&credential.id).map(|id| encode(&id))? {
+        return Err(WebAuthnError("credential id mismatch"));
+    }
+
+    match fmt {
+        "none" if statement.is_empty() => {}
+        "packed" => {
+            let statement = Value::Map(statement.clone());
+            if map_get(&statement, &Value::Text("x5c".into())).is_some() {
+                return Err(WebAuthnError("certificate attestation is not supported"));
+            }
+            let alg = map_get(&statement, &Value::Text("alg".into())).and_then(as_i64);
+            let sig = map_get(&statement, &Value::Text("sig".into()))
+                .and_then(Value::as_bytes)
+                .ok_or(WebAuthnError("attestation signature missing"))?;
+            if alg != Some(COSE_ALG_ES256) {
+                return Err(WebAuthnError("unsupported attestation algorithm"));
+            }
+            verify_signature(&attested.public_key, auth_data_bytes, client_data, sig)?;
+        }
+        _ => return Err(WebAuthnError("unsupported attestation format")),
+    }
+
+    Ok(NewCredential {
+        credential_id: encode(&attested.credential_id),
+        public_key: encode(attested.public_key.to_encoded_point(false).as_bytes()),
+        sign_count: auth_data.sign_count,
+    })
+}
+
+/// Checks an assertion from `navigator.credentials.get()` against the stored
+/// public key and returns the authenticator's new signature counter.
+pub fn verify_authentication(
+    rp: &RelyingParty,
+    credential: &AuthenticationCredential,
+    client_data: &ClientData,
+    public_key: &str,
+) -> Result<u32> {
+    client_data.check(rp, "webauthn.get")?;
+
+    let auth_data_bytes = decode(&credential.response.authenticator_data)?;
+    let auth_data = AuthenticatorData::parse(&auth_data_bytes)?;
+    auth_data.check(rp)?;
+
+    let public_key = VerifyingKey::from_sec1_bytes(&decode(public_key)?)
+        .map_err(|_| WebAuthnError("stored public key is invalid"))?;
+    let signature = decode(&credential.response.signature)?;
+    verify_signature(&public_key, &auth_data_bytes, client_data, &signature)?;
+
+    Ok(auth_data.sign_count)
+}
+
+fn verify_signature(
+    key: &VerifyingKey,
+    auth_data: &[u8],
+    client_data: &ClientData,
+    signature: &[u8],
+) -> Result<()> {
+    let signature =
+        Signature::from_der(signature).map_err(|_| WebAuthnError("malformed signature"))?;
+    let mut message = auth_data.to_vec();
+    message.extend_from_slice(&client_data.hash());
+
+    key.verify(&message, &signature)
+        .map_err(|_| WebAuthnError("signature verification failed"))
+}
+
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    attested_credential: Option<AttestedCredential>,
+}
+
+struct AttestedCredential {
+    credential_id: Vec<u8>,
+    public_key: VerifyingKey,
+}
+
+impl AuthenticatorData {
+    /// Layout: rpIdHash (32) | flags (1) | signCount (4) | attested credential data.
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 37 {
+            return Err(WebAuthnError("authenticator data too short"));
+        }
+        let flags = data[32];
+        let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+        let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+            // aaguid (16) | credentialIdLength (2) | credentialId | COSE key
+            let rest = data
+                .get(37 + 16..)
+                .ok_or(WebAuthnError("truncated credential data"))?;
+            let (len, rest) = rest
+                .split_first_chunk::<2>()
+                .ok_or(WebAuthnError("truncated credential data"))?;
+            let len = u16::from_be_bytes(*len) as usize;
+            if rest.len() < len {
+                return Err(WebAuthnError("truncated credential data"));
+            }
+            let (credential_id, mut key_bytes) = rest.split_at(len);
+            let cose_key: Value = ciborium::de::from_reader(&mut key_bytes)
+                .map_err(|_| WebAuthnError("malformed credential public key"))?;
+
+            Some(AttestedCredential {
+                credential_id: credential_id.to_vec(),
+                public_key: cose_es256_key(&cose_key)?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            rp_id_hash: data[..32].try_into().expect("checked length"),
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+
+    /// Passkeys replace the password, so the authenticator must have
+    /// verified the user (PIN, biometrics), not just seen a tap.
+    fn check(&self, rp: &RelyingParty) -> Result<()> {
+        if self.rp_id_hash != <[u8; 32]>::from(Sha256::digest(rp.id.as_bytes())) {
+            return Err(WebAuthnError("relying party mismatch"));
+        }
+        if self.flags & FLAG_USER_PRESENT == 0 {
+            return Err(WebAuthnError("user not present"));
+        }
+        if self.flags & FLAG_USER_VERIFIED == 0 {
+            return Err(WebAuthnError("user not verified"));
+        }
+        Ok(())
+    }
+}
+
+/// Reads an EC2 P-256 COSE key: `{1: 2, 3: -7, -1: 1, -2: x, -3: y}`.
+fn cose_es256_key(key: &Value) -> Result<VerifyingKey> {
+    let field = |label: i64| map_get(key, &Value::Integer(label.into()));
+
+    if field(1).and_then(as_i64) != Some(2)
+        || field(3).and_then(as_i64) != Some(COSE_ALG_ES256)
+        || field(-1).and_then(as_i64) != Some(1)
+    {
+        return Err(WebAuthnError("only ES256 credentials are supported"));
+    }
+
+    let coordinate = |label: i64| -> Result<[u8; 32]> {
+        field(label)
+            .and_then(Value::as_bytes)
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes.as_slice()).ok())
+            .ok_or(WebAuthnError("malformed credential public key"))
+    };
+    let point = EncodedPoint::from_affine_coordinates(
+        &coordinate(-2)?.into(),
+        &coordinate(-3)?.into(),
+        false,
+    );
+
+    VerifyingKey::from_encoded_point(&point)
+        .map_err(|_| WebAuthnError("invalid credential public key"))
+}
+
+fn map_get<'a>(map: &'a Value, key: &Value) -> Option<&'a Value> {
+    map.as_map()?.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    value.as_integer().and_then(|i| i64::try_from(i).ok())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use rand::{rngs::OsRng, RngCore};
+    use serde_json::json;
+
+    use super::*;
+
+    /// A software passkey: a P-256 key pair and a signature counter, making
+    /// the same attestations and assertions a platform authenticator would.
+    pub(crate) struct Authenticator {
+        key: SigningKey,
+        pub credential_id: Vec<u8>,
+        pub sign_count: u32,
+        pub origin: String,
+    }
+
+    impl Authenticator {
+        pub fn new(rp: &RelyingParty) -> Self {
+            let mut credential_id = vec![0u8; 16];
+            OsRng.fill_bytes(&mut credential_id);
+            Self {
+                key: SigningKey::random(&mut OsRng),
+                credential_id,
+                sign_count: 0,
+                origin: rp.origin.clone(),
+            }
+        }
+
+        pub fn id(&self) -> String {
+            encode(&self.credential_id)
+        }
+
+        /// Answers `navigator.credentials.create()`, self-attested with
+        /// `packed` or unattested with `none`.
+        pub fn register(
+            &mut self,
+            rp: &RelyingParty,
+            challenge: &str,
+            fmt: &str,
+        ) -> RegistrationCredential {
+            let client_data = self.client_data("webauthn.create", challenge);
+            let auth_data = self.authenticator_data(rp, true);
+            let statement = match fmt {
+                "packed" => vec![
+                    (
+                        Value::Text("alg".into()),
+                        Value::Integer(COSE_ALG_ES256.into()),
+                    ),
+                    (
+                        Value::Text("sig".into()),
+                        Value::Bytes(self.sign(&auth_data, &client_data)),
+                    ),
+                ],
+                _ => Vec::new(),
+            };
+            let attestation = Value::Map(vec![
+                (Value::Text("fmt".into()), Value::Text(fmt.into())),
+                (Value::Text("attStmt".into()), Value::Map(statement)),
+                (Value::Text("authData".into()), Value::Bytes(auth_data)),
+            ]);
+            let mut attestation_object = Vec::new();
+            ciborium::ser::into_writer(&attestation, &mut attestation_object).unwrap();
+
+            RegistrationCredential {
+                id: self.id(),
+                response: AttestationResponse {
+                    client_data_json: encode(&client_data),
+                    attestation_object: encode(&attestation_object),
+                },
+            }
+        }
+
+        /// Answers `navigator.credentials.get()`, counting the signature.
+        pub fn assert(
+            &mut self,
+            rp: &RelyingParty,
+            challenge: &str,
+            user_handle: Option<String>,
+        ) -> AuthenticationCredential {
+            self.sign_count += 1;
+            let client_data = self.client_data("webauthn.get", challenge);
+            let auth_data = self.authenticator_data(rp, false);
+
+            AuthenticationCredential {
+                id: self.id(),
+                response: AssertionResponse {
+                    client_data_json: encode(&client_data),
+                    authenticator_data: encode(&auth_data),
+                    signature: encode(&self.sign(&auth_data, &client_data)),
+                    user_handle,
+                },
+            }
+        }
+
+        fn client_data(&self, kind: &str, challenge: &str) -> Vec<u8> {
+            serde_json::to_vec(&json!({
+                "type": kind,
+                "challenge": challenge,
+                "origin": self.origin,
+            }))
+            .unwrap()
+        }
+
+        fn authenticator_data(&self, rp: &RelyingParty, attested: bool) -> Vec<u8> {
+            let mut data = Sha256::digest(rp.id.as_bytes()).to_vec();
+            let mut flags = FLAG_USER_PRESENT | FLAG_USER_VERIFIED;
+            if attested {
+                flags |= FLAG_ATTESTED_CREDENTIAL;
+            }
+            data.push(flags);
+            data.extend_from_slice(&self.sign_count.to_be_bytes());
+            if attested {
+                data.extend_from_slice(&[0; 16]);
+                data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+                data.extend_from_slice(&self.credential_id);
+                let point = self.key.verifying_key().to_encoded_point(false);
+                let cose_key = Value::Map(vec![
+                    (Value::Integer(1.into()), Value::Integer(2.into())),
+                    (
+                        Value::Integer(3.into()),
+                        Value::Integer(COSE_ALG_ES256.into()),
+                    ),
+                    (Value::Integer((-1).into()), Value::Integer(1.into())),
+                    (
+                        Value::Integer((-2).into()),
+                        Value::Bytes(point.x().unwrap().to_vec()),
+                    ),
+                    (
+                        Value::Integer((-3).into()),
+                        Value::Bytes(point.y().unwrap().to_vec()),
+                    ),
+                ]);
+                ciborium::ser::into_writer(&cose_key, &mut data).unwrap();
+            }
+            data
+        }
+
+        fn sign(&self, auth_data: &[u8], client_data: &[u8]) -> Vec<u8> {
+            let mut message = auth_data.to_vec();
+            message.extend_from_slice(&Sha256::digest(client_data));
+            let signature: Signature = self.key.sign(&message);
+            signature.to_der().as_bytes().to_vec()
+        }
+    }
+
+    pub(crate) fn relying_party() -> RelyingParty {
+        RelyingParty {
+            id: "localhost".to_string(),
+            name: "Blog".to_string(),
+            origin: "http://localhost:5173".to_string(),
+        }
+    }
+
+    fn register(
+        rp: &RelyingParty,
+        authenticator: &mut Authenticator,
+        fmt: &str,
+    ) -> Result<NewCredential> {
+        let credential = authenticator.register(rp, "challenge", fmt);
+        let client_data = ClientData::parse(&credential.response.client_data_json)?;
+        verify_registration(rp, &credential, &client_data)
+    }
+
+    fn authenticate(
+        rp: &RelyingParty,
+        credential: &AuthenticationCredential,
+        public_key: &str,
+    ) -> Result<u32> {
+        let client_data = ClientData::parse(&credential.response.client_data_json)?;
+        verify_authentication(rp, credential, &client_data, public_key)
+    }
+
+    #[test]
+    fn registers_unattested_and_self_attested_credentials() {
+        let rp = relying_party();
+        for fmt in ["none", "packed"] {
+            let mut authenticator = Authenticator::new(&rp);
+            let credential = register(&rp, &mut authenticator, fmt).unwrap();
+
+            assert_eq!(credential.credential_id, authenticator.id());
+            assert_eq!(
+                decode(&credential.public_key).unwrap(),
+                authenticator
+                    .key
+                    .verifying_key()
+                    .to_encoded_point(false)
+                    .as_bytes()
+            );
+            assert_eq!(credential.sign_count, 0);
+        }
+    }
+
+    #[test]
+    fn registration_is_bound_to_the_origin_and_relying_party() {
+        let rp = relying_party();
+        let mut authenticator = Authenticator::new(&rp);
+        authenticator.origin = "https://evil.example".to_string();
+        assert!(register(&rp, &mut authenticator, "none").is_err());
+
+        let other = RelyingParty {
+            id: "evil.example".to_string(),
+            ..relying_party()
+        };
+        let mut authenticator = Authenticator::new(&rp);
+        let credential = authenticator.register(&other, "challenge", "none");
+        let client_data = ClientData::parse(&credential.response.client_data_json).unwrap();
+        assert!(verify_registration(&rp, &credential, &client_data).is_err());
+    }
+
+    #[test]
+    fn assertions_verify_against_the_registered_key() {
+        let rp = relying_party();
+        let mut authenticator = Authenticator::new(&rp);
+        let registered = register(&rp, &mut authenticator, "none").unwrap();
+
+        let assertion = authenticator.assert(&rp, "challenge", None);
+        assert_eq!(
+            authenticate(&rp, &assertion, &registered.public_key).unwrap(),
+            1
+        );
+        let assertion = authenticator.assert(&rp, "challenge", None);
+        assert_eq!(
+            authenticate(&rp, &assertion, &registered.public_key).unwrap(),
+            2
+        );
+
+        let mut other = Authenticator::new(&rp);
+        let other_key = register(&rp, &mut other, "none").unwrap().public_key;
+        assert!(authenticate(&rp, &assertion, &other_key).is_err());
+
+        let mut tampered = authenticator.assert(&rp, "challenge", None);
+        tampered.response.client_data_json = encode(
+            &serde_json::to_vec(&json!({
+                "type": "webauthn.get",
+                "challenge": "another-challenge",
+                "origin": rp.origin,
+            }))
+            .unwrap(),
+        );
+        assert!(authenticate(&rp, &tampered, &registered.public_key).is_err());
+    }
+}
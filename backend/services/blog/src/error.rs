@@ -4,6 +4,7 @@ use axum::{
     Json,
 };
 use serde_json::json;
+use service_auth::AuthError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,6 +18,9 @@ pub enum BlogError {
     #[error("Tag not found")]
     TagNotFound,
 
+    #[error("Authentication required")]
+    Unauthorized,
+
     #[error("Permission denied")]
     Forbidden,
 
@@ -42,6 +46,7 @@ impl IntoResponse for BlogError {
             BlogError::PostNotFound => (StatusCode::NOT_FOUND, self.to_string()),
             BlogError::CategoryNotFound => (StatusCode::NOT_FOUND, self.to_string()),
             BlogError::TagNotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            BlogError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
             BlogError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
             BlogError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             BlogError::Database(msg) => {
@@ -76,4 +81,14 @@ impl IntoResponse for BlogError {
     }
 }
 
+impl From<AuthError> for BlogError {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::Unauthorized => BlogError::Unauthorized,
+            AuthError::Forbidden => BlogError::Forbidden,
+            AuthError::Internal(err) => BlogError::Internal(err),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, BlogError>;
\ No newline at end of file
@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+
+use service_auth::CurrentUser;
+
+use crate::{error::Result, services::BlogService};
+
+/// The caller's posts, for the data export the API gateway assembles.
+pub async fn export_data(
+    CurrentUser(claims): CurrentUser,
+    State(service): State<Arc<dyn BlogService>>,
+) -> Result<Json<serde_json::Value>> {
+    let posts = service.list_posts_by_author(claims.sub).await?;
+    Ok(Json(serde_json::json!({ "posts": posts })))
+}
+
+/// Called by the API gateway when the caller deletes their account.
+pub async fn delete_data(
+    CurrentUser(claims): CurrentUser,
+    State(service): State<Arc<dyn BlogService>>,
+) -> Result<StatusCode> {
+    let deleted = service.delete_posts_by_author(claims.sub).await?;
+    tracing::info!(user_id = %claims.sub, deleted, "Deleted posts of deleted account");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,13 +1,28 @@
-use axum::{extract::State, Json};
+use std::sync::Arc;
 
-use crate::{
-    error::Result,
-    services::{BlogService, MockBlogService},
-};
+use axum::{extract::State, http::StatusCode, Json};
+
+use service_auth::{perm, scope, Require, Scoped};
+
+use crate::{error::Result, models::CreateCategoryRequest, services::BlogService};
 
 pub async fn list_categories(
-    State(service): State<MockBlogService>,
+    State(service): State<Arc<dyn BlogService>>,
 ) -> Result<Json<serde_json::Value>> {
     let categories = service.list_categories().await?;
     Ok(Json(serde_json::json!({ "categories": categories })))
-}
\ No newline at end of file
+}
+
+/// Categories are shared by every author, so only editors manage them.
+pub async fn create_category(
+    _: Require<perm::EditAnyPost>,
+    _: Scoped<scope::PostsWrite>,
+    State(service): State<Arc<dyn BlogService>>,
+    Json(req): Json<CreateCategoryRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>)> {
+    let category = service.create_category(req).await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "category": category })),
+    ))
+}
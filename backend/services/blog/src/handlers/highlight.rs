@@ -0,0 +1,26 @@
+use axum::{
+    extract::Query,
+    http::header::{CACHE_CONTROL, CONTENT_TYPE},
+    response::IntoResponse,
+};
+
+use crate::{
+    error::{BlogError, Result},
+    highlight,
+    models::ThemeParams,
+};
+
+/// The stylesheet for the highlighted code blocks in `content_html`. It only
+/// changes with the service, so browsers may cache it for a day.
+pub async fn theme_css(Query(params): Query<ThemeParams>) -> Result<impl IntoResponse> {
+    let css = highlight::theme_css(params.theme.as_deref())
+        .ok_or_else(|| BlogError::BadRequest("Unknown theme".to_string()))?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, "text/css; charset=utf-8"),
+            (CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        css,
+    ))
+}
@@ -1,3 +1,5 @@
+pub mod account;
 pub mod categories;
+pub mod highlight;
 pub mod posts;
 pub mod tags;
\ No newline at end of file
@@ -1,23 +1,54 @@
+use std::sync::Arc;
+
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header::LOCATION, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use uuid::Uuid;
 
+use service_auth::{perm, scope, Caller, Claims, Permission, Require, Scoped};
+
 use crate::{
-    error::Result,
-    models::{CreatePostRequest, PaginationParams, PostFilters, UpdatePostRequest},
-    services::{BlogService, MockBlogService},
+    error::{BlogError, Result},
+    models::{
+        CreatePostRequest, PaginationParams, PostFilters, PostResponse, PostStatus, SlugLookup,
+        UpdatePostRequest,
+    },
+    services::BlogService,
 };
 
+const MAX_PER_PAGE: u32 = 100;
+
+/// Drafts are only visible to their author and to editors.
+fn can_view(viewer: Option<&Claims>, post: &PostResponse) -> bool {
+    post.status == PostStatus::Published
+        || viewer.is_some_and(|claims| {
+            claims
+                .ensure_owner_or(post.author_id, Permission::EditAnyPost)
+                .is_ok()
+        })
+}
+
 pub async fn list_posts(
+    viewer: Option<Caller>,
     Query(pagination): Query<PaginationParams>,
-    Query(filters): Query<PostFilters>,
-    State(service): State<MockBlogService>,
+    Query(mut filters): Query<PostFilters>,
+    State(service): State<Arc<dyn BlogService>>,
 ) -> Result<Json<serde_json::Value>> {
-    let page = pagination.page.unwrap_or(1);
-    let per_page = pagination.per_page.unwrap_or(10);
+    let page = pagination.page.unwrap_or(1).max(1);
+    let per_page = pagination.per_page.unwrap_or(10).clamp(1, MAX_PER_PAGE);
+
+    let sees_drafts = viewer.is_some_and(|Caller(claims)| {
+        claims.can(Permission::EditAnyPost) || filters.author_id == Some(claims.sub)
+    });
+    if !sees_drafts {
+        match filters.status {
+            Some(PostStatus::Draft) => return Err(BlogError::Forbidden),
+            _ => filters.status = Some(PostStatus::Published),
+        }
+    }
 
     let response = service.list_posts(page, per_page, Some(filters)).await?;
 
@@ -32,21 +63,40 @@ pub async fn list_posts(
     })))
 }
 
+/// Looks a post up by id or by slug. A slug the post had before it was
+/// renamed answers with a `301` to its current one.
 pub async fn get_post(
-    Path(id): Path<Uuid>,
-    State(service): State<MockBlogService>,
-) -> Result<Json<serde_json::Value>> {
-    let post = service.get_post(id).await?;
-    Ok(Json(serde_json::json!({ "post": post })))
+    viewer: Option<Caller>,
+    Path(key): Path<String>,
+    State(service): State<Arc<dyn BlogService>>,
+) -> Result<Response> {
+    let lookup = match Uuid::parse_str(&key) {
+        Ok(id) => SlugLookup::Current(service.get_post(id).await?),
+        Err(_) => service.get_post_by_slug(&key).await?,
+    };
+
+    let viewer = viewer.as_ref().map(|Caller(claims)| claims);
+    match lookup {
+        SlugLookup::Current(post) if can_view(viewer, &post) => {
+            Ok(Json(serde_json::json!({ "post": post })).into_response())
+        }
+        SlugLookup::Moved(post) if can_view(viewer, &post) => Ok((
+            StatusCode::MOVED_PERMANENTLY,
+            [(LOCATION, format!("/posts/{}", post.slug))],
+            Json(serde_json::json!({ "slug": post.slug })),
+        )
+            .into_response()),
+        _ => Err(BlogError::PostNotFound),
+    }
 }
 
 pub async fn create_post(
-    State(service): State<MockBlogService>,
+    Require { claims, .. }: Require<perm::CreatePosts>,
+    _: Scoped<scope::PostsWrite>,
+    State(service): State<Arc<dyn BlogService>>,
     Json(req): Json<CreatePostRequest>,
 ) -> Result<(StatusCode, Json<serde_json::Value>)> {
-    // TODO: Get author_id from authenticated user
-    let author_id = Uuid::new_v4();
-    let post = service.create_post(author_id, req).await?;
+    let post = service.create_post(claims.sub, req).await?;
 
     Ok((
         StatusCode::CREATED,
@@ -55,24 +105,169 @@ pub async fn create_post(
 }
 
 pub async fn update_post(
+    Require { claims, .. }: Require<perm::CreatePosts>,
+    _: Scoped<scope::PostsWrite>,
     Path(id): Path<Uuid>,
-    State(service): State<MockBlogService>,
+    State(service): State<Arc<dyn BlogService>>,
     Json(req): Json<UpdatePostRequest>,
 ) -> Result<Json<serde_json::Value>> {
-    // TODO: Get author_id from authenticated user
-    let author_id = Uuid::new_v4();
-    let post = service.update_post(id, author_id, req).await?;
+    let existing = service.get_post(id).await?;
+    claims.ensure_owner_or(existing.author_id, Permission::EditAnyPost)?;
+
+    let post = service.update_post(id, existing.author_id, req).await?;
 
     Ok(Json(serde_json::json!({ "post": post })))
 }
 
 pub async fn delete_post(
+    Require { claims, .. }: Require<perm::CreatePosts>,
+    _: Scoped<scope::PostsWrite>,
     Path(id): Path<Uuid>,
-    State(service): State<MockBlogService>,
+    State(service): State<Arc<dyn BlogService>>,
 ) -> Result<StatusCode> {
-    // TODO: Get author_id from authenticated user
-    let author_id = Uuid::new_v4();
-    service.delete_post(id, author_id).await?;
+    let existing = service.get_post(id).await?;
+    claims.ensure_owner_or(existing.author_id, Permission::EditAnyPost)?;
+
+    service.delete_post(id, existing.author_id).await?;
 
     Ok(StatusCode::NO_CONTENT)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{services::SqliteBlogService, slugs::SlugStrategy};
+    use service_auth::Role;
+
+    fn service() -> Arc<dyn BlogService> {
+        Arc::new(SqliteBlogService::open(":memory:", SlugStrategy::Romaji).unwrap())
+    }
+
+    fn caller(roles: Vec<Role>) -> Caller {
+        Caller(Claims {
+            sub: Uuid::new_v4(),
+            roles,
+            scope: None,
+            client_id: None,
+            act: None,
+        })
+    }
+
+    fn page() -> Query<PaginationParams> {
+        Query(PaginationParams {
+            page: None,
+            per_page: None,
+        })
+    }
+
+    fn drafts() -> Query<PostFilters> {
+        Query(PostFilters {
+            status: Some(PostStatus::Draft),
+            ..Default::default()
+        })
+    }
+
+    async fn draft_by(service: &Arc<dyn BlogService>, author_id: Uuid) -> PostResponse {
+        service
+            .create_post(
+                author_id,
+                CreatePostRequest {
+                    title: "Draft".to_string(),
+                    slug: None,
+                    content: "Not yet".to_string(),
+                    excerpt: None,
+                    status: PostStatus::Draft,
+                    category_ids: Vec::new(),
+                    tag_ids: Vec::new(),
+                },
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn listing_drafts_is_forbidden_to_other_users() {
+        let service = service();
+
+        let anonymous = list_posts(None, page(), drafts(), State(service.clone())).await;
+        assert!(matches!(anonymous, Err(BlogError::Forbidden)));
+        let author = list_posts(
+            Some(caller(vec![Role::Author])),
+            page(),
+            drafts(),
+            State(service.clone()),
+        )
+        .await;
+        assert!(matches!(author, Err(BlogError::Forbidden)));
+
+        let editor = list_posts(
+            Some(caller(vec![Role::Editor])),
+            page(),
+            drafts(),
+            State(service),
+        )
+        .await;
+        assert!(editor.is_ok());
+    }
+
+    #[tokio::test]
+    async fn drafts_are_hidden_from_other_users() {
+        let service = service();
+        let owner = caller(vec![Role::Author]);
+        let draft = draft_by(&service, owner.0.sub).await;
+        let path = || Path(draft.id.to_string());
+
+        let other = get_post(
+            Some(caller(vec![Role::Author])),
+            path(),
+            State(service.clone()),
+        )
+        .await;
+        assert!(matches!(other, Err(BlogError::PostNotFound)));
+
+        let response = get_post(Some(owner), path(), State(service)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn old_slugs_redirect_to_the_current_one() {
+        let service = service();
+        let author = Uuid::new_v4();
+        let post = service
+            .create_post(
+                author,
+                CreatePostRequest {
+                    title: "Hello World".to_string(),
+                    slug: None,
+                    content: "Hi".to_string(),
+                    excerpt: None,
+                    status: PostStatus::Published,
+                    category_ids: Vec::new(),
+                    tag_ids: Vec::new(),
+                },
+            )
+            .await
+            .unwrap();
+        service
+            .update_post(
+                post.id,
+                author,
+                UpdatePostRequest {
+                    title: Some("Goodbye World".to_string()),
+                    content: None,
+                    excerpt: None,
+                    status: None,
+                    category_ids: None,
+                    tag_ids: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        let response = get_post(None, Path("hello-world".to_string()), State(service))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()[LOCATION], "/posts/goodbye-world");
+    }
+}
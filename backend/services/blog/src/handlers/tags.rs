@@ -1,13 +1,25 @@
-use axum::{extract::State, Json};
+use std::sync::Arc;
 
-use crate::{
-    error::Result,
-    services::{BlogService, MockBlogService},
-};
+use axum::{extract::State, http::StatusCode, Json};
+
+use service_auth::{perm, scope, Require, Scoped};
+
+use crate::{error::Result, models::CreateTagRequest, services::BlogService};
 
 pub async fn list_tags(
-    State(service): State<MockBlogService>,
+    State(service): State<Arc<dyn BlogService>>,
 ) -> Result<Json<serde_json::Value>> {
     let tags = service.list_tags().await?;
     Ok(Json(serde_json::json!({ "tags": tags })))
-}
\ No newline at end of file
+}
+
+/// Tags are shared by every author, so only editors manage them.
+pub async fn create_tag(
+    _: Require<perm::EditAnyPost>,
+    _: Scoped<scope::PostsWrite>,
+    State(service): State<Arc<dyn BlogService>>,
+    Json(req): Json<CreateTagRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>)> {
+    let tag = service.create_tag(req).await?;
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "tag": tag }))))
+}
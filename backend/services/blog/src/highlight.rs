@@ -0,0 +1,255 @@
+//! Syntax highlighting for fenced code blocks, done on the server so the
+//! frontend doesn't need a highlighter of its own.
+//!
+//! Code is highlighted into `hl-` prefixed classes rather than inline styles,
+//! so one stylesheet per theme (`GET /highlight.css`) colours every post.
+//! Each line is wrapped in its own `<span class="code-line" data-line="n">`,
+//! which the stylesheet numbers, and lines listed in the fence's info string
+//! (```` ```rust {3,5-7} ````) also get `code-line-highlighted`. These don't
+//! use the prefix, which syntect's scope classes (`hl-line` included) own.
+
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+
+use syntect::{
+    html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+use two_face::theme::{EmbeddedLazyThemeSet, EmbeddedThemeName};
+
+pub const CLASS_PREFIX: &str = "hl-";
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed {
+    prefix: CLASS_PREFIX,
+};
+
+/// Used by `GET /highlight.css` when no theme is asked for.
+const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// Line numbers and highlighted lines, which syntect's theme CSS knows
+/// nothing about. `{highlight}` is the theme's own line highlight colour.
+const LINE_CSS: &str = "
+.hl-code .code-line::before {
+ content: attr(data-line);
+ display: inline-block;
+ width: 3ch;
+ margin-right: 2ch;
+ text-align: right;
+ opacity: 0.5;
+ user-select: none;
+}
+
+.hl-code .code-line-highlighted {
+ display: inline-block;
+ width: 100%;
+ background-color: {highlight};
+}
+";
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(two_face::syntax::extra_newlines)
+}
+
+fn themes() -> &'static EmbeddedLazyThemeSet {
+    static THEMES: OnceLock<EmbeddedLazyThemeSet> = OnceLock::new();
+    THEMES.get_or_init(two_face::theme::extra)
+}
+
+/// Renders a fenced code block. `info` is everything after the opening
+/// fence: a language, then optionally the lines to highlight. Code in a
+/// language we have no syntax for still gets line numbers.
+pub fn code_block(info: &str, code: &str) -> String {
+    let (language, highlighted) = parse_info(info);
+    let syntax = language.and_then(|language| syntaxes().find_syntax_by_token(language));
+    let lines = match syntax {
+        Some(syntax) => highlight_lines(syntax, code),
+        None => code
+            .lines()
+            .map(|line| html_escape(line).into_owned())
+            .collect(),
+    };
+
+    let mut out = String::from(r#"<pre class="hl-code">"#);
+    match language {
+        Some(language) => out.push_str(&format!(
+            r#"<code class="language-{}">"#,
+            html_escape(language)
+        )),
+        None => out.push_str("<code>"),
+    }
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 1;
+        let class = if highlighted.iter().any(|range| range.contains(&number)) {
+            "code-line code-line-highlighted"
+        } else {
+            "code-line"
+        };
+        out.push_str(&format!(
+            r#"<span class="{}" data-line="{}">{}</span>"#,
+            class, number, line
+        ));
+        out.push('\n');
+    }
+    out.push_str("</code></pre>\n");
+    out
+}
+
+/// Splits `rust {3,5-7}` into the language and the lines to highlight.
+/// Numbers that don't parse are ignored rather than rejecting the post.
+fn parse_info(info: &str) -> (Option<&str>, Vec<RangeInclusive<usize>>) {
+    let (language, lines) = match info.find('{') {
+        Some(brace) => (&info[..brace], &info[brace + 1..]),
+        None => (info, ""),
+    };
+    let language = language.split_whitespace().next();
+
+    let highlighted = lines
+        .split('}')
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter_map(|part| match part.split_once('-') {
+            Some((start, end)) => Some(start.trim().parse().ok()?..=end.trim().parse().ok()?),
+            None => part.parse().ok().map(|line| line..=line),
+        })
+        .collect();
+    (language, highlighted)
+}
+
+/// Highlights `code` one line at a time. syntect's spans can run across
+/// lines, so each line closes the spans still open at its end and the next
+/// line opens them again.
+fn highlight_lines(syntax: &SyntaxReference, code: &str) -> Vec<String> {
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes(), CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        if generator
+            .parse_html_for_line_which_includes_newline(line)
+            .is_err()
+        {
+            return code
+                .lines()
+                .map(|line| html_escape(line).into_owned())
+                .collect();
+        }
+    }
+    let html = generator.finalize();
+
+    let mut lines = Vec::new();
+    let mut open: Vec<&str> = Vec::new();
+    let mut line = String::new();
+    // Whether `line` has any code in it, rather than just the spans closed
+    // after the last newline.
+    let mut has_text = false;
+    let mut rest = html.as_str();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("</span>") {
+            open.pop();
+            line.push_str("</span>");
+            rest = after;
+        } else if rest.starts_with("<span") {
+            let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+            open.push(&rest[..end]);
+            line.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else if let Some(after) = rest.strip_prefix('\n') {
+            line.push_str(&"</span>".repeat(open.len()));
+            lines.push(std::mem::replace(&mut line, open.concat()));
+            has_text = false;
+            rest = after;
+        } else {
+            let end = rest.find(['<', '\n']).unwrap_or(rest.len());
+            line.push_str(&rest[..end]);
+            has_text = true;
+            rest = &rest[end..];
+        }
+    }
+    if has_text {
+        lines.push(line);
+    }
+    lines
+}
+
+fn html_escape(text: &str) -> std::borrow::Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"']) {
+        return text.into();
+    }
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .into()
+}
+
+/// The stylesheet for `theme`, or the default theme. `None` if there is no
+/// such theme. Names are matched ignoring case.
+pub fn theme_css(theme: Option<&str>) -> Option<String> {
+    let wanted = theme.unwrap_or(DEFAULT_THEME);
+    let name: EmbeddedThemeName = *EmbeddedLazyThemeSet::theme_names()
+        .iter()
+        .find(|name| name.as_name().eq_ignore_ascii_case(wanted))?;
+    let theme = themes().get(name);
+
+    let mut css = css_for_theme_with_class_style(theme, CLASS_STYLE).ok()?;
+    let highlight = theme
+        .settings
+        .line_highlight
+        .map(|c| {
+            format!(
+                "rgba({}, {}, {}, {:.2})",
+                c.r,
+                c.g,
+                c.b,
+                f32::from(c.a) / 255.0
+            )
+        })
+        .unwrap_or_else(|| "rgba(255, 230, 0, 0.2)".to_string());
+    css.push_str(&LINE_CSS.replace("{highlight}", &highlight));
+    Some(css)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_strings_give_the_language_and_lines_to_highlight() {
+        assert_eq!(
+            parse_info("rust {3,5-7}"),
+            (Some("rust"), vec![3..=3, 5..=7])
+        );
+        assert_eq!(parse_info("{3,5-7}"), (None, vec![3..=3, 5..=7]));
+        assert_eq!(parse_info("rust"), (Some("rust"), vec![]));
+        assert_eq!(parse_info("py {x, 2, 4-}"), (Some("py"), vec![2..=2]));
+    }
+
+    #[test]
+    fn code_blocks_mark_the_highlighted_lines() {
+        let code: String = (1..=8).map(|n| format!("let x{} = {};\n", n, n)).collect();
+        let html = code_block("rust {3,5-7}", &code);
+
+        let highlighted: Vec<usize> = html
+            .lines()
+            .filter(|line| line.contains("code-line-highlighted"))
+            .filter_map(|line| {
+                line.split("data-line=\"")
+                    .nth(1)?
+                    .split('"')
+                    .next()?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        assert_eq!(highlighted, [3, 5, 6, 7]);
+        assert_eq!(html.matches(r#"class="code-line""#).count(), 4);
+        assert!(html.contains(r#"<code class="language-rust">"#), "{}", html);
+    }
+
+    #[test]
+    fn unknown_languages_are_escaped_but_not_highlighted() {
+        let html = code_block("nosuchlang", "<b>&</b>\n");
+        assert!(html.contains("&lt;b&gt;&amp;&lt;/b&gt;"), "{}", html);
+        assert_eq!(html.matches(CLASS_PREFIX).count(), 1, "{}", html);
+    }
+}
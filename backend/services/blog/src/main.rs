@@ -1,14 +1,19 @@
 mod error;
 mod handlers;
+mod highlight;
+mod markdown;
 mod models;
+mod romaji;
 mod services;
+mod slugs;
 
 use axum::{
-    routing::{get, post, put, delete},
-    Router,
+    routing::{delete, get},
+    Extension, Router,
 };
 use dotenv::dotenv;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -32,6 +37,10 @@ async fn main() {
         .allow_methods(Any)
         .allow_headers(Any);
 
+    // Access tokens are verified against the auth service's published keys
+    let jwks = Arc::new(service_auth::JwksCache::from_env());
+    jwks.clone().spawn_refresh();
+
     // Build the router
     let app = Router::new()
         .route("/health", get(health_check))
@@ -42,8 +51,19 @@ async fn main() {
                 .put(handlers::posts::update_post)
                 .delete(handlers::posts::delete_post),
         )
-        .route("/categories", get(handlers::categories::list_categories))
-        .route("/tags", get(handlers::tags::list_tags))
+        .route(
+            "/categories",
+            get(handlers::categories::list_categories).post(handlers::categories::create_category),
+        )
+        .route(
+            "/tags",
+            get(handlers::tags::list_tags).post(handlers::tags::create_tag),
+        )
+        .route("/highlight.css", get(handlers::highlight::theme_css))
+        .route("/me", delete(handlers::account::delete_data))
+        .route("/me/export", get(handlers::account::export_data))
+        .with_state(services::from_env())
+        .layer(Extension(jwks))
         .layer(TraceLayer::new_for_http())
         .layer(cors);
 
@@ -56,10 +76,8 @@ async fn main() {
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
     tracing::info!("listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
 }
 
 async fn health_check() -> &'static str {
@@ -0,0 +1,308 @@
+//! Post content is written in Markdown (CommonMark with the GFM extensions:
+//! tables, footnotes, task lists, strikethrough and alerts) and rendered to
+//! HTML on the server, so the frontend only has to insert it.
+//!
+//! Fenced code blocks are syntax highlighted (see `highlight`). Every
+//! heading gets an `id` and a `<a class="anchor">` link to itself, and
+//! the same ids make up the table of contents. Raw HTML is allowed in the
+//! Markdown, so the rendered HTML always goes through an allowlist
+//! sanitizer before it is stored. Ids written in raw HTML are kept but
+//! prefixed with `user-content-`, so they can't pose as a heading's or a
+//! footnote's.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use ammonia::Builder;
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use uuid::Uuid;
+
+use crate::{highlight, models::TocEntry};
+
+/// Classes the renderer itself puts on elements. Any other class, such as
+/// one written in raw HTML, is dropped.
+const CLASSES: [&str; 6] = [
+    "anchor",
+    "footnote-reference",
+    "footnote-definition",
+    "footnote-definition-label",
+    "code-line",
+    "code-line-highlighted",
+];
+
+/// Classes allowed by prefix: code block languages, alert kinds and syntax
+/// highlighting.
+const CLASS_PREFIXES: [&str; 3] = ["language-", "markdown-alert-", highlight::CLASS_PREFIX];
+
+/// Footnote ids get a prefix so `[^1]` can't collide with a heading's id.
+const FOOTNOTE_PREFIX: &str = "fn-";
+
+/// What the sanitizer puts in front of every id, generated or not. The
+/// renderer's own ids carry a one-off marker through the sanitizer, and lose
+/// both again afterwards.
+const USER_ID_PREFIX: &str = "user-content-";
+
+fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_GFM
+}
+
+/// Renders `markdown` to sanitized HTML.
+pub fn to_html(markdown: &str) -> String {
+    // Raw HTML can't guess this, so only ids the renderer made carry it.
+    let marker = format!("g{}-", Uuid::new_v4().simple());
+    let mut events: Vec<Event> = Parser::new_ext(markdown, options())
+        .map(|event| prefix_footnotes(event, &marker))
+        .collect();
+    anchor_headings(&mut events, &marker);
+
+    let mut out = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut out, highlight_code_blocks(events).into_iter());
+    sanitizer()
+        .clean(&out)
+        .to_string()
+        .replace(&format!("{}{}", USER_ID_PREFIX, marker), "")
+        .replace(&marker, "")
+}
+
+/// The headings of `markdown`, in order, with the ids `to_html` gives them.
+pub fn table_of_contents(markdown: &str) -> Vec<TocEntry> {
+    let mut events: Vec<Event> = Parser::new_ext(markdown, options()).collect();
+    anchor_headings(&mut events, "")
+}
+
+fn prefix_footnotes<'a>(event: Event<'a>, marker: &str) -> Event<'a> {
+    match event {
+        Event::FootnoteReference(label) => {
+            Event::FootnoteReference(format!("{}{}{}", marker, FOOTNOTE_PREFIX, label).into())
+        }
+        Event::Start(Tag::FootnoteDefinition(label)) => Event::Start(Tag::FootnoteDefinition(
+            format!("{}{}{}", marker, FOOTNOTE_PREFIX, label).into(),
+        )),
+        event => event,
+    }
+}
+
+/// Replaces each fenced code block with its highlighted HTML. Indented code
+/// blocks have no language and are left as they are.
+fn highlight_code_blocks(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut block: Option<(CowStr, String)> = None;
+
+    for event in events {
+        match (event, &mut block) {
+            (Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))), None) => {
+                block = Some((info, String::new()));
+            }
+            (Event::Text(text), Some((_, code))) => code.push_str(&text),
+            (Event::End(TagEnd::CodeBlock), Some(_)) => {
+                let (info, code) = block.take().expect("inside a code block");
+                out.push(Event::Html(highlight::code_block(&info, &code).into()));
+            }
+            (event, _) => out.push(event),
+        }
+    }
+    out
+}
+
+/// Gives each heading an id and an anchor link, returning them as a table of
+/// contents. `marker` goes in front of the ids in the HTML, but not in the
+/// table of contents.
+fn anchor_headings(events: &mut Vec<Event<'_>>, marker: &str) -> Vec<TocEntry> {
+    let mut toc = Vec::new();
+    let mut used = HashSet::new();
+    let mut i = 0;
+
+    while i < events.len() {
+        let Event::Start(Tag::Heading { level, .. }) = events[i] else {
+            i += 1;
+            continue;
+        };
+        let end = events[i..]
+            .iter()
+            .position(|event| matches!(event, Event::End(TagEnd::Heading(_))))
+            .map_or(events.len(), |offset| i + offset);
+
+        let text: String = events[i + 1..end]
+            .iter()
+            .filter_map(|event| match event {
+                Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                _ => None,
+            })
+            .collect();
+        let id = unique_id(&text, &mut used);
+
+        if let Event::Start(Tag::Heading { id: heading_id, .. }) = &mut events[i] {
+            *heading_id = Some(CowStr::from(format!("{}{}", marker, id)));
+        }
+        events.insert(
+            i + 1,
+            Event::InlineHtml(
+                format!(
+                    r##"<a class="anchor" href="#{}{}" aria-hidden="true"></a>"##,
+                    marker, id
+                )
+                .into(),
+            ),
+        );
+
+        toc.push(TocEntry {
+            level: level as u8,
+            id,
+            text: text.trim().to_string(),
+        });
+        i = end + 2;
+    }
+
+    toc
+}
+
+/// GitHub's heading ids: lowercase, punctuation dropped, spaces as hyphens,
+/// and `-1`, `-2` and so on for repeats. Japanese is kept as it is.
+fn unique_id(text: &str, used: &mut HashSet<String>) -> String {
+    let mut base: String = text
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+    if base.is_empty() {
+        base = "section".to_string();
+    }
+
+    let id = if used.contains(&base) {
+        (1..)
+            .map(|n| format!("{}-{}", base, n))
+            .find(|candidate| !used.contains(candidate))
+            .expect("some suffix is free")
+    } else {
+        base
+    };
+    used.insert(id.clone());
+    id
+}
+
+/// ammonia's defaults, plus the ids, classes and elements the renderer
+/// produces.
+fn sanitizer() -> &'static Builder<'static> {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = Builder::default();
+        builder
+            .id_prefix(Some(USER_ID_PREFIX))
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["checked", "disabled"])
+            .add_tag_attribute_values("input", "type", ["checkbox"])
+            .add_tag_attributes("a", ["class", "aria-hidden"])
+            .add_tag_attributes("pre", ["class"])
+            .add_tag_attributes("code", ["class"])
+            .add_tag_attributes("span", ["class", "data-line"])
+            .add_tag_attributes("sup", ["class"])
+            .add_tag_attributes("div", ["id", "class"])
+            .add_tag_attributes("blockquote", ["class"])
+            .add_tag_attributes("th", ["style"])
+            .add_tag_attributes("td", ["style"])
+            .filter_style_properties(["text-align"].into())
+            .attribute_filter(|_, attribute, value| match attribute {
+                "class" => allowed_classes(value),
+                _ => Some(value.into()),
+            });
+        for heading in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+            builder.add_tag_attributes(heading, ["id"]);
+        }
+        builder
+    })
+}
+
+fn allowed_classes(value: &str) -> Option<Cow<'_, str>> {
+    let classes: Vec<&str> = value
+        .split_whitespace()
+        .filter(|class| {
+            CLASSES.contains(class)
+                || CLASS_PREFIXES
+                    .iter()
+                    .any(|prefix| class.len() > prefix.len() && class.starts_with(prefix))
+        })
+        .collect();
+    (!classes.is_empty()).then(|| classes.join(" ").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_html_ids_cannot_pose_as_generated_ones() {
+        let html = to_html(
+            "# Intro\n\nSee[^1].\n\n[^1]: A note.\n\n\
+             <div id=\"fn-1\">fake</div>\n\n<h2 id=\"intro\">Fake</h2>\n",
+        );
+
+        assert!(html.contains(r#"<h1 id="intro">"#), "{}", html);
+        assert!(html.contains(r##"href="#intro""##), "{}", html);
+        assert!(html.contains(r#"id="fn-1""#), "{}", html);
+        assert!(html.contains(r##"href="#fn-1""##), "{}", html);
+        assert!(html.contains(r#"<div id="user-content-fn-1">"#), "{}", html);
+        assert!(html.contains(r#"<h2 id="user-content-intro">"#), "{}", html);
+        assert_eq!(html.matches(r#"id="fn-1""#).count(), 1);
+        assert_eq!(html.matches(r#"id="intro""#).count(), 1);
+    }
+
+    #[test]
+    fn repeated_headings_get_unique_ids_matching_the_toc() {
+        let markdown = "# Setup\n\n## Setup\n\n## Setup\n\n### Setup-1\n";
+        let html = to_html(markdown);
+        let toc = table_of_contents(markdown);
+
+        let ids: Vec<&str> = toc.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, ["setup", "setup-1", "setup-2", "setup-1-1"]);
+        let levels: Vec<u8> = toc.iter().map(|entry| entry.level).collect();
+        assert_eq!(levels, [1, 2, 2, 3]);
+        let texts: Vec<&str> = toc.iter().map(|entry| entry.text.as_str()).collect();
+        assert_eq!(texts, ["Setup", "Setup", "Setup", "Setup-1"]);
+        for entry in &toc {
+            assert!(html.contains(&format!(r#"id="{}""#, entry.id)), "{}", html);
+            assert!(
+                html.contains(&format!(r##"href="#{}""##, entry.id)),
+                "{}",
+                html
+            );
+        }
+    }
+
+    #[test]
+    fn scripts_and_unknown_classes_are_stripped() {
+        let html = to_html(
+            "<script>alert(1)</script>\n\n\
+             <span class=\"evil language-rust\" onclick=\"alert(2)\">x</span>\n",
+        );
+
+        assert!(!html.contains("<script"), "{}", html);
+        assert!(!html.contains("alert"), "{}", html);
+        assert!(!html.contains("evil"), "{}", html);
+        assert!(
+            html.contains(r#"<span class="language-rust">x</span>"#),
+            "{}",
+            html
+        );
+    }
+
+    #[test]
+    fn only_known_classes_are_allowed() {
+        assert_eq!(allowed_classes("anchor").as_deref(), Some("anchor"));
+        assert_eq!(
+            allowed_classes("language- markdown-alert-note x").as_deref(),
+            Some("markdown-alert-note")
+        );
+        assert_eq!(allowed_classes("evil"), None);
+    }
+}
@@ -2,13 +2,18 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::markdown;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Post {
     pub id: Uuid,
     pub author_id: Uuid,
     pub title: String,
     pub slug: String,
+    /// Markdown, as the author wrote it.
     pub content: String,
+    /// `content` rendered and sanitized, kept up to date with it.
+    pub content_html: String,
     pub excerpt: Option<String>,
     pub status: PostStatus,
     pub published_at: Option<DateTime<Utc>>,
@@ -16,13 +21,43 @@ pub struct Post {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+impl Post {
+    pub fn into_response(self, categories: Vec<Category>, tags: Vec<Tag>) -> PostResponse {
+        PostResponse {
+            id: self.id,
+            author_id: self.author_id,
+            title: self.title,
+            slug: self.slug,
+            toc: markdown::table_of_contents(&self.content),
+            content: self.content,
+            content_html: self.content_html,
+            excerpt: self.excerpt,
+            status: self.status,
+            published_at: self.published_at,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            categories,
+            tags,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum PostStatus {
     Draft,
     Published,
 }
 
+impl PostStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PostStatus::Draft => "draft",
+            PostStatus::Published => "published",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Category {
     pub id: Uuid,
@@ -44,6 +79,9 @@ pub struct Tag {
 #[derive(Debug, Deserialize)]
 pub struct CreatePostRequest {
     pub title: String,
+    /// Used instead of the one derived from the title. Must already be in slug
+    /// form (lowercase letters, digits and hyphens) and not be taken.
+    pub slug: Option<String>,
     pub content: String,
     pub excerpt: Option<String>,
     pub status: PostStatus,
@@ -61,6 +99,18 @@ pub struct UpdatePostRequest {
     pub tag_ids: Option<Vec<Uuid>>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateCategoryRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub parent_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTagRequest {
+    pub name: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct PostResponse {
     pub id: Uuid,
@@ -68,6 +118,8 @@ pub struct PostResponse {
     pub title: String,
     pub slug: String,
     pub content: String,
+    pub content_html: String,
+    pub toc: Vec<TocEntry>,
     pub excerpt: Option<String>,
     pub status: PostStatus,
     pub published_at: Option<DateTime<Utc>>,
@@ -77,6 +129,23 @@ pub struct PostResponse {
     pub tags: Vec<Tag>,
 }
 
+/// A heading in a post, linked to by `#id` in its `content_html`.
+#[derive(Debug, Serialize)]
+pub struct TocEntry {
+    /// 1 for `#`, up to 6 for `######`.
+    pub level: u8,
+    pub id: String,
+    pub text: String,
+}
+
+/// What a slug points at. Old slugs are kept when a post is renamed, so links
+/// to them can be redirected to the post's current slug.
+#[derive(Debug)]
+pub enum SlugLookup {
+    Current(PostResponse),
+    Moved(PostResponse),
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PaginationParams {
     pub page: Option<u32>,
@@ -105,10 +174,15 @@ impl<T> PaginatedResponse<T> {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct PostFilters {
     pub status: Option<PostStatus>,
     pub author_id: Option<Uuid>,
     pub category_id: Option<Uuid>,
     pub tag_id: Option<Uuid>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ThemeParams {
+    pub theme: Option<String>,
+}
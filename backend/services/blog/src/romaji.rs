@@ -0,0 +1,389 @@
+//! Japanese to romaji, for slugs.
+//!
+//! Kana are read in Hepburn (`shi`, `chi`, `tsu`, `fu`, `ji`), with small
+//! kana folded into the syllable before them and long vowel marks dropped.
+//! Kanji are looked up in an embedded dictionary: whole words first, longest
+//! match winning, then one kanji at a time, read as part of a compound, as
+//! the stem of its okurigana, or on its own depending on its neighbours.
+//! Words are split where the script changes, so `Rustで始めるマイクロサービス`
+//! reads `Rust de hajimeru maikurosabisu`.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Hiragana that, following a kanji on their own, are a particle rather than
+/// the kanji's okurigana.
+const PARTICLES: [&str; 21] = [
+    "の", "を", "に", "で", "と", "は", "が", "へ", "も", "や", "か", "から", "まで", "より",
+    "では", "には", "とは", "での", "への", "との", "でも",
+];
+
+enum Reading {
+    /// A whole word, read as one.
+    Word(&'static str),
+    Kanji {
+        compound: &'static str,
+        stem: Option<&'static str>,
+        alone: Option<&'static str>,
+    },
+}
+
+struct Dictionary {
+    entries: HashMap<&'static str, Reading>,
+    /// The longest entry, in characters.
+    longest: usize,
+}
+
+fn dictionary() -> &'static Dictionary {
+    static DICTIONARY: OnceLock<Dictionary> = OnceLock::new();
+    DICTIONARY.get_or_init(|| {
+        let mut entries = HashMap::new();
+        for line in include_str!("../data/kanji_readings.tsv").lines() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut columns = line.split('\t').map(str::trim);
+            let (Some(key), Some(reading)) = (columns.next(), columns.next()) else {
+                continue;
+            };
+            let mut optional = || columns.next().filter(|column| *column != "-");
+            let entry = if key.chars().count() == 1 {
+                Reading::Kanji {
+                    compound: reading,
+                    stem: optional(),
+                    alone: optional(),
+                }
+            } else {
+                Reading::Word(reading)
+            };
+            entries.entry(key).or_insert(entry);
+        }
+
+        let longest = entries
+            .keys()
+            .map(|key| key.chars().count())
+            .max()
+            .unwrap_or(1);
+        Dictionary { entries, longest }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Kanji,
+    Hiragana,
+    Katakana,
+    Other,
+}
+
+fn script_of(c: char) -> Script {
+    match c {
+        '\u{3041}'..='\u{309F}' => Script::Hiragana,
+        '\u{30A1}'..='\u{30FA}' | '\u{31F0}'..='\u{31FF}' => Script::Katakana,
+        '々' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' => {
+            Script::Kanji
+        }
+        _ => Script::Other,
+    }
+}
+
+/// Reads `text` as romaji, with a space wherever the script changes and
+/// anything that isn't Japanese left as it is. `None` if a kanji isn't in the
+/// dictionary.
+pub fn to_romaji(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut scripts: Vec<Script> = Vec::with_capacity(chars.len());
+    for &c in &chars {
+        // A long vowel mark belongs to whichever kana it lengthens.
+        let script = match (c, scripts.last()) {
+            ('ー', Some(&previous @ (Script::Hiragana | Script::Katakana))) => previous,
+            ('ー', _) => Script::Katakana,
+            _ => script_of(c),
+        };
+        scripts.push(script);
+    }
+
+    let dictionary = dictionary();
+    let mut out = String::new();
+    let mut previous: Option<Script> = None;
+    let mut last_kanji = "";
+    let mut i = 0;
+
+    while i < chars.len() {
+        let script = scripts[i];
+        let joins_previous = match previous {
+            None => true,
+            Some(Script::Kanji) if script == Script::Hiragana => !is_particle(&chars, &scripts, i),
+            Some(previous) => previous == script,
+        };
+        if !joins_previous {
+            out.push(' ');
+        }
+
+        let end = match script {
+            Script::Kanji => {
+                let (end, reading) = read_kanji(dictionary, &chars, &scripts, i, last_kanji)?;
+                out.push_str(reading);
+                last_kanji = reading;
+                end
+            }
+            Script::Hiragana | Script::Katakana => {
+                let end = (i..chars.len())
+                    .find(|&j| scripts[j] != script)
+                    .unwrap_or(chars.len());
+                out.push_str(&kana_to_romaji(&chars[i..end]));
+                end
+            }
+            Script::Other => {
+                out.push(chars[i]);
+                i + 1
+            }
+        };
+
+        previous = Some(scripts[end - 1]);
+        i = end;
+    }
+
+    Some(out)
+}
+
+/// Whether the hiragana run starting at `start` is a particle on its own.
+fn is_particle(chars: &[char], scripts: &[Script], start: usize) -> bool {
+    let end = (start..chars.len())
+        .find(|&j| scripts[j] != Script::Hiragana)
+        .unwrap_or(chars.len());
+    let run: String = chars[start..end].iter().collect();
+    PARTICLES.contains(&run.as_str())
+}
+
+/// Reads the word or kanji at `start`, returning where it ends.
+fn read_kanji(
+    dictionary: &Dictionary,
+    chars: &[char],
+    scripts: &[Script],
+    start: usize,
+    last_kanji: &'static str,
+) -> Option<(usize, &'static str)> {
+    let longest = dictionary.longest.min(chars.len() - start);
+    for len in (2..=longest).rev() {
+        let key: String = chars[start..start + len].iter().collect();
+        if let Some(Reading::Word(reading)) = dictionary.entries.get(key.as_str()) {
+            return Some((start + len, reading));
+        }
+    }
+
+    // The iteration mark repeats the kanji before it.
+    if chars[start] == '々' {
+        return Some((start + 1, last_kanji));
+    }
+
+    let key = chars[start].to_string();
+    let Some(Reading::Kanji {
+        compound,
+        stem,
+        alone,
+    }) = dictionary.entries.get(key.as_str())
+    else {
+        return None;
+    };
+
+    // Okurigana after a compound usually make a noun into a verb (追加します),
+    // so only a kanji on its own takes its stem reading.
+    let after = scripts.get(start + 1).copied();
+    let follows_kanji = start > 0 && scripts[start - 1] == Script::Kanji;
+    let reading = if follows_kanji || after == Some(Script::Kanji) {
+        compound
+    } else if after == Some(Script::Hiragana) && !is_particle(chars, scripts, start + 1) {
+        stem.unwrap_or(compound)
+    } else {
+        alone.unwrap_or(compound)
+    };
+    Some((start + 1, reading))
+}
+
+fn kana_to_romaji(kana: &[char]) -> String {
+    let mut syllables: Vec<String> = Vec::with_capacity(kana.len());
+    let mut double_next = false;
+
+    for &c in kana {
+        let c = to_hiragana(c);
+        match c {
+            'っ' => double_next = true,
+            'ー' => {}
+            'ゃ' | 'ゅ' | 'ょ' => {
+                let vowel = match c {
+                    'ゃ' => 'a',
+                    'ゅ' => 'u',
+                    _ => 'o',
+                };
+                match syllables.last_mut() {
+                    // き+ゃ is kya, し+ゃ is sha, and て+ゅ (デュ) is tyu.
+                    Some(last)
+                        if last.len() > 1
+                            && (last.ends_with('i') || last == "te" || last == "de") =>
+                    {
+                        last.pop();
+                        if !(last.ends_with("sh") || last.ends_with("ch") || last.ends_with('j')) {
+                            last.push('y');
+                        }
+                        last.push(vowel);
+                    }
+                    _ => syllables.push(format!("y{}", vowel)),
+                }
+            }
+            'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'ゎ' => {
+                let vowel = match c {
+                    'ぁ' | 'ゎ' => "a",
+                    'ぃ' => "i",
+                    'ぅ' => "u",
+                    'ぇ' => "e",
+                    _ => "o",
+                };
+                match syllables.last_mut() {
+                    // ウィ is wi, ファ is fa, ティ is ti.
+                    Some(last) if last == "u" => *last = format!("w{}", vowel),
+                    Some(last) if last.len() > 1 => {
+                        last.pop();
+                        last.push_str(vowel);
+                    }
+                    _ => syllables.push(vowel.to_string()),
+                }
+            }
+            _ => {
+                let Some(romaji) = hiragana_romaji(c) else {
+                    continue;
+                };
+                let mut syllable = String::with_capacity(romaji.len() + 1);
+                if std::mem::take(&mut double_next) {
+                    if romaji.starts_with("ch") {
+                        syllable.push('t');
+                    } else if let Some(first) =
+                        romaji.chars().next().filter(|c| !"aiueon".contains(*c))
+                    {
+                        syllable.push(first);
+                    }
+                }
+                syllable.push_str(romaji);
+                syllables.push(syllable);
+            }
+        }
+    }
+
+    syllables.concat()
+}
+
+/// Katakana read the same as the hiragana 0x60 code points below them.
+fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn hiragana_romaji(c: char) -> Option<&'static str> {
+    let romaji = match c {
+        'あ' => "a",
+        'い' => "i",
+        'う' => "u",
+        'え' => "e",
+        'お' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' => "ya",
+        'ゆ' => "yu",
+        'よ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' => "wa",
+        'ゐ' => "i",
+        'ゑ' => "e",
+        'を' => "o",
+        'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None,
+    };
+    Some(romaji)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kana(text: &str) -> String {
+        kana_to_romaji(&text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn kana_are_read_in_hepburn() {
+        assert_eq!(kana("しちつふじ"), "shichitsufuji");
+        assert_eq!(kana("きゃしゅちょ"), "kyashucho");
+        assert_eq!(kana("がっこう"), "gakkou");
+        assert_eq!(kana("マッチ"), "matchi");
+        assert_eq!(kana("ウィキ"), "wiki");
+        assert_eq!(kana("サーバー"), "saba");
+    }
+
+    #[test]
+    fn words_are_split_where_the_script_changes() {
+        assert_eq!(
+            to_romaji("Rustで始めるマイクロサービス").as_deref(),
+            Some("Rust de hajimeru maikurosabisu")
+        );
+        assert_eq!(to_romaji("齉"), None);
+    }
+}
@@ -0,0 +1,290 @@
+mod postgrest;
+mod sqlite;
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    error::{BlogError, Result},
+    models::{
+        Category, CreateCategoryRequest, CreatePostRequest, CreateTagRequest, PaginatedResponse,
+        PostFilters, PostResponse, PostStatus, SlugLookup, Tag, UpdatePostRequest,
+    },
+    slugs::SlugStrategy,
+};
+
+pub use self::postgrest::{PostgrestBlogService, SupabaseClient};
+pub use self::sqlite::SqliteBlogService;
+
+#[async_trait]
+pub trait BlogService: Send + Sync {
+    /// Newest first. `total` counts every post matching `filters`, not just
+    /// the ones on this page.
+    async fn list_posts(
+        &self,
+        page: u32,
+        per_page: u32,
+        filters: Option<PostFilters>,
+    ) -> Result<PaginatedResponse<PostResponse>>;
+
+    async fn get_post(&self, id: Uuid) -> Result<PostResponse>;
+    /// Finds a post by its current slug, or by one it had before being
+    /// renamed.
+    async fn get_post_by_slug(&self, slug: &str) -> Result<SlugLookup>;
+    async fn create_post(&self, author_id: Uuid, req: CreatePostRequest) -> Result<PostResponse>;
+    async fn update_post(
+        &self,
+        id: Uuid,
+        author_id: Uuid,
+        req: UpdatePostRequest,
+    ) -> Result<PostResponse>;
+    async fn delete_post(&self, id: Uuid, author_id: Uuid) -> Result<()>;
+
+    async fn list_categories(&self) -> Result<Vec<Category>>;
+    async fn create_category(&self, req: CreateCategoryRequest) -> Result<Category>;
+    async fn list_tags(&self) -> Result<Vec<Tag>>;
+    async fn create_tag(&self, req: CreateTagRequest) -> Result<Tag>;
+
+    /// Every post the user wrote, drafts included, for their data export.
+    async fn list_posts_by_author(&self, author_id: Uuid) -> Result<Vec<PostResponse>>;
+
+    /// Deletes the user's posts when their account is deleted. Posts always
+    /// have an author, so unlike comments they cannot be kept anonymously.
+    /// Returns how many were deleted.
+    async fn delete_posts_by_author(&self, author_id: Uuid) -> Result<u64>;
+}
+
+/// Uses Supabase when `SUPABASE_URL` is set, and otherwise an embedded SQLite
+/// database at `SQLITE_PATH`, kept in memory when that is unset too. Slugs
+/// are made the way `SLUG_STRATEGY` says.
+pub fn from_env() -> Arc<dyn BlogService> {
+    let slugs = SlugStrategy::from_env();
+    match SupabaseClient::from_env() {
+        Some(client) => Arc::new(PostgrestBlogService::new(client, slugs)),
+        None => {
+            let path = std::env::var("SQLITE_PATH").unwrap_or_else(|_| ":memory:".to_string());
+            tracing::warn!(path = %path, "SUPABASE_URL is not set, using SQLite");
+            Arc::new(SqliteBlogService::open(&path, slugs).expect("Failed to open SQLite database"))
+        }
+    }
+}
+
+/// Posts get their slug from their title, categories and tags from their
+/// name.
+fn slug_for(slugs: SlugStrategy, text: &str) -> Result<String> {
+    let slug = slugs.slugify(text);
+    if slug.is_empty() {
+        return Err(BlogError::Validation(
+            "Name must contain at least one letter or digit".to_string(),
+        ));
+    }
+    Ok(slug)
+}
+
+/// How many times a post is written with its derived slug resolved again,
+/// when another post takes the slug between the lookup and the write.
+const SLUG_ATTEMPTS: usize = 3;
+
+/// The slug a new or renamed post asks for.
+enum SlugRequest {
+    /// Chosen by the author; taken means `SlugExists`.
+    Custom(String),
+    /// Derived from the title; taken means trying `-2`, `-3` and so on.
+    FromTitle(String),
+}
+
+impl SlugRequest {
+    fn new(slugs: SlugStrategy, title: &str, custom: Option<String>) -> Result<Self> {
+        match custom {
+            Some(slug) => {
+                if slug.is_empty() || slug::slugify(&slug) != slug {
+                    return Err(BlogError::Validation(
+                        "Slug may only contain lowercase letters, digits and hyphens".to_string(),
+                    ));
+                }
+                Ok(SlugRequest::Custom(slug))
+            }
+            None => Ok(SlugRequest::FromTitle(slug_for(slugs, title)?)),
+        }
+    }
+
+    /// The slug whose variants the backend looks up: the slug itself and
+    /// anything starting with `base-`.
+    fn base(&self) -> &str {
+        match self {
+            SlugRequest::Custom(slug) | SlugRequest::FromTitle(slug) => slug,
+        }
+    }
+
+    /// `taken` holds the slugs other posts use or used to use.
+    fn resolve(&self, taken: &HashSet<String>) -> Result<String> {
+        match self {
+            SlugRequest::Custom(slug) if taken.contains(slug) => Err(BlogError::SlugExists),
+            SlugRequest::Custom(slug) => Ok(slug.clone()),
+            SlugRequest::FromTitle(base) => {
+                if !taken.contains(base) {
+                    return Ok(base.clone());
+                }
+                Ok((2..)
+                    .map(|n| format!("{}-{}", base, n))
+                    .find(|candidate| !taken.contains(candidate))
+                    .expect("some suffix is free"))
+            }
+        }
+    }
+}
+
+/// Runs `write`, which resolves `slug` and saves the post, again when it
+/// fails with `SlugExists` because another post took a derived slug first.
+/// A custom slug is the author's choice, so for it `SlugExists` is final.
+async fn with_unique_slug<T, F, Fut>(slug: Option<&SlugRequest>, mut write: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let retries = matches!(slug, Some(SlugRequest::FromTitle(_)));
+    let mut attempt = 1;
+    loop {
+        match write().await {
+            Err(BlogError::SlugExists) if retries && attempt < SLUG_ATTEMPTS => attempt += 1,
+            result => return result,
+        }
+    }
+}
+
+/// The slug a retitled post should move to, if any. Slugs the author chose
+/// stay put, and so do derived ones the new title still produces.
+fn retitled_slug(
+    slugs: SlugStrategy,
+    current: &str,
+    old_title: &str,
+    new_title: &str,
+) -> Result<Option<SlugRequest>> {
+    let base = slug_for(slugs, new_title)?;
+    if !derived_from(current, &slugs.slugify(old_title)) || derived_from(current, &base) {
+        return Ok(None);
+    }
+    Ok(Some(SlugRequest::FromTitle(base)))
+}
+
+/// Whether `slug` is `base`, possibly with the suffix it got to be unique.
+fn derived_from(slug: &str, base: &str) -> bool {
+    slug == base
+        || slug
+            .strip_prefix(base)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .is_some_and(|suffix| !suffix.is_empty() && suffix.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// A post keeps the date it was first published, even if it is later moved
+/// back to draft and published again.
+fn published_at_for(status: PostStatus, current: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match status {
+        PostStatus::Published => current.or_else(|| Some(Utc::now())),
+        PostStatus::Draft => current,
+    }
+}
+
+/// Drops repeated ids so the join tables never see the same pair twice.
+fn dedup_ids(ids: Vec<Uuid>) -> Vec<Uuid> {
+    let mut unique = Vec::with_capacity(ids.len());
+    for id in ids {
+        if !unique.contains(&id) {
+            unique.push(id);
+        }
+    }
+    unique
+}
+
+fn offset_for(page: u32, per_page: u32) -> usize {
+    page.saturating_sub(1) as usize * per_page as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn taken(slugs: &[&str]) -> HashSet<String> {
+        slugs.iter().map(|slug| slug.to_string()).collect()
+    }
+
+    #[test]
+    fn derived_slugs_get_the_first_free_suffix() {
+        let slug = SlugRequest::FromTitle("hello".to_string());
+        assert_eq!(slug.resolve(&taken(&[])).unwrap(), "hello");
+        assert_eq!(
+            slug.resolve(&taken(&["hello", "hello-2", "hello-4"]))
+                .unwrap(),
+            "hello-3"
+        );
+    }
+
+    #[test]
+    fn taken_custom_slugs_are_refused() {
+        let slug = SlugRequest::Custom("hello".to_string());
+        assert_eq!(slug.resolve(&taken(&["hello-2"])).unwrap(), "hello");
+        assert!(matches!(
+            slug.resolve(&taken(&["hello"])),
+            Err(BlogError::SlugExists)
+        ));
+    }
+
+    /// Fails with `SlugExists` `conflicts` times, then succeeds.
+    async fn write_after(conflicts: usize, slug: &SlugRequest) -> (Result<()>, usize) {
+        let calls = Cell::new(0);
+        let result = with_unique_slug(Some(slug), || {
+            calls.set(calls.get() + 1);
+            let conflict = calls.get() <= conflicts;
+            async move {
+                if conflict {
+                    Err(BlogError::SlugExists)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+        (result, calls.get())
+    }
+
+    #[tokio::test]
+    async fn derived_slug_conflicts_are_retried() {
+        let slug = SlugRequest::FromTitle("hello".to_string());
+
+        let (result, calls) = write_after(1, &slug).await;
+        assert!(result.is_ok());
+        assert_eq!(calls, 2);
+
+        let (result, calls) = write_after(SLUG_ATTEMPTS, &slug).await;
+        assert!(matches!(result, Err(BlogError::SlugExists)));
+        assert_eq!(calls, SLUG_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn custom_slug_conflicts_are_final() {
+        let (result, calls) = write_after(1, &SlugRequest::Custom("hello".to_string())).await;
+        assert!(matches!(result, Err(BlogError::SlugExists)));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn retitling_moves_only_derived_slugs() {
+        let slugs = SlugStrategy::Ascii;
+        let moved = retitled_slug(slugs, "hello-2", "Hello", "Goodbye").unwrap();
+        assert!(matches!(moved, Some(SlugRequest::FromTitle(base)) if base == "goodbye"));
+
+        assert!(retitled_slug(slugs, "my-own-slug", "Hello", "Goodbye")
+            .unwrap()
+            .is_none());
+        assert!(retitled_slug(slugs, "hello", "Hello", "hello!")
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,559 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use postgrest::{Builder, Postgrest};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{
+    dedup_ids, offset_for, published_at_for, retitled_slug, slug_for, with_unique_slug,
+    BlogService, SlugRequest,
+};
+use crate::{
+    error::{BlogError, Result},
+    markdown,
+    models::{
+        Category, CreateCategoryRequest, CreatePostRequest, CreateTagRequest, PaginatedResponse,
+        Post, PostFilters, PostResponse, SlugLookup, Tag, UpdatePostRequest,
+    },
+    slugs::SlugStrategy,
+};
+
+/// Postgres error code PostgREST reports for unique constraint violations.
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// Posts are read with their categories and tags embedded, which PostgREST
+/// resolves through the `post_categories` and `post_tags` join tables.
+const POST_COLUMNS: &str = "*,categories(*),tags(*)";
+
+/// Extra embeds `list_posts` filters on. `!inner` drops the posts without a
+/// matching join row, while `categories` and `tags` above still list all of
+/// a post's terms.
+const CATEGORY_FILTER: &str = "in_category:post_categories!inner(category_id)";
+const TAG_FILTER: &str = "with_tag:post_tags!inner(tag_id)";
+
+#[derive(Clone)]
+pub struct SupabaseClient {
+    client: Postgrest,
+}
+
+impl SupabaseClient {
+    pub fn new(url: &str, service_key: &str) -> Self {
+        let client = Postgrest::new(format!("{}/rest/v1", url.trim_end_matches('/')))
+            .insert_header("apikey", service_key)
+            .insert_header("Authorization", format!("Bearer {}", service_key));
+
+        Self { client }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("SUPABASE_URL").ok()?;
+        let service_key = std::env::var("SUPABASE_SERVICE_KEY")
+            .or_else(|_| std::env::var("SUPABASE_ANON_KEY"))
+            .ok()?;
+
+        Some(Self::new(&url, &service_key))
+    }
+
+    fn from(&self, table: &str) -> Builder {
+        self.client.from(table)
+    }
+
+    fn rpc(&self, function: &str, params: String) -> Builder {
+        self.client.rpc(function, params)
+    }
+}
+
+enum QueryError {
+    Conflict,
+    Failed(String),
+}
+
+impl From<QueryError> for BlogError {
+    fn from(err: QueryError) -> Self {
+        match err {
+            QueryError::Conflict => BlogError::Database("unique constraint violation".to_string()),
+            QueryError::Failed(msg) => BlogError::Database(msg),
+        }
+    }
+}
+
+fn check_status(status: StatusCode, body: &str) -> std::result::Result<(), QueryError> {
+    if status.is_success() {
+        return Ok(());
+    }
+    if status == StatusCode::CONFLICT || body.contains(UNIQUE_VIOLATION) {
+        return Err(QueryError::Conflict);
+    }
+    Err(QueryError::Failed(format!("{}: {}", status, body)))
+}
+
+async fn fetch_rows<T: DeserializeOwned>(
+    builder: Builder,
+) -> std::result::Result<Vec<T>, QueryError> {
+    let response = builder
+        .execute()
+        .await
+        .map_err(|e| QueryError::Failed(e.to_string()))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| QueryError::Failed(e.to_string()))?;
+
+    check_status(status, &body)?;
+    serde_json::from_str(&body).map_err(|e| QueryError::Failed(e.to_string()))
+}
+
+/// Like [`fetch_rows`], for requests whose response has no rows to read.
+async fn execute(builder: Builder) -> std::result::Result<(), QueryError> {
+    let response = builder
+        .execute()
+        .await
+        .map_err(|e| QueryError::Failed(e.to_string()))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| QueryError::Failed(e.to_string()))?;
+    check_status(status, &body)
+}
+
+/// Like [`fetch_rows`], for a request made with `exact_count()`. Also returns
+/// the total PostgREST reports in `Content-Range` (`0-9/42`).
+async fn fetch_page<T: DeserializeOwned>(
+    builder: Builder,
+) -> std::result::Result<(Vec<T>, u64), QueryError> {
+    let response = builder
+        .execute()
+        .await
+        .map_err(|e| QueryError::Failed(e.to_string()))?;
+
+    let status = response.status();
+    let total = response
+        .headers()
+        .get("content-range")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|range| range.rsplit('/').next())
+        .and_then(|total| total.parse().ok());
+    let body = response
+        .text()
+        .await
+        .map_err(|e| QueryError::Failed(e.to_string()))?;
+
+    // Asking for a page past the end is not an error, just an empty page.
+    if status == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok((Vec::new(), total.unwrap_or(0)));
+    }
+    check_status(status, &body)?;
+
+    let rows = serde_json::from_str(&body).map_err(|e| QueryError::Failed(e.to_string()))?;
+    let total = total.ok_or_else(|| QueryError::Failed("response has no total".to_string()))?;
+    Ok((rows, total))
+}
+
+async fn fetch_optional<T: DeserializeOwned>(builder: Builder) -> Result<Option<T>> {
+    Ok(fetch_rows(builder.limit(1)).await?.into_iter().next())
+}
+
+fn to_body<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value)
+        .map_err(|e| BlogError::Internal(anyhow::anyhow!("Failed to encode row: {}", e)))
+}
+
+fn first_row<T>(rows: Vec<T>) -> Result<T> {
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| BlogError::Database("insert returned no rows".to_string()))
+}
+
+#[derive(Deserialize)]
+struct PostRow {
+    #[serde(flatten)]
+    post: Post,
+    #[serde(default)]
+    categories: Vec<Category>,
+    #[serde(default)]
+    tags: Vec<Tag>,
+}
+
+impl From<PostRow> for PostResponse {
+    fn from(row: PostRow) -> Self {
+        row.post.into_response(row.categories, row.tags)
+    }
+}
+
+#[derive(Deserialize)]
+struct PostIdRow {
+    post_id: Uuid,
+}
+
+#[derive(Deserialize)]
+struct SlugRow {
+    slug: String,
+}
+
+#[derive(Deserialize)]
+struct IdRow {
+    id: Uuid,
+}
+
+pub struct PostgrestBlogService {
+    db: SupabaseClient,
+    slugs: SlugStrategy,
+}
+
+impl PostgrestBlogService {
+    pub fn new(db: SupabaseClient, slugs: SlugStrategy) -> Self {
+        Self { db, slugs }
+    }
+
+    /// Fails with `missing` unless every id exists in `table`.
+    async fn ensure_exist(
+        &self,
+        table: &str,
+        ids: &[Uuid],
+        missing: fn() -> BlogError,
+    ) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let rows: Vec<IdRow> = fetch_rows(
+            self.db
+                .from(table)
+                .select("id")
+                .in_("id", ids.iter().map(Uuid::to_string)),
+        )
+        .await?;
+        let found: HashSet<Uuid> = rows.into_iter().map(|row| row.id).collect();
+        if ids.iter().all(|id| found.contains(id)) {
+            Ok(())
+        } else {
+            Err(missing())
+        }
+    }
+
+    /// Writes `post`, new or updated, together with its categories, tags
+    /// and previous slug in one transaction (the `save_post` function). `None`
+    /// leaves the categories or tags as they are.
+    async fn save_post(
+        &self,
+        post: &Post,
+        category_ids: Option<&[Uuid]>,
+        tag_ids: Option<&[Uuid]>,
+        old_slug: Option<&str>,
+    ) -> std::result::Result<(), QueryError> {
+        let params = json!({
+            "post": post,
+            "category_ids": category_ids,
+            "tag_ids": tag_ids,
+            "old_slug": old_slug,
+        });
+        execute(self.db.rpc("save_post", params.to_string())).await
+    }
+
+    async fn find_post(&self, column: &str, value: &str) -> Result<Option<PostResponse>> {
+        Ok(
+            fetch_optional::<PostRow>(self.db.from("posts").select(POST_COLUMNS).eq(column, value))
+                .await?
+                .map(PostResponse::from),
+        )
+    }
+
+    /// Slugs that are `base` or start with `base-`, used by posts other than
+    /// `exclude` now or before they were renamed.
+    async fn taken_slugs(&self, base: &str, exclude: Option<Uuid>) -> Result<HashSet<String>> {
+        let filter = format!("slug.eq.{0},slug.like.{0}-*", base);
+        let mut posts = self.db.from("posts").select("slug").or(&filter);
+        let mut history = self.db.from("post_slug_history").select("slug").or(&filter);
+        if let Some(id) = exclude {
+            posts = posts.neq("id", id.to_string());
+            history = history.neq("post_id", id.to_string());
+        }
+
+        let mut taken = HashSet::new();
+        for query in [posts, history] {
+            let rows: Vec<SlugRow> = fetch_rows(query).await?;
+            taken.extend(rows.into_iter().map(|row| row.slug));
+        }
+        Ok(taken)
+    }
+}
+
+fn post_write_error(err: QueryError) -> BlogError {
+    match err {
+        QueryError::Conflict => BlogError::SlugExists,
+        err => err.into(),
+    }
+}
+
+fn term_write_error(slug: &str, err: QueryError) -> BlogError {
+    match err {
+        QueryError::Conflict => BlogError::Validation(format!("'{}' already exists", slug)),
+        err => err.into(),
+    }
+}
+
+#[async_trait]
+impl BlogService for PostgrestBlogService {
+    async fn list_posts(
+        &self,
+        page: u32,
+        per_page: u32,
+        filters: Option<PostFilters>,
+    ) -> Result<PaginatedResponse<PostResponse>> {
+        let filters = filters.unwrap_or_default();
+
+        // Category and tag filters are inner joins in the same request, so
+        // the page and its total are both computed over the matching posts.
+        let mut columns = POST_COLUMNS.to_string();
+        if filters.category_id.is_some() {
+            columns = format!("{},{}", columns, CATEGORY_FILTER);
+        }
+        if filters.tag_id.is_some() {
+            columns = format!("{},{}", columns, TAG_FILTER);
+        }
+
+        let mut query = self
+            .db
+            .from("posts")
+            .select(columns)
+            .order("created_at.desc");
+        if let Some(status) = filters.status {
+            query = query.eq("status", status.as_str());
+        }
+        if let Some(author_id) = filters.author_id {
+            query = query.eq("author_id", author_id.to_string());
+        }
+        if let Some(category_id) = filters.category_id {
+            query = query.eq("in_category.category_id", category_id.to_string());
+        }
+        if let Some(tag_id) = filters.tag_id {
+            query = query.eq("with_tag.tag_id", tag_id.to_string());
+        }
+
+        let offset = offset_for(page, per_page);
+        let query = query
+            .exact_count()
+            .range(offset, offset + per_page as usize - 1);
+        let (rows, total) = fetch_page::<PostRow>(query).await?;
+
+        Ok(PaginatedResponse::new(
+            rows.into_iter().map(PostResponse::from).collect(),
+            total,
+            page,
+            per_page,
+        ))
+    }
+
+    async fn get_post(&self, id: Uuid) -> Result<PostResponse> {
+        self.find_post("id", &id.to_string())
+            .await?
+            .ok_or(BlogError::PostNotFound)
+    }
+
+    async fn get_post_by_slug(&self, slug: &str) -> Result<SlugLookup> {
+        if let Some(post) = self.find_post("slug", slug).await? {
+            return Ok(SlugLookup::Current(post));
+        }
+
+        let moved: Option<PostIdRow> = fetch_optional(
+            self.db
+                .from("post_slug_history")
+                .select("post_id")
+                .eq("slug", slug),
+        )
+        .await?;
+        let post_id = moved.ok_or(BlogError::PostNotFound)?.post_id;
+        Ok(SlugLookup::Moved(self.get_post(post_id).await?))
+    }
+
+    async fn create_post(&self, author_id: Uuid, req: CreatePostRequest) -> Result<PostResponse> {
+        let category_ids = dedup_ids(req.category_ids);
+        let tag_ids = dedup_ids(req.tag_ids);
+        self.ensure_exist("categories", &category_ids, || BlogError::CategoryNotFound)
+            .await?;
+        self.ensure_exist("tags", &tag_ids, || BlogError::TagNotFound)
+            .await?;
+
+        let slug = SlugRequest::new(self.slugs, &req.title, req.slug)?;
+        let now = Utc::now();
+        let post = Post {
+            id: Uuid::new_v4(),
+            author_id,
+            slug: String::new(),
+            title: req.title,
+            content_html: markdown::to_html(&req.content),
+            content: req.content,
+            excerpt: req.excerpt,
+            status: req.status,
+            published_at: published_at_for(req.status, None),
+            created_at: now,
+            updated_at: now,
+        };
+
+        with_unique_slug(Some(&slug), || async {
+            let taken = self.taken_slugs(slug.base(), None).await?;
+            let post = Post {
+                slug: slug.resolve(&taken)?,
+                ..post.clone()
+            };
+            self.save_post(&post, Some(&category_ids), Some(&tag_ids), None)
+                .await
+                .map_err(post_write_error)
+        })
+        .await?;
+
+        self.get_post(post.id).await
+    }
+
+    async fn update_post(
+        &self,
+        id: Uuid,
+        _author_id: Uuid,
+        req: UpdatePostRequest,
+    ) -> Result<PostResponse> {
+        let mut post: Post = fetch_optional(self.db.from("posts").eq("id", id.to_string()))
+            .await?
+            .ok_or(BlogError::PostNotFound)?;
+
+        let category_ids = req.category_ids.map(dedup_ids);
+        let tag_ids = req.tag_ids.map(dedup_ids);
+        if let Some(ids) = &category_ids {
+            self.ensure_exist("categories", ids, || BlogError::CategoryNotFound)
+                .await?;
+        }
+        if let Some(ids) = &tag_ids {
+            self.ensure_exist("tags", ids, || BlogError::TagNotFound)
+                .await?;
+        }
+
+        let new_slug = match &req.title {
+            Some(title) => retitled_slug(self.slugs, &post.slug, &post.title, title)?,
+            None => None,
+        };
+        if let Some(title) = req.title {
+            post.title = title;
+        }
+        if let Some(content) = req.content {
+            post.content_html = markdown::to_html(&content);
+            post.content = content;
+        }
+        if let Some(excerpt) = req.excerpt {
+            post.excerpt = Some(excerpt);
+        }
+        if let Some(status) = req.status {
+            post.published_at = published_at_for(status, post.published_at);
+            post.status = status;
+        }
+        post.updated_at = Utc::now();
+
+        with_unique_slug(new_slug.as_ref(), || async {
+            let mut post = post.clone();
+            let mut old_slug = None;
+            if let Some(slug) = &new_slug {
+                let taken = self.taken_slugs(slug.base(), Some(id)).await?;
+                old_slug = Some(std::mem::replace(&mut post.slug, slug.resolve(&taken)?));
+            }
+            self.save_post(
+                &post,
+                category_ids.as_deref(),
+                tag_ids.as_deref(),
+                old_slug.as_deref(),
+            )
+            .await
+            .map_err(post_write_error)
+        })
+        .await?;
+
+        self.get_post(id).await
+    }
+
+    async fn delete_post(&self, id: Uuid, author_id: Uuid) -> Result<()> {
+        let rows: Vec<IdRow> = fetch_rows(
+            self.db
+                .from("posts")
+                .select("id")
+                .delete()
+                .eq("id", id.to_string())
+                .eq("author_id", author_id.to_string()),
+        )
+        .await?;
+
+        if rows.is_empty() {
+            return Err(BlogError::PostNotFound);
+        }
+        Ok(())
+    }
+
+    async fn list_categories(&self) -> Result<Vec<Category>> {
+        Ok(fetch_rows(self.db.from("categories").order("name.asc")).await?)
+    }
+
+    async fn create_category(&self, req: CreateCategoryRequest) -> Result<Category> {
+        if let Some(parent_id) = req.parent_id {
+            self.ensure_exist("categories", &[parent_id], || BlogError::CategoryNotFound)
+                .await?;
+        }
+
+        let category = Category {
+            id: Uuid::new_v4(),
+            slug: slug_for(self.slugs, &req.name)?,
+            name: req.name,
+            description: req.description,
+            parent_id: req.parent_id,
+            created_at: Utc::now(),
+        };
+        let rows = fetch_rows(self.db.from("categories").insert(to_body(&category)?))
+            .await
+            .map_err(|e| term_write_error(&category.slug, e))?;
+        first_row(rows)
+    }
+
+    async fn list_tags(&self) -> Result<Vec<Tag>> {
+        Ok(fetch_rows(self.db.from("tags").order("name.asc")).await?)
+    }
+
+    async fn create_tag(&self, req: CreateTagRequest) -> Result<Tag> {
+        let tag = Tag {
+            id: Uuid::new_v4(),
+            slug: slug_for(self.slugs, &req.name)?,
+            name: req.name,
+            created_at: Utc::now(),
+        };
+        let rows = fetch_rows(self.db.from("tags").insert(to_body(&tag)?))
+            .await
+            .map_err(|e| term_write_error(&tag.slug, e))?;
+        first_row(rows)
+    }
+
+    async fn list_posts_by_author(&self, author_id: Uuid) -> Result<Vec<PostResponse>> {
+        let rows: Vec<PostRow> = fetch_rows(
+            self.db
+                .from("posts")
+                .select(POST_COLUMNS)
+                .eq("author_id", author_id.to_string())
+                .order("created_at.desc"),
+        )
+        .await?;
+        Ok(rows.into_iter().map(PostResponse::from).collect())
+    }
+
+    async fn delete_posts_by_author(&self, author_id: Uuid) -> Result<u64> {
+        let rows: Vec<IdRow> = fetch_rows(
+            self.db
+                .from("posts")
+                .select("id")
+                .delete()
+                .eq("author_id", author_id.to_string()),
+        )
+        .await?;
+        Ok(rows.len() as u64)
+    }
+}
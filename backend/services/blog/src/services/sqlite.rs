@@ -0,0 +1,1019 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use rusqlite::{
+    params,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+    Connection, ErrorCode, OptionalExtension, Row, ToSql, Transaction,
+};
+use uuid::Uuid;
+
+use super::{
+    dedup_ids, offset_for, published_at_for, retitled_slug, slug_for, with_unique_slug,
+    BlogService, SlugRequest,
+};
+use crate::{
+    error::{BlogError, Result},
+    markdown,
+    models::{
+        Category, CreateCategoryRequest, CreatePostRequest, CreateTagRequest, PaginatedResponse,
+        Post, PostFilters, PostResponse, PostStatus, SlugLookup, Tag, UpdatePostRequest,
+    },
+    slugs::SlugStrategy,
+};
+
+/// The same tables as the Supabase schema, minus what SQLite has no use for
+/// (row level security, the `users` foreign key).
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS posts (
+        id BLOB PRIMARY KEY,
+        author_id BLOB NOT NULL,
+        title TEXT NOT NULL,
+        slug TEXT NOT NULL UNIQUE,
+        content TEXT NOT NULL,
+        content_html TEXT NOT NULL,
+        excerpt TEXT,
+        status TEXT NOT NULL DEFAULT 'draft',
+        published_at TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS posts_author_id_idx ON posts (author_id);
+    CREATE INDEX IF NOT EXISTS posts_created_at_idx ON posts (created_at);
+
+    CREATE TABLE IF NOT EXISTS categories (
+        id BLOB PRIMARY KEY,
+        name TEXT NOT NULL,
+        slug TEXT NOT NULL UNIQUE,
+        description TEXT,
+        parent_id BLOB REFERENCES categories (id),
+        created_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS tags (
+        id BLOB PRIMARY KEY,
+        name TEXT NOT NULL,
+        slug TEXT NOT NULL UNIQUE,
+        created_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS post_categories (
+        post_id BLOB NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+        category_id BLOB NOT NULL REFERENCES categories (id) ON DELETE CASCADE,
+        PRIMARY KEY (post_id, category_id)
+    );
+    CREATE INDEX IF NOT EXISTS post_categories_category_id_idx ON post_categories (category_id);
+
+    CREATE TABLE IF NOT EXISTS post_tags (
+        post_id BLOB NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+        tag_id BLOB NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+        PRIMARY KEY (post_id, tag_id)
+    );
+    CREATE INDEX IF NOT EXISTS post_tags_tag_id_idx ON post_tags (tag_id);
+
+    CREATE TABLE IF NOT EXISTS post_slug_history (
+        slug TEXT PRIMARY KEY,
+        post_id BLOB NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS post_slug_history_post_id_idx ON post_slug_history (post_id);
+";
+
+const POST_COLUMNS: &str = "id, author_id, title, slug, content, content_html, excerpt, status, \
+                            published_at, created_at, updated_at";
+
+/// Matches posts against `PostFilters`; a `NULL` parameter leaves that filter
+/// out.
+const POST_FILTER: &str = "
+    (?1 IS NULL OR status = ?1)
+    AND (?2 IS NULL OR author_id = ?2)
+    AND (?3 IS NULL OR id IN (SELECT post_id FROM post_categories WHERE category_id = ?3))
+    AND (?4 IS NULL OR id IN (SELECT post_id FROM post_tags WHERE tag_id = ?4))
+";
+
+impl ToSql for PostStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for PostStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "draft" => Ok(PostStatus::Draft),
+            "published" => Ok(PostStatus::Published),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// Databases created before posts kept their rendered HTML get the column,
+/// filled in from each post's Markdown.
+fn add_content_html(conn: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info('posts') WHERE name = 'content_html')",
+        [],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(());
+    }
+
+    conn.execute_batch("ALTER TABLE posts ADD COLUMN content_html TEXT NOT NULL DEFAULT ''")?;
+    let posts = conn
+        .prepare("SELECT id, content FROM posts")?
+        .query_map([], |row| {
+            Ok((row.get::<_, Uuid>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, content) in posts {
+        conn.execute(
+            "UPDATE posts SET content_html = ?2 WHERE id = ?1",
+            params![id, markdown::to_html(&content)],
+        )?;
+    }
+    Ok(())
+}
+
+fn db_error(err: rusqlite::Error) -> BlogError {
+    BlogError::Database(err.to_string())
+}
+
+fn is_unique_violation(err: &rusqlite::Error) -> bool {
+    matches!(
+        err,
+        rusqlite::Error::SqliteFailure(e, _)
+            if e.code == ErrorCode::ConstraintViolation
+                && e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+    )
+}
+
+fn post_from_row(row: &Row<'_>) -> rusqlite::Result<Post> {
+    Ok(Post {
+        id: row.get(0)?,
+        author_id: row.get(1)?,
+        title: row.get(2)?,
+        slug: row.get(3)?,
+        content: row.get(4)?,
+        content_html: row.get(5)?,
+        excerpt: row.get(6)?,
+        status: row.get(7)?,
+        published_at: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+fn category_from_row(row: &Row<'_>) -> rusqlite::Result<Category> {
+    Ok(Category {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        slug: row.get(2)?,
+        description: row.get(3)?,
+        parent_id: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn tag_from_row(row: &Row<'_>) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        slug: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
+/// Attaches the post's categories and tags.
+fn with_terms(conn: &Connection, post: Post) -> Result<PostResponse> {
+    let categories = conn
+        .prepare(
+            "SELECT c.id, c.name, c.slug, c.description, c.parent_id, c.created_at
+             FROM categories c JOIN post_categories pc ON pc.category_id = c.id
+             WHERE pc.post_id = ?1 ORDER BY c.name",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([post.id], category_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(db_error)?;
+    let tags = conn
+        .prepare(
+            "SELECT t.id, t.name, t.slug, t.created_at
+             FROM tags t JOIN post_tags pt ON pt.tag_id = t.id
+             WHERE pt.post_id = ?1 ORDER BY t.name",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([post.id], tag_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(db_error)?;
+
+    Ok(post.into_response(categories, tags))
+}
+
+fn find_post(conn: &Connection, column: &str, value: &dyn ToSql) -> Result<Option<Post>> {
+    conn.query_row(
+        &format!("SELECT {} FROM posts WHERE {} = ?1", POST_COLUMNS, column),
+        [value],
+        post_from_row,
+    )
+    .optional()
+    .map_err(db_error)
+}
+
+fn query_posts(conn: &Connection, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<PostResponse>> {
+    let posts = conn
+        .prepare(sql)
+        .and_then(|mut stmt| {
+            stmt.query_map(params, post_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(db_error)?;
+
+    posts
+        .into_iter()
+        .map(|post| with_terms(conn, post))
+        .collect()
+}
+
+/// Fails with `missing` unless every id exists in `table`.
+fn ensure_exist(conn: &Connection, table: &str, ids: &[Uuid], missing: BlogError) -> Result<()> {
+    let sql = format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = ?1)", table);
+    for id in ids {
+        let exists: bool = conn
+            .query_row(&sql, [id], |row| row.get(0))
+            .map_err(db_error)?;
+        if !exists {
+            return Err(missing);
+        }
+    }
+    Ok(())
+}
+
+/// Slugs that are `base` or start with `base-`, used by posts other than
+/// `exclude` now or before they were renamed.
+fn taken_slugs(conn: &Connection, base: &str, exclude: Option<Uuid>) -> Result<HashSet<String>> {
+    conn.prepare(
+        "SELECT slug FROM posts WHERE (slug = ?1 OR slug LIKE ?2) AND (?3 IS NULL OR id != ?3)
+         UNION
+         SELECT slug FROM post_slug_history
+         WHERE (slug = ?1 OR slug LIKE ?2) AND (?3 IS NULL OR post_id != ?3)",
+    )
+    .and_then(|mut stmt| {
+        stmt.query_map(params![base, format!("{}-%", base), exclude], |row| {
+            row.get(0)
+        })?
+        .collect::<rusqlite::Result<HashSet<_>>>()
+    })
+    .map_err(db_error)
+}
+
+/// Replaces the post's rows in a join table with `term_ids`.
+fn link_terms(
+    tx: &Transaction<'_>,
+    table: &str,
+    column: &str,
+    post_id: Uuid,
+    term_ids: &[Uuid],
+) -> Result<()> {
+    tx.execute(
+        &format!("DELETE FROM {} WHERE post_id = ?1", table),
+        [post_id],
+    )
+    .map_err(db_error)?;
+
+    let sql = format!(
+        "INSERT INTO {} (post_id, {}) VALUES (?1, ?2)",
+        table, column
+    );
+    for term_id in term_ids {
+        tx.execute(&sql, [post_id, *term_id]).map_err(db_error)?;
+    }
+    Ok(())
+}
+
+/// An embedded database for running the service, and its tests, without
+/// Supabase.
+pub struct SqliteBlogService {
+    conn: Mutex<Connection>,
+    slugs: SlugStrategy,
+}
+
+impl SqliteBlogService {
+    /// Opens (or creates) the database at `path`; `:memory:` keeps it in
+    /// memory for the life of the process.
+    pub fn open(path: &str, slugs: SlugStrategy) -> Result<Self> {
+        let conn = Connection::open(path).map_err(db_error)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .and_then(|_| add_content_html(&conn))
+            .map_err(db_error)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            slugs,
+        })
+    }
+
+    fn with_conn<T>(&self, f: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|_| BlogError::Database("SQLite connection lock poisoned".to_string()))?;
+        f(&mut conn)
+    }
+}
+
+#[async_trait]
+impl BlogService for SqliteBlogService {
+    async fn list_posts(
+        &self,
+        page: u32,
+        per_page: u32,
+        filters: Option<PostFilters>,
+    ) -> Result<PaginatedResponse<PostResponse>> {
+        let filters = filters.unwrap_or_default();
+        let offset = offset_for(page, per_page) as i64;
+
+        self.with_conn(|conn| {
+            let total: u64 = conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM posts WHERE {}", POST_FILTER),
+                    params![
+                        filters.status,
+                        filters.author_id,
+                        filters.category_id,
+                        filters.tag_id
+                    ],
+                    |row| row.get(0),
+                )
+                .map_err(db_error)?;
+
+            let items = query_posts(
+                conn,
+                &format!(
+                    "SELECT {} FROM posts WHERE {} ORDER BY created_at DESC, id LIMIT ?5 OFFSET ?6",
+                    POST_COLUMNS, POST_FILTER
+                ),
+                params![
+                    filters.status,
+                    filters.author_id,
+                    filters.category_id,
+                    filters.tag_id,
+                    per_page,
+                    offset
+                ],
+            )?;
+
+            Ok(PaginatedResponse::new(items, total, page, per_page))
+        })
+    }
+
+    async fn get_post(&self, id: Uuid) -> Result<PostResponse> {
+        self.with_conn(|conn| {
+            let post = find_post(conn, "id", &id)?.ok_or(BlogError::PostNotFound)?;
+            with_terms(conn, post)
+        })
+    }
+
+    async fn get_post_by_slug(&self, slug: &str) -> Result<SlugLookup> {
+        self.with_conn(|conn| {
+            if let Some(post) = find_post(conn, "slug", &slug)? {
+                return Ok(SlugLookup::Current(with_terms(conn, post)?));
+            }
+
+            let post_id: Uuid = conn
+                .query_row(
+                    "SELECT post_id FROM post_slug_history WHERE slug = ?1",
+                    [slug],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(db_error)?
+                .ok_or(BlogError::PostNotFound)?;
+            let post = find_post(conn, "id", &post_id)?.ok_or(BlogError::PostNotFound)?;
+            Ok(SlugLookup::Moved(with_terms(conn, post)?))
+        })
+    }
+
+    async fn create_post(&self, author_id: Uuid, req: CreatePostRequest) -> Result<PostResponse> {
+        let category_ids = dedup_ids(req.category_ids);
+        let tag_ids = dedup_ids(req.tag_ids);
+        let slug = SlugRequest::new(self.slugs, &req.title, req.slug)?;
+        let now = Utc::now();
+        let post = Post {
+            id: Uuid::new_v4(),
+            author_id,
+            slug: String::new(),
+            title: req.title,
+            content_html: markdown::to_html(&req.content),
+            content: req.content,
+            excerpt: req.excerpt,
+            status: req.status,
+            published_at: published_at_for(req.status, None),
+            created_at: now,
+            updated_at: now,
+        };
+
+        with_unique_slug(Some(&slug), || async {
+            self.with_conn(|conn| {
+                let tx = conn.transaction().map_err(db_error)?;
+                let taken = taken_slugs(&tx, slug.base(), None)?;
+                let post = Post {
+                    slug: slug.resolve(&taken)?,
+                    ..post.clone()
+                };
+                ensure_exist(
+                    &tx,
+                    "categories",
+                    &category_ids,
+                    BlogError::CategoryNotFound,
+                )?;
+                ensure_exist(&tx, "tags", &tag_ids, BlogError::TagNotFound)?;
+
+                tx.execute(
+                    &format!(
+                    "INSERT INTO posts ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    POST_COLUMNS
+                ),
+                    params![
+                        post.id,
+                        post.author_id,
+                        post.title,
+                        post.slug,
+                        post.content,
+                        post.content_html,
+                        post.excerpt,
+                        post.status,
+                        post.published_at,
+                        post.created_at,
+                        post.updated_at
+                    ],
+                )
+                .map_err(|e| {
+                    if is_unique_violation(&e) {
+                        BlogError::SlugExists
+                    } else {
+                        db_error(e)
+                    }
+                })?;
+                link_terms(
+                    &tx,
+                    "post_categories",
+                    "category_id",
+                    post.id,
+                    &category_ids,
+                )?;
+                link_terms(&tx, "post_tags", "tag_id", post.id, &tag_ids)?;
+
+                let response = with_terms(&tx, post)?;
+                tx.commit().map_err(db_error)?;
+                Ok(response)
+            })
+        })
+        .await
+    }
+
+    async fn update_post(
+        &self,
+        id: Uuid,
+        _author_id: Uuid,
+        req: UpdatePostRequest,
+    ) -> Result<PostResponse> {
+        let category_ids = req.category_ids.map(dedup_ids);
+        let tag_ids = req.tag_ids.map(dedup_ids);
+        let content_html = req.content.as_deref().map(markdown::to_html);
+
+        // Which slug a new title moves the post to is worked out before the
+        // transaction, so a slug another post takes meanwhile can be retried.
+        let new_slug = match &req.title {
+            Some(title) => self.with_conn(|conn| {
+                let post = find_post(conn, "id", &id)?.ok_or(BlogError::PostNotFound)?;
+                retitled_slug(self.slugs, &post.slug, &post.title, title)
+            })?,
+            None => None,
+        };
+
+        with_unique_slug(new_slug.as_ref(), || async {
+            self.with_conn(|conn| {
+                let tx = conn.transaction().map_err(db_error)?;
+                let mut post = find_post(&tx, "id", &id)?.ok_or(BlogError::PostNotFound)?;
+                if let Some(ids) = &category_ids {
+                    ensure_exist(&tx, "categories", ids, BlogError::CategoryNotFound)?;
+                }
+                if let Some(ids) = &tag_ids {
+                    ensure_exist(&tx, "tags", ids, BlogError::TagNotFound)?;
+                }
+
+                if let Some(title) = &req.title {
+                    post.title = title.clone();
+                }
+                if let (Some(content), Some(html)) = (&req.content, &content_html) {
+                    post.content = content.clone();
+                    post.content_html = html.clone();
+                }
+                if let Some(excerpt) = &req.excerpt {
+                    post.excerpt = Some(excerpt.clone());
+                }
+                if let Some(status) = req.status {
+                    post.status = status;
+                    post.published_at = published_at_for(status, post.published_at);
+                }
+                post.updated_at = Utc::now();
+
+                let mut previous_slug = None;
+                if let Some(slug) = &new_slug {
+                    let taken = taken_slugs(&tx, slug.base(), Some(id))?;
+                    previous_slug = Some(std::mem::replace(&mut post.slug, slug.resolve(&taken)?));
+                }
+
+                tx.execute(
+                    "UPDATE posts SET title = ?2, slug = ?3, content = ?4, content_html = ?5,
+                         excerpt = ?6, status = ?7, published_at = ?8, updated_at = ?9
+                         WHERE id = ?1",
+                    params![
+                        post.id,
+                        post.title,
+                        post.slug,
+                        post.content,
+                        post.content_html,
+                        post.excerpt,
+                        post.status,
+                        post.published_at,
+                        post.updated_at
+                    ],
+                )
+                .map_err(|e| {
+                    if is_unique_violation(&e) {
+                        BlogError::SlugExists
+                    } else {
+                        db_error(e)
+                    }
+                })?;
+                if let Some(previous_slug) = previous_slug {
+                    // The new slug may be one this post had before.
+                    tx.execute(
+                        "DELETE FROM post_slug_history WHERE slug = ?1",
+                        [&post.slug],
+                    )
+                    .and_then(|_| {
+                        tx.execute(
+                            "INSERT INTO post_slug_history (slug, post_id, created_at)
+                             VALUES (?1, ?2, ?3)",
+                            params![previous_slug, id, post.updated_at],
+                        )
+                    })
+                    .map_err(db_error)?;
+                }
+                if let Some(ids) = &category_ids {
+                    link_terms(&tx, "post_categories", "category_id", id, ids)?;
+                }
+                if let Some(ids) = &tag_ids {
+                    link_terms(&tx, "post_tags", "tag_id", id, ids)?;
+                }
+
+                let response = with_terms(&tx, post)?;
+                tx.commit().map_err(db_error)?;
+                Ok(response)
+            })
+        })
+        .await
+    }
+
+    async fn delete_post(&self, id: Uuid, author_id: Uuid) -> Result<()> {
+        self.with_conn(|conn| {
+            let deleted = conn
+                .execute(
+                    "DELETE FROM posts WHERE id = ?1 AND author_id = ?2",
+                    [id, author_id],
+                )
+                .map_err(db_error)?;
+
+            if deleted == 0 {
+                return Err(BlogError::PostNotFound);
+            }
+            Ok(())
+        })
+    }
+
+    async fn list_categories(&self) -> Result<Vec<Category>> {
+        self.with_conn(|conn| {
+            conn.prepare(
+                "SELECT id, name, slug, description, parent_id, created_at
+                 FROM categories ORDER BY name",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([], category_from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(db_error)
+        })
+    }
+
+    async fn create_category(&self, req: CreateCategoryRequest) -> Result<Category> {
+        let category = Category {
+            id: Uuid::new_v4(),
+            slug: slug_for(self.slugs, &req.name)?,
+            name: req.name,
+            description: req.description,
+            parent_id: req.parent_id,
+            created_at: Utc::now(),
+        };
+
+        self.with_conn(|conn| {
+            if let Some(parent_id) = category.parent_id {
+                ensure_exist(
+                    conn,
+                    "categories",
+                    &[parent_id],
+                    BlogError::CategoryNotFound,
+                )?;
+            }
+
+            conn.execute(
+                "INSERT INTO categories (id, name, slug, description, parent_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    category.id,
+                    category.name,
+                    category.slug,
+                    category.description,
+                    category.parent_id,
+                    category.created_at
+                ],
+            )
+            .map_err(|e| {
+                if is_unique_violation(&e) {
+                    BlogError::Validation(format!("'{}' already exists", category.slug))
+                } else {
+                    db_error(e)
+                }
+            })?;
+            Ok(())
+        })?;
+
+        Ok(category)
+    }
+
+    async fn list_tags(&self) -> Result<Vec<Tag>> {
+        self.with_conn(|conn| {
+            conn.prepare("SELECT id, name, slug, created_at FROM tags ORDER BY name")
+                .and_then(|mut stmt| {
+                    stmt.query_map([], tag_from_row)?
+                        .collect::<rusqlite::Result<Vec<_>>>()
+                })
+                .map_err(db_error)
+        })
+    }
+
+    async fn create_tag(&self, req: CreateTagRequest) -> Result<Tag> {
+        let tag = Tag {
+            id: Uuid::new_v4(),
+            slug: slug_for(self.slugs, &req.name)?,
+            name: req.name,
+            created_at: Utc::now(),
+        };
+
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO tags (id, name, slug, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![tag.id, tag.name, tag.slug, tag.created_at],
+            )
+            .map_err(|e| {
+                if is_unique_violation(&e) {
+                    BlogError::Validation(format!("'{}' already exists", tag.slug))
+                } else {
+                    db_error(e)
+                }
+            })?;
+            Ok(())
+        })?;
+
+        Ok(tag)
+    }
+
+    async fn list_posts_by_author(&self, author_id: Uuid) -> Result<Vec<PostResponse>> {
+        self.with_conn(|conn| {
+            query_posts(
+                conn,
+                &format!(
+                    "SELECT {} FROM posts WHERE author_id = ?1 ORDER BY created_at DESC, id",
+                    POST_COLUMNS
+                ),
+                &[&author_id],
+            )
+        })
+    }
+
+    async fn delete_posts_by_author(&self, author_id: Uuid) -> Result<u64> {
+        self.with_conn(|conn| {
+            let deleted = conn
+                .execute("DELETE FROM posts WHERE author_id = ?1", [author_id])
+                .map_err(db_error)?;
+            Ok(deleted as u64)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> SqliteBlogService {
+        SqliteBlogService::open(":memory:", SlugStrategy::Romaji).unwrap()
+    }
+
+    fn new_post(title: &str, status: PostStatus) -> CreatePostRequest {
+        CreatePostRequest {
+            title: title.to_string(),
+            slug: None,
+            content: format!("# {}", title),
+            excerpt: None,
+            status,
+            category_ids: Vec::new(),
+            tag_ids: Vec::new(),
+        }
+    }
+
+    fn no_changes() -> UpdatePostRequest {
+        UpdatePostRequest {
+            title: None,
+            content: None,
+            excerpt: None,
+            status: None,
+            category_ids: None,
+            tag_ids: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn list_posts_filters_by_status_author_category_and_tag() {
+        let service = service();
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let rust = service
+            .create_category(CreateCategoryRequest {
+                name: "Rust".to_string(),
+                description: None,
+                parent_id: None,
+            })
+            .await
+            .unwrap();
+        let axum = service
+            .create_tag(CreateTagRequest {
+                name: "axum".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let mut tagged = new_post("Tagged", PostStatus::Published);
+        tagged.category_ids = vec![rust.id];
+        tagged.tag_ids = vec![axum.id, axum.id];
+        let tagged = service.create_post(alice, tagged).await.unwrap();
+        let mut categorized = new_post("Categorized", PostStatus::Published);
+        categorized.category_ids = vec![rust.id];
+        service.create_post(bob, categorized).await.unwrap();
+        service
+            .create_post(alice, new_post("Draft", PostStatus::Draft))
+            .await
+            .unwrap();
+
+        let count = |filters: PostFilters| {
+            let service = &service;
+            async move {
+                service
+                    .list_posts(1, 10, Some(filters))
+                    .await
+                    .unwrap()
+                    .total
+            }
+        };
+        assert_eq!(count(PostFilters::default()).await, 3);
+        assert_eq!(
+            count(PostFilters {
+                status: Some(PostStatus::Draft),
+                ..Default::default()
+            })
+            .await,
+            1
+        );
+        assert_eq!(
+            count(PostFilters {
+                author_id: Some(alice),
+                ..Default::default()
+            })
+            .await,
+            2
+        );
+        assert_eq!(
+            count(PostFilters {
+                category_id: Some(rust.id),
+                ..Default::default()
+            })
+            .await,
+            2
+        );
+        assert_eq!(
+            count(PostFilters {
+                category_id: Some(rust.id),
+                author_id: Some(bob),
+                ..Default::default()
+            })
+            .await,
+            1
+        );
+
+        let with_tag = service
+            .list_posts(
+                1,
+                10,
+                Some(PostFilters {
+                    tag_id: Some(axum.id),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(with_tag.total, 1);
+        assert_eq!(with_tag.items[0].id, tagged.id);
+        assert_eq!(with_tag.items[0].categories.len(), 1);
+        assert_eq!(with_tag.items[0].tags.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn list_posts_pages_count_every_match() {
+        let service = service();
+        let author = Uuid::new_v4();
+        for n in 0..5 {
+            service
+                .create_post(
+                    author,
+                    new_post(&format!("Post {}", n), PostStatus::Published),
+                )
+                .await
+                .unwrap();
+        }
+
+        let first = service.list_posts(1, 2, None).await.unwrap();
+        let last = service.list_posts(3, 2, None).await.unwrap();
+        let past_end = service.list_posts(4, 2, None).await.unwrap();
+
+        assert_eq!(
+            (first.items.len(), first.total, first.total_pages),
+            (2, 5, 3)
+        );
+        assert_eq!((last.items.len(), last.total), (1, 5));
+        assert!(past_end.items.is_empty());
+        assert_eq!(past_end.total, 5);
+
+        let mut seen: Vec<Uuid> = Vec::new();
+        for page in 1..=3 {
+            let items = service.list_posts(page, 2, None).await.unwrap().items;
+            seen.extend(items.iter().map(|post| post.id));
+        }
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn missing_posts_and_terms_are_not_found() {
+        let service = service();
+        let author = Uuid::new_v4();
+
+        assert!(matches!(
+            service.get_post(Uuid::new_v4()).await,
+            Err(BlogError::PostNotFound)
+        ));
+        assert!(matches!(
+            service.get_post_by_slug("nothing-here").await,
+            Err(BlogError::PostNotFound)
+        ));
+        assert!(matches!(
+            service
+                .update_post(Uuid::new_v4(), author, no_changes())
+                .await,
+            Err(BlogError::PostNotFound)
+        ));
+
+        let mut unknown_category = new_post("Unknown category", PostStatus::Draft);
+        unknown_category.category_ids = vec![Uuid::new_v4()];
+        assert!(matches!(
+            service.create_post(author, unknown_category).await,
+            Err(BlogError::CategoryNotFound)
+        ));
+        let mut unknown_tag = new_post("Unknown tag", PostStatus::Draft);
+        unknown_tag.tag_ids = vec![Uuid::new_v4()];
+        assert!(matches!(
+            service.create_post(author, unknown_tag).await,
+            Err(BlogError::TagNotFound)
+        ));
+        assert_eq!(service.list_posts(1, 10, None).await.unwrap().total, 0);
+    }
+
+    #[tokio::test]
+    async fn delete_post_only_matches_its_author() {
+        let service = service();
+        let author = Uuid::new_v4();
+        let post = service
+            .create_post(author, new_post("Mine", PostStatus::Published))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service.delete_post(post.id, Uuid::new_v4()).await,
+            Err(BlogError::PostNotFound)
+        ));
+        service.delete_post(post.id, author).await.unwrap();
+        assert!(matches!(
+            service.get_post(post.id).await,
+            Err(BlogError::PostNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn posts_with_the_same_title_get_unique_slugs() {
+        let service = service();
+        let author = Uuid::new_v4();
+
+        let first = service
+            .create_post(author, new_post("Hello World", PostStatus::Published))
+            .await
+            .unwrap();
+        let second = service
+            .create_post(author, new_post("Hello World", PostStatus::Published))
+            .await
+            .unwrap();
+
+        assert_eq!(first.slug, "hello-world");
+        assert_eq!(second.slug, "hello-world-2");
+    }
+
+    #[tokio::test]
+    async fn custom_slugs_must_be_free_now_and_before() {
+        let service = service();
+        let author = Uuid::new_v4();
+        let post = service
+            .create_post(author, new_post("Hello World", PostStatus::Published))
+            .await
+            .unwrap();
+
+        let mut custom = new_post("Another", PostStatus::Published);
+        custom.slug = Some("hello-world".to_string());
+        assert!(matches!(
+            service.create_post(author, custom).await,
+            Err(BlogError::SlugExists)
+        ));
+
+        let mut retitle = no_changes();
+        retitle.title = Some("Goodbye World".to_string());
+        service.update_post(post.id, author, retitle).await.unwrap();
+
+        let mut custom = new_post("Another", PostStatus::Published);
+        custom.slug = Some("hello-world".to_string());
+        assert!(matches!(
+            service.create_post(author, custom).await,
+            Err(BlogError::SlugExists)
+        ));
+    }
+
+    #[tokio::test]
+    async fn renamed_posts_are_found_by_their_old_slug() {
+        let service = service();
+        let author = Uuid::new_v4();
+        let post = service
+            .create_post(author, new_post("Hello World", PostStatus::Published))
+            .await
+            .unwrap();
+
+        let mut retitle = no_changes();
+        retitle.title = Some("Goodbye World".to_string());
+        let renamed = service.update_post(post.id, author, retitle).await.unwrap();
+        assert_eq!(renamed.slug, "goodbye-world");
+
+        match service.get_post_by_slug("hello-world").await.unwrap() {
+            SlugLookup::Moved(moved) => assert_eq!(moved.id, post.id),
+            other => panic!("expected a moved post, got {:?}", other),
+        }
+        assert!(matches!(
+            service.get_post_by_slug("goodbye-world").await.unwrap(),
+            SlugLookup::Current(_)
+        ));
+
+        // Renaming it back takes the old slug back, and moves the other one.
+        let mut retitle = no_changes();
+        retitle.title = Some("Hello World".to_string());
+        let restored = service.update_post(post.id, author, retitle).await.unwrap();
+        assert_eq!(restored.slug, "hello-world");
+        assert!(matches!(
+            service.get_post_by_slug("hello-world").await.unwrap(),
+            SlugLookup::Current(_)
+        ));
+        assert!(matches!(
+            service.get_post_by_slug("goodbye-world").await.unwrap(),
+            SlugLookup::Moved(_)
+        ));
+    }
+}
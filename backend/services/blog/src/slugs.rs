@@ -0,0 +1,97 @@
+//! How slugs are made from post titles and from category and tag names.
+
+use crate::romaji;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlugStrategy {
+    /// Japanese is transliterated to romaji (`rust-de-hajimeru`). Text with a
+    /// kanji the dictionary can't read gets a short id instead.
+    Romaji,
+    /// The `slug` crate's generic ASCII transliteration, which reads kanji as
+    /// Chinese.
+    Ascii,
+    /// Always a short id.
+    ShortId,
+}
+
+impl SlugStrategy {
+    /// Reads `SLUG_STRATEGY`: `romaji` (the default), `ascii` or `short_id`.
+    pub fn from_env() -> Self {
+        match std::env::var("SLUG_STRATEGY").as_deref() {
+            Ok("romaji") | Err(_) => SlugStrategy::Romaji,
+            Ok("ascii") => SlugStrategy::Ascii,
+            Ok("short_id") => SlugStrategy::ShortId,
+            Ok(other) => {
+                tracing::warn!("Unknown SLUG_STRATEGY {:?}, using romaji", other);
+                SlugStrategy::Romaji
+            }
+        }
+    }
+
+    /// Empty when `text` has no letters or digits at all.
+    pub fn slugify(self, text: &str) -> String {
+        if !text.chars().any(char::is_alphanumeric) {
+            return String::new();
+        }
+
+        match self {
+            SlugStrategy::Ascii => slug::slugify(text),
+            SlugStrategy::ShortId => short_id(text),
+            SlugStrategy::Romaji => match romaji::to_romaji(text).map(slug::slugify) {
+                Some(slug) if !slug.is_empty() => slug,
+                _ => short_id(text),
+            },
+        }
+    }
+}
+
+/// Eight hex digits of an FNV-1a hash of `text`, so the same title always
+/// gets the same id, across restarts and on every backend.
+fn short_id(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:08x}", (hash >> 32) ^ (hash & 0xffff_ffff))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn romaji_reads_japanese_titles() {
+        let cases = [
+            (
+                "Rustで始めるマイクロサービス",
+                "rust-de-hajimeru-maikurosabisu",
+            ),
+            ("今日の天気", "kyou-no-tenki"),
+            (
+                "新しい機能を追加しました",
+                "atarashii-kinou-o-tsuikashimashita",
+            ),
+            ("Hello, World!", "hello-world"),
+        ];
+        for (title, slug) in cases {
+            assert_eq!(SlugStrategy::Romaji.slugify(title), slug, "{title}");
+        }
+    }
+
+    #[test]
+    fn romaji_falls_back_to_a_short_id_for_unknown_kanji() {
+        let slug = SlugStrategy::Romaji.slugify("齉齾");
+        assert_eq!(slug, "48cea28e");
+        assert_eq!(slug, SlugStrategy::ShortId.slugify("齉齾"));
+    }
+
+    #[test]
+    fn text_without_letters_has_no_slug() {
+        for strategy in [
+            SlugStrategy::Romaji,
+            SlugStrategy::Ascii,
+            SlugStrategy::ShortId,
+        ] {
+            assert_eq!(strategy.slugify("!!! ---"), "");
+        }
+    }
+}
@@ -4,64 +4,32 @@ use axum::{
     Json,
 };
 use serde_json::json;
+use service_auth::AuthError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum CommentError {
-    #[error("Comment not found")]
-    CommentNotFound,
-
-    #[error("Post not found")]
-    PostNotFound,
+    #[error("Authentication required")]
+    Unauthorized,
 
     #[error("Permission denied")]
     Forbidden,
 
-    #[error("Invalid input: {0}")]
-    BadRequest(String),
-
-    #[error("Database error: {0}")]
-    Database(String),
-
     #[error("Internal server error")]
     Internal(#[from] anyhow::Error),
 
     #[error("Validation error: {0}")]
     Validation(String),
 
-    #[error("Comment is already moderated")]
-    AlreadyModerated,
-
-    #[error("Invalid moderation action")]
-    InvalidModeration,
-
     #[error("Maximum nesting level reached")]
     MaxNestingLevel,
-
-    #[error("Comment is deleted")]
-    CommentDeleted,
-
-    #[error("Parent comment not found")]
-    ParentNotFound,
-
-    #[error("Parent comment is not approved")]
-    ParentNotApproved,
 }
 
 impl IntoResponse for CommentError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
-            CommentError::CommentNotFound => (StatusCode::NOT_FOUND, self.to_string()),
-            CommentError::PostNotFound => (StatusCode::NOT_FOUND, self.to_string()),
+            CommentError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
             CommentError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
-            CommentError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            CommentError::Database(msg) => {
-                tracing::error!("Database error: {}", msg);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                )
-            }
             CommentError::Internal(err) => {
                 tracing::error!("Internal error: {:?}", err);
                 (
@@ -70,30 +38,10 @@ impl IntoResponse for CommentError {
                 )
             }
             CommentError::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
-            CommentError::AlreadyModerated => (
-                StatusCode::CONFLICT,
-                "This comment has already been moderated".to_string(),
-            ),
-            CommentError::InvalidModeration => (
-                StatusCode::BAD_REQUEST,
-                "Invalid moderation action".to_string(),
-            ),
             CommentError::MaxNestingLevel => (
                 StatusCode::BAD_REQUEST,
                 "Maximum comment nesting level reached".to_string(),
             ),
-            CommentError::CommentDeleted => (
-                StatusCode::GONE,
-                "This comment has been deleted".to_string(),
-            ),
-            CommentError::ParentNotFound => (
-                StatusCode::BAD_REQUEST,
-                "Parent comment not found".to_string(),
-            ),
-            CommentError::ParentNotApproved => (
-                StatusCode::BAD_REQUEST,
-                "Cannot reply to unapproved comment".to_string(),
-            ),
         };
 
         let body = Json(json!({
@@ -107,4 +55,14 @@ impl IntoResponse for CommentError {
     }
 }
 
+impl From<AuthError> for CommentError {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::Unauthorized => CommentError::Unauthorized,
+            AuthError::Forbidden => CommentError::Forbidden,
+            AuthError::Internal(err) => CommentError::Internal(err),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, CommentError>;
\ No newline at end of file
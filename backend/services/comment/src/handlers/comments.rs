@@ -6,10 +6,12 @@ use axum::{
 use uuid::Uuid;
 use validator::Validate;
 
+use service_auth::{perm, scope, Caller, Permission, Require, Scoped};
+
 use crate::{
     error::{CommentError, Result},
     models::{
-        CommentFilters, CreateCommentRequest, ModerateCommentRequest, PaginationParams,
+        CreateCommentRequest, ModerateCommentRequest, PaginationParams,
         UpdateCommentRequest,
     },
     services::{CommentService, MockCommentService},
@@ -18,14 +20,13 @@ use crate::{
 pub async fn list_comments(
     Path(post_id): Path<Uuid>,
     Query(pagination): Query<PaginationParams>,
-    Query(filters): Query<CommentFilters>,
     State(service): State<MockCommentService>,
 ) -> Result<Json<serde_json::Value>> {
     let page = pagination.page.unwrap_or(1);
     let per_page = pagination.per_page.unwrap_or(20);
 
     let response = service
-        .list_comments(post_id, page, per_page, Some(filters))
+        .list_comments(post_id, page, per_page)
         .await?;
 
     Ok(Json(serde_json::json!({
@@ -48,6 +49,8 @@ pub async fn get_comment(
 }
 
 pub async fn create_comment(
+    user: Option<Caller>,
+    _: Scoped<scope::CommentsWrite>,
     State(service): State<MockCommentService>,
     Json(req): Json<CreateCommentRequest>,
 ) -> Result<(StatusCode, Json<serde_json::Value>)> {
@@ -55,8 +58,7 @@ pub async fn create_comment(
     req.validate()
         .map_err(|e| CommentError::Validation(e.to_string()))?;
 
-    // TODO: Get author_id from authenticated user
-    let author_id = None;
+    let author_id = user.map(|Caller(claims)| claims.sub);
     let comment = service.create_comment(author_id, req).await?;
 
     Ok((
@@ -66,6 +68,8 @@ pub async fn create_comment(
 }
 
 pub async fn update_comment(
+    Caller(claims): Caller,
+    _: Scoped<(scope::CommentsWrite, scope::CommentsModerate)>,
     Path(id): Path<Uuid>,
     State(service): State<MockCommentService>,
     Json(req): Json<UpdateCommentRequest>,
@@ -74,31 +78,43 @@ pub async fn update_comment(
     req.validate()
         .map_err(|e| CommentError::Validation(e.to_string()))?;
 
-    // TODO: Get author_id from authenticated user
-    let author_id = None;
+    let author_id = service
+        .get_comment(id)
+        .await?
+        .author
+        .map(|author| author.id);
+    claims.ensure_owner_or(author_id, Permission::ModerateComments)?;
+
     let comment = service.update_comment(id, author_id, req).await?;
 
     Ok(Json(serde_json::json!({ "comment": comment })))
 }
 
 pub async fn delete_comment(
+    Caller(claims): Caller,
+    _: Scoped<(scope::CommentsWrite, scope::CommentsModerate)>,
     Path(id): Path<Uuid>,
     State(service): State<MockCommentService>,
 ) -> Result<StatusCode> {
-    // TODO: Get author_id from authenticated user
-    let author_id = None;
+    let author_id = service
+        .get_comment(id)
+        .await?
+        .author
+        .map(|author| author.id);
+    claims.ensure_owner_or(author_id, Permission::ModerateComments)?;
+
     service.delete_comment(id, author_id).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn moderate_comment(
+    Require { claims, .. }: Require<perm::ModerateComments>,
+    _: Scoped<scope::CommentsModerate>,
     Path(id): Path<Uuid>,
     State(service): State<MockCommentService>,
     Json(req): Json<ModerateCommentRequest>,
 ) -> Result<Json<serde_json::Value>> {
-    // TODO: Get moderator_id from authenticated user and verify moderator role
-    let moderator_id = Uuid::new_v4();
-    let comment = service.moderate_comment(id, moderator_id, req).await?;
+    let comment = service.moderate_comment(id, claims.sub, req).await?;
 
     Ok(Json(serde_json::json!({ "comment": comment })))
 }
@@ -125,6 +141,8 @@ pub async fn list_replies(
 }
 
 pub async fn create_reply(
+    user: Option<Caller>,
+    _: Scoped<scope::CommentsWrite>,
     Path(id): Path<Uuid>,
     State(service): State<MockCommentService>,
     Json(mut req): Json<CreateCommentRequest>,
@@ -136,12 +154,11 @@ pub async fn create_reply(
     req.validate()
         .map_err(|e| CommentError::Validation(e.to_string()))?;
 
-    // TODO: Get author_id from authenticated user
-    let author_id = None;
+    let author_id = user.map(|Caller(claims)| claims.sub);
     let comment = service.create_comment(author_id, req).await?;
 
     Ok((
         StatusCode::CREATED,
         Json(serde_json::json!({ "comment": comment })),
     ))
-}
\ No newline at end of file
+}
@@ -4,11 +4,12 @@ mod models;
 mod services;
 
 use axum::{
-    routing::{get, post, put, delete},
-    Router,
+    routing::{get, put},
+    Extension, Router,
 };
 use dotenv::dotenv;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -32,6 +33,10 @@ async fn main() {
         .allow_methods(Any)
         .allow_headers(Any);
 
+    // Access tokens are verified against the auth service's published keys
+    let jwks = Arc::new(service_auth::JwksCache::from_env());
+    jwks.clone().spawn_refresh();
+
     // Build the router
     let app = Router::new()
         .route("/health", get(health_check))
@@ -55,6 +60,8 @@ async fn main() {
             "/comments/:id/moderate",
             put(handlers::comments::moderate_comment),
         )
+        .with_state(services::MockCommentService)
+        .layer(Extension(jwks))
         .layer(TraceLayer::new_for_http())
         .layer(cors);
 
@@ -67,10 +74,8 @@ async fn main() {
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
     tracing::info!("listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
 }
 
 async fn health_check() -> &'static str {
@@ -3,19 +3,6 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Comment {
-    pub id: Uuid,
-    pub post_id: Uuid,
-    pub author_id: Option<Uuid>,
-    pub parent_id: Option<Uuid>,
-    pub content: String,
-    pub status: CommentStatus,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
-    pub deleted_at: Option<DateTime<Utc>>,
-}
-
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum CommentStatus {
@@ -96,19 +83,4 @@ pub struct CommentListResponse {
     pub page: i32,
     pub per_page: i32,
     pub total_pages: i32,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct CommentFilters {
-    pub status: Option<CommentStatus>,
-    pub author_id: Option<Uuid>,
-}
-
-#[derive(Debug)]
-pub struct ModerationLog {
-    pub comment_id: Uuid,
-    pub moderator_id: Uuid,
-    pub action: ModerateAction,
-    pub reason: Option<String>,
-    pub created_at: DateTime<Utc>,
 }
\ No newline at end of file
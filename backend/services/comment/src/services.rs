@@ -5,7 +5,7 @@ use uuid::Uuid;
 use crate::{
     error::{CommentError, Result},
     models::{
-        Comment, CommentAuthor, CommentFilters, CommentListResponse, CommentResponse,
+        CommentAuthor, CommentListResponse, CommentResponse,
         CommentStatus, CreateCommentRequest, ModerateAction, ModerateCommentRequest,
         UpdateCommentRequest,
     },
@@ -20,7 +20,6 @@ pub trait CommentService: Send + Sync {
         post_id: Uuid,
         page: i32,
         per_page: i32,
-        filters: Option<CommentFilters>,
     ) -> Result<CommentListResponse>;
 
     async fn get_comment(&self, id: Uuid) -> Result<CommentResponse>;
@@ -59,16 +58,16 @@ pub trait CommentService: Send + Sync {
     ) -> Result<CommentListResponse>;
 }
 
+#[derive(Clone)]
 pub struct MockCommentService;
 
 #[async_trait]
 impl CommentService for MockCommentService {
     async fn list_comments(
         &self,
-        post_id: Uuid,
+        _post_id: Uuid,
         page: i32,
         per_page: i32,
-        _filters: Option<CommentFilters>,
     ) -> Result<CommentListResponse> {
         Ok(CommentListResponse {
             comments: vec![],
@@ -108,7 +107,7 @@ impl CommentService for MockCommentService {
             let mut current_id = Some(parent_id);
             let mut nesting_level = 0;
 
-            while let Some(id) = current_id {
+            while current_id.is_some() {
                 nesting_level += 1;
                 if nesting_level > MAX_NESTING_LEVEL {
                     return Err(CommentError::MaxNestingLevel);
@@ -172,7 +171,7 @@ impl CommentService for MockCommentService {
     async fn moderate_comment(
         &self,
         id: Uuid,
-        _moderator_id: Uuid,
+        moderator_id: Uuid,
         req: ModerateCommentRequest,
     ) -> Result<CommentResponse> {
         let status = match req.action {
@@ -180,6 +179,13 @@ impl CommentService for MockCommentService {
             ModerateAction::Reject => CommentStatus::Rejected,
             ModerateAction::MarkAsSpam => CommentStatus::Spam,
         };
+        tracing::info!(
+            comment_id = %id,
+            %moderator_id,
+            ?status,
+            reason = ?req.reason,
+            "Moderated comment"
+        );
 
         Ok(CommentResponse {
             id,
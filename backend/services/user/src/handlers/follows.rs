@@ -3,43 +3,44 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use uuid::Uuid;
+
+use service_auth::{scope, Caller, Scoped};
 
 use crate::{
     error::Result,
     models::PaginationParams,
-    services::{UserService, MockUserService},
+    services::{MockUserService, UserService},
 };
 
 pub async fn follow_user(
+    Caller(claims): Caller,
+    _: Scoped<scope::ProfileWrite>,
     Path(username): Path<String>,
     State(service): State<MockUserService>,
 ) -> Result<StatusCode> {
-    // TODO: Get follower_id from authenticated user
-    let follower_id = Uuid::new_v4();
-    service.follow_user(follower_id, &username).await?;
+    service.follow_user(claims.sub, &username).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn unfollow_user(
+    Caller(claims): Caller,
+    _: Scoped<scope::ProfileWrite>,
     Path(username): Path<String>,
     State(service): State<MockUserService>,
 ) -> Result<StatusCode> {
-    // TODO: Get follower_id from authenticated user
-    let follower_id = Uuid::new_v4();
-    service.unfollow_user(follower_id, &username).await?;
+    service.unfollow_user(claims.sub, &username).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn get_followers(
+    user: Option<Caller>,
     Path(username): Path<String>,
     Query(params): Query<PaginationParams>,
     State(service): State<MockUserService>,
 ) -> Result<Json<serde_json::Value>> {
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20);
-    // TODO: Get current_user_id from authenticated user
-    let current_user_id = None;
+    let current_user_id = user.map(|Caller(claims)| claims.sub);
 
     let response = service
         .get_followers(&username, page, per_page, current_user_id)
@@ -57,14 +58,14 @@ pub async fn get_followers(
 }
 
 pub async fn get_following(
+    user: Option<Caller>,
     Path(username): Path<String>,
     Query(params): Query<PaginationParams>,
     State(service): State<MockUserService>,
 ) -> Result<Json<serde_json::Value>> {
     let page = params.page.unwrap_or(1);
     let per_page = params.per_page.unwrap_or(20);
-    // TODO: Get current_user_id from authenticated user
-    let current_user_id = None;
+    let current_user_id = user.map(|Caller(claims)| claims.sub);
 
     let response = service
         .get_following(&username, page, per_page, current_user_id)
@@ -79,4 +80,4 @@ pub async fn get_following(
             "total_pages": response.total_pages
         }
     })))
-}
\ No newline at end of file
+}
@@ -5,10 +5,12 @@ use axum::{
 use uuid::Uuid;
 use validator::Validate;
 
+use service_auth::{scope, Caller, Permission, Scoped};
+
 use crate::{
     error::{Result, UserError},
     models::UpdateProfileRequest,
-    services::{UserService, MockUserService},
+    services::{MockUserService, UserService},
 };
 
 pub async fn get_profile(
@@ -20,14 +22,18 @@ pub async fn get_profile(
 }
 
 pub async fn update_profile(
+    Caller(claims): Caller,
+    _: Scoped<scope::ProfileWrite>,
     Path(id): Path<Uuid>,
     State(service): State<MockUserService>,
     Json(req): Json<UpdateProfileRequest>,
 ) -> Result<Json<serde_json::Value>> {
+    claims.ensure_owner_or(id, Permission::ManageUsers)?;
+
     // Validate request
     req.validate()
         .map_err(|e| UserError::Validation(e.to_string()))?;
 
     let profile = service.update_profile(id, req).await?;
     Ok(Json(serde_json::json!({ "profile": profile })))
-}
\ No newline at end of file
+}
@@ -2,19 +2,22 @@ use axum::{
     extract::{Path, State},
     Json,
 };
-use uuid::Uuid;
+
+use service_auth::Caller;
 
 use crate::{
     error::Result,
-    services::{UserService, MockUserService},
+    services::{MockUserService, UserService},
 };
 
 pub async fn get_user(
+    user: Option<Caller>,
     Path(username): Path<String>,
     State(service): State<MockUserService>,
 ) -> Result<Json<serde_json::Value>> {
-    // TODO: Get current_user_id from authenticated user
-    let current_user_id = None;
-    let user = service.get_user_by_username(&username, current_user_id).await?;
+    let current_user_id = user.map(|Caller(claims)| claims.sub);
+    let user = service
+        .get_user_by_username(&username, current_user_id)
+        .await?;
     Ok(Json(serde_json::json!({ "user": user })))
-}
\ No newline at end of file
+}
@@ -4,7 +4,7 @@ use uuid::Uuid;
 
 use crate::{
     error::{Result, UserError},
-    models::{Profile, UpdateProfileRequest, User, UserListResponse, UserResponse},
+    models::{Profile, UpdateProfileRequest, UserListResponse, UserResponse},
 };
 
 #[async_trait]
@@ -52,6 +52,7 @@ pub trait UserService: Send + Sync {
     ) -> Result<UserListResponse>;
 }
 
+#[derive(Clone)]
 pub struct MockUserService;
 
 #[async_trait]
@@ -108,7 +109,7 @@ impl UserService for MockUserService {
 
     async fn follow_user(
         &self,
-        follower_id: Uuid,
+        _follower_id: Uuid,
         username: &str,
     ) -> Result<()> {
         // Mock implementation
@@ -120,7 +121,7 @@ impl UserService for MockUserService {
 
     async fn unfollow_user(
         &self,
-        follower_id: Uuid,
+        _follower_id: Uuid,
         username: &str,
     ) -> Result<()> {
         // Mock implementation
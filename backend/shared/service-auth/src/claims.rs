@@ -0,0 +1,240 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    error::{AuthError, Result},
+    jwks::JwksCache,
+    rbac::{has_permission, Permission, RequiredPermission, RequiredScope, Role, Scope},
+};
+
+/// The parts of the auth service's access token claims the services use.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Claims {
+    pub sub: Uuid,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    /// Space-separated scopes of a token issued to an OAuth client. The
+    /// user's own sessions are not limited.
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Set when an OAuth client is acting for the user.
+    #[serde(default)]
+    pub client_id: Option<Uuid>,
+    /// The admin acting as `sub`, on impersonation tokens.
+    #[serde(default)]
+    pub act: Option<Actor>,
+}
+
+/// Who is really behind a token issued to act as another user (RFC 8693).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Actor {
+    pub sub: Uuid,
+}
+
+impl Claims {
+    pub fn can(&self, permission: Permission) -> bool {
+        has_permission(&self.roles, permission)
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scope
+            .as_deref()
+            .is_none_or(|granted| granted.split(' ').any(|s| s == scope.as_str()))
+    }
+
+    /// Passes for the owner of a resource, and for anyone whose roles grant
+    /// `permission` over everyone's. `None` is a resource nobody owns, such
+    /// as an anonymous comment.
+    pub fn ensure_owner_or(
+        &self,
+        owner_id: impl Into<Option<Uuid>>,
+        permission: Permission,
+    ) -> Result<()> {
+        if owner_id.into() == Some(self.sub) || self.can(permission) {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden)
+        }
+    }
+}
+
+/// The user a request acts for, identified by a valid `Authorization:
+/// Bearer` access token: the user's own session, an OAuth client they
+/// authorized, or an admin impersonating them.
+///
+/// Only the signature and expiry are checked here; revoked tokens are
+/// rejected by the API gateway in front of the services.
+pub struct Caller(pub Claims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Caller
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthError::Unauthorized)?;
+
+        let jwks = parts
+            .extensions
+            .get::<Arc<JwksCache>>()
+            .cloned()
+            .ok_or_else(|| AuthError::Internal(anyhow::anyhow!("JWKS cache is not installed")))?;
+
+        Ok(Caller(jwks.verify(token).await?))
+    }
+}
+
+/// A [`Caller`] using their own session. Tokens issued to OAuth clients or
+/// to an admin impersonating the user are refused with `Forbidden`: they act
+/// for the user, but never manage the account itself.
+pub struct CurrentUser(pub Claims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let Caller(claims) = Caller::from_request_parts(parts, state).await?;
+        if claims.client_id.is_some() || claims.act.is_some() {
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(CurrentUser(claims))
+    }
+}
+
+/// A [`Caller`] whose roles grant `P`. Anyone else is rejected with
+/// `Forbidden`.
+pub struct Require<P> {
+    pub claims: Claims,
+    _permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for Require<P>
+where
+    S: Send + Sync,
+    P: RequiredPermission,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let Caller(claims) = Caller::from_request_parts(parts, state).await?;
+        if !claims.can(P::PERMISSION) {
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(Require {
+            claims,
+            _permission: PhantomData,
+        })
+    }
+}
+
+/// Passes when the request may use scope `S`: any session, or a token
+/// issued to an OAuth client that was granted it. Other tokens are rejected
+/// with `Forbidden`. Scopes only limit tokens, so a request without one is
+/// left to the handler's other extractors.
+pub struct Scoped<S>(PhantomData<S>);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for Scoped<T>
+where
+    S: Send + Sync,
+    T: RequiredScope,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        if parts.headers.contains_key(AUTHORIZATION) {
+            let Caller(claims) = Caller::from_request_parts(parts, state).await?;
+            if !T::is_met(|scope| claims.has_scope(scope)) {
+                return Err(AuthError::Forbidden);
+            }
+        }
+
+        Ok(Scoped(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbac::scope;
+
+    fn claims(roles: Vec<Role>) -> Claims {
+        Claims {
+            sub: Uuid::new_v4(),
+            roles,
+            scope: None,
+            client_id: None,
+            act: None,
+        }
+    }
+
+    #[test]
+    fn ensure_owner_or_passes_owners_and_permission_holders() {
+        let author = claims(vec![Role::Author]);
+        assert!(author
+            .ensure_owner_or(author.sub, Permission::EditAnyPost)
+            .is_ok());
+        assert!(matches!(
+            author.ensure_owner_or(Uuid::new_v4(), Permission::EditAnyPost),
+            Err(AuthError::Forbidden)
+        ));
+        assert!(matches!(
+            author.ensure_owner_or(None, Permission::ModerateComments),
+            Err(AuthError::Forbidden)
+        ));
+
+        let editor = claims(vec![Role::Reader, Role::Editor]);
+        assert!(editor
+            .ensure_owner_or(Uuid::new_v4(), Permission::EditAnyPost)
+            .is_ok());
+        let moderator = claims(vec![Role::Moderator]);
+        assert!(moderator
+            .ensure_owner_or(None, Permission::ModerateComments)
+            .is_ok());
+    }
+
+    #[test]
+    fn scopes_limit_only_client_tokens() {
+        let session = claims(vec![Role::Reader]);
+        assert!(session.has_scope(Scope::ProfileWrite));
+        assert!(scope::ProfileWrite::is_met(|scope| session.has_scope(scope)));
+
+        let client = Claims {
+            scope: Some("comments:write profile:write".to_string()),
+            client_id: Some(Uuid::new_v4()),
+            ..claims(vec![Role::Moderator])
+        };
+        let allows = |scope| client.has_scope(scope);
+        assert!(scope::CommentsWrite::is_met(allows));
+        assert!(!scope::CommentsModerate::is_met(allows));
+        assert!(!scope::PostsWrite::is_met(allows));
+        assert!(<(scope::CommentsModerate, scope::CommentsWrite)>::is_met(
+            allows
+        ));
+        assert!(!<(scope::CommentsModerate, scope::PostsWrite)>::is_met(
+            allows
+        ));
+    }
+}
@@ -0,0 +1,48 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("Authentication required")]
+    Unauthorized,
+
+    #[error("Permission denied")]
+    Forbidden,
+
+    #[error("Internal server error")]
+    Internal(#[from] anyhow::Error),
+}
+
+/// The same body the services use for their own errors, so a rejected token
+/// looks the same whichever service rejected it.
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let (status, error_message) = match self {
+            AuthError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
+            AuthError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
+            AuthError::Internal(err) => {
+                tracing::error!("Internal error: {:?}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                )
+            }
+        };
+
+        let body = Json(json!({
+            "error": {
+                "message": error_message,
+                "code": status.as_u16()
+            }
+        }));
+
+        (status, body).into_response()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AuthError>;
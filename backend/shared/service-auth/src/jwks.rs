@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::{AuthError, Result};
+
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 300;
+
+/// Minimum time between refreshes triggered by an unknown `kid`, so a flood
+/// of forged tokens cannot turn into a flood of JWKS requests.
+const MIN_FORCED_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+struct VerificationKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+impl VerificationKey {
+    /// Only asymmetric keys are accepted, and the algorithm comes from the key
+    /// rather than from the token header.
+    fn from_jwk(jwk: &Jwk) -> Option<Self> {
+        let algorithm = match (&jwk.algorithm, jwk.common.key_algorithm) {
+            (AlgorithmParameters::OctetKey(_), _) => return None,
+            (_, Some(algorithm)) => asymmetric_algorithm(algorithm)?,
+            (AlgorithmParameters::OctetKeyPair(_), None) => Algorithm::EdDSA,
+            (AlgorithmParameters::RSA(_), None) => Algorithm::RS256,
+            (AlgorithmParameters::EllipticCurve(params), None) => match params.curve {
+                EllipticCurve::P256 => Algorithm::ES256,
+                EllipticCurve::P384 => Algorithm::ES384,
+                _ => return None,
+            },
+        };
+
+        let key = DecodingKey::from_jwk(jwk).ok()?;
+        Some(Self { algorithm, key })
+    }
+}
+
+fn asymmetric_algorithm(algorithm: KeyAlgorithm) -> Option<Algorithm> {
+    match algorithm {
+        KeyAlgorithm::ES256 => Some(Algorithm::ES256),
+        KeyAlgorithm::ES384 => Some(Algorithm::ES384),
+        KeyAlgorithm::RS256 => Some(Algorithm::RS256),
+        KeyAlgorithm::RS384 => Some(Algorithm::RS384),
+        KeyAlgorithm::RS512 => Some(Algorithm::RS512),
+        KeyAlgorithm::PS256 => Some(Algorithm::PS256),
+        KeyAlgorithm::PS384 => Some(Algorithm::PS384),
+        KeyAlgorithm::PS512 => Some(Algorithm::PS512),
+        KeyAlgorithm::EdDSA => Some(Algorithm::EdDSA),
+        _ => None,
+    }
+}
+
+/// The auth service's public signing keys, fetched from its JWKS endpoint.
+///
+/// Keys are refreshed in the background, and also on demand when a token names
+/// a `kid` we have not seen yet, which is what happens right after a rotation.
+pub struct JwksCache {
+    url: String,
+    http: reqwest::Client,
+    keys: RwLock<HashMap<String, Arc<VerificationKey>>>,
+    last_refresh: Mutex<Option<Instant>>,
+}
+
+impl JwksCache {
+    /// Reads the keys from `AUTH_SERVICE_URL`.
+    pub fn from_env() -> Self {
+        let auth_url = std::env::var("AUTH_SERVICE_URL")
+            .unwrap_or_else(|_| "http://localhost:3001".to_string());
+
+        Self {
+            url: format!("{}/.well-known/jwks.json", auth_url.trim_end_matches('/')),
+            http: reqwest::Client::new(),
+            keys: RwLock::new(HashMap::new()),
+            last_refresh: Mutex::new(None),
+        }
+    }
+
+    /// Verifies the token's signature with the key named by its `kid` header
+    /// and returns the claims.
+    pub async fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T> {
+        let kid = decode_header(token)
+            .map_err(|_| AuthError::Unauthorized)?
+            .kid
+            .ok_or(AuthError::Unauthorized)?;
+
+        let key = match self.key(&kid) {
+            Some(key) => key,
+            None => {
+                self.refresh_unknown_kid().await;
+                self.key(&kid).ok_or(AuthError::Unauthorized)?
+            }
+        };
+
+        decode::<T>(token, &key.key, &Validation::new(key.algorithm))
+            .map(|data| data.claims)
+            .map_err(|_| AuthError::Unauthorized)
+    }
+
+    fn key(&self, kid: &str) -> Option<Arc<VerificationKey>> {
+        self.keys.read().unwrap().get(kid).cloned()
+    }
+
+    async fn refresh_unknown_kid(&self) {
+        {
+            let last_refresh = self.last_refresh.lock().unwrap();
+            if last_refresh.is_some_and(|at| at.elapsed() < MIN_FORCED_REFRESH_INTERVAL) {
+                return;
+            }
+        }
+
+        if let Err(err) = self.refresh().await {
+            tracing::warn!("Failed to refresh JWKS: {}", err);
+        }
+    }
+
+    pub async fn refresh(&self) -> Result<()> {
+        *self.last_refresh.lock().unwrap() = Some(Instant::now());
+        let jwks: JwkSet = self
+            .http
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AuthError::Internal(anyhow::anyhow!("Failed to fetch JWKS: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AuthError::Internal(anyhow::anyhow!("Invalid JWKS: {}", e)))?;
+
+        let keys = jwks
+            .keys
+            .iter()
+            .filter_map(|jwk| {
+                let kid = jwk.common.key_id.clone()?;
+                match VerificationKey::from_jwk(jwk) {
+                    Some(key) => Some((kid, Arc::new(key))),
+                    None => {
+                        tracing::warn!("Ignoring unsupported JWK {}", kid);
+                        None
+                    }
+                }
+            })
+            .collect();
+        *self.keys.write().unwrap() = keys;
+
+        Ok(())
+    }
+
+    /// Refetches the key set every `JWKS_REFRESH_SECS` seconds. On failure
+    /// the previous keys are kept.
+    pub fn spawn_refresh(self: Arc<Self>) {
+        let interval = std::env::var("JWKS_REFRESH_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL_SECS);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.refresh().await {
+                    tracing::warn!("Failed to refresh JWKS: {}", err);
+                }
+            }
+        });
+    }
+}
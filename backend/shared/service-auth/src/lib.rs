@@ -0,0 +1,18 @@
+//! Access token verification and role-based access control shared by the
+//! services behind the API gateway.
+//!
+//! The auth service signs access tokens and publishes its keys as a JWKS;
+//! [`JwksCache`] verifies tokens against them, [`Caller`], [`CurrentUser`],
+//! [`Require`] and [`Scoped`] pull the verified [`Claims`] out of a request,
+//! and [`rbac`] is the role/permission matrix every service checks them
+//! against.
+
+pub mod claims;
+pub mod error;
+pub mod jwks;
+pub mod rbac;
+
+pub use claims::{Actor, Caller, Claims, CurrentUser, Require, Scoped};
+pub use error::{AuthError, Result};
+pub use jwks::JwksCache;
+pub use rbac::{perm, scope, Permission, Role, Scope};
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+/// A role assigned to a user. Roles are carried in the access token, and
+/// every service derives permissions from them with [`Role::grants`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Reader,
+    Author,
+    Editor,
+    Moderator,
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    CreateComments,
+    CreatePosts,
+    /// Edit or delete posts written by someone else.
+    EditAnyPost,
+    /// Approve, reject, edit or delete anyone's comments.
+    ModerateComments,
+    /// Manage accounts, including assigning roles.
+    ManageUsers,
+}
+
+/// What a personal access token may be used for. A token can never do more
+/// than its owner's roles allow, whatever its scopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "posts:write")]
+    PostsWrite,
+    #[serde(rename = "comments:write")]
+    CommentsWrite,
+    #[serde(rename = "comments:moderate")]
+    CommentsModerate,
+    #[serde(rename = "profile:write")]
+    ProfileWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [
+        Scope::PostsWrite,
+        Scope::CommentsWrite,
+        Scope::CommentsModerate,
+        Scope::ProfileWrite,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::PostsWrite => "posts:write",
+            Scope::CommentsWrite => "comments:write",
+            Scope::CommentsModerate => "comments:moderate",
+            Scope::ProfileWrite => "profile:write",
+        }
+    }
+
+    /// The permission the owner needs before a token can carry this scope.
+    pub fn permission(self) -> Option<Permission> {
+        match self {
+            Scope::PostsWrite => Some(Permission::CreatePosts),
+            Scope::CommentsWrite => Some(Permission::CreateComments),
+            Scope::CommentsModerate => Some(Permission::ModerateComments),
+            Scope::ProfileWrite => None,
+        }
+    }
+}
+
+impl Permission {
+    pub const ALL: [Permission; 5] = [
+        Permission::CreateComments,
+        Permission::CreatePosts,
+        Permission::EditAnyPost,
+        Permission::ModerateComments,
+        Permission::ManageUsers,
+    ];
+}
+
+impl Role {
+    pub fn grants(self, permission: Permission) -> bool {
+        use Permission::*;
+
+        match self {
+            Role::Reader => matches!(permission, CreateComments),
+            Role::Author => matches!(permission, CreateComments | CreatePosts),
+            Role::Editor => matches!(permission, CreateComments | CreatePosts | EditAnyPost),
+            Role::Moderator => matches!(permission, CreateComments | ModerateComments),
+            Role::Admin => true,
+        }
+    }
+}
+
+pub fn has_permission(roles: &[Role], permission: Permission) -> bool {
+    roles.iter().any(|role| role.grants(permission))
+}
+
+/// Every permission granted by at least one of `roles`.
+pub fn permissions(roles: &[Role]) -> Vec<Permission> {
+    Permission::ALL
+        .into_iter()
+        .filter(|permission| has_permission(roles, *permission))
+        .collect()
+}
+
+/// Type-level names for [`Permission`]s, so a handler can declare what it
+/// needs in its signature, e.g. `Require<perm::ManageUsers>`.
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+macro_rules! permission_markers {
+    ($($name:ident),* $(,)?) => {
+        pub mod perm {
+            $(
+                pub struct $name;
+
+                impl super::RequiredPermission for $name {
+                    const PERMISSION: super::Permission = super::Permission::$name;
+                }
+            )*
+        }
+    };
+}
+
+permission_markers!(
+    CreateComments,
+    CreatePosts,
+    EditAnyPost,
+    ModerateComments,
+    ManageUsers
+);
+
+/// Type-level names for [`Scope`]s, used as `Scoped<scope::PostsWrite>`. A
+/// pair, `Scoped<(scope::CommentsWrite, scope::CommentsModerate)>`, passes
+/// with either scope.
+pub trait RequiredScope {
+    /// Whether a token that has exactly the scopes `has_scope` accepts may
+    /// be used.
+    fn is_met(has_scope: impl Fn(Scope) -> bool) -> bool;
+}
+
+macro_rules! scope_markers {
+    ($($name:ident),* $(,)?) => {
+        pub mod scope {
+            $(
+                pub struct $name;
+
+                impl super::RequiredScope for $name {
+                    fn is_met(has_scope: impl Fn(super::Scope) -> bool) -> bool {
+                        has_scope(super::Scope::$name)
+                    }
+                }
+            )*
+        }
+    };
+}
+
+scope_markers!(PostsWrite, CommentsWrite, CommentsModerate, ProfileWrite);
+
+impl<A: RequiredScope, B: RequiredScope> RequiredScope for (A, B) {
+    fn is_met(has_scope: impl Fn(Scope) -> bool) -> bool {
+        A::is_met(&has_scope) || B::is_met(&has_scope)
+    }
+}